@@ -1,13 +1,46 @@
 use clap::{Arg, Command};
-use http_client_vcr::{Cassette, CassetteFormat, Interaction};
+use http_client::Error;
+use http_client_vcr::{
+    group_by_template, Cassette, CassetteFormat, CassetteStorage, DirectoryStorage,
+    ErrorBodyFilter, FilesystemStorage, FilterChain, HostCatalog, Interaction, VcrConfig,
+    CURRENT_FORMAT_VERSION,
+};
 use serde_json::{json, Value};
-use std::path::PathBuf;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use time::format_description::well_known::Rfc3339;
+
+/// Set by `--no-verify`
+static NO_VERIFY: AtomicBool = AtomicBool::new(false);
+
+/// The `vcr.toml` for this run, looked up on first use. An invalid one is reported
+/// and ignored.
+fn config() -> &'static VcrConfig {
+    static CONFIG: OnceLock<VcrConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        VcrConfig::discover().unwrap_or_else(|e| {
+            eprintln!("Ignoring invalid VCR config: {e}");
+            VcrConfig::default()
+        })
+    })
+}
+
+fn main() {
+    futures_lite::future::block_on(run());
+}
 
-#[tokio::main]
-async fn main() {
+async fn run() {
     let matches = Command::new("vcr-inspect")
         .version("0.2.0")
         .about("Inspect VCR cassettes")
+        .arg(
+            Arg::new("no-verify")
+                .help("Load directory cassettes without checking body files against their checksums")
+                .long("no-verify")
+                .global(true)
+                .action(clap::ArgAction::SetTrue),
+        )
         .subcommand(
             Command::new("list")
                 .about("List all requests in a cassette")
@@ -16,6 +49,13 @@ async fn main() {
                         .help("Path to the cassette file or directory")
                         .required(true)
                         .index(1),
+                )
+                .arg(
+                    Arg::new("grouped")
+                        .help("Group requests by URL template")
+                        .long("grouped")
+                        .short('g')
+                        .action(clap::ArgAction::SetTrue),
                 ),
         )
         .subcommand(
@@ -58,8 +98,7 @@ async fn main() {
                 )
                 .arg(
                     Arg::new("format")
-                        .help("Output format: 'file' or 'directory'")
-                        .required(true)
+                        .help("Output format: 'file' or 'directory' (defaults to cli.format in vcr.toml)")
                         .long("format")
                         .short('f')
                         .value_parser(["file", "directory"]),
@@ -82,12 +121,88 @@ async fn main() {
                         .value_parser(clap::value_parser!(usize)),
                 ),
         )
+        .subcommand(
+            Command::new("scrub")
+                .about("Drop or truncate recorded response bodies, keeping status and headers")
+                .arg(
+                    Arg::new("cassette")
+                        .help("Path to the cassette file or directory")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("errors-only")
+                        .help("Only scrub 4xx and 5xx responses")
+                        .long("errors-only")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("truncate")
+                        .help("Keep the first N bytes of each body instead of dropping it")
+                        .long("truncate")
+                        .short('t')
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("hosts")
+                .about("Generate a host catalog mapping recorded hosts to a local listener")
+                .arg(
+                    Arg::new("cassette")
+                        .help("Path to the cassette file or directory")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("listen")
+                        .help("Address of the listener serving the replayed traffic")
+                        .long("listen")
+                        .short('l')
+                        .default_value("127.0.0.1:8080"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("Output: 'json', 'hosts' (hosts-file snippet) or 'pac' (proxy auto-config)")
+                        .long("output")
+                        .short('o')
+                        .value_parser(["json", "hosts", "pac"])
+                        .default_value("json"),
+                ),
+        )
+        .subcommand(
+            Command::new("gc")
+                .about("Remove body files no longer referenced by directory cassettes")
+                .arg(
+                    Arg::new("path")
+                        .help("Path to a directory cassette, or a directory of cassettes")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Rewrite cassettes stored in older layouts in the latest format version")
+                .arg(
+                    Arg::new("path")
+                        .help("Path to a cassette, or a directory of cassettes")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("check")
+                        .help("Only report outdated cassettes, failing if there are any")
+                        .long("check")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
         .get_matches();
+    NO_VERIFY.store(matches.get_flag("no-verify"), Ordering::Relaxed);
 
     let result = match matches.subcommand() {
         Some(("list", sub_matches)) => {
             let cassette_path = sub_matches.get_one::<String>("cassette").unwrap();
-            list_requests(cassette_path).await
+            let grouped = sub_matches.get_flag("grouped");
+            list_requests(cassette_path, grouped).await
         }
         Some(("field", sub_matches)) => {
             let cassette_path = sub_matches.get_one::<String>("cassette").unwrap();
@@ -98,7 +213,7 @@ async fn main() {
         Some(("convert", sub_matches)) => {
             let source_path = sub_matches.get_one::<String>("source").unwrap();
             let destination_path = sub_matches.get_one::<String>("destination").unwrap();
-            let format = sub_matches.get_one::<String>("format").unwrap();
+            let format = sub_matches.get_one::<String>("format").map(String::as_str);
             convert_cassette(source_path, destination_path, format).await
         }
         Some(("fields", sub_matches)) => {
@@ -106,6 +221,27 @@ async fn main() {
             let interaction_idx = sub_matches.get_one::<usize>("interaction").copied();
             list_fields(cassette_path, interaction_idx).await
         }
+        Some(("scrub", sub_matches)) => {
+            let cassette_path = sub_matches.get_one::<String>("cassette").unwrap();
+            let errors_only = sub_matches.get_flag("errors-only");
+            let truncate = sub_matches.get_one::<usize>("truncate").copied();
+            scrub_bodies(cassette_path, errors_only, truncate).await
+        }
+        Some(("hosts", sub_matches)) => {
+            let cassette_path = sub_matches.get_one::<String>("cassette").unwrap();
+            let listen = sub_matches.get_one::<String>("listen").unwrap();
+            let output = sub_matches.get_one::<String>("output").unwrap();
+            host_catalog(cassette_path, listen, output).await
+        }
+        Some(("gc", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("path").unwrap();
+            remove_orphaned_bodies(path)
+        }
+        Some(("migrate", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("path").unwrap();
+            let check = sub_matches.get_flag("check");
+            migrate_cassettes(path, check)
+        }
         _ => {
             eprintln!("No subcommand provided. Use --help for usage information.");
             std::process::exit(1);
@@ -118,11 +254,9 @@ async fn main() {
     }
 }
 
-async fn list_requests(cassette_path: &str) -> Result<(), String> {
-    let path = PathBuf::from(cassette_path);
-    let cassette = Cassette::load_from_file(path)
-        .await
-        .map_err(|e| format!("Failed to load cassette: {e}"))?;
+async fn list_requests(cassette_path: &str, grouped: bool) -> Result<(), String> {
+    let path = config().resolve_cassette_path(cassette_path);
+    let cassette = load_cassette(&path).map_err(|e| format!("Failed to load cassette: {e}"))?;
 
     let mut requests = Vec::new();
     for (index, interaction) in cassette.interactions.iter().enumerate() {
@@ -134,12 +268,47 @@ async fn list_requests(cassette_path: &str) -> Result<(), String> {
         }));
     }
 
-    let output = json!({
-        "total_interactions": cassette.interactions.len(),
-        "requests": requests
-    });
+    let mut output = if grouped {
+        let groups = group_by_template(
+            cassette.interactions.iter().map(|i| i.request.url.as_str()),
+            &cassette.url_templates(),
+        );
+        let groups: Vec<Value> = groups
+            .into_iter()
+            .map(|(template, indices)| {
+                let requests: Vec<&Value> = indices.iter().map(|&i| &requests[i]).collect();
+                json!({
+                    "template": template,
+                    "count": indices.len(),
+                    "requests": requests
+                })
+            })
+            .collect();
+
+        json!({
+            "total_interactions": cassette.interactions.len(),
+            "groups": groups
+        })
+    } else {
+        json!({
+            "total_interactions": cassette.interactions.len(),
+            "requests": requests
+        })
+    };
 
-    println!("{}", serde_json::to_string(&output).unwrap());
+    if !cassette.metadata.is_empty() {
+        output["metadata"] = json!({
+            "recorder_version": cassette.recorder_version(),
+            "recorded_at": cassette
+                .recorded_at()
+                .and_then(|recorded_at| recorded_at.format(&Rfc3339).ok()),
+            "recorded_by": cassette.recorded_by(),
+            "tags": cassette.tags(),
+            "description": cassette.description(),
+        });
+    }
+
+    print_json(&output);
     Ok(())
 }
 
@@ -148,10 +317,8 @@ async fn extract_field(
     field_path: &str,
     interaction_idx: Option<usize>,
 ) -> Result<(), String> {
-    let path = PathBuf::from(cassette_path);
-    let cassette = Cassette::load_from_file(path)
-        .await
-        .map_err(|e| format!("Failed to load cassette: {e}"))?;
+    let path = config().resolve_cassette_path(cassette_path);
+    let cassette = load_cassette(&path).map_err(|e| format!("Failed to load cassette: {e}"))?;
 
     if let Some(idx) = interaction_idx {
         if idx >= cassette.interactions.len() {
@@ -178,7 +345,7 @@ async fn extract_field(
                 })),
             }
         }
-        println!("{}", serde_json::to_string(&results).unwrap());
+        print_json(&Value::Array(results));
     }
 
     Ok(())
@@ -281,6 +448,32 @@ fn parse_field_path(field_path: &str) -> Vec<FieldPathPart> {
     parts
 }
 
+/// Load the cassette at `path`, checking directory cassettes' body checksums unless
+/// `--no-verify` was given
+fn load_cassette(path: &Path) -> Result<Cassette, Error> {
+    let cassette = if NO_VERIFY.load(Ordering::Relaxed) {
+        FilesystemStorage.load_unverified(path)?
+    } else {
+        FilesystemStorage.load(path)?
+    };
+    cassette.ok_or_else(|| {
+        Error::from_str(
+            500,
+            format!("Failed to read cassette file: {path:?} does not exist"),
+        )
+    })
+}
+
+/// Print a JSON document, pretty-printed if `cli.pretty` is set in vcr.toml
+fn print_json(value: &Value) {
+    let output = if config().cli.pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    println!("{}", output.unwrap());
+}
+
 fn print_value(value: &Value) {
     match value {
         Value::String(s) => {
@@ -297,24 +490,33 @@ fn print_value(value: &Value) {
 async fn convert_cassette(
     source_path: &str,
     destination_path: &str,
-    format: &str,
+    format: Option<&str>,
 ) -> Result<(), String> {
-    let source = PathBuf::from(source_path);
-    let destination = PathBuf::from(destination_path);
+    let config = config();
+    let source = config.resolve_cassette_path(source_path);
+    let destination = config.resolve_cassette_path(destination_path);
 
     let target_format = match format {
-        "file" => CassetteFormat::File,
-        "directory" => CassetteFormat::Directory,
-        _ => {
+        Some("file") => CassetteFormat::File,
+        Some("directory") => CassetteFormat::Directory,
+        Some(format) => {
             return Err(format!(
                 "Invalid format '{format}'. Must be 'file' or 'directory'"
             ))
         }
+        None => config
+            .cli
+            .format
+            .clone()
+            .ok_or("No --format given and no cli.format set in vcr.toml")?,
+    };
+    let format = match target_format {
+        CassetteFormat::File => "file",
+        CassetteFormat::Directory => "directory",
     };
 
-    let mut cassette = Cassette::load_from_file(source)
-        .await
-        .map_err(|e| format!("Failed to load source cassette: {e}"))?;
+    let mut cassette =
+        load_cassette(&source).map_err(|e| format!("Failed to load source cassette: {e}"))?;
 
     cassette = cassette.with_path(destination).with_format(target_format);
 
@@ -331,15 +533,141 @@ async fn convert_cassette(
         "interactions_converted": cassette.interactions.len()
     });
 
-    println!("{}", serde_json::to_string(&result).unwrap());
+    print_json(&result);
     Ok(())
 }
 
-async fn list_fields(cassette_path: &str, interaction_idx: Option<usize>) -> Result<(), String> {
-    let path = PathBuf::from(cassette_path);
-    let cassette = Cassette::load_from_file(path)
+async fn scrub_bodies(
+    cassette_path: &str,
+    errors_only: bool,
+    truncate: Option<usize>,
+) -> Result<(), String> {
+    let path = config().resolve_cassette_path(cassette_path);
+    let mut cassette = load_cassette(&path).map_err(|e| format!("Failed to load cassette: {e}"))?;
+
+    let mut filter = ErrorBodyFilter::new();
+    if !errors_only {
+        filter = filter.with_status_range(0..=u16::MAX);
+    }
+    if let Some(bytes) = truncate {
+        filter = filter.truncate_to(bytes);
+    }
+
+    let scrubbed = cassette
+        .interactions
+        .iter()
+        .filter(|i| filter.applies_to(&i.response))
+        .count();
+
+    let filter_chain = FilterChain::new().add_filter(Box::new(filter));
+    for interaction in &mut cassette.interactions {
+        filter_chain.filter_response(&mut interaction.response);
+    }
+
+    cassette
+        .save_to_file()
         .await
-        .map_err(|e| format!("Failed to load cassette: {e}"))?;
+        .map_err(|e| format!("Failed to save scrubbed cassette: {e}"))?;
+
+    let result = json!({
+        "success": true,
+        "cassette_path": cassette_path,
+        "errors_only": errors_only,
+        "truncated_to": truncate,
+        "interactions_scrubbed": scrubbed
+    });
+
+    print_json(&result);
+    Ok(())
+}
+
+fn remove_orphaned_bodies(path: &str) -> Result<(), String> {
+    let root = config().resolve_cassette_path(path);
+    let cassettes = DirectoryStorage
+        .list(&root)
+        .map_err(|e| format!("Failed to find cassettes: {e}"))?;
+
+    let mut removed = Vec::new();
+    for cassette in &cassettes {
+        let files = DirectoryStorage
+            .remove_orphaned_bodies(cassette)
+            .map_err(|e| format!("Failed to clean {}: {e}", cassette.display()))?;
+        removed.extend(files.iter().map(|file| file.display().to_string()));
+    }
+
+    let result = json!({
+        "success": true,
+        "path": path,
+        "cassettes": cassettes.len(),
+        "removed": removed
+    });
+
+    print_json(&result);
+    Ok(())
+}
+
+fn migrate_cassettes(path: &str, check: bool) -> Result<(), String> {
+    let root = config().resolve_cassette_path(path);
+    let cassettes = FilesystemStorage
+        .list(&root)
+        .map_err(|e| format!("Failed to find cassettes: {e}"))?;
+
+    let mut outdated = Vec::new();
+    for path in &cassettes {
+        let cassette =
+            load_cassette(path).map_err(|e| format!("Failed to load {}: {e}", path.display()))?;
+        if !cassette.needs_migration() {
+            continue;
+        }
+        if !check {
+            FilesystemStorage
+                .save(path, &cassette)
+                .map_err(|e| format!("Failed to save {}: {e}", path.display()))?;
+        }
+        outdated.push(json!({
+            "path": path.display().to_string(),
+            "from_version": cassette.format_version
+        }));
+    }
+
+    let result = json!({
+        "success": !check || outdated.is_empty(),
+        "path": path,
+        "format_version": CURRENT_FORMAT_VERSION,
+        "cassettes": cassettes.len(),
+        (if check { "outdated" } else { "migrated" }): outdated
+    });
+
+    print_json(&result);
+    if check && !outdated.is_empty() {
+        return Err(format!(
+            "{} cassette(s) need `vcr-inspect migrate`",
+            outdated.len()
+        ));
+    }
+    Ok(())
+}
+
+async fn host_catalog(cassette_path: &str, listen: &str, output: &str) -> Result<(), String> {
+    let path = config().resolve_cassette_path(cassette_path);
+    let cassette = load_cassette(&path).map_err(|e| format!("Failed to load cassette: {e}"))?;
+
+    let catalog = HostCatalog::from_cassette(&cassette, listen);
+    match output {
+        "hosts" => print!("{}", catalog.hosts_file()),
+        "pac" => print!("{}", catalog.pac_file()),
+        _ => print_json(
+            &serde_json::to_value(&catalog)
+                .map_err(|e| format!("Failed to serialize host catalog: {e}"))?,
+        ),
+    }
+
+    Ok(())
+}
+
+async fn list_fields(cassette_path: &str, interaction_idx: Option<usize>) -> Result<(), String> {
+    let path = config().resolve_cassette_path(cassette_path);
+    let cassette = load_cassette(&path).map_err(|e| format!("Failed to load cassette: {e}"))?;
 
     if cassette.interactions.is_empty() {
         return Err("Cassette contains no interactions".to_string());
@@ -367,7 +695,7 @@ async fn list_fields(cassette_path: &str, interaction_idx: Option<usize>) -> Res
         "field_paths": field_paths
     });
 
-    println!("{}", serde_json::to_string(&result).unwrap());
+    print_json(&result);
     Ok(())
 }
 
@@ -1,5 +1,6 @@
-use http_client_vcr::{Cassette, CassetteFormat, SerializableRequest, SerializableResponse};
-use std::collections::HashMap;
+use http_client_vcr::{
+    Cassette, CassetteFormat, Headers, SerializableRequest, SerializableResponse,
+};
 use std::path::PathBuf;
 
 #[tokio::main]
@@ -11,7 +12,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         method: "POST".to_string(),
         url: "https://api.example.com/users".to_string(),
         headers: {
-            let mut headers = HashMap::new();
+            let mut headers = Headers::new();
             headers.insert("content-type".to_string(), vec!["application/json".to_string()]);
             headers.insert("authorization".to_string(), vec!["Bearer token123".to_string()]);
             headers
@@ -24,12 +25,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let test_response = SerializableResponse {
         status: 201,
         headers: {
-            let mut headers = HashMap::new();
+            let mut headers = Headers::new();
             headers.insert("content-type".to_string(), vec!["application/json".to_string()]);
             headers.insert("location".to_string(), vec!["/users/123".to_string()]);
             headers
         },
-        body: Some(r#"{"id": 123, "name": "John Doe", "email": "john@example.com", "created_at": "2024-01-01T00:00:00Z", "profile": "A very long bio that contains lots of information about the user, including their interests, background, and other detailed information that would make a large body payload.", "preferences": {"theme": "dark", "notifications": true, "language": "en"}}"#.to_string()),
+        body: Some(r#"{"id": 123, "name": "John Doe", "email": "john@example.com", "created_at": "2024-01-01T00:00:00Z", "profile": "A very long bio that contains lots of information about the user, including their interests, background, and other detailed information that would make a large body payload.", "preferences": {"theme": "dark", "notifications": true, "language": "en"}}"#.into()),
         body_base64: None,
         version: "HTTP/1.1".to_string(),
     };
@@ -1,5 +1,6 @@
-use http_client_vcr::{Cassette, CassetteFormat, SerializableRequest, SerializableResponse};
-use std::collections::HashMap;
+use http_client_vcr::{
+    Cassette, CassetteFormat, Headers, SerializableRequest, SerializableResponse,
+};
 use std::path::PathBuf;
 
 #[tokio::main]
@@ -24,7 +25,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         method: "GET".to_string(),
         url: "https://example.com/api/test".to_string(),
         headers: {
-            let mut headers = HashMap::new();
+            let mut headers = Headers::new();
             headers.insert(
                 "content-type".to_string(),
                 vec!["application/json".to_string()],
@@ -39,14 +40,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let response1 = SerializableResponse {
         status: 200,
         headers: {
-            let mut headers = HashMap::new();
+            let mut headers = Headers::new();
             headers.insert(
                 "content-type".to_string(),
                 vec!["application/json".to_string()],
             );
             headers
         },
-        body: Some(r#"{"message": "Hello, World!", "status": "success"}"#.to_string()),
+        body: Some(r#"{"message": "Hello, World!", "status": "success"}"#.into()),
         body_base64: None,
         version: "HTTP/1.1".to_string(),
     };
@@ -55,7 +56,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         method: "POST".to_string(),
         url: "https://example.com/api/data".to_string(),
         headers: {
-            let mut headers = HashMap::new();
+            let mut headers = Headers::new();
             headers.insert("content-type".to_string(), vec!["text/html".to_string()]);
             headers
         },
@@ -67,11 +68,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let response2 = SerializableResponse {
         status: 201,
         headers: {
-            let mut headers = HashMap::new();
+            let mut headers = Headers::new();
             headers.insert("content-type".to_string(), vec!["text/html".to_string()]);
             headers
         },
-        body: Some("<html><body><h1>Created Successfully</h1></body></html>".to_string()),
+        body: Some("<html><body><h1>Created Successfully</h1></body></html>".into()),
         body_base64: None,
         version: "HTTP/1.1".to_string(),
     };
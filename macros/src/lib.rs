@@ -0,0 +1,88 @@
+//! The `#[vcr_test]` attribute, re-exported by `http-client-vcr` behind its `macros`
+//! feature. See `http_client_vcr::vcr_test` for documentation.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, LitStr, Path};
+
+/// Turn `async fn name(vcr: VcrClient)` into a `#[tokio::test]` that builds the
+/// client for the test's own cassette and passes it in.
+///
+/// The cassette is `<cassette_dir>/<module path>/<function name>.yaml`, with
+/// `cassette_dir` defaulting to `tests/fixtures`. Setting `VCR_RECORD` to `1`, `true`
+/// or `on` records it, otherwise it is replayed. `inner_client` names a function
+/// returning the `Box<dyn HttpClient>` used when recording; it is only called then.
+///
+/// The client comes from `http_client_vcr::testing::VcrTestContext`, which tests that
+/// need more control can use directly.
+///
+/// ```ignore
+/// #[vcr_test(cassette_dir = "tests/fixtures", inner_client = real_client)]
+/// async fn fetches_user(vcr: VcrClient) -> Result<(), Box<dyn std::error::Error>> {
+///     let response = vcr.send(request).await?;
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn vcr_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut cassette_dir = LitStr::new("tests/fixtures", proc_macro2::Span::call_site());
+    let mut inner_client: Option<Path> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("cassette_dir") {
+            cassette_dir = meta.value()?.parse()?;
+            Ok(())
+        } else if meta.path.is_ident("inner_client") {
+            inner_client = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `cassette_dir` or `inner_client`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let test = parse_macro_input!(item as ItemFn);
+    if test.sig.asyncness.is_none() {
+        return syn::Error::new_spanned(test.sig.fn_token, "#[vcr_test] functions must be async")
+            .to_compile_error()
+            .into();
+    }
+    if test.sig.inputs.len() != 1 {
+        return syn::Error::new_spanned(
+            &test.sig.inputs,
+            "#[vcr_test] functions take exactly one argument, the VcrClient",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let attrs = &test.attrs;
+    let vis = &test.vis;
+    let name = &test.sig.ident;
+    let output = &test.sig.output;
+    let inner_client = inner_client.map(|factory| quote!(.with_inner_client(#factory)));
+    // The attributes move to the generated test
+    let body = ItemFn {
+        attrs: Vec::new(),
+        ..test.clone()
+    };
+
+    quote! {
+        #(#attrs)*
+        #[::tokio::test]
+        #vis async fn #name() #output {
+            #body
+
+            let vcr = ::http_client_vcr::testing::VcrTestContext::for_test(
+                module_path!(),
+                stringify!(#name),
+            )
+            .with_cassette_dir(#cassette_dir)
+            #inner_client
+            .client()
+            .await
+            .expect("Failed to build VcrClient for test");
+            #name(vcr).await
+        }
+    }
+    .into()
+}
@@ -0,0 +1,19 @@
+//! Adapters between `VcrClient` and other HTTP client libraries: [`HttpClient`]
+//! implementations to record through them, and middleware putting a `VcrClient`
+//! behind their own client types.
+//!
+//! [`HttpClient`]: http_client::HttpClient
+
+#[cfg(feature = "isahc")]
+mod isahc_client;
+#[cfg(feature = "reqwest")]
+mod reqwest_client;
+#[cfg(feature = "surf")]
+mod surf_client;
+
+#[cfg(feature = "isahc")]
+pub use isahc_client::IsahcClient;
+#[cfg(feature = "reqwest")]
+pub use reqwest_client::ReqwestClient;
+#[cfg(feature = "surf")]
+pub use surf_client::{SurfClient, VcrMiddleware};
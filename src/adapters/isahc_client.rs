@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use http_client::{Config, Error, HttpClient, Request, Response};
+use http_types::{StatusCode, Version};
+use isahc::config::Configurable;
+use isahc::AsyncReadResponseExt;
+
+/// Sends requests with an [`isahc::HttpClient`].
+///
+/// Like [`ReqwestClient`](super::ReqwestClient) it passes bodies through as bytes,
+/// keeps every value of repeated headers and applies `Config::timeout`.
+#[derive(Debug, Clone)]
+pub struct IsahcClient {
+    client: isahc::HttpClient,
+    config: Config,
+}
+
+impl IsahcClient {
+    pub fn new() -> Result<Self, Error> {
+        let client = isahc::HttpClient::new()
+            .map_err(|e| Error::from_str(500, format!("Failed to create isahc client: {e}")))?;
+        Ok(Self::from_client(client))
+    }
+
+    /// Wrap an already configured `isahc::HttpClient`
+    pub fn from_client(client: isahc::HttpClient) -> Self {
+        Self {
+            client,
+            config: Config::new(),
+        }
+    }
+}
+
+impl From<isahc::HttpClient> for IsahcClient {
+    fn from(client: isahc::HttpClient) -> Self {
+        Self::from_client(client)
+    }
+}
+
+#[async_trait]
+impl HttpClient for IsahcClient {
+    async fn send(&self, mut req: Request) -> Result<Response, Error> {
+        let mut request = isahc::Request::builder()
+            .method(req.method().to_string().as_str())
+            .uri(req.url().as_str());
+        for (name, values) in req.iter() {
+            for value in values.iter() {
+                request = request.header(name.as_str(), value.as_str());
+            }
+        }
+        if let Some(timeout) = self.config.timeout {
+            request = request.timeout(timeout);
+        }
+        let request = request
+            .body(req.body_bytes().await?)
+            .map_err(|e| Error::from_str(400, format!("Invalid request: {e}")))?;
+
+        let mut upstream = self
+            .client
+            .send_async(request)
+            .await
+            .map_err(|e| Error::from_str(502, format!("isahc request failed: {e}")))?;
+
+        let mut response = Response::new(StatusCode::try_from(upstream.status().as_u16())?);
+        response.set_version(match upstream.version() {
+            isahc::http::Version::HTTP_09 => Some(Version::Http0_9),
+            isahc::http::Version::HTTP_10 => Some(Version::Http1_0),
+            isahc::http::Version::HTTP_11 => Some(Version::Http1_1),
+            isahc::http::Version::HTTP_2 => Some(Version::Http2_0),
+            isahc::http::Version::HTTP_3 => Some(Version::Http3_0),
+            _ => None,
+        });
+        for (name, value) in upstream.headers() {
+            // http-types header values are strings, so invalid UTF-8 is replaced
+            response.append_header(
+                name.as_str(),
+                String::from_utf8_lossy(value.as_bytes()).as_ref(),
+            )?;
+        }
+        let body = upstream
+            .bytes()
+            .await
+            .map_err(|e| Error::from_str(502, format!("Failed to read isahc response: {e}")))?;
+        response.set_body(body);
+        Ok(response)
+    }
+
+    fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_isahc_client_keeps_repeated_headers_and_binary_bodies() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 4096]).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 201 Created\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\nContent-Length: 2\r\nConnection: close\r\n\r\n\xff\x00")
+                .unwrap();
+        });
+
+        let request = Request::new(
+            http_types::Method::Post,
+            format!("http://{address}/").as_str(),
+        );
+        let mut response = IsahcClient::new().unwrap().send(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::Created);
+        assert_eq!(response["set-cookie"].iter().count(), 2);
+        assert_eq!(response.body_bytes().await.unwrap(), vec![0xff, 0x00]);
+    }
+}
@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use http_client::{Config, Error, HttpClient, Request, Response};
+use http_types::{StatusCode, Version};
+
+/// Sends requests with a [`reqwest::Client`].
+///
+/// Bodies are passed through as bytes, every value of repeated headers is kept and
+/// the client's `Config::timeout` is applied to each request.
+///
+/// ```rust,no_run
+/// # use http_client_vcr::{adapters::ReqwestClient, VcrClient};
+/// # async fn run() -> Result<(), http_client::Error> {
+/// let vcr = VcrClient::builder("tests/fixtures/api.yaml")
+///     .inner_client(Box::new(ReqwestClient::new()))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReqwestClient {
+    client: reqwest::Client,
+    config: Config,
+}
+
+impl ReqwestClient {
+    pub fn new() -> Self {
+        Self::from_client(reqwest::Client::new())
+    }
+
+    /// Wrap an already configured `reqwest::Client`
+    pub fn from_client(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            config: Config::new(),
+        }
+    }
+}
+
+impl Default for ReqwestClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<reqwest::Client> for ReqwestClient {
+    fn from(client: reqwest::Client) -> Self {
+        Self::from_client(client)
+    }
+}
+
+#[async_trait]
+impl HttpClient for ReqwestClient {
+    async fn send(&self, mut req: Request) -> Result<Response, Error> {
+        let method = reqwest::Method::from_bytes(req.method().to_string().as_bytes())
+            .map_err(|e| Error::from_str(400, format!("Unsupported method: {e}")))?;
+        let mut request = self.client.request(method, req.url().as_str());
+        for (name, values) in req.iter() {
+            for value in values.iter() {
+                request = request.header(name.as_str(), value.as_str());
+            }
+        }
+        let body = req.body_bytes().await?;
+        if !body.is_empty() {
+            request = request.body(body);
+        }
+        if let Some(timeout) = self.config.timeout {
+            request = request.timeout(timeout);
+        }
+
+        let upstream = request
+            .send()
+            .await
+            .map_err(|e| Error::from_str(502, format!("reqwest request failed: {e}")))?;
+
+        let status = StatusCode::try_from(upstream.status().as_u16())?;
+        let mut response = Response::new(status);
+        response.set_version(match upstream.version() {
+            reqwest::Version::HTTP_09 => Some(Version::Http0_9),
+            reqwest::Version::HTTP_10 => Some(Version::Http1_0),
+            reqwest::Version::HTTP_11 => Some(Version::Http1_1),
+            reqwest::Version::HTTP_2 => Some(Version::Http2_0),
+            reqwest::Version::HTTP_3 => Some(Version::Http3_0),
+            _ => None,
+        });
+        for (name, value) in upstream.headers() {
+            // http-types header values are strings, so invalid UTF-8 is replaced
+            response.append_header(
+                name.as_str(),
+                String::from_utf8_lossy(value.as_bytes()).as_ref(),
+            )?;
+        }
+        let body = upstream
+            .bytes()
+            .await
+            .map_err(|e| Error::from_str(502, format!("Failed to read reqwest response: {e}")))?;
+        response.set_body(body.to_vec());
+        Ok(response)
+    }
+
+    fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_reqwest_client_keeps_repeated_headers_and_binary_bodies() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let read = stream.read(&mut request).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\nContent-Length: 3\r\nConnection: close\r\n\r\n\xff\x00\xfe")
+                .unwrap();
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+
+        let url = format!("http://{address}/upload");
+        let mut request = Request::new(http_types::Method::Report, url.as_str());
+        request.set_body(vec![0xc3, 0x28]);
+        let mut response = ReqwestClient::new().send(request).await.unwrap();
+
+        let cookies: Vec<_> = response["set-cookie"].iter().map(|v| v.as_str()).collect();
+        assert_eq!(cookies, vec!["a=1", "b=2"]);
+        assert_eq!(response.body_bytes().await.unwrap(), vec![0xff, 0x00, 0xfe]);
+        assert!(server.join().unwrap().starts_with("REPORT /upload"));
+    }
+}
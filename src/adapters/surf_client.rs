@@ -0,0 +1,208 @@
+use crate::VcrClient;
+use async_trait::async_trait;
+use http_client::{Config, Error, HttpClient, Request, Response};
+use http_types::{Method, StatusCode, Url};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Surf middleware answering every request with a [`VcrClient`].
+///
+/// Requests never continue down the surf middleware stack: the `VcrClient` records
+/// or replays them, sending real ones through its own inner client. To record with
+/// surf, give it a [`SurfClient`] wrapping a separate `surf::Client` without this
+/// middleware.
+///
+/// ```rust,no_run
+/// # use http_client_vcr::{adapters::{SurfClient, VcrMiddleware}, VcrClient};
+/// # async fn run(recording: surf::Client, app: surf::Client) -> Result<(), http_client::Error> {
+/// let vcr = VcrClient::builder("tests/fixtures/api.yaml")
+///     .inner_client(Box::new(SurfClient::new(recording)))
+///     .build()
+///     .await?;
+/// let client = app.with(VcrMiddleware::new(vcr));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VcrMiddleware {
+    client: Arc<VcrClient>,
+}
+
+impl VcrMiddleware {
+    pub fn new(client: VcrClient) -> Self {
+        Self::from_arc(Arc::new(client))
+    }
+
+    /// Share a client that is also used elsewhere, e.g. to `finalize` it
+    pub fn from_arc(client: Arc<VcrClient>) -> Self {
+        Self { client }
+    }
+
+    pub fn client(&self) -> &VcrClient {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl surf::middleware::Middleware for VcrMiddleware {
+    async fn handle(
+        &self,
+        req: surf::Request,
+        _client: surf::Client,
+        _next: surf::middleware::Next<'_>,
+    ) -> surf::Result<surf::Response> {
+        let result = async {
+            let request = from_surf_request(req.into()).await?;
+            let mut response = self.client.send(request).await?;
+            to_surf_response(&mut response).await
+        }
+        .await;
+        result.map_err(|e| {
+            let status = surf::StatusCode::try_from(u16::from(e.status()))
+                .unwrap_or(surf::StatusCode::InternalServerError);
+            surf::Error::from_str(status, e.to_string())
+        })
+    }
+}
+
+/// Sends requests with a [`surf::Client`].
+///
+/// Bodies are passed through as bytes and every value of repeated headers is
+/// kept. Timeouts and other settings come from the `surf::Client`'s own config.
+#[derive(Debug, Clone)]
+pub struct SurfClient {
+    client: surf::Client,
+    config: Config,
+}
+
+impl SurfClient {
+    pub fn new(client: surf::Client) -> Self {
+        Self {
+            client,
+            config: Config::new(),
+        }
+    }
+}
+
+impl From<surf::Client> for SurfClient {
+    fn from(client: surf::Client) -> Self {
+        Self::new(client)
+    }
+}
+
+#[async_trait]
+impl HttpClient for SurfClient {
+    async fn send(&self, mut req: Request) -> Result<Response, Error> {
+        let method = surf::http::Method::from_str(req.method().as_ref())
+            .map_err(|e| Error::from_str(400, format!("Unsupported method: {e}")))?;
+        let url = surf::Url::parse(req.url().as_str())?;
+        let mut request = surf::http::Request::new(method, url);
+        for (name, values) in req.iter() {
+            for value in values.iter() {
+                request.append_header(name.as_str(), value.as_str());
+            }
+        }
+        request.set_body(req.body_bytes().await?);
+
+        let upstream = self
+            .client
+            .send(request)
+            .await
+            .map_err(|e| Error::from_str(502, format!("surf request failed: {e}")))?;
+        let mut upstream: surf::http::Response = upstream.into();
+
+        let mut response = Response::new(StatusCode::try_from(u16::from(upstream.status()))?);
+        for (name, values) in upstream.iter() {
+            for value in values.iter() {
+                response.append_header(name.as_str(), value.as_str())?;
+            }
+        }
+        let body = upstream
+            .body_bytes()
+            .await
+            .map_err(|e| Error::from_str(502, format!("Failed to read surf response: {e}")))?;
+        response.set_body(body);
+        Ok(response)
+    }
+
+    fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+async fn from_surf_request(mut req: surf::http::Request) -> Result<Request, Error> {
+    let method = Method::from_str(req.method().as_ref())?;
+    let mut request = Request::new(method, Url::parse(req.url().as_str())?);
+    for (name, values) in req.iter() {
+        for value in values.iter() {
+            request.append_header(name.as_str(), value.as_str())?;
+        }
+    }
+    let body = req
+        .body_bytes()
+        .await
+        .map_err(|e| Error::from_str(400, format!("Failed to read surf request: {e}")))?;
+    request.set_body(body);
+    Ok(request)
+}
+
+async fn to_surf_response(response: &mut Response) -> Result<surf::Response, Error> {
+    let status = surf::StatusCode::try_from(u16::from(response.status()))
+        .map_err(|e| Error::from_str(500, e.to_string()))?;
+    let mut converted = surf::http::Response::new(status);
+    for (name, values) in response.iter() {
+        for value in values.iter() {
+            converted.append_header(name.as_str(), value.as_str());
+        }
+    }
+    converted.set_body(response.body_bytes().await?);
+    Ok(converted.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cassette, Persistence, VcrClientBuilder, VcrMode};
+
+    /// A surf backend answering with its name and the request path
+    #[derive(Debug)]
+    struct Backend(&'static str);
+
+    #[async_trait]
+    impl surf::HttpClient for Backend {
+        async fn send(&self, req: surf::http::Request) -> surf::Result<surf::http::Response> {
+            let mut response = surf::http::Response::new(200);
+            response.append_header("set-cookie", "a=1");
+            response.append_header("set-cookie", "b=2");
+            response.set_body(format!("{} {}", self.0, req.url().path()));
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_records_through_surf_client() {
+        let vcr = VcrClientBuilder::from_cassette(Cassette::new())
+            .mode(VcrMode::Record)
+            .persistence(Persistence::InMemory)
+            .inner_client(Box::new(SurfClient::new(surf::Client::with_http_client(
+                Backend("live"),
+            ))))
+            .build()
+            .await
+            .unwrap();
+        let middleware = VcrMiddleware::new(vcr);
+        // The middleware answers before the app's own backend is reached
+        let app = surf::Client::with_http_client(Backend("app")).with(middleware.clone());
+
+        let mut response = app.get("https://example.com/items").await.unwrap();
+        assert_eq!(response.body_string().await.unwrap(), "live /items");
+        let cookies: Vec<_> = response["set-cookie"].iter().map(|v| v.as_str()).collect();
+        assert_eq!(cookies, vec!["a=1", "b=2"]);
+        assert_eq!(middleware.client().interactions().await.len(), 1);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::runtime;
+use http_client::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// A cassette save running on a background thread. Dropping the handle lets the save
+/// finish unobserved.
+#[derive(Debug)]
+pub struct SaveHandle {
+    task: Option<runtime::Task<Result<(), Error>>>,
+}
+
+impl SaveHandle {
+    pub(crate) fn spawn<F>(save: F) -> Self
+    where
+        F: FnOnce() -> Result<(), Error> + Send + 'static,
+    {
+        Self {
+            task: Some(runtime::unblock(save)),
+        }
+    }
+
+    /// A handle for a save that didn't need to happen
+    pub(crate) fn done() -> Self {
+        Self { task: None }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.task.as_ref().is_none_or(runtime::Task::is_finished)
+    }
+
+    /// Wait for the save to finish and return its result
+    pub async fn flush(mut self) -> Result<(), Error> {
+        match self.task.take() {
+            Some(task) => task.await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SaveHandle {
+    fn drop(&mut self) {
+        // A dropped task is cancelled, so detach it instead
+        if let Some(task) = self.task.take() {
+            task.detach();
+        }
+    }
+}
+
+/// Orders cassette writes, so a snapshot taken earlier never overwrites one taken later
+/// at the same path when saves finish out of order
+#[derive(Debug, Default)]
+pub(crate) struct SaveSequence {
+    next: AtomicU64,
+    written: Mutex<HashMap<PathBuf, u64>>,
+}
+
+impl SaveSequence {
+    /// Number a snapshot; take it while the cassette is locked
+    pub(crate) fn ticket(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Run `write` for snapshot `ticket` unless a later snapshot was already written
+    /// to `path`
+    pub(crate) fn write(
+        &self,
+        ticket: u64,
+        path: &Path,
+        write: impl FnOnce() -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(newer) = written.get(path).filter(|newer| **newer > ticket) {
+            log::debug!("Skipping snapshot {ticket} of {path:?}: snapshot {newer} is newer");
+            return Ok(());
+        }
+        write()?;
+        written.insert(path.to_path_buf(), ticket);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_older_snapshots_are_not_written_after_newer_ones() {
+        let sequence = SaveSequence::default();
+        let first = sequence.ticket();
+        let second = sequence.ticket();
+        let path = Path::new("cassette.yaml");
+        let mut writes = Vec::new();
+
+        sequence
+            .write(second, path, || {
+                writes.push(second);
+                Ok(())
+            })
+            .unwrap();
+        sequence
+            .write(first, path, || {
+                writes.push(first);
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(writes, vec![second]);
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// The text of a recorded response body.
+///
+/// A body recorded from a live response shares its buffer with the response handed
+/// back to the caller instead of holding a second copy. It derefs to `str`, and
+/// [`BodyText::to_mut`] copies it into a `String` of its own the first time it is
+/// changed while the caller still holds the buffer.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct BodyText(Arc<String>);
+
+impl BodyText {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The text as a `String` to modify in place
+    pub fn to_mut(&mut self) -> &mut String {
+        Arc::make_mut(&mut self.0)
+    }
+
+    pub fn into_string(self) -> String {
+        Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+
+    /// Whether something else, like the response handed back to the caller, still
+    /// reads from the same buffer
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.0) > 1
+    }
+}
+
+impl Deref for BodyText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for BodyText {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for BodyText {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl From<String> for BodyText {
+    fn from(text: String) -> Self {
+        BodyText(Arc::new(text))
+    }
+}
+
+impl From<&str> for BodyText {
+    fn from(text: &str) -> Self {
+        BodyText(Arc::new(text.to_string()))
+    }
+}
+
+impl From<BodyText> for String {
+    fn from(text: BodyText) -> Self {
+        text.into_string()
+    }
+}
+
+impl PartialEq<str> for BodyText {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for BodyText {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for BodyText {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl fmt::Debug for BodyText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for BodyText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+impl Serialize for BodyText {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for BodyText {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(BodyText::from)
+    }
+}
@@ -1,15 +1,133 @@
+#[cfg(feature = "fs")]
+use crate::filesystem::FilesystemStorage;
+use crate::migration::{
+    current_format_version, migrate_cassette, serialize_current_version, CURRENT_FORMAT_VERSION,
+};
+#[cfg(feature = "fs")]
+use crate::runtime;
 use crate::serializable::{SerializableRequest, SerializableResponse};
+use crate::storage::CassetteStorage;
+use crate::streaming::StreamChunk;
+use crate::template::UrlTemplate;
+use crate::transport_error::RecordedError;
+#[cfg(feature = "fs")]
+use futures_lite::stream::StreamExt;
+use futures_lite::{stream, Stream};
 use http_client::Error;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use time::OffsetDateTime;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Interaction {
     pub request: SerializableRequest,
+    /// Empty, with status 0, for interactions that recorded an `error` instead
+    #[serde(default = "no_response", skip_serializing_if = "is_no_response")]
     pub response: SerializableResponse,
+    /// The transport error the request failed with, when recorded with `record_errors`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RecordedError>,
+    /// The body of a streamed response, like Server-Sent Events, as the chunks it
+    /// arrived in. `response` then has no body and replay yields the chunks at
+    /// their recorded times.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<Vec<StreamChunk>>,
+    /// Free-form notes about the interaction, e.g. added by a `RecordHook`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Labels grouping the interaction with others, e.g. by the flow that made it,
+    /// so replay can be scoped with `VcrClient::scope_to_tag`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Scenario state the replaying client must be in for the interaction to match, or
+    /// any state if unset. Clients start out in [`SCENARIO_STARTED`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires_state: Option<String>,
+    /// Scenario state the client moves to once the interaction is played, so the same
+    /// request can get a different interaction afterwards
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sets_state: Option<String>,
+    /// When the request was sent (missing in older cassettes)
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recorded_at: Option<OffsetDateTime>,
+    /// How long the server took to respond, including reading the body
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+    /// The order a `VcrClient` sent the request in, which keeps interactions recorded
+    /// concurrently in that order. Not saved.
+    #[serde(skip)]
+    pub sequence: Option<u64>,
+}
+
+/// The scenario state a `VcrClient` starts out in
+pub const SCENARIO_STARTED: &str = "started";
+
+/// The `response` of an interaction that recorded an error
+pub(crate) fn no_response() -> SerializableResponse {
+    SerializableResponse {
+        status: 0,
+        headers: Default::default(),
+        body: None,
+        body_base64: None,
+        version: "None".to_string(),
+    }
+}
+
+fn is_no_response(response: &SerializableResponse) -> bool {
+    response.status == 0
+}
+
+impl Interaction {
+    /// Recorded response time, if known
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed_ms.map(Duration::from_millis)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Add a tag, ignoring duplicates
+    pub fn add_tag(&mut self, tag: impl Into<String>) {
+        let tag = tag.into();
+        if !self.has_tag(&tag) {
+            self.tags.push(tag);
+        }
+    }
+
+    /// Whether the interaction can be played while the client is in scenario `state`
+    pub fn available_in_state(&self, state: &str) -> bool {
+        self.requires_state
+            .as_deref()
+            .is_none_or(|required| required == state)
+    }
+
+    /// Replay the response as a stream of `chunks` parts instead of a single body,
+    /// for code that processes bodies incrementally. The parts are spread over the
+    /// recorded response time, which replay follows unless `stream_replay_speed`
+    /// says otherwise. A response that is already streamed is split again.
+    pub fn stream_in_chunks(&mut self, chunks: usize) {
+        let bytes = match self.stream.take() {
+            Some(stream) => stream.iter().flat_map(StreamChunk::bytes).collect(),
+            None => self.response.bytes().unwrap_or_default(),
+        };
+        self.response.body = None;
+        self.response.body_base64 = None;
+        self.stream = Some(crate::streaming::split_body(bytes, chunks, self.elapsed_ms));
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+/// Interactions yielded one at a time by [`Cassette::stream_interactions`]
+pub type InteractionStream = stream::Boxed<Result<Interaction, Error>>;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CassetteFormat {
     /// Traditional single YAML file format
     #[default]
@@ -18,8 +136,131 @@ pub enum CassetteFormat {
     Directory,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Whether directory cassettes compress their body files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BodyCompression {
+    /// Store every body as plain text
+    #[default]
+    None,
+    /// zstd-compress bodies of at least `min_size` bytes at `level`, as `.txt.zst` or
+    /// `.bin.zst` files
+    #[cfg(feature = "zstd")]
+    Zstd { min_size: usize, level: i32 },
+}
+
+impl BodyCompression {
+    /// zstd at the default level for bodies of at least `min_size` bytes
+    #[cfg(feature = "zstd")]
+    pub fn zstd(min_size: usize) -> Self {
+        BodyCompression::Zstd { min_size, level: 3 }
+    }
+}
+
+/// When a single-file cassette is split into numbered parts (`api.0001.yaml`,
+/// `api.0002.yaml`, ...) on save. A part is started once the current one has
+/// `max_interactions` interactions or its interactions would take more than
+/// `max_bytes` of YAML; a single interaction bigger than that gets a part of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rotation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_interactions: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
+}
+
+impl Rotation {
+    /// Parts of at most `max_interactions` interactions
+    pub fn interactions(max_interactions: usize) -> Self {
+        Self::default().max_interactions(max_interactions)
+    }
+
+    /// Parts of roughly at most `max_bytes` bytes
+    pub fn bytes(max_bytes: usize) -> Self {
+        Self::default().max_bytes(max_bytes)
+    }
+
+    pub fn max_interactions(mut self, max_interactions: usize) -> Self {
+        self.max_interactions = Some(max_interactions.max(1));
+        self
+    }
+
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Whether a part holding `interactions` interactions in `bytes` bytes is full
+    /// before taking one more of `next_bytes` bytes
+    #[cfg(feature = "fs")]
+    pub(crate) fn is_full(&self, interactions: usize, bytes: usize, next_bytes: usize) -> bool {
+        interactions > 0
+            && (self.max_interactions.is_some_and(|max| interactions >= max)
+                || self.max_bytes.is_some_and(|max| bytes + next_bytes > max))
+    }
+}
+
+/// Cassette-level information stored alongside the interactions
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CassetteMetadata {
+    /// URL templates inferred from (or hand-written for) the recorded traffic
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub url_templates: Vec<String>,
+    /// Sanitized environment variables captured when the cassette was recorded
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+    /// When recording of the cassette started
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recorded_at: Option<OffsetDateTime>,
+    /// Version of this crate that recorded the cassette
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recorder_version: Option<String>,
+    /// Who (or what, e.g. a CI job) recorded the cassette
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recorded_by: Option<String>,
+    /// Free-form labels for finding and auditing fixtures
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// How the cassette is split into parts when saved as a single file, kept here so
+    /// tools that load and save the cassette keep splitting it the same way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<Rotation>,
+    /// Highest number given to captured placeholders (`<<TOKEN_2>>`), by prefix, so
+    /// recordings appended later don't reuse them for other values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub placeholders: BTreeMap<String, usize>,
+}
+
+impl CassetteMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.url_templates.is_empty()
+            && self.environment.is_empty()
+            && self.recorded_at.is_none()
+            && self.recorder_version.is_none()
+            && self.recorded_by.is_none()
+            && self.tags.is_empty()
+            && self.description.is_none()
+            && self.rotation.is_none()
+            && self.placeholders.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cassette {
+    /// The layout version the cassette was stored in. Older cassettes are migrated
+    /// when loaded, and saving always writes [`CURRENT_FORMAT_VERSION`].
+    #[serde(
+        default = "current_format_version",
+        serialize_with = "serialize_current_version"
+    )]
+    pub format_version: u32,
+    #[serde(default, skip_serializing_if = "CassetteMetadata::is_empty")]
+    pub metadata: CassetteMetadata,
     pub interactions: Vec<Interaction>,
     #[serde(skip)]
     pub path: Option<PathBuf>,
@@ -27,15 +268,22 @@ pub struct Cassette {
     pub modified_since_load: bool,
     #[serde(skip)]
     pub format: CassetteFormat,
+    /// How body files are compressed when saved in the directory format. Compressed
+    /// bodies are always read back, whatever this is set to.
+    #[serde(skip)]
+    pub body_compression: BodyCompression,
 }
 
 impl Cassette {
     pub fn new() -> Self {
         Self {
+            format_version: CURRENT_FORMAT_VERSION,
+            metadata: CassetteMetadata::default(),
             interactions: Vec::new(),
             path: None,
             modified_since_load: false,
             format: CassetteFormat::File, // Default to file format
+            body_compression: BodyCompression::None,
         }
     }
 
@@ -44,322 +292,419 @@ impl Cassette {
         self
     }
 
+    pub fn with_body_compression(mut self, body_compression: BodyCompression) -> Self {
+        self.body_compression = body_compression;
+        self
+    }
+
     /// Explicitly set the cassette format (useful when creating new cassettes)
     pub fn with_format(mut self, format: CassetteFormat) -> Self {
         self.format = format;
         self
     }
 
+    /// Parse a single-file cassette, migrating it from an older layout if needed
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        let parse_error = |e: serde_yaml::Error| {
+            Error::from_str(500, format!("Failed to parse cassette YAML: {e}"))
+        };
+        let mut document: serde_yaml::Value = serde_yaml::from_str(yaml).map_err(parse_error)?;
+        let format_version = migrate_cassette(&mut document)?;
+        let mut cassette: Cassette = serde_yaml::from_value(document).map_err(parse_error)?;
+        cassette.format_version = format_version;
+        Ok(cassette)
+    }
+
+    /// Whether the cassette was stored in an older layout than the one saving writes
+    pub fn needs_migration(&self) -> bool {
+        self.format_version < CURRENT_FORMAT_VERSION
+    }
+
+    #[cfg(feature = "fs")]
     pub async fn load_from_file(path: PathBuf) -> Result<Self, Error> {
-        // Simple detection: if it's a directory, load as directory format, otherwise as file
-        if path.is_dir() {
-            Self::load_from_directory(path).await
+        let loaded = {
+            let path = path.clone();
+            runtime::unblock(move || FilesystemStorage.load(&path)).await?
+        };
+        loaded.ok_or_else(|| {
+            Error::from_str(
+                500,
+                format!("Failed to read cassette file: {path:?} does not exist"),
+            )
+        })
+    }
+
+    /// The interactions of the cassette `storage` has at `path`, read one at a time.
+    ///
+    /// Directory and SQLite storage read each interaction's bodies when it is yielded,
+    /// so analysis tools can go through very large cassettes in bounded memory; other
+    /// backends load the cassette first (see [`CassetteStorage::interactions`]).
+    pub fn stream<'a, S: CassetteStorage + ?Sized>(
+        storage: &'a S,
+        path: &Path,
+    ) -> Result<impl Stream<Item = Result<Interaction, Error>> + Send + 'a, Error> {
+        let interactions = storage.interactions(path)?.ok_or_else(|| {
+            Error::from_str(404, format!("No cassette stored at {}", path.display()))
+        })?;
+        Ok(stream::iter(interactions))
+    }
+
+    /// Iterate over the interactions stored at `path` without loading the whole cassette.
+    ///
+    /// For directory cassettes only `interactions.yaml` is read up front; each body file
+    /// is read when its interaction is yielded, so batch tools can process very large
+    /// cassettes in bounded memory. Single-file cassettes have to be parsed in full.
+    ///
+    /// ```rust,no_run
+    /// # async fn run() -> Result<(), http_client::Error> {
+    /// use futures_lite::StreamExt;
+    /// use http_client_vcr::Cassette;
+    ///
+    /// let mut interactions = Cassette::stream_interactions("tests/fixtures/big")?;
+    /// while let Some(interaction) = interactions.next().await {
+    ///     println!("{}", interaction?.request.url);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn stream_interactions<P: Into<PathBuf>>(path: P) -> Result<InteractionStream, Error> {
+        Ok(Cassette::stream(&FilesystemStorage, &path.into())?.boxed())
+    }
+
+    #[cfg(feature = "fs")]
+    pub async fn save_to_file(&self) -> Result<(), Error> {
+        let snapshot = self.clone();
+        runtime::unblock(move || snapshot.save_sync()).await
+    }
+
+    /// Blocking save, usable from contexts that can't await such as Drop
+    #[cfg(feature = "fs")]
+    pub(crate) fn save_sync(&self) -> Result<(), Error> {
+        if let Some(path) = &self.path {
+            FilesystemStorage.save(path, self)
         } else {
-            Self::load_from_single_file(path).await
+            Err(Error::from_str(400, "No path specified for cassette"))
         }
     }
 
-    async fn load_from_single_file(path: PathBuf) -> Result<Self, Error> {
-        let content = std::fs::read_to_string(&path)
-            .map_err(|e| Error::from_str(500, format!("Failed to read cassette file: {e}")))?;
-
-        let mut cassette: Cassette = serde_yaml::from_str(&content)
-            .map_err(|e| Error::from_str(500, format!("Failed to parse cassette YAML: {e}")))?;
+    pub fn clear(&mut self) {
+        self.interactions.clear();
+        self.metadata.recorded_at = None;
+        self.metadata.placeholders.clear();
+    }
 
-        cassette.path = Some(path);
-        cassette.format = CassetteFormat::File;
-        cassette.modified_since_load = false;
+    pub async fn record_interaction(
+        &mut self,
+        serializable_request: SerializableRequest,
+        serializable_response: SerializableResponse,
+    ) -> Result<(), Error> {
+        self.push_interaction(
+            serializable_request,
+            serializable_response,
+            OffsetDateTime::now_utc(),
+            None,
+            None,
+        );
+        Ok(())
+    }
 
-        Ok(cassette)
+    /// Record an interaction along with when the request was sent and how long the
+    /// response took, so replays can simulate the original latency
+    pub async fn record_timed_interaction(
+        &mut self,
+        serializable_request: SerializableRequest,
+        serializable_response: SerializableResponse,
+        started_at: OffsetDateTime,
+        elapsed: Duration,
+    ) -> Result<(), Error> {
+        self.push_interaction(
+            serializable_request,
+            serializable_response,
+            started_at,
+            Some(elapsed),
+            None,
+        );
+        Ok(())
     }
 
-    async fn load_from_directory(path: PathBuf) -> Result<Self, Error> {
-        // Load interactions metadata from interactions.yaml
-        let interactions_file = path.join("interactions.yaml");
-        if !interactions_file.exists() {
-            return Err(Error::from_str(
-                404,
-                format!("Directory cassette missing interactions.yaml: {path:?}"),
-            ));
-        }
+    /// Add a complete interaction, placed among the interactions recorded with a
+    /// sequence by the order their requests were sent, however late the response
+    /// arrived. Returns where the interaction was inserted.
+    pub(crate) fn record_sequenced_interaction(&mut self, interaction: Interaction) -> usize {
+        self.insert_interaction(interaction)
+    }
 
-        let content = std::fs::read_to_string(&interactions_file)
-            .map_err(|e| Error::from_str(500, format!("Failed to read interactions.yaml: {e}")))?;
+    fn push_interaction(
+        &mut self,
+        serializable_request: SerializableRequest,
+        serializable_response: SerializableResponse,
+        started_at: OffsetDateTime,
+        elapsed: Option<Duration>,
+        sequence: Option<u64>,
+    ) -> usize {
+        self.insert_interaction(Interaction {
+            request: serializable_request,
+            response: serializable_response,
+            recorded_at: Some(started_at),
+            elapsed_ms: elapsed.map(|elapsed| elapsed.as_millis() as u64),
+            sequence,
+            error: None,
+            stream: None,
+            metadata: BTreeMap::new(),
+            tags: Vec::new(),
+            requires_state: None,
+            sets_state: None,
+        })
+    }
 
-        // For directory format, we serialize a simplified structure
-        #[derive(Deserialize)]
-        struct DirectoryInteraction {
-            request: DirectorySerializableRequest,
-            response: DirectorySerializableResponse,
+    fn insert_interaction(&mut self, interaction: Interaction) -> usize {
+        // Interactions without a sequence sort first, so only ones sent later are passed
+        let mut position = self.interactions.len();
+        while position > 0 && self.interactions[position - 1].sequence > interaction.sequence {
+            position -= 1;
         }
 
-        #[derive(Deserialize)]
-        struct DirectorySerializableRequest {
-            method: String,
-            url: String,
-            headers: std::collections::HashMap<String, Vec<String>>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            body_file: Option<String>,
-            version: String,
+        match (self.metadata.recorded_at, interaction.recorded_at) {
+            (None, started_at) => {
+                self.metadata.recorded_at = started_at;
+                self.metadata.recorder_version = Some(env!("CARGO_PKG_VERSION").to_string());
+            }
+            // A request sent earlier may finish after later ones
+            (Some(recorded_at), Some(started_at)) if started_at < recorded_at => {
+                self.metadata.recorded_at = Some(started_at);
+            }
+            _ => {}
         }
 
-        #[derive(Deserialize)]
-        struct DirectorySerializableResponse {
-            status: u16,
-            headers: std::collections::HashMap<String, Vec<String>>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            body_file: Option<String>,
-            version: String,
-        }
+        self.interactions.insert(position, interaction);
+        self.modified_since_load = true; // Mark as modified when recording new interactions
+        position
+    }
 
-        let dir_interactions: Vec<DirectoryInteraction> = serde_yaml::from_str(&content)
-            .map_err(|e| Error::from_str(500, format!("Failed to parse interactions.yaml: {e}")))?;
-
-        let bodies_dir = path.join("bodies");
-        let mut interactions = Vec::new();
-
-        for dir_interaction in dir_interactions {
-            // Load request body if specified
-            let (request_body, request_body_base64) =
-                if let Some(ref body_file) = dir_interaction.request.body_file {
-                    let body_path = bodies_dir.join(body_file);
-                    let content = std::fs::read_to_string(&body_path).map_err(|e| {
-                        Error::from_str(
-                            500,
-                            format!("Failed to read request body file {body_file}: {e}"),
-                        )
-                    })?;
-
-                    // Check if this is a base64 file based on extension
-                    if body_file.ends_with(".b64") {
-                        (None, Some(content))
-                    } else {
-                        (Some(content), None)
-                    }
-                } else {
-                    (None, None)
-                };
-
-            // Load response body if specified
-            let (response_body, response_body_base64) =
-                if let Some(ref body_file) = dir_interaction.response.body_file {
-                    let body_path = bodies_dir.join(body_file);
-                    let content = std::fs::read_to_string(&body_path).map_err(|e| {
-                        Error::from_str(
-                            500,
-                            format!("Failed to read response body file {body_file}: {e}"),
-                        )
-                    })?;
-
-                    // Check if this is a base64 file based on extension
-                    if body_file.ends_with(".b64") {
-                        (None, Some(content))
-                    } else {
-                        (Some(content), None)
-                    }
-                } else {
-                    (None, None)
-                };
-
-            let interaction = Interaction {
-                request: SerializableRequest {
-                    method: dir_interaction.request.method,
-                    url: dir_interaction.request.url,
-                    headers: dir_interaction.request.headers,
-                    body: request_body,
-                    body_base64: request_body_base64,
-                    version: dir_interaction.request.version,
-                },
-                response: SerializableResponse {
-                    status: dir_interaction.response.status,
-                    headers: dir_interaction.response.headers,
-                    body: response_body,
-                    body_base64: response_body_base64,
-                    version: dir_interaction.response.version,
-                },
-            };
-
-            interactions.push(interaction);
+    /// Add the URL template of the interaction at `position` to the metadata if it's
+    /// new, ahead of the templates introduced by the interactions after it
+    pub(crate) fn infer_url_template_at(&mut self, position: usize) {
+        let template = UrlTemplate::infer(&self.interactions[position].request.url).to_string();
+        if !self.metadata.url_templates.contains(&template) {
+            match self.first_template_after(position) {
+                Some(index) => self.metadata.url_templates.insert(index, template),
+                None => self.metadata.url_templates.push(template),
+            }
+            self.modified_since_load = true;
         }
+    }
 
-        Ok(Cassette {
-            interactions,
-            path: Some(path),
-            format: CassetteFormat::Directory,
-            modified_since_load: false,
-        })
+    /// Where in the metadata the first URL template introduced by the interactions
+    /// after `position` is
+    fn first_template_after(&self, position: usize) -> Option<usize> {
+        if position + 1 >= self.interactions.len() {
+            return None;
+        }
+        let infer =
+            |interaction: &Interaction| UrlTemplate::infer(&interaction.request.url).to_string();
+        let earlier: HashSet<String> = self.interactions[..position].iter().map(infer).collect();
+        let later: HashSet<String> = self.interactions[position + 1..]
+            .iter()
+            .map(infer)
+            .filter(|template| !earlier.contains(template))
+            .collect();
+        self.metadata
+            .url_templates
+            .iter()
+            .position(|template| later.contains(template))
     }
 
-    pub async fn save_to_file(&self) -> Result<(), Error> {
-        if let Some(path) = &self.path {
-            match self.format {
-                CassetteFormat::File => self.save_to_single_file(path).await,
-                CassetteFormat::Directory => self.save_to_directory(path).await,
+    /// Recompute the URL templates in the metadata from the recorded interactions,
+    /// keeping any templates that were written by hand. Recording only adds templates
+    /// when `VcrClientBuilder::infer_url_templates` is on.
+    pub fn infer_url_templates(&mut self) {
+        for interaction in &self.interactions {
+            let template = UrlTemplate::infer(&interaction.request.url).to_string();
+            if !self.metadata.url_templates.contains(&template) {
+                self.metadata.url_templates.push(template);
+                self.modified_since_load = true;
             }
-        } else {
-            Err(Error::from_str(400, "No path specified for cassette"))
         }
     }
 
-    async fn save_to_single_file(&self, path: &PathBuf) -> Result<(), Error> {
-        let yaml = serde_yaml::to_string(self)
-            .map_err(|e| Error::from_str(500, format!("Failed to serialize cassette: {e}")))?;
+    /// The URL templates from the metadata, parsed
+    pub fn url_templates(&self) -> Vec<UrlTemplate> {
+        self.metadata
+            .url_templates
+            .iter()
+            .map(|template| UrlTemplate::parse(template))
+            .collect()
+    }
 
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| Error::from_str(500, format!("Failed to create directory: {e}")))?;
+    /// Store a snapshot of the environment the cassette is being recorded under
+    pub fn record_environment(&mut self, environment: BTreeMap<String, String>) {
+        if self.metadata.environment != environment {
+            self.metadata.environment = environment;
+            self.modified_since_load = true;
         }
+    }
 
-        std::fs::write(path, yaml)
-            .map_err(|e| Error::from_str(500, format!("Failed to write cassette file: {e}")))?;
+    /// The environment snapshot captured at record time
+    pub fn environment(&self) -> &BTreeMap<String, String> {
+        &self.metadata.environment
+    }
 
-        Ok(())
+    /// When recording of this cassette started, if known
+    pub fn recorded_at(&self) -> Option<OffsetDateTime> {
+        self.metadata.recorded_at
     }
 
-    async fn save_to_directory(&self, path: &PathBuf) -> Result<(), Error> {
-        // Create the cassette directory and bodies subdirectory
-        std::fs::create_dir_all(path).map_err(|e| {
-            Error::from_str(500, format!("Failed to create cassette directory: {e}"))
-        })?;
+    pub fn recorder_version(&self) -> Option<&str> {
+        self.metadata.recorder_version.as_deref()
+    }
 
-        let bodies_dir = path.join("bodies");
-        std::fs::create_dir_all(&bodies_dir)
-            .map_err(|e| Error::from_str(500, format!("Failed to create bodies directory: {e}")))?;
+    pub fn recorded_by(&self) -> Option<&str> {
+        self.metadata.recorded_by.as_deref()
+    }
 
-        // Create directory format structures for serialization
-        use serde::Serialize;
+    pub fn set_recorded_by(&mut self, recorded_by: impl Into<String>) {
+        self.metadata.recorded_by = Some(recorded_by.into());
+    }
 
-        #[derive(Serialize)]
-        struct DirectoryInteraction {
-            request: DirectorySerializableRequest,
-            response: DirectorySerializableResponse,
-        }
+    pub fn tags(&self) -> &[String] {
+        &self.metadata.tags
+    }
 
-        #[derive(Serialize)]
-        struct DirectorySerializableRequest {
-            method: String,
-            url: String,
-            headers: std::collections::HashMap<String, Vec<String>>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            body_file: Option<String>,
-            version: String,
+    /// Add a tag, ignoring duplicates
+    pub fn add_tag(&mut self, tag: impl Into<String>) {
+        let tag = tag.into();
+        if !self.metadata.tags.contains(&tag) {
+            self.metadata.tags.push(tag);
         }
+    }
 
-        #[derive(Serialize)]
-        struct DirectorySerializableResponse {
-            status: u16,
-            headers: std::collections::HashMap<String, Vec<String>>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            body_file: Option<String>,
-            version: String,
-        }
+    /// The interactions tagged with `tag`, with their indices
+    pub fn interactions_tagged<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a Interaction)> + 'a {
+        self.interactions
+            .iter()
+            .enumerate()
+            .filter(move |(_, interaction)| interaction.has_tag(tag))
+    }
+
+    /// The interactions `predicate` accepts, with their indices
+    pub fn find<'a, F>(
+        &'a self,
+        predicate: F,
+    ) -> impl Iterator<Item = (usize, &'a Interaction)> + 'a
+    where
+        F: Fn(&Interaction) -> bool + 'a,
+    {
+        self.interactions
+            .iter()
+            .enumerate()
+            .filter(move |(_, interaction)| predicate(interaction))
+    }
 
-        let mut dir_interactions = Vec::new();
-
-        for (i, interaction) in self.interactions.iter().enumerate() {
-            let interaction_num = format!("{:03}", i + 1);
-
-            // Handle request body
-            let request_body_file = if let Some(ref body) = interaction.request.body {
-                if !body.is_empty() {
-                    let filename = format!("req_{interaction_num}.txt");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body).map_err(|e| {
-                        Error::from_str(500, format!("Failed to write request body file: {e}"))
-                    })?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else if let Some(ref body_base64) = interaction.request.body_base64 {
-                if !body_base64.is_empty() {
-                    let filename = format!("req_{interaction_num}.b64");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body_base64).map_err(|e| {
-                        Error::from_str(500, format!("Failed to write request body file: {e}"))
-                    })?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            // Handle response body
-            let response_body_file = if let Some(ref body) = interaction.response.body {
-                if !body.is_empty() {
-                    let filename = format!("resp_{interaction_num}.txt");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body).map_err(|e| {
-                        Error::from_str(500, format!("Failed to write response body file: {e}"))
-                    })?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else if let Some(ref body_base64) = interaction.response.body_base64 {
-                if !body_base64.is_empty() {
-                    let filename = format!("resp_{interaction_num}.b64");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body_base64).map_err(|e| {
-                        Error::from_str(500, format!("Failed to write response body file: {e}"))
-                    })?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            let dir_interaction = DirectoryInteraction {
-                request: DirectorySerializableRequest {
-                    method: interaction.request.method.clone(),
-                    url: interaction.request.url.clone(),
-                    headers: interaction.request.headers.clone(),
-                    body_file: request_body_file,
-                    version: interaction.request.version.clone(),
-                },
-                response: DirectorySerializableResponse {
-                    status: interaction.response.status,
-                    headers: interaction.response.headers.clone(),
-                    body_file: response_body_file,
-                    version: interaction.response.version.clone(),
-                },
-            };
-
-            dir_interactions.push(dir_interaction);
+    /// The interactions whose request went to `host` (compared case-insensitively,
+    /// without the port), with their indices
+    pub fn interactions_for_host<'a>(
+        &'a self,
+        host: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a Interaction)> + 'a {
+        self.find(move |interaction| {
+            url::Url::parse(&interaction.request.url).is_ok_and(|url| {
+                url.host_str()
+                    .is_some_and(|request_host| request_host.eq_ignore_ascii_case(host))
+            })
+        })
+    }
+
+    /// The interactions whose request used `method` (case-insensitively) and has a URL
+    /// containing a match for the regex `url_pattern`, with their indices. Anchor the
+    /// pattern with `^` and `$` to match the whole URL.
+    pub fn requests_matching<'a>(
+        &'a self,
+        method: &'a str,
+        url_pattern: &str,
+    ) -> Result<impl Iterator<Item = (usize, &'a Interaction)> + 'a, regex::Error> {
+        let url_pattern = regex::Regex::new(url_pattern)?;
+        Ok(self.find(move |interaction| {
+            interaction.request.method.eq_ignore_ascii_case(method)
+                && url_pattern.is_match(&interaction.request.url)
+        }))
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.metadata.description.as_deref()
+    }
+
+    pub fn set_description(&mut self, description: impl Into<String>) {
+        self.metadata.description = Some(description.into());
+    }
+
+    /// Split the cassette into numbered parts when it is saved as a single file, or
+    /// `None` to save it as one file again
+    pub fn set_rotation(&mut self, rotation: Option<Rotation>) {
+        if self.metadata.rotation != rotation {
+            self.metadata.rotation = rotation;
+            self.modified_since_load = true;
         }
+    }
 
-        // Write the interactions.yaml file
-        let interactions_yaml = serde_yaml::to_string(&dir_interactions)
-            .map_err(|e| Error::from_str(500, format!("Failed to serialize interactions: {e}")))?;
+    /// Whether the cassette was recorded more than `interval` ago. Cassettes without a
+    /// recording time are never considered expired.
+    pub fn is_older_than(&self, interval: Duration) -> bool {
+        self.recorded_at()
+            .is_some_and(|recorded_at| OffsetDateTime::now_utc() - recorded_at > interval)
+    }
 
-        let interactions_file = path.join("interactions.yaml");
-        std::fs::write(&interactions_file, interactions_yaml)
-            .map_err(|e| Error::from_str(500, format!("Failed to write interactions.yaml: {e}")))?;
+    /// Remove the interaction at `index`, returning it, or `None` if there is none
+    pub fn remove_interaction(&mut self, index: usize) -> Option<Interaction> {
+        if index >= self.interactions.len() {
+            return None;
+        }
+        self.modified_since_load = true;
+        Some(self.interactions.remove(index))
+    }
 
-        Ok(())
+    /// Keep only the interactions `predicate` accepts, returning how many were removed
+    pub fn retain<F: FnMut(&Interaction) -> bool>(&mut self, predicate: F) -> usize {
+        let before = self.interactions.len();
+        self.interactions.retain(predicate);
+        let removed = before - self.interactions.len();
+        if removed > 0 {
+            self.modified_since_load = true;
+        }
+        removed
     }
 
-    pub fn clear(&mut self) {
-        self.interactions.clear();
+    /// Swap the interactions at `a` and `b`, e.g. to change which of two matching
+    /// interactions replays first.
+    ///
+    /// # Panics
+    ///
+    /// If either index is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.interactions.swap(a, b);
+        if a != b {
+            self.modified_since_load = true;
+        }
     }
 
-    pub async fn record_interaction(
+    /// Put `interaction` in place of the one at `index`, returning the old one, or
+    /// `None` (leaving the cassette unchanged) if there is none
+    pub fn replace_interaction(
         &mut self,
-        serializable_request: SerializableRequest,
-        serializable_response: SerializableResponse,
-    ) -> Result<(), Error> {
-        let interaction = Interaction {
-            request: serializable_request,
-            response: serializable_response,
-        };
-
-        self.interactions.push(interaction);
-        self.modified_since_load = true; // Mark as modified when recording new interactions
-        Ok(())
+        index: usize,
+        interaction: Interaction,
+    ) -> Option<Interaction> {
+        let slot = self.interactions.get_mut(index)?;
+        let replaced = std::mem::replace(slot, interaction);
+        self.modified_since_load = true;
+        // Only cassettes that keep URL templates need one for the new request
+        if !self.metadata.url_templates.is_empty() {
+            self.infer_url_template_at(index);
+        }
+        Some(replaced)
     }
 
     pub fn len(&self) -> usize {
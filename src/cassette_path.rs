@@ -0,0 +1,91 @@
+use crate::VcrMode;
+use http_client::Error;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the running test, for the `{test}` placeholder in
+/// cassette paths
+pub const TEST_NAME_ENV: &str = "VCR_TEST_NAME";
+
+/// The name of the running test: `VCR_TEST_NAME` if it is set, otherwise the name
+/// the test harness gives the test's thread, e.g. `checkout::test_pays`
+pub fn current_test_name() -> Option<String> {
+    match std::env::var(TEST_NAME_ENV) {
+        Ok(name) if !name.trim().is_empty() => Some(name),
+        _ => std::thread::current()
+            .name()
+            .filter(|name| *name != "main")
+            .map(str::to_string),
+    }
+}
+
+/// Fill in the placeholders of a cassette path: `{test}` becomes the test name, with
+/// module separators as directories, and `{mode}` the mode's name. Other text,
+/// including other braces, is kept as is.
+pub(crate) fn expand(
+    template: &Path,
+    test_name: Option<&str>,
+    mode: Option<&VcrMode>,
+) -> Result<PathBuf, Error> {
+    let Some(text) = template.to_str().filter(|text| text.contains('{')) else {
+        return Ok(template.to_path_buf());
+    };
+
+    let mut expanded = text.to_string();
+    if expanded.contains("{test}") {
+        let test_name = test_name
+            .map(str::to_string)
+            .or_else(current_test_name)
+            .ok_or_else(|| {
+                Error::from_str(
+                    400,
+                    format!(
+                        "Cassette path {template:?} uses {{test}}, but the test name is unknown. \
+                         Set it with `test_name` or the {TEST_NAME_ENV} environment variable."
+                    ),
+                )
+            })?;
+        expanded = expanded.replace("{test}", &test_name.replace("::", "/"));
+    }
+    if expanded.contains("{mode}") {
+        let mode = mode.ok_or_else(|| {
+            Error::from_str(
+                400,
+                format!(
+                    "Cassette path {template:?} uses {{mode}}, but the mode depends on whether \
+                     the cassette exists. Set it with `mode`."
+                ),
+            )
+        })?;
+        expanded = expanded.replace("{mode}", &mode.to_string());
+    }
+    Ok(PathBuf::from(expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders_are_filled_in() {
+        let expanded = expand(
+            Path::new("fixtures/{test}/{mode}.yaml"),
+            Some("checkout::test_pays"),
+            Some(&VcrMode::Replay),
+        )
+        .unwrap();
+        assert_eq!(
+            expanded,
+            Path::new("fixtures/checkout/test_pays/replay.yaml")
+        );
+
+        // The harness names each test's thread after the test
+        let expanded = expand(Path::new("{test}.yaml"), None, None).unwrap();
+        assert!(expanded.ends_with("test_placeholders_are_filled_in.yaml"));
+
+        assert!(expand(Path::new("{mode}.yaml"), None, None).is_err());
+        assert_eq!(
+            expand(Path::new("{other}.yaml"), None, None).unwrap(),
+            Path::new("{other}.yaml")
+        );
+    }
+}
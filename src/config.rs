@@ -0,0 +1,251 @@
+use crate::cassette::CassetteFormat;
+use crate::filter::{BodyFilter, FilterChain, HeaderFilter, UrlFilter};
+use crate::matcher::{DefaultMatcher, ExactMatcher, RequestMatcher, TemplateMatcher};
+use http_client::Error;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Name of the project-level configuration file
+pub const CONFIG_FILE_NAME: &str = "vcr.toml";
+
+/// Shared defaults read from a `vcr.toml` file.
+///
+/// `vcr-inspect` reads the file on its own, but the library never does: clients only
+/// use it after it is loaded with [`discover`](Self::discover) or [`load`](Self::load)
+/// and handed to `VcrClientBuilder::with_config`. `discover` looks the file up in this
+/// order:
+/// 1. the path in the `VCR_CONFIG` environment variable
+/// 2. `vcr.toml` in the current directory or any of its ancestors
+/// 3. `$XDG_CONFIG_HOME/http-client-vcr/vcr.toml` (or `~/.config/...`)
+///
+/// ```toml
+/// cassette_root = "tests/fixtures"
+/// format = "directory"
+/// matcher = "without_cookies"
+/// filter_profile = "strict"
+///
+/// [cli]
+/// pretty = true
+/// format = "file"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VcrConfig {
+    /// Directory that relative cassette paths are resolved against
+    pub cassette_root: Option<PathBuf>,
+    /// Format used when creating new cassettes
+    pub format: Option<CassetteFormat>,
+    /// Matcher used when the builder isn't given one
+    pub matcher: Option<MatcherProfile>,
+    /// Filters installed when the builder starts out
+    pub filter_profile: Option<FilterProfile>,
+    /// Defaults for the `vcr-inspect` command line tool
+    pub cli: CliConfig,
+    #[serde(skip)]
+    source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CliConfig {
+    /// Pretty-print JSON output
+    pub pretty: bool,
+    /// Default output format for `vcr-inspect convert`
+    pub format: Option<CassetteFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatcherProfile {
+    /// `DefaultMatcher::new()`
+    Default,
+    /// `DefaultMatcher::without_cookies()`
+    WithoutCookies,
+    /// Match on method and URL only
+    MethodAndUrl,
+    /// `ExactMatcher`
+    Exact,
+    /// `TemplateMatcher` using inferred URL templates
+    Template,
+}
+
+impl MatcherProfile {
+    pub fn matcher(self) -> Box<dyn RequestMatcher> {
+        match self {
+            MatcherProfile::Default => Box::new(DefaultMatcher::new()),
+            MatcherProfile::WithoutCookies => Box::new(DefaultMatcher::without_cookies()),
+            MatcherProfile::MethodAndUrl => Box::new(DefaultMatcher::new().with_headers(vec![])),
+            MatcherProfile::Exact => Box::new(ExactMatcher),
+            MatcherProfile::Template => Box::new(TemplateMatcher::new()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterProfile {
+    /// No filters
+    None,
+    /// Remove authentication headers
+    Auth,
+    /// Remove authentication headers, common sensitive JSON keys and query parameters
+    Strict,
+}
+
+impl FilterProfile {
+    pub fn filter_chain(self) -> FilterChain {
+        match self {
+            FilterProfile::None => FilterChain::new(),
+            FilterProfile::Auth => {
+                FilterChain::new().add_filter(Box::new(HeaderFilter::new().remove_auth_headers()))
+            }
+            FilterProfile::Strict => FilterChain::new()
+                .add_filter(Box::new(HeaderFilter::new().remove_auth_headers()))
+                .add_filter(Box::new(BodyFilter::new().remove_common_sensitive_keys()))
+                .add_filter(Box::new(UrlFilter::new().remove_common_sensitive_params())),
+        }
+    }
+}
+
+impl VcrConfig {
+    /// Find and load the config file, or return the empty default config if there
+    /// isn't one. Fails if the file can't be read or parsed.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{VcrClientBuilder, VcrConfig};
+    /// # fn run() -> Result<(), http_client::Error> {
+    /// let builder = VcrClientBuilder::new("login.yaml").with_config(VcrConfig::discover()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn discover() -> Result<Self, Error> {
+        match Self::find_config_file() {
+            Some(path) => Self::load(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn find_config_file() -> Option<PathBuf> {
+        if let Ok(path) = std::env::var("VCR_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+
+        if let Ok(cwd) = std::env::current_dir() {
+            for dir in cwd.ancestors() {
+                let candidate = dir.join(CONFIG_FILE_NAME);
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        let candidate = config_home.join("http-client-vcr").join(CONFIG_FILE_NAME);
+        candidate.is_file().then_some(candidate)
+    }
+
+    /// Load a config file from an explicit path
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            Error::from_str(500, format!("Failed to read config file {path:?}: {e}"))
+        })?;
+
+        let mut config = Self::parse(&content)
+            .map_err(|e| Error::from_str(500, format!("Failed to parse {path:?}: {e}")))?;
+
+        // A relative cassette root is relative to the config file, not the cwd. It is
+        // made absolute so paths resolve the same wherever the client is built.
+        if let (Some(root), Some(dir)) = (&config.cassette_root, path.parent()) {
+            if root.is_relative() {
+                let root = dir.join(root);
+                config.cassette_root = Some(std::path::absolute(&root).unwrap_or(root));
+            }
+        }
+        config.source = Some(path.to_path_buf());
+
+        Ok(config)
+    }
+
+    /// Parse config file contents
+    pub fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// The file this config was loaded from, if any
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Resolve a cassette path against the configured cassette root.
+    ///
+    /// Relative paths are always joined onto the root, whatever exists on disk;
+    /// absolute paths, and any path when there is no root, are returned unchanged.
+    pub fn resolve_cassette_path<P: Into<PathBuf>>(&self, path: P) -> PathBuf {
+        resolve_cassette_path(self.cassette_root.as_deref(), path.into())
+    }
+}
+
+/// Join a relative `path` onto `root`, if there is one
+pub(crate) fn resolve_cassette_path(root: Option<&Path>, path: PathBuf) -> PathBuf {
+    match root {
+        Some(root) if path.is_relative() => root.join(path),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_config() {
+        let config = VcrConfig::parse(
+            r#"
+            cassette_root = "fixtures"
+            format = "directory"
+            matcher = "method_and_url"
+            filter_profile = "auth"
+
+            [cli]
+            pretty = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.cassette_root, Some(PathBuf::from("fixtures")));
+        assert!(matches!(config.format, Some(CassetteFormat::Directory)));
+        assert_eq!(config.matcher, Some(MatcherProfile::MethodAndUrl));
+        assert_eq!(config.filter_profile, Some(FilterProfile::Auth));
+        assert!(config.cli.pretty);
+        assert!(config.cli.format.is_none());
+    }
+
+    #[test]
+    fn test_resolve_cassette_path() {
+        let config = VcrConfig {
+            cassette_root: Some(PathBuf::from("/fixtures")),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.resolve_cassette_path("missing.yaml"),
+            PathBuf::from("/fixtures/missing.yaml")
+        );
+        assert_eq!(
+            config.resolve_cassette_path("/abs/cassette.yaml"),
+            PathBuf::from("/abs/cassette.yaml")
+        );
+        // Existing relative paths are rooted too, so the cwd doesn't matter
+        assert_eq!(
+            config.resolve_cassette_path("src"),
+            PathBuf::from("/fixtures/src")
+        );
+        assert_eq!(
+            VcrConfig::default().resolve_cassette_path("src"),
+            PathBuf::from("src")
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use async_lock::{Mutex, MutexGuardArc};
+use event_listener::Event;
+use futures_lite::future;
+use futures_lite::io::{AsyncBufRead, AsyncRead};
+use http_types::Body;
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Order in which responses on the same connection are released
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayOrdering {
+    /// First come, first served
+    Arrival,
+    /// In the order the interactions were recorded, like HTTP/1.1 pipelining
+    Recorded,
+}
+
+/// Emulate per-connection semantics during replay.
+///
+/// By default replayed responses are independent, like HTTP/2 multiplexing. With a
+/// `ConnectionReplay` set, requests to the same host share one connection: a response
+/// isn't released until the previous response on that host has been read to the end,
+/// reproducing HTTP/1.1 head-of-line blocking. Combine it with `ChunkedReplay` delays
+/// to make slow responses hold up the ones queued behind them.
+#[derive(Debug, Clone)]
+pub struct ConnectionReplay {
+    ordering: ReplayOrdering,
+    max_wait: Duration,
+}
+
+impl ConnectionReplay {
+    /// One connection per host, serving requests in arrival order
+    pub fn per_host() -> Self {
+        Self {
+            ordering: ReplayOrdering::Arrival,
+            max_wait: Duration::from_secs(5),
+        }
+    }
+
+    /// Release responses in recorded order instead of arrival order
+    pub fn recorded_order(mut self) -> Self {
+        self.ordering = ReplayOrdering::Recorded;
+        self
+    }
+
+    /// How long a request waits for earlier recorded requests on its host that never
+    /// arrive before being served anyway (only used with recorded ordering)
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    pub fn ordering(&self) -> ReplayOrdering {
+        self.ordering
+    }
+
+    pub fn max_wait(&self) -> Duration {
+        self.max_wait
+    }
+}
+
+/// Per-host connection state shared by all requests of a `VcrClient`
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionGate {
+    connections: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    served: std::sync::Mutex<HashSet<usize>>,
+    served_changed: Event,
+}
+
+impl ConnectionGate {
+    /// Wait for the connection to `host` to become free for interaction `index`.
+    ///
+    /// `earlier` are the indices of interactions recorded before `index` on the same
+    /// host. The returned guard holds the connection until dropped.
+    pub(crate) async fn acquire(
+        &self,
+        config: &ConnectionReplay,
+        host: &str,
+        index: usize,
+        earlier: &[usize],
+    ) -> MutexGuardArc<()> {
+        if config.ordering == ReplayOrdering::Recorded {
+            let deadline = crate::runtime::Instant::now() + config.max_wait;
+            loop {
+                // Register for the wakeup before checking, so a notification in between isn't lost
+                let notified = self.served_changed.listen();
+                let ready = {
+                    let served = self.served.lock().unwrap();
+                    earlier.iter().all(|i| served.contains(i))
+                };
+                if ready {
+                    break;
+                }
+                let timed_out = future::or(
+                    async {
+                        notified.await;
+                        false
+                    },
+                    async {
+                        crate::runtime::Timer::at(deadline).await;
+                        true
+                    },
+                );
+                if timed_out.await {
+                    break;
+                }
+            }
+        }
+
+        let connection = self
+            .connections
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_default()
+            .clone();
+        let guard = connection.lock_arc().await;
+
+        self.served.lock().unwrap().insert(index);
+        self.served_changed.notify(usize::MAX);
+
+        guard
+    }
+
+    /// Indices of the interactions served so far
+    pub(crate) fn served(&self) -> HashSet<usize> {
+        self.served.lock().unwrap().clone()
+    }
+
+    pub(crate) fn restore(&self, served: HashSet<usize>) {
+        *self.served.lock().unwrap() = served;
+        self.served_changed.notify(usize::MAX);
+    }
+}
+
+/// Connection key for a URL: scheme, host and port, like a client's connection pool
+pub(crate) fn connection_key(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(url) => format!(
+            "{}://{}:{}",
+            url.scheme(),
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        ),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Keep the connection held until `body` has been read to the end (or dropped)
+pub(crate) fn hold_until_read(body: Body, guard: MutexGuardArc<()>) -> Body {
+    let len = body.len();
+    let mime = body.mime().cloned();
+    let mut held = Body::from_reader(
+        HeldBody {
+            body,
+            guard: Some(guard),
+        },
+        len,
+    );
+    held.set_mime(mime);
+    held
+}
+
+struct HeldBody {
+    body: Body,
+    guard: Option<MutexGuardArc<()>>,
+}
+
+impl AsyncBufRead for HeldBody {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.body).poll_fill_buf(cx) {
+            Poll::Ready(Ok(buf)) => {
+                if buf.is_empty() {
+                    // Fully read: free the connection for the next response
+                    this.guard = None;
+                }
+                Poll::Ready(Ok(buf))
+            }
+            other => other,
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut self.get_mut().body).consume(amt);
+    }
+}
+
+impl AsyncRead for HeldBody {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.body).poll_read(cx, buf);
+        if let Poll::Ready(Ok(0)) = result {
+            this.guard = None;
+        }
+        result
+    }
+}
@@ -0,0 +1,168 @@
+use crate::cassette::{Cassette, Interaction};
+use crate::filter::FilterChain;
+use crate::matcher::{DefaultMatcher, RequestMatcher};
+use crate::serializable::{SerializableRequest, SerializableResponse};
+use async_lock::Mutex;
+use async_trait::async_trait;
+use http_client::{Error, HttpClient, Request, Response};
+
+/// Drives both sides of a contract test from a single recorded session.
+///
+/// The consumer under test uses this as its `HttpClient`. Every request must match
+/// the next interaction in the cassette, in order. When a provider client is
+/// configured, the recorded request for that same interaction is sent to the
+/// provider and its response is checked against the recording before the recorded
+/// response is handed back to the consumer, so both sides advance in lockstep.
+///
+/// Without a provider only the consumer side is checked; `verify_provider` checks
+/// only the provider side.
+#[derive(Debug)]
+pub struct LockstepClient {
+    cassette: Cassette,
+    provider: Option<Box<dyn HttpClient>>,
+    matcher: Box<dyn RequestMatcher>,
+    filter_chain: FilterChain,
+    compare_bodies: bool,
+    position: Mutex<usize>,
+}
+
+impl LockstepClient {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            cassette,
+            provider: None,
+            matcher: Box::new(DefaultMatcher::new()),
+            filter_chain: FilterChain::new(),
+            compare_bodies: true,
+            position: Mutex::new(0),
+        }
+    }
+
+    /// Client used to replay recorded consumer requests against the provider
+    pub fn with_provider(mut self, provider: Box<dyn HttpClient>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    pub fn with_matcher(mut self, matcher: Box<dyn RequestMatcher>) -> Self {
+        self.matcher = matcher;
+        self
+    }
+
+    /// Filters the cassette was recorded with, applied to live requests and provider
+    /// responses before they are compared with the recording
+    pub fn with_filter_chain(mut self, filter_chain: FilterChain) -> Self {
+        self.filter_chain = filter_chain;
+        self
+    }
+
+    /// Only compare provider status codes, not bodies
+    pub fn status_only(mut self) -> Self {
+        self.compare_bodies = false;
+        self
+    }
+
+    /// Index of the next interaction to be exchanged
+    pub async fn position(&self) -> usize {
+        *self.position.lock().await
+    }
+
+    /// Whether every recorded interaction has been exchanged
+    pub async fn is_complete(&self) -> bool {
+        self.position().await >= self.cassette.interactions.len()
+    }
+
+    /// Replay every recorded request against the provider, without a consumer
+    pub async fn verify_provider(&self) -> Result<(), Error> {
+        for (index, interaction) in self.cassette.interactions.iter().enumerate() {
+            self.verify_interaction(index, interaction).await?;
+        }
+        Ok(())
+    }
+
+    async fn verify_interaction(
+        &self,
+        index: usize,
+        interaction: &Interaction,
+    ) -> Result<(), Error> {
+        let Some(provider) = &self.provider else {
+            return Ok(());
+        };
+
+        let request = interaction.request.to_request().await?;
+        let response = provider.send(request).await?;
+        let mut actual = SerializableResponse::from_response(response).await?;
+        self.filter_chain
+            .filter_response_for(&interaction.request, &mut actual);
+
+        let expected = &interaction.response;
+        if actual.status != expected.status {
+            return Err(Error::from_str(
+                500,
+                format!(
+                    "Contract violation at interaction {index} ({} {}): provider returned status {}, recorded {}",
+                    interaction.request.method,
+                    interaction.request.url,
+                    actual.status,
+                    expected.status
+                ),
+            ));
+        }
+
+        if self.compare_bodies && actual.text() != expected.text() {
+            return Err(Error::from_str(
+                500,
+                format!(
+                    "Contract violation at interaction {index} ({} {}): provider response body differs from recording",
+                    interaction.request.method, interaction.request.url
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HttpClient for LockstepClient {
+    async fn send(&self, req: Request) -> Result<Response, Error> {
+        let mut position = self.position.lock().await;
+        let index = *position;
+
+        let interaction = self.cassette.interactions.get(index).ok_or_else(|| {
+            Error::from_str(
+                500,
+                format!(
+                    "Lockstep session exhausted: all {} recorded interactions were already exchanged",
+                    self.cassette.interactions.len()
+                ),
+            )
+        })?;
+
+        let mut request = SerializableRequest::from_request(req).await?;
+        self.filter_chain.filter_request(&mut request);
+        if !self
+            .matcher
+            .matches_serializable(&request, &interaction.request)
+        {
+            return Err(Error::from_str(
+                500,
+                format!(
+                    "Consumer diverged at interaction {index}: expected {} {}, got {} {}",
+                    interaction.request.method,
+                    interaction.request.url,
+                    request.method,
+                    request.url
+                ),
+            ));
+        }
+
+        self.verify_interaction(index, interaction).await?;
+
+        *position += 1;
+        if let Some(error) = &interaction.error {
+            return Err(error.to_error());
+        }
+        Ok(interaction.response.to_response().await)
+    }
+}
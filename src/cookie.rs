@@ -0,0 +1,172 @@
+use crate::serializable::Headers;
+use std::fmt;
+
+/// A cookie parsed from a `Cookie` or `Set-Cookie` header.
+///
+/// Parsing is lenient: surrounding whitespace is trimmed, values keep any quotes
+/// and `=` signs, and attribute names keep their original case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// `Set-Cookie` attributes in order, like `("Path", Some("/"))` or
+    /// `("HttpOnly", None)`. Always empty for cookies sent by a client.
+    pub attributes: Vec<(String, Option<String>)>,
+}
+
+impl Cookie {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Parse a `Cookie` request header, which holds any number of `name=value`
+    /// pairs separated by `;`
+    pub fn parse_cookie_header(header: &str) -> Vec<Cookie> {
+        header
+            .split(';')
+            .filter_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                let name = name.trim();
+                (!name.is_empty()).then(|| Cookie::new(name, value.trim()))
+            })
+            .collect()
+    }
+
+    /// Parse a `Set-Cookie` response header. `None` if it doesn't start with a
+    /// `name=value` pair.
+    pub fn parse_set_cookie(header: &str) -> Option<Cookie> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let attributes = parts
+            .map(str::trim)
+            .filter(|attribute| !attribute.is_empty())
+            .map(|attribute| match attribute.split_once('=') {
+                Some((name, value)) => (name.trim().to_string(), Some(value.trim().to_string())),
+                None => (attribute.to_string(), None),
+            })
+            .collect();
+        Some(Cookie {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+            attributes,
+        })
+    }
+
+    /// The value of an attribute, matched case-insensitively. Flags like `Secure`
+    /// give `Some("")`.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_deref().unwrap_or_default())
+    }
+}
+
+/// Formats as a `Set-Cookie` value: `name=value` followed by the attributes
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        for (name, value) in &self.attributes {
+            match value {
+                Some(value) => write!(f, "; {name}={value}")?,
+                None => write!(f, "; {name}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The cookies of every `Cookie` header, in order
+pub(crate) fn request_cookies(headers: &Headers) -> Vec<Cookie> {
+    header_values(headers, "cookie")
+        .flat_map(|value| Cookie::parse_cookie_header(value))
+        .collect()
+}
+
+/// The cookies of every parseable `Set-Cookie` header, in order
+pub(crate) fn response_cookies(headers: &Headers) -> Vec<Cookie> {
+    header_values(headers, "set-cookie")
+        .filter_map(|value| Cookie::parse_set_cookie(value))
+        .collect()
+}
+
+/// Sorted cookie names of `Cookie` header values, for matching on names alone
+pub(crate) fn cookie_names(values: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = values
+        .iter()
+        .flat_map(|value| Cookie::parse_cookie_header(value))
+        .map(|cookie| cookie.name)
+        .collect();
+    names.sort();
+    names
+}
+
+/// Replace the value of every cookie in `Cookie` headers with `placeholder`
+pub(crate) fn redact_request_cookies(headers: &mut Headers, placeholder: &str) {
+    for value in header_values_mut(headers, "cookie") {
+        *value = Cookie::parse_cookie_header(value)
+            .iter()
+            .map(|cookie| format!("{}={placeholder}", cookie.name))
+            .collect::<Vec<_>>()
+            .join("; ");
+    }
+}
+
+/// Replace the value of every `Set-Cookie` cookie with `placeholder`, keeping its
+/// attributes. Headers that don't parse are left alone.
+pub(crate) fn redact_response_cookies(headers: &mut Headers, placeholder: &str) {
+    for value in header_values_mut(headers, "set-cookie") {
+        if let Some(mut cookie) = Cookie::parse_set_cookie(value) {
+            cookie.value = placeholder.to_string();
+            *value = cookie.to_string();
+        }
+    }
+}
+
+fn header_values<'a>(headers: &'a Headers, name: &'a str) -> impl Iterator<Item = &'a String> {
+    headers
+        .iter()
+        .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+        .flat_map(|(_, values)| values)
+}
+
+fn header_values_mut<'a>(
+    headers: &'a mut Headers,
+    name: &'a str,
+) -> impl Iterator<Item = &'a mut String> {
+    headers
+        .iter_mut()
+        .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+        .flat_map(|(_, values)| values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_cookie_redaction_keeps_name_and_attributes() {
+        let mut headers = Headers::from([(
+            "Set-Cookie".to_string(),
+            vec!["session=abc=123; Path=/; HttpOnly".to_string()],
+        )]);
+        let cookie = &response_cookies(&headers)[0];
+        assert_eq!(cookie.value, "abc=123");
+        assert_eq!(cookie.attribute("path"), Some("/"));
+        assert_eq!(cookie.attribute("HttpOnly"), Some(""));
+
+        redact_response_cookies(&mut headers, "[FILTERED]");
+        assert_eq!(
+            headers["Set-Cookie"],
+            ["session=[FILTERED]; Path=/; HttpOnly"]
+        );
+    }
+}
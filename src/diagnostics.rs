@@ -0,0 +1,97 @@
+use crate::cassette::Cassette;
+use std::fmt;
+
+/// Message for a request that matched no recorded interaction.
+///
+/// Only the raw request and recorded method/URL pairs are captured when the miss
+/// happens; the similarity search and method summary are computed when the message
+/// is displayed, so misses that are expected (and never printed) stay cheap.
+pub(crate) struct NoMatchDiagnostics {
+    mode_description: String,
+    method: String,
+    url: String,
+    recorded: Vec<(String, String)>,
+}
+
+impl NoMatchDiagnostics {
+    pub(crate) fn new(
+        mode_description: &str,
+        method: String,
+        url: String,
+        cassette: &Cassette,
+    ) -> Self {
+        Self {
+            mode_description: mode_description.to_string(),
+            method,
+            url,
+            recorded: cassette
+                .interactions
+                .iter()
+                .map(|i| (i.request.method.clone(), i.request.url.clone()))
+                .collect(),
+        }
+    }
+
+    /// The most similar recorded URLs by Levenshtein distance
+    fn similar_urls(&self) -> Vec<(&str, usize)> {
+        let mut similarities: Vec<(&str, usize)> = self
+            .recorded
+            .iter()
+            .map(|(_, url)| (url.as_str(), levenshtein::levenshtein(&self.url, url)))
+            .collect();
+
+        // Sort by distance (smaller distance = more similar)
+        similarities.sort_by_key(|(_, distance)| *distance);
+
+        // Return only the top 5 most similar URLs
+        similarities.truncate(5);
+        similarities
+    }
+}
+
+impl fmt::Display for NoMatchDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No matching interaction found in cassette ({})\n\nRequest details:\n  Method: {}\n  URL: {}",
+            self.mode_description, self.method, self.url
+        )?;
+
+        if self.recorded.is_empty() {
+            return write!(
+                f,
+                "\n\nCassette is empty - no recorded interactions available."
+            );
+        }
+
+        write!(
+            f,
+            "\n\nCassette contains {} recorded interactions.",
+            self.recorded.len()
+        )?;
+
+        let similar_urls = self.similar_urls();
+        if !similar_urls.is_empty() {
+            write!(
+                f,
+                "\n\nMost similar recorded URLs (by Levenshtein distance):"
+            )?;
+            for (i, (url, distance)) in similar_urls.iter().enumerate() {
+                write!(f, "\n  {}. {} (distance: {})", i + 1, url, distance)?;
+            }
+        }
+
+        // Show unique methods in cassette
+        let mut methods: Vec<&str> = self.recorded.iter().map(|(m, _)| m.as_str()).collect();
+        methods.sort_unstable();
+        methods.dedup();
+
+        write!(f, "\n\nRecorded methods: {}", methods.join(", "))
+    }
+}
+
+impl fmt::Debug for NoMatchDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::serializable::SerializableResponse;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::Read;
+
+/// How responses with a `Content-Encoding` are recorded and replayed.
+///
+/// Inner clients that decompress responses themselves, like reqwest with its `gzip`
+/// feature, already drop the header, so this only matters for ones that pass the
+/// compressed bytes through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// Store the body exactly as received, with its original headers, and replay it
+    /// the same way
+    #[default]
+    Preserve,
+    /// Store gzip and deflate bodies decoded, dropping `Content-Encoding` and fixing
+    /// `Content-Length`, so cassettes are readable and body filters apply. Responses
+    /// already recorded compressed are decoded on replay. Other encodings, such as
+    /// `br`, are stored as received.
+    Decode,
+}
+
+/// Decode `response`'s body according to its `Content-Encoding` header and update
+/// the headers to match. Responses using an unsupported encoding, or whose body
+/// fails to decode, are left unchanged.
+pub(crate) fn decode_response(response: &mut SerializableResponse) {
+    let codings: Vec<String> = response
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("content-encoding"))
+        .flat_map(|(_, values)| values.iter().flat_map(|value| value.split(',')))
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty() && coding != "identity")
+        .collect();
+    if codings.is_empty() {
+        return;
+    }
+    let Some(mut body) = response.bytes() else {
+        return;
+    };
+
+    // Codings are listed in the order they were applied
+    for coding in codings.iter().rev() {
+        match decode(coding, &body) {
+            Some(decoded) => body = decoded,
+            None => {
+                log::debug!("Not decoding response body with Content-Encoding {coding}");
+                return;
+            }
+        }
+    }
+
+    response
+        .headers
+        .retain(|name, _| !name.eq_ignore_ascii_case("content-encoding"));
+    response.set_bytes(body);
+    response.fix_body_headers();
+}
+
+fn decode(coding: &str, body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    match coding {
+        "gzip" | "x-gzip" => GzDecoder::new(body).read_to_end(&mut decoded).ok()?,
+        // Deflate should be zlib-wrapped, but some servers send it raw
+        "deflate" => match ZlibDecoder::new(body).read_to_end(&mut decoded) {
+            Ok(read) => read,
+            Err(_) => {
+                decoded.clear();
+                DeflateDecoder::new(body).read_to_end(&mut decoded).ok()?
+            }
+        },
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializable::Headers;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn test_gzip_body_is_decoded_and_headers_fixed() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"{\"ok\":true}").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut response = SerializableResponse {
+            status: 200,
+            headers: Headers::from([
+                ("content-encoding".to_string(), vec!["gzip".to_string()]),
+                (
+                    "content-length".to_string(),
+                    vec![compressed.len().to_string()],
+                ),
+            ]),
+            body: None,
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+        response.set_bytes(compressed);
+        decode_response(&mut response);
+
+        assert_eq!(response.body.as_deref(), Some("{\"ok\":true}"));
+        assert!(!response.headers.contains_key("content-encoding"));
+        assert_eq!(response.headers["content-length"], vec!["11".to_string()]);
+
+        // Unsupported encodings are kept as received
+        response
+            .headers
+            .insert("content-encoding".to_string(), vec!["br".to_string()]);
+        decode_response(&mut response);
+        assert_eq!(response.body.as_deref(), Some("{\"ok\":true}"));
+        assert!(response.headers.contains_key("content-encoding"));
+    }
+}
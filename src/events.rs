@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Something a `VcrClient` did, passed to the handlers registered with
+/// `VcrClientBuilder::on_event`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VcrEvent {
+    /// An interaction was added to the cassette at `index`. `summary` reads like
+    /// `GET https://example.com/users -> 200`.
+    Recorded { index: usize, summary: String },
+    /// The cassette's interaction at `index` was replayed. Interactions of
+    /// additional cassettes don't produce this event.
+    Replayed { index: usize },
+    /// No recorded interaction matched a request, summarized like
+    /// `GET https://example.com/users`
+    Miss { request_summary: String },
+    /// The cassette was written to `path`
+    Saved { path: PathBuf },
+}
+
+type EventFn = dyn Fn(VcrEvent) + Send + Sync;
+
+/// The registered event handlers, called in the order they were added. Cloning
+/// shares the handlers, so background saves can report to them.
+#[derive(Clone, Default)]
+pub(crate) struct EventHandlers(Vec<Arc<EventFn>>);
+
+impl EventHandlers {
+    pub(crate) fn push<F>(&mut self, handler: F)
+    where
+        F: Fn(VcrEvent) + Send + Sync + 'static,
+    {
+        self.0.push(Arc::new(handler));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn emit(&self, event: VcrEvent) {
+        if let Some((last, rest)) = self.0.split_last() {
+            for handler in rest {
+                handler(event.clone());
+            }
+            last(event);
+        }
+    }
+}
+
+impl std::fmt::Debug for EventHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EventHandlers({})", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_every_handler_sees_each_event() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut handlers = EventHandlers::default();
+        for name in ["first", "second"] {
+            let seen = Arc::clone(&seen);
+            handlers.push(move |event| {
+                seen.lock().unwrap().push((name, event));
+            });
+        }
+
+        handlers.emit(VcrEvent::Replayed { index: 3 });
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                ("first", VcrEvent::Replayed { index: 3 }),
+                ("second", VcrEvent::Replayed { index: 3 })
+            ]
+        );
+    }
+}
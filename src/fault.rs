@@ -0,0 +1,166 @@
+use futures_lite::io::{AsyncBufRead, AsyncRead};
+use http_client::{Error, Response};
+use http_types::{Body, StatusCode};
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A failure substituted for a replayed response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Return an empty response with this status instead, e.g. 500 or 429
+    Status(StatusCode),
+    /// Return the recorded status and headers, but fail after half of the body
+    /// has been read, as if the connection dropped mid-transfer
+    TruncateBody,
+    /// Fail the request with a connection reset before any response is returned
+    ConnectionError,
+}
+
+#[derive(Debug, Clone)]
+enum Trigger {
+    Probability(f64),
+    Interactions(HashSet<usize>),
+}
+
+/// Inject failures into replayed responses.
+///
+/// Resilience tests can reuse the cassettes of the happy-path tests: faults fire
+/// either at random with a given probability or on specific interaction indices.
+/// Rules are checked in the order they were added and the first one that fires wins.
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector {
+    rules: Vec<(Trigger, Fault)>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inject `fault` into each replayed response with the given probability (0.0 to 1.0)
+    pub fn with_probability(mut self, probability: f64, fault: Fault) -> Self {
+        self.rules
+            .push((Trigger::Probability(probability.clamp(0.0, 1.0)), fault));
+        self
+    }
+
+    /// Inject `fault` whenever one of the interactions at `indices` is replayed
+    pub fn at_interactions(
+        mut self,
+        indices: impl IntoIterator<Item = usize>,
+        fault: Fault,
+    ) -> Self {
+        self.rules
+            .push((Trigger::Interactions(indices.into_iter().collect()), fault));
+        self
+    }
+
+    /// The fault to inject when replaying interaction `index`, if any
+    pub fn fault_for(&self, index: usize) -> Option<Fault> {
+        self.pick(Some(index))
+    }
+
+    /// Like `fault_for`, but interactions without an index (from additional
+    /// cassettes) only trigger probability rules
+    pub(crate) fn pick(&self, index: Option<usize>) -> Option<Fault> {
+        self.rules
+            .iter()
+            .find(|(trigger, _)| match trigger {
+                Trigger::Probability(probability) => fastrand::f64() < *probability,
+                Trigger::Interactions(indices) => index.is_some_and(|i| indices.contains(&i)),
+            })
+            .map(|(_, fault)| *fault)
+    }
+
+    /// Apply `fault` to a replayed response
+    pub(crate) async fn apply(fault: Fault, mut response: Response) -> Result<Response, Error> {
+        match fault {
+            Fault::Status(status) => Ok(Response::new(status)),
+            Fault::TruncateBody => {
+                let body = response.take_body();
+                let mime = body.mime().cloned();
+                let bytes = body.into_bytes().await?;
+                let len = bytes.len();
+                let mut body = Body::from_reader(
+                    TruncatedBody {
+                        data: bytes[..len / 2].to_vec(),
+                        pos: 0,
+                    },
+                    Some(len as u64),
+                );
+                body.set_mime(mime);
+                response.set_body(body);
+                Ok(response)
+            }
+            Fault::ConnectionError => Err(Error::new(
+                StatusCode::InternalServerError,
+                std::io::Error::new(ErrorKind::ConnectionReset, "Injected connection error"),
+            )),
+        }
+    }
+}
+
+/// A reader that yields a prefix of the body and then fails
+struct TruncatedBody {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl AsyncBufRead for TruncatedBody {
+    fn poll_fill_buf(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.pos >= this.data.len() {
+            return Poll::Ready(Err(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Injected fault: response body truncated",
+            )));
+        }
+        Poll::Ready(Ok(&this.data[this.pos..]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.pos = (this.pos + amt).min(this.data.len());
+    }
+}
+
+impl AsyncRead for TruncatedBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let injector = FaultInjector::new()
+            .at_interactions([1], Fault::Status(StatusCode::TooManyRequests))
+            .with_probability(0.0, Fault::ConnectionError)
+            .with_probability(1.0, Fault::TruncateBody);
+
+        assert_eq!(
+            injector.fault_for(1),
+            Some(Fault::Status(StatusCode::TooManyRequests))
+        );
+        assert_eq!(injector.fault_for(0), Some(Fault::TruncateBody));
+        assert_eq!(FaultInjector::new().fault_for(0), None);
+    }
+}
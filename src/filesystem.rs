@@ -0,0 +1,869 @@
+use crate::cassette::{
+    BodyCompression, Cassette, CassetteFormat, CassetteMetadata, Interaction, Rotation,
+};
+use crate::lock::CassetteLock;
+use crate::migration::{document_version, migrate_interactions, serialize_current_version};
+use crate::serializable::{
+    body_bytes, body_fields, join_url, split_url, Headers, QueryParam, SerializableRequest,
+    SerializableResponse,
+};
+use crate::storage::{CassetteStorage, InteractionIter};
+use crate::streaming::StreamChunk;
+use crate::transport_error::RecordedError;
+use base64::{engine::general_purpose, Engine as _};
+use http_client::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// Cassettes as single YAML files.
+///
+/// Reads and writes hold an advisory lock on the cassette, so processes sharing it
+/// don't see or produce half-written files.
+///
+/// Cassettes with a [`Rotation`](crate::Rotation) in their metadata are saved as
+/// numbered parts next to the cassette path, `api.yaml` becoming `api.0001.yaml`,
+/// `api.0002.yaml` and so on, and loading `api.yaml` reads the parts back as one
+/// cassette.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileStorage;
+
+impl CassetteStorage for FileStorage {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        let parts: Vec<PathBuf> = if path.is_file() {
+            vec![path.to_path_buf()]
+        } else {
+            (1..)
+                .map(|number| part_path(path, number))
+                .take_while(|part| part.is_file())
+                .collect()
+        };
+        if parts.is_empty() {
+            return Ok(None);
+        }
+
+        let _lock = CassetteLock::shared(path)?;
+        let mut cassette: Option<Cassette> = None;
+        for part in &parts {
+            let content = std::fs::read_to_string(part).map_err(|e| {
+                Error::from_str(500, format!("Failed to read cassette file {part:?}: {e}"))
+            })?;
+            let loaded = Cassette::from_yaml(&content)?;
+            match &mut cassette {
+                Some(cassette) => cassette.interactions.extend(loaded.interactions),
+                None => cassette = Some(loaded),
+            }
+        }
+
+        let mut cassette = cassette.unwrap_or_default();
+        cassette.path = Some(path.to_path_buf());
+        cassette.format = CassetteFormat::File;
+        cassette.modified_since_load = false;
+
+        Ok(Some(cassette))
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        let parts = match cassette.metadata.rotation {
+            Some(rotation) => rotated_parts(cassette, rotation)?,
+            None => vec![serde_yaml::to_string(cassette).map_err(serialize_error)?],
+        };
+
+        let _lock = CassetteLock::exclusive(path)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| Error::from_str(500, format!("Failed to create directory: {e}")))?;
+        }
+
+        let write = |file: &Path, yaml: &str| {
+            std::fs::write(file, yaml)
+                .map_err(|e| Error::from_str(500, format!("Failed to write cassette file: {e}")))
+        };
+        let remove = |file: &Path| {
+            std::fs::remove_file(file)
+                .map_err(|e| Error::from_str(500, format!("Failed to remove {file:?}: {e}")))
+        };
+        let written_parts = if let [yaml] = parts.as_slice() {
+            write(path, yaml)?;
+            0
+        } else {
+            for (index, yaml) in parts.iter().enumerate() {
+                write(&part_path(path, index + 1), yaml)?;
+            }
+            if path.is_file() {
+                remove(path)?;
+            }
+            parts.len()
+        };
+        // Parts left over from a save that needed more of them
+        for stale in (written_parts + 1..)
+            .map(|number| part_path(path, number))
+            .take_while(|part| part.is_file())
+        {
+            remove(&stale)?;
+        }
+
+        Ok(())
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut found = Vec::new();
+        walk(root, &mut |path| {
+            if path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == "yaml" || ext == "yml")
+            {
+                // Parts of a rotated cassette are listed once, by the cassette's path
+                let path = rotated_cassette_path(path).unwrap_or_else(|| path.to_path_buf());
+                if !found.contains(&path) {
+                    found.push(path);
+                }
+            }
+        })?;
+        Ok(found)
+    }
+}
+
+fn serialize_error(e: serde_yaml::Error) -> Error {
+    Error::from_str(500, format!("Failed to serialize cassette: {e}"))
+}
+
+/// The path of part `number` of the rotated cassette at `path`
+fn part_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}.{number:04}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{number:04}"),
+    };
+    path.with_file_name(name)
+}
+
+/// The cassette `part` is a part of, if it is named like one and the cassette's
+/// first part exists
+fn rotated_cassette_path(part: &Path) -> Option<PathBuf> {
+    let stem = part.file_stem()?.to_str()?;
+    let (cassette_stem, number) = stem.rsplit_once('.')?;
+    if number.len() < 4 || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut cassette = part.with_file_name(cassette_stem);
+    if let Some(extension) = part.extension() {
+        cassette.set_extension(extension);
+    }
+    part_path(&cassette, 1).is_file().then_some(cassette)
+}
+
+/// Split `cassette` into the YAML of its parts. The first part carries the metadata.
+fn rotated_parts(cassette: &Cassette, rotation: Rotation) -> Result<Vec<String>, Error> {
+    #[derive(Serialize)]
+    struct CassettePart<'a> {
+        #[serde(serialize_with = "serialize_current_version")]
+        format_version: u32,
+        #[serde(skip_serializing_if = "CassetteMetadata::is_empty")]
+        metadata: &'a CassetteMetadata,
+        interactions: &'a [Interaction],
+    }
+
+    let mut ranges = Vec::new();
+    let (mut start, mut bytes) = (0, 0);
+    for (index, interaction) in cassette.interactions.iter().enumerate() {
+        let size = serde_yaml::to_string(std::slice::from_ref(interaction))
+            .map_err(serialize_error)?
+            .len();
+        if rotation.is_full(index - start, bytes, size) {
+            ranges.push(start..index);
+            (start, bytes) = (index, 0);
+        }
+        bytes += size;
+    }
+    ranges.push(start..cassette.interactions.len());
+
+    let no_metadata = CassetteMetadata::default();
+    ranges
+        .into_iter()
+        .enumerate()
+        .map(|(index, range)| {
+            serde_yaml::to_string(&CassettePart {
+                format_version: cassette.format_version,
+                metadata: if index == 0 {
+                    &cassette.metadata
+                } else {
+                    &no_metadata
+                },
+                interactions: &cassette.interactions[range],
+            })
+            .map_err(serialize_error)
+        })
+        .collect()
+}
+
+/// Cassettes as directories: `interactions.yaml`, `metadata.yaml` and a `bodies/`
+/// folder of request and response bodies. Locked like [`FileStorage`].
+///
+/// Body files are named after the SHA-256 of their content, so identical bodies, such
+/// as repeated polling responses, are stored once. Saving never deletes body files;
+/// [`DirectoryStorage::remove_orphaned_bodies`] cleans up the ones no longer
+/// referenced.
+///
+/// `interactions.yaml` also records each body's SHA-256 next to its file name, and
+/// loading fails, naming the file, if a body file is missing or no longer matches it.
+/// [`DirectoryStorage::load_unverified`] skips the check.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DirectoryStorage;
+
+impl DirectoryStorage {
+    /// Read `interactions.yaml` from a directory cassette, without any bodies
+    pub(crate) fn read_index(path: &Path) -> Result<Vec<DirectoryInteraction>, Error> {
+        let interactions_file = path.join("interactions.yaml");
+        if !interactions_file.exists() {
+            return Err(Error::from_str(
+                404,
+                format!("Directory cassette missing interactions.yaml: {path:?}"),
+            ));
+        }
+
+        let content = std::fs::read_to_string(&interactions_file)
+            .map_err(|e| Error::from_str(500, format!("Failed to read interactions.yaml: {e}")))?;
+
+        let parse_error = |e: serde_yaml::Error| {
+            Error::from_str(500, format!("Failed to parse interactions.yaml: {e}"))
+        };
+        let mut interactions: serde_yaml::Value =
+            serde_yaml::from_str(&content).map_err(parse_error)?;
+        let format_version = document_version(&Self::read_metadata(path)?)?;
+        migrate_interactions(format_version, &mut interactions)?;
+        serde_yaml::from_value(interactions).map_err(parse_error)
+    }
+
+    /// Read `metadata.yaml`, which also holds the cassette's `format_version`
+    fn read_metadata(path: &Path) -> Result<serde_yaml::Value, Error> {
+        let metadata_file = path.join("metadata.yaml");
+        if !metadata_file.exists() {
+            return Ok(serde_yaml::Value::Null);
+        }
+        let content = std::fs::read_to_string(&metadata_file)
+            .map_err(|e| Error::from_str(500, format!("Failed to read metadata.yaml: {e}")))?;
+        serde_yaml::from_str(&content)
+            .map_err(|e| Error::from_str(500, format!("Failed to parse metadata.yaml: {e}")))
+    }
+
+    /// Delete the body files of the directory cassette at `path` that
+    /// `interactions.yaml` no longer references, returning the removed files
+    pub fn remove_orphaned_bodies(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let _lock = CassetteLock::exclusive(path)?;
+        let referenced: HashSet<String> = Self::read_index(path)?
+            .into_iter()
+            .flat_map(|interaction| {
+                let chunk_files = interaction
+                    .stream
+                    .into_iter()
+                    .flatten()
+                    .map(|chunk| chunk.body_file);
+                [
+                    interaction.request.body_file,
+                    interaction.response.body_file,
+                ]
+                .into_iter()
+                .chain(chunk_files)
+            })
+            .flatten()
+            .collect();
+
+        let bodies_dir = path.join("bodies");
+        if !bodies_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let entries = std::fs::read_dir(&bodies_dir)
+            .map_err(|e| Error::from_str(500, format!("Failed to list {bodies_dir:?}: {e}")))?;
+        let mut removed = Vec::new();
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| Error::from_str(500, format!("Failed to list {bodies_dir:?}: {e}")))?;
+            let name = entry.file_name();
+            if referenced.contains(name.to_string_lossy().as_ref()) {
+                continue;
+            }
+            std::fs::remove_file(entry.path()).map_err(|e| {
+                Error::from_str(500, format!("Failed to remove {:?}: {e}", entry.path()))
+            })?;
+            removed.push(entry.path());
+        }
+        removed.sort();
+        Ok(removed)
+    }
+}
+
+impl DirectoryStorage {
+    /// Load a cassette without checking its body files against their recorded
+    /// checksums, e.g. to inspect or repair one that fails to load
+    pub fn load_unverified(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        self.load_checked(path, false)
+    }
+
+    fn load_checked(&self, path: &Path, verify: bool) -> Result<Option<Cassette>, Error> {
+        if !path.is_dir() {
+            return Ok(None);
+        }
+        let _lock = CassetteLock::shared(path)?;
+        let dir_interactions = Self::read_index(path)?;
+
+        let bodies_dir = path.join("bodies");
+        let interactions = dir_interactions
+            .into_iter()
+            .map(|dir_interaction| dir_interaction.load(&bodies_dir, verify))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Metadata lives in its own file so interactions.yaml stays a plain list
+        let metadata = Self::read_metadata(path)?;
+        let format_version = document_version(&metadata)?;
+        let metadata = if metadata.is_null() {
+            CassetteMetadata::default()
+        } else {
+            serde_yaml::from_value(metadata)
+                .map_err(|e| Error::from_str(500, format!("Failed to parse metadata.yaml: {e}")))?
+        };
+
+        Ok(Some(Cassette {
+            format_version,
+            metadata,
+            interactions,
+            path: Some(path.to_path_buf()),
+            format: CassetteFormat::Directory,
+            body_compression: BodyCompression::None,
+            modified_since_load: false,
+        }))
+    }
+}
+
+impl CassetteStorage for DirectoryStorage {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        self.load_checked(path, true)
+    }
+
+    /// Reads `interactions.yaml` up front and each interaction's body files as it is
+    /// yielded. The cassette stays locked for reading until the iterator is dropped.
+    fn interactions<'a>(&'a self, path: &Path) -> Result<Option<InteractionIter<'a>>, Error> {
+        if !path.is_dir() {
+            return Ok(None);
+        }
+        let lock = CassetteLock::shared(path)?;
+        let bodies_dir = path.join("bodies");
+        let dir_interactions = Self::read_index(path)?;
+        Ok(Some(Box::new(dir_interactions.into_iter().map(
+            move |dir_interaction| {
+                let _lock = &lock;
+                dir_interaction.load(&bodies_dir, true)
+            },
+        ))))
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        let _lock = CassetteLock::exclusive(path)?;
+        // Create the cassette directory and bodies subdirectory
+        std::fs::create_dir_all(path).map_err(|e| {
+            Error::from_str(500, format!("Failed to create cassette directory: {e}"))
+        })?;
+
+        let bodies_dir = path.join("bodies");
+        std::fs::create_dir_all(&bodies_dir)
+            .map_err(|e| Error::from_str(500, format!("Failed to create bodies directory: {e}")))?;
+
+        // Create directory format structures for serialization
+        #[derive(Serialize)]
+        struct DirectoryInteraction {
+            request: DirectorySerializableRequest,
+            response: DirectorySerializableResponse,
+            #[serde(
+                with = "time::serde::rfc3339::option",
+                skip_serializing_if = "Option::is_none"
+            )]
+            recorded_at: Option<OffsetDateTime>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            elapsed_ms: Option<u64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            error: Option<RecordedError>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            stream: Option<Vec<DirectoryStreamChunk>>,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            metadata: BTreeMap<String, String>,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            tags: Vec<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            requires_state: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            sets_state: Option<String>,
+        }
+
+        #[derive(Serialize)]
+        struct DirectorySerializableRequest {
+            method: String,
+            url: String,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            query: Vec<QueryParam>,
+            headers: Headers,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            body_file: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            body_sha256: Option<String>,
+            version: String,
+        }
+
+        #[derive(Serialize)]
+        struct DirectorySerializableResponse {
+            status: u16,
+            headers: Headers,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            body_file: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            body_sha256: Option<String>,
+            version: String,
+        }
+
+        let mut dir_interactions = Vec::new();
+
+        for interaction in &cassette.interactions {
+            let (request_body_file, request_body_sha256) = write_body(
+                &bodies_dir,
+                interaction.request.body.as_deref(),
+                interaction.request.body_base64.as_deref(),
+                cassette.body_compression,
+                "request",
+            )?
+            .unzip();
+            let (response_body_file, response_body_sha256) = write_body(
+                &bodies_dir,
+                interaction.response.body.as_deref(),
+                interaction.response.body_base64.as_deref(),
+                cassette.body_compression,
+                "response",
+            )?
+            .unzip();
+
+            let stream = match &interaction.stream {
+                Some(chunks) => Some(
+                    chunks
+                        .iter()
+                        .map(|chunk| {
+                            let (body_file, body_sha256) = write_body(
+                                &bodies_dir,
+                                Some(chunk.data.as_str()).filter(|_| chunk.data_base64.is_none()),
+                                chunk.data_base64.as_deref(),
+                                cassette.body_compression,
+                                "stream chunk",
+                            )?
+                            .unzip();
+                            Ok(DirectoryStreamChunk {
+                                at_ms: chunk.at_ms,
+                                body_file,
+                                body_sha256,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?,
+                ),
+                None => None,
+            };
+
+            let (url, query) = split_url(&interaction.request.url);
+            let dir_interaction = DirectoryInteraction {
+                request: DirectorySerializableRequest {
+                    method: interaction.request.method.clone(),
+                    url: url.into_owned(),
+                    query,
+                    headers: interaction.request.headers.clone(),
+                    body_file: request_body_file,
+                    body_sha256: request_body_sha256,
+                    version: interaction.request.version.clone(),
+                },
+                response: DirectorySerializableResponse {
+                    status: interaction.response.status,
+                    headers: interaction.response.headers.clone(),
+                    body_file: response_body_file,
+                    body_sha256: response_body_sha256,
+                    version: interaction.response.version.clone(),
+                },
+                recorded_at: interaction.recorded_at,
+                elapsed_ms: interaction.elapsed_ms,
+                error: interaction.error.clone(),
+                stream,
+                metadata: interaction.metadata.clone(),
+                tags: interaction.tags.clone(),
+                requires_state: interaction.requires_state.clone(),
+                sets_state: interaction.sets_state.clone(),
+            };
+
+            dir_interactions.push(dir_interaction);
+        }
+
+        // Write the interactions.yaml file
+        let interactions_yaml = serde_yaml::to_string(&dir_interactions)
+            .map_err(|e| Error::from_str(500, format!("Failed to serialize interactions: {e}")))?;
+
+        let interactions_file = path.join("interactions.yaml");
+        std::fs::write(&interactions_file, interactions_yaml)
+            .map_err(|e| Error::from_str(500, format!("Failed to write interactions.yaml: {e}")))?;
+
+        #[derive(Serialize)]
+        struct DirectoryMetadata<'a> {
+            #[serde(serialize_with = "serialize_current_version")]
+            format_version: u32,
+            #[serde(flatten)]
+            metadata: &'a CassetteMetadata,
+        }
+
+        let metadata_yaml = serde_yaml::to_string(&DirectoryMetadata {
+            format_version: cassette.format_version,
+            metadata: &cassette.metadata,
+        })
+        .map_err(|e| Error::from_str(500, format!("Failed to serialize metadata: {e}")))?;
+        std::fs::write(path.join("metadata.yaml"), metadata_yaml)
+            .map_err(|e| Error::from_str(500, format!("Failed to write metadata.yaml: {e}")))?;
+
+        Ok(())
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut found = Vec::new();
+        walk(root, &mut |path| {
+            if path.join("interactions.yaml").is_file() {
+                found.push(path.to_path_buf());
+            }
+        })?;
+        Ok(found)
+    }
+}
+
+/// The default storage: loads whichever of the file or directory format exists at a
+/// path, and saves each cassette in its own `format`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FilesystemStorage;
+
+impl FilesystemStorage {
+    /// Like `load`, but without checking directory cassettes' body checksums
+    pub fn load_unverified(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        if path.is_dir() {
+            DirectoryStorage.load_unverified(path)
+        } else {
+            FileStorage.load(path)
+        }
+    }
+}
+
+impl CassetteStorage for FilesystemStorage {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        if path.is_dir() {
+            DirectoryStorage.load(path)
+        } else {
+            FileStorage.load(path)
+        }
+    }
+
+    fn interactions<'a>(&'a self, path: &Path) -> Result<Option<InteractionIter<'a>>, Error> {
+        if path.is_dir() {
+            DirectoryStorage.interactions(path)
+        } else {
+            FileStorage.interactions(path)
+        }
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        match cassette.format {
+            CassetteFormat::File => FileStorage.save(path, cassette),
+            CassetteFormat::Directory => DirectoryStorage.save(path, cassette),
+        }
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let directories = DirectoryStorage.list(root)?;
+        // Skip the YAML files inside directory cassettes
+        let files: Vec<PathBuf> = FileStorage
+            .list(root)?
+            .into_iter()
+            .filter(|file| !directories.iter().any(|dir| file.starts_with(dir)))
+            .collect();
+        let mut found: Vec<PathBuf> = directories.into_iter().chain(files).collect();
+        found.sort();
+        Ok(found)
+    }
+}
+
+/// Call `visit` on `root` and everything below it
+fn walk(root: &Path, visit: &mut dyn FnMut(&Path)) -> Result<(), Error> {
+    visit(root);
+    if !root.is_dir() {
+        return Ok(());
+    }
+    let entries = std::fs::read_dir(root)
+        .map_err(|e| Error::from_str(500, format!("Failed to list {root:?}: {e}")))?;
+    for entry in entries {
+        let entry =
+            entry.map_err(|e| Error::from_str(500, format!("Failed to list {root:?}: {e}")))?;
+        walk(&entry.path(), visit)?;
+    }
+    Ok(())
+}
+
+// Entry of a directory cassette's interactions.yaml, with bodies stored as separate files
+#[derive(Deserialize)]
+pub(crate) struct DirectoryInteraction {
+    request: DirectorySerializableRequest,
+    response: DirectorySerializableResponse,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    recorded_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    elapsed_ms: Option<u64>,
+    #[serde(default)]
+    error: Option<RecordedError>,
+    #[serde(default)]
+    stream: Option<Vec<DirectoryStreamChunk>>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    requires_state: Option<String>,
+    #[serde(default)]
+    sets_state: Option<String>,
+}
+
+// A chunk of a streamed response, stored in its own body file like other bodies
+#[derive(Serialize, Deserialize)]
+struct DirectoryStreamChunk {
+    at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_sha256: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DirectorySerializableRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    query: Vec<QueryParam>,
+    headers: Headers,
+    body_file: Option<String>,
+    #[serde(default)]
+    body_sha256: Option<String>,
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct DirectorySerializableResponse {
+    status: u16,
+    headers: Headers,
+    body_file: Option<String>,
+    #[serde(default)]
+    body_sha256: Option<String>,
+    version: String,
+}
+
+impl DirectoryInteraction {
+    /// Read the body files and build the full interaction, checking each body against
+    /// its recorded checksum if `verify` is set
+    pub(crate) fn load(self, bodies_dir: &Path, verify: bool) -> Result<Interaction, Error> {
+        let checksum = |sha256: Option<String>| sha256.filter(|_| verify);
+        let (request_body, request_body_base64) = read_body_file(
+            bodies_dir,
+            self.request.body_file.as_deref(),
+            checksum(self.request.body_sha256).as_deref(),
+            "request",
+        )?;
+        let (response_body, response_body_base64) = read_body_file(
+            bodies_dir,
+            self.response.body_file.as_deref(),
+            checksum(self.response.body_sha256).as_deref(),
+            "response",
+        )?;
+        let stream = match self.stream {
+            Some(chunks) => Some(
+                chunks
+                    .into_iter()
+                    .map(|chunk| {
+                        let (body, body_base64) = read_body_file::<String>(
+                            bodies_dir,
+                            chunk.body_file.as_deref(),
+                            checksum(chunk.body_sha256).as_deref(),
+                            "stream chunk",
+                        )?;
+                        let bytes = body_bytes(&body, &body_base64).unwrap_or_default();
+                        Ok(StreamChunk::new(chunk.at_ms, bytes))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?,
+            ),
+            None => None,
+        };
+
+        Ok(Interaction {
+            request: SerializableRequest {
+                method: self.request.method,
+                url: join_url(self.request.url, self.request.query),
+                headers: self.request.headers,
+                body: request_body,
+                body_base64: request_body_base64,
+                version: self.request.version,
+            },
+            response: SerializableResponse {
+                status: self.response.status,
+                headers: self.response.headers,
+                body: response_body,
+                body_base64: response_body_base64,
+                version: self.response.version,
+            },
+            recorded_at: self.recorded_at,
+            elapsed_ms: self.elapsed_ms,
+            sequence: None,
+            error: self.error,
+            stream,
+            metadata: self.metadata,
+            tags: self.tags,
+            requires_state: self.requires_state,
+            sets_state: self.sets_state,
+        })
+    }
+}
+
+/// Store a body under the hash of its content, returning the file name and the hash.
+/// Base64 bodies are written as raw bytes with a `.bin` extension, and compressed ones
+/// get an extra `.zst`.
+fn write_body(
+    bodies_dir: &Path,
+    body: Option<&str>,
+    body_base64: Option<&str>,
+    compression: BodyCompression,
+    kind: &str,
+) -> Result<Option<(String, String)>, Error> {
+    let (content, extension): (Cow<[u8]>, _) = match (body, body_base64) {
+        (Some(body), _) if !body.is_empty() => (Cow::Borrowed(body.as_bytes()), "txt"),
+        (None, Some(body_base64)) if !body_base64.is_empty() => {
+            // Binary bodies are written as their raw bytes, unless the base64 is malformed
+            match general_purpose::STANDARD.decode(body_base64) {
+                Ok(bytes) => (Cow::Owned(bytes), "bin"),
+                Err(_) => (Cow::Borrowed(body_base64.as_bytes()), "b64"),
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    let hash = sha256_hex(&content);
+    let filename = match compression {
+        #[cfg(feature = "zstd")]
+        BodyCompression::Zstd { min_size, .. } if content.len() >= min_size => {
+            format!("{hash}.{extension}.zst")
+        }
+        _ => format!("{hash}.{extension}"),
+    };
+    let body_path = bodies_dir.join(&filename);
+    // An existing file with this name holds the same content, unless it was truncated
+    // since, in which case it is rewritten. Loading checks the hash in full.
+    if holds_content(&body_path, &filename, content.len()) {
+        return Ok(Some((filename, hash)));
+    }
+
+    let stored = match compression {
+        #[cfg(feature = "zstd")]
+        BodyCompression::Zstd { level, .. } if filename.ends_with(".zst") => {
+            zstd::encode_all(&content[..], level)
+                .map_err(|e| Error::from_str(500, format!("Failed to compress {kind} body: {e}")))?
+        }
+        _ => content.into_owned(),
+    };
+    std::fs::write(&body_path, stored)
+        .map_err(|e| Error::from_str(500, format!("Failed to write {kind} body file: {e}")))?;
+    Ok(Some((filename, hash)))
+}
+
+/// Whether the body file at `body_path` can be kept, going by its name and, unless it
+/// is compressed, by its size being `len` bytes
+fn holds_content(body_path: &Path, filename: &str, len: usize) -> bool {
+    match std::fs::metadata(body_path) {
+        Ok(metadata) => filename.ends_with(".zst") || metadata.len() == len as u64,
+        Err(_) => false,
+    }
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Read a body file, returning it as `(body, body_base64)`. With a `sha256`, the
+/// (decompressed) content has to match it.
+fn read_body_file<B: AsRef<str> + From<String>>(
+    bodies_dir: &Path,
+    body_file: Option<&str>,
+    sha256: Option<&str>,
+    kind: &str,
+) -> Result<(Option<B>, Option<String>), Error> {
+    let Some(body_file) = body_file else {
+        return Ok((None, None));
+    };
+
+    let body_path = bodies_dir.join(body_file);
+    let content = std::fs::read(&body_path).map_err(|e| {
+        let problem = if e.kind() == std::io::ErrorKind::NotFound {
+            "is missing".to_string()
+        } else {
+            format!("can't be read: {e}")
+        };
+        Error::from_str(
+            500,
+            format!("{kind} body file {} {problem}", body_path.display()),
+        )
+    })?;
+    let (content, name) = match body_file.strip_suffix(".zst") {
+        Some(name) => (decompress(&content, body_file)?, name),
+        None => (content, body_file),
+    };
+    if let Some(expected) = sha256 {
+        let actual = sha256_hex(&content);
+        if actual != expected {
+            return Err(Error::from_str(
+                500,
+                format!(
+                    "{kind} body file {} doesn't match the sha256 recorded in \
+                     interactions.yaml (expected {expected}, found {actual}); it was truncated \
+                     or edited. Re-record the cassette, or inspect it with `load_unverified` or \
+                     `vcr-inspect --no-verify`",
+                    body_path.display()
+                ),
+            ));
+        }
+    }
+    if name.ends_with(".bin") {
+        return Ok(body_fields(content));
+    }
+    let content = String::from_utf8(content).map_err(|e| {
+        Error::from_str(
+            500,
+            format!("{kind} body file {body_file} is not UTF-8: {e}"),
+        )
+    })?;
+
+    // Check if this is a base64 file based on extension
+    if name.ends_with(".b64") {
+        Ok((None, Some(content)))
+    } else {
+        Ok((Some(content.into()), None))
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn decompress(content: &[u8], body_file: &str) -> Result<Vec<u8>, Error> {
+    zstd::decode_all(content)
+        .map_err(|e| Error::from_str(500, format!("Failed to decompress {body_file}: {e}")))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress(_content: &[u8], body_file: &str) -> Result<Vec<u8>, Error> {
+    Err(Error::from_str(
+        500,
+        format!("{body_file} is zstd-compressed; enable the zstd feature to read it"),
+    ))
+}
@@ -1,18 +1,188 @@
-use crate::form_data::{analyze_form_data, filter_form_data};
-use crate::serializable::{SerializableRequest, SerializableResponse};
-use regex::Regex;
+use crate::cassette::CassetteMetadata;
+use crate::form_data::{
+    analyze_form_data, filter_form_data, filter_multipart_data, multipart_boundary,
+    redact_form_data, redact_multipart_data,
+};
+use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+use regex::{Captures, Regex};
 use serde_json::{Map, Value};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
 
 pub trait Filter: Debug + Send + Sync {
     fn filter_request(&self, request: &mut SerializableRequest);
     fn filter_response(&self, response: &mut SerializableResponse);
+
+    /// Filter the response to `request`, for filters that need the request to make
+    /// sense of the response. Defaults to `filter_response`.
+    fn filter_response_for(
+        &self,
+        _request: &SerializableRequest,
+        response: &mut SerializableResponse,
+    ) {
+        self.filter_response(response);
+    }
+
+    /// Undo filtering on a recorded response right before it is replayed.
+    /// Most filters are one-way, so the default does nothing.
+    fn restore_response(&self, _response: &mut SerializableResponse) {}
+
+    /// Pick up what earlier recordings left in the metadata of a cassette that new
+    /// interactions are about to be appended to
+    fn resume(&self, _metadata: &CassetteMetadata) {}
+
+    /// Leave what later recordings need to pick up in the cassette's metadata, after
+    /// an interaction was recorded
+    fn annotate(&self, _metadata: &mut CassetteMetadata) {}
+}
+
+/// Restricts a filter to requests for particular hosts, paths or methods.
+///
+/// Each criterion left empty matches anything; otherwise the request must match one
+/// of the given values.
+#[derive(Debug, Clone, Default)]
+pub struct FilterScope {
+    hosts: Vec<String>,
+    path_prefixes: Vec<String>,
+    methods: Vec<String>,
+}
+
+impl FilterScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match this host. A leading `*.` matches any subdomain.
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.hosts.push(host.into().to_lowercase());
+        self
+    }
+
+    /// Match paths starting with this prefix
+    pub fn path_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.path_prefixes.push(prefix.into());
+        self
+    }
+
+    /// Match this HTTP method
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.methods.push(method.into().to_uppercase());
+        self
+    }
+
+    pub fn matches(&self, request: &SerializableRequest) -> bool {
+        if !self.methods.is_empty() && !self.methods.contains(&request.method.to_uppercase()) {
+            return false;
+        }
+
+        if self.hosts.is_empty() && self.path_prefixes.is_empty() {
+            return true;
+        }
+
+        let Ok(url) = url::Url::parse(&request.url) else {
+            return false;
+        };
+
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        let host_matches = self.hosts.is_empty()
+            || self
+                .hosts
+                .iter()
+                .any(|pattern| host_matches(pattern, &host));
+
+        let path_matches = self.path_prefixes.is_empty()
+            || self
+                .path_prefixes
+                .iter()
+                .any(|prefix| url.path().starts_with(prefix.as_str()));
+
+        host_matches && path_matches
+    }
+}
+
+/// Whether the lowercase `host` matches `pattern`, where a leading `*.` matches any
+/// subdomain
+pub(crate) fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.ends_with(&format!(".{domain}")),
+        None => host == pattern,
+    }
+}
+
+/// When a filter runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStage {
+    /// On requests and responses before they are stored (and on live requests before
+    /// matching). Changes end up in the cassette.
+    BeforeRecord,
+    /// On recorded responses right before they are returned to the caller during
+    /// replay. The cassette is not modified.
+    BeforePlayback,
+}
+
+/// A filter with the requests it applies to and the stage it runs at, for adding under
+/// a name with `FilterChain::add_named_entry` or `FilterChain::insert_entry_before`
+#[derive(Debug)]
+pub struct FilterEntry {
+    name: Option<String>,
+    scope: Option<FilterScope>,
+    stage: FilterStage,
+    filter: Box<dyn Filter>,
+}
+
+impl FilterEntry {
+    /// An unscoped entry for the `BeforeRecord` stage
+    pub fn new(filter: Box<dyn Filter>) -> Self {
+        Self {
+            name: None,
+            scope: None,
+            stage: FilterStage::BeforeRecord,
+            filter,
+        }
+    }
+
+    /// Only apply the filter to requests (and their responses) matching `scope`
+    pub fn scoped(mut self, scope: FilterScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Run the filter at `stage`
+    pub fn at_stage(mut self, stage: FilterStage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    /// `filter` with the scope and stage of `entry`, if there is one
+    fn like(entry: Option<&FilterEntry>, filter: Box<dyn Filter>) -> Self {
+        Self {
+            scope: entry.and_then(|entry| entry.scope.clone()),
+            stage: entry.map_or(FilterStage::BeforeRecord, |entry| entry.stage),
+            ..Self::new(filter)
+        }
+    }
+
+    /// Whether the filter runs at `stage` for an exchange for `request`. Scoped
+    /// filters only apply when the request is known.
+    fn applies_to(&self, stage: FilterStage, request: Option<&SerializableRequest>) -> bool {
+        if self.stage != stage {
+            return false;
+        }
+
+        match (&self.scope, request) {
+            (None, _) => true,
+            (Some(scope), Some(request)) => scope.matches(request),
+            (Some(_), None) => false,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct FilterChain {
-    filters: Vec<Box<dyn Filter>>,
+    filters: Vec<FilterEntry>,
 }
 
 impl FilterChain {
@@ -23,20 +193,233 @@ impl FilterChain {
     }
 
     pub fn add_filter(mut self, filter: Box<dyn Filter>) -> Self {
-        self.filters.push(filter);
+        self.filters.push(FilterEntry::new(filter));
         self
     }
 
-    pub fn filter_request(&self, request: &mut SerializableRequest) {
-        for filter in &self.filters {
-            filter.filter_request(request);
+    /// Add a filter for the `BeforeRecord` stage; the same as `add_filter`
+    pub fn before_record(self, filter: Box<dyn Filter>) -> Self {
+        self.add_filter(filter)
+    }
+
+    /// Add a filter for the `BeforePlayback` stage. Its `filter_response` runs on
+    /// every replayed response, after the record-stage filters have restored it;
+    /// `filter_request` is never called.
+    pub fn before_playback(self, filter: Box<dyn Filter>) -> Self {
+        self.add_filter_for_stage(FilterStage::BeforePlayback, filter)
+    }
+
+    /// Add a filter that runs at `stage`
+    pub fn add_filter_for_stage(mut self, stage: FilterStage, filter: Box<dyn Filter>) -> Self {
+        self.filters.push(FilterEntry {
+            stage,
+            ..FilterEntry::new(filter)
+        });
+        self
+    }
+
+    /// Add a filter that only applies to requests (and their responses) matching `scope`
+    pub fn add_scoped_filter(mut self, scope: FilterScope, filter: Box<dyn Filter>) -> Self {
+        self.filters.push(FilterEntry {
+            scope: Some(scope),
+            ..FilterEntry::new(filter)
+        });
+        self
+    }
+
+    /// Add a filter under `name` so it can later be removed or positioned against.
+    /// A filter already registered under the same name is replaced in place, keeping
+    /// its scope and stage.
+    pub fn add_named_filter(self, name: impl Into<String>, filter: Box<dyn Filter>) -> Self {
+        let name = name.into();
+        let entry = FilterEntry::like(self.entry(&name), filter);
+        self.add_named_entry(name, entry)
+    }
+
+    /// Add `entry` under `name`, replacing any entry already registered under it in
+    /// place
+    pub fn add_named_entry(mut self, name: impl Into<String>, mut entry: FilterEntry) -> Self {
+        let name = name.into();
+        let index = self.position(&name);
+        entry.name = Some(name);
+        match index {
+            Some(index) => self.filters[index] = entry,
+            None => self.filters.push(entry),
         }
+        self
     }
 
-    pub fn filter_response(&self, response: &mut SerializableResponse) {
-        for filter in &self.filters {
-            filter.filter_response(response);
+    /// Insert a named filter directly before the filter named `before`, or at the end
+    /// if there is no such filter. A filter already registered under the same name is
+    /// replaced, moving to the new position with its scope and stage; a new one takes
+    /// the scope and stage of the filter it is inserted before.
+    pub fn insert_before(
+        self,
+        before: &str,
+        name: impl Into<String>,
+        filter: Box<dyn Filter>,
+    ) -> Self {
+        let name = name.into();
+        let like = self.entry(&name).or_else(|| self.entry(before));
+        let entry = FilterEntry::like(like, filter);
+        self.insert_entry_before(before, name, entry)
+    }
+
+    /// Insert `entry` under `name` directly before the filter named `before`, or at the
+    /// end if there is no such filter. An entry already registered under the same name
+    /// is replaced, moving to the new position.
+    pub fn insert_entry_before(
+        mut self,
+        before: &str,
+        name: impl Into<String>,
+        entry: FilterEntry,
+    ) -> Self {
+        let name = name.into();
+        if name == before {
+            return self.add_named_entry(name, entry);
+        }
+        if let Some(index) = self.position(&name) {
+            self.filters.remove(index);
+        }
+        let entry = FilterEntry {
+            name: Some(name),
+            ..entry
+        };
+        match self.position(before) {
+            Some(index) => self.filters.insert(index, entry),
+            None => self.filters.push(entry),
         }
+        self
+    }
+
+    /// Remove the filter named `name`, if present
+    pub fn remove_filter(mut self, name: &str) -> Self {
+        self.filters
+            .retain(|entry| entry.name.as_deref() != Some(name));
+        self
+    }
+
+    /// Whether a filter is registered under `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// The filters in the order they run, with their names if they have one
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&str>, &dyn Filter)> {
+        self.filters
+            .iter()
+            .map(|entry| (entry.name.as_deref(), entry.filter.as_ref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    fn entry(&self, name: &str) -> Option<&FilterEntry> {
+        self.position(name).map(|index| &self.filters[index])
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.filters
+            .iter()
+            .position(|entry| entry.name.as_deref() == Some(name))
+    }
+
+    /// Let every filter pick up where the recordings of a cassette that is appended to
+    /// left off
+    pub fn resume(&self, metadata: &CassetteMetadata) {
+        for entry in &self.filters {
+            entry.filter.resume(metadata);
+        }
+    }
+
+    /// Let every filter leave its state for later recordings in `metadata`
+    pub fn annotate(&self, metadata: &mut CassetteMetadata) {
+        for entry in &self.filters {
+            entry.filter.annotate(metadata);
+        }
+    }
+
+    /// Filter a request. Bodies stored as base64 are decoded first, so filters only
+    /// need to look at `body`.
+    pub fn filter_request(&self, request: &mut SerializableRequest) {
+        request.with_decoded_body(|request| {
+            for entry in &self.filters {
+                if entry.applies_to(FilterStage::BeforeRecord, Some(request)) {
+                    entry.filter.filter_request(request);
+                }
+            }
+        });
+    }
+
+    /// Filter a response without knowing its request, so scoped filters are skipped.
+    /// Prefer `filter_response_for` or `filter_interaction` when the request is known.
+    pub fn filter_response(&self, response: &mut SerializableResponse) {
+        self.run_response_filters(None, response);
+    }
+
+    /// Filter the response to `request`
+    pub fn filter_response_for(
+        &self,
+        request: &SerializableRequest,
+        response: &mut SerializableResponse,
+    ) {
+        self.run_response_filters(Some(request), response);
+    }
+
+    /// Filter a request and its response
+    pub fn filter_interaction(
+        &self,
+        request: &mut SerializableRequest,
+        response: &mut SerializableResponse,
+    ) {
+        self.filter_request(request);
+        self.filter_response_for(request, response);
+    }
+
+    /// Prepare the recorded response to `request` for replay: record-stage filters
+    /// restore it in reverse order, then playback-stage filters run in order
+    pub fn prepare_playback(
+        &self,
+        request: &SerializableRequest,
+        response: &mut SerializableResponse,
+    ) {
+        response.with_decoded_body(|response| {
+            for entry in self.filters.iter().rev() {
+                if entry.applies_to(FilterStage::BeforeRecord, Some(request)) {
+                    entry.filter.restore_response(response);
+                }
+            }
+
+            for entry in &self.filters {
+                if entry.applies_to(FilterStage::BeforePlayback, Some(request)) {
+                    entry.filter.filter_response_for(request, response);
+                }
+            }
+        });
+    }
+
+    /// Bodies stored as base64 are decoded first, so filters only need to look at `body`
+    fn run_response_filters(
+        &self,
+        request: Option<&SerializableRequest>,
+        response: &mut SerializableResponse,
+    ) {
+        response.with_decoded_body(|response| {
+            for entry in &self.filters {
+                if !entry.applies_to(FilterStage::BeforeRecord, request) {
+                    continue;
+                }
+                match request {
+                    Some(request) => entry.filter.filter_response_for(request, response),
+                    None => entry.filter.filter_response(response),
+                }
+            }
+        });
     }
 }
 
@@ -83,9 +466,9 @@ impl HeaderFilter {
             .remove_header("X-Auth-Token")
     }
 
-    fn filter_headers(&self, headers: &mut HashMap<String, Vec<String>>) {
+    fn filter_headers(&self, headers: &mut Headers) {
         for header in &self.headers_to_remove {
-            headers.remove(header);
+            headers.shift_remove(header);
         }
 
         for (header, replacement) in &self.headers_to_replace {
@@ -113,11 +496,16 @@ impl Default for HeaderFilter {
     }
 }
 
+/// Filters JSON keys, form fields and regex matches out of bodies.
+///
+/// Bodies with an NDJSON content type (`application/x-ndjson`, `application/ndjson`
+/// or `application/jsonl`) are filtered one JSON document per line.
 #[derive(Debug)]
 pub struct BodyFilter {
     json_keys_to_remove: Vec<String>,
     json_keys_to_replace: HashMap<String, String>,
     regex_replacements: Vec<(Regex, String)>,
+    ndjson: bool,
 }
 
 impl BodyFilter {
@@ -126,9 +514,16 @@ impl BodyFilter {
             json_keys_to_remove: Vec::new(),
             json_keys_to_replace: HashMap::new(),
             regex_replacements: Vec::new(),
+            ndjson: false,
         }
     }
 
+    /// Filter every body line by line as NDJSON, whatever its content type
+    pub fn ndjson(mut self, ndjson: bool) -> Self {
+        self.ndjson = ndjson;
+        self
+    }
+
     pub fn remove_json_key(mut self, key: impl Into<String>) -> Self {
         self.json_keys_to_remove.push(key.into());
         self
@@ -192,22 +587,60 @@ impl BodyFilter {
         }
     }
 
-    fn filter_body(&self, body: &mut Option<String>) {
+    /// Filter each line holding a JSON document, keeping blank lines and line
+    /// endings. Lines that aren't JSON get the regex replacements.
+    fn filter_ndjson(&self, body: &str) -> String {
+        body.split_inclusive('\n')
+            .map(|line| {
+                let content = line.trim_end_matches(['\r', '\n']);
+                let ending = &line[content.len()..];
+                if content.trim().is_empty() {
+                    return line.to_string();
+                }
+                match serde_json::from_str::<Value>(content) {
+                    Ok(mut json_value) => {
+                        self.filter_json_value(&mut json_value);
+                        let filtered = serde_json::to_string(&json_value)
+                            .unwrap_or_else(|_| content.to_string());
+                        format!("{filtered}{ending}")
+                    }
+                    Err(_) => {
+                        let mut content = content.to_string();
+                        for (regex, replacement) in &self.regex_replacements {
+                            content = regex.replace_all(&content, replacement).to_string();
+                        }
+                        format!("{content}{ending}")
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn filter_body<B: AsRef<str> + From<String>>(&self, headers: &Headers, body: &mut Option<B>) {
+        if self.ndjson || is_ndjson(headers) {
+            if let Some(body_str) = body {
+                *body_str = self.filter_ndjson(body_str.as_ref()).into();
+            }
+            return;
+        }
         if let Some(body_str) = body {
-            if let Ok(mut json_value) = serde_json::from_str::<Value>(body_str) {
+            if let Ok(mut json_value) = serde_json::from_str::<Value>(body_str.as_ref()) {
                 // Handle JSON body
                 self.filter_json_value(&mut json_value);
                 if let Ok(filtered_json) = serde_json::to_string(&json_value) {
-                    *body_str = filtered_json;
+                    *body_str = filtered_json.into();
                 }
-            } else if body_str.contains('=') && body_str.contains('&') {
+            } else if body_str.as_ref().contains('=') && body_str.as_ref().contains('&') {
                 // Handle form-encoded body with smart form data parsing
-                let filtered = filter_form_data(body_str, "[FILTERED]");
-                *body_str = filtered;
+                let filtered = redact_form_data(body_str.as_ref());
+                *body_str = filtered.into();
             } else {
                 // Handle other text formats with regex
                 for (regex, replacement) in &self.regex_replacements {
-                    *body_str = regex.replace_all(body_str, replacement).to_string();
+                    if let Cow::Owned(replaced) = regex.replace_all(body_str.as_ref(), replacement)
+                    {
+                        *body_str = replaced.into();
+                    }
                 }
             }
         }
@@ -216,14 +649,33 @@ impl BodyFilter {
 
 impl Filter for BodyFilter {
     fn filter_request(&self, request: &mut SerializableRequest) {
-        self.filter_body(&mut request.body);
+        self.filter_body(&request.headers, &mut request.body);
     }
 
     fn filter_response(&self, response: &mut SerializableResponse) {
-        self.filter_body(&mut response.body);
+        self.filter_body(&response.headers, &mut response.body);
     }
 }
 
+/// Whether the `Content-Type` is one of the newline-delimited JSON types
+fn is_ndjson(headers: &Headers) -> bool {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .flat_map(|(_, values)| values)
+        .filter_map(|value| value.split(';').next())
+        .any(|mime| {
+            let mime = mime.trim();
+            [
+                "application/x-ndjson",
+                "application/ndjson",
+                "application/jsonl",
+            ]
+            .iter()
+            .any(|ndjson| mime.eq_ignore_ascii_case(ndjson))
+        })
+}
+
 impl Default for BodyFilter {
     fn default() -> Self {
         Self::new()
@@ -269,27 +721,20 @@ impl UrlFilter {
 
 impl Filter for UrlFilter {
     fn filter_request(&self, request: &mut SerializableRequest) {
-        if let Ok(mut url) = url::Url::parse(&request.url) {
-            let mut query_pairs: Vec<(String, String)> = url
-                .query_pairs()
-                .map(|(k, v)| (k.to_string(), v.to_string()))
-                .collect();
-
-            query_pairs.retain(|(key, _)| !self.query_params_to_remove.contains(key));
+        let mut query_pairs = request.query_pairs();
+        if query_pairs.is_empty() {
+            return;
+        }
 
-            for (key, value) in &mut query_pairs {
-                if let Some(replacement) = self.query_params_to_replace.get(key) {
-                    *value = replacement.clone();
-                }
-            }
+        query_pairs.retain(|(key, _)| !self.query_params_to_remove.contains(key));
 
-            url.query_pairs_mut().clear();
-            for (key, value) in query_pairs {
-                url.query_pairs_mut().append_pair(&key, &value);
+        for (key, value) in &mut query_pairs {
+            if let Some(replacement) = self.query_params_to_replace.get(key) {
+                *value = replacement.clone();
             }
-
-            request.url = url.to_string();
         }
+
+        request.set_query_pairs(query_pairs);
     }
 
     fn filter_response(&self, _response: &mut SerializableResponse) {
@@ -303,22 +748,150 @@ impl Default for UrlFilter {
     }
 }
 
+/// Rewrites request URLs into one canonical form before they are stored, so a
+/// cassette doesn't depend on how the client library formatted them. Live requests
+/// are rewritten the same way before matching.
+///
+/// Add it before scoped filters so their scopes see the rewritten URLs.
+#[derive(Debug, Default)]
+pub struct UrlRewriteFilter {
+    strip_default_ports: bool,
+    lowercase_host: bool,
+    drop_fragment: bool,
+    regex_replacements: Vec<(Regex, String)>,
+}
+
+impl UrlRewriteFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Strip default ports, lowercase the host and drop fragments
+    pub fn canonical() -> Self {
+        Self::new()
+            .strip_default_ports()
+            .lowercase_host()
+            .drop_fragment()
+    }
+
+    /// Remove `:80` from `http` and `ws` URLs and `:443` from `https` and `wss` ones
+    pub fn strip_default_ports(mut self) -> Self {
+        self.strip_default_ports = true;
+        self
+    }
+
+    /// Lowercase the host, and the scheme with it
+    pub fn lowercase_host(mut self) -> Self {
+        self.lowercase_host = true;
+        self
+    }
+
+    /// Remove everything from `#` on
+    pub fn drop_fragment(mut self) -> Self {
+        self.drop_fragment = true;
+        self
+    }
+
+    /// Replace every match of `pattern` in the URL, query string included.
+    /// Replacements run in the order they were added, after the other rewrites, and
+    /// can refer to capture groups as `$1` or `${name}`.
+    pub fn replace_regex(
+        mut self,
+        pattern: &str,
+        replacement: impl Into<String>,
+    ) -> Result<Self, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        self.regex_replacements.push((regex, replacement.into()));
+        Ok(self)
+    }
+
+    /// Apply the rewrites to `url`
+    pub fn rewrite(&self, url: &str) -> String {
+        let mut url = url.to_string();
+        if self.drop_fragment {
+            if let Some(at) = url.find('#') {
+                url.truncate(at);
+            }
+        }
+        if self.lowercase_host || self.strip_default_ports {
+            self.rewrite_authority(&mut url);
+        }
+        for (regex, replacement) in &self.regex_replacements {
+            if let Cow::Owned(replaced) = regex.replace_all(&url, replacement.as_str()) {
+                url = replaced;
+            }
+        }
+        url
+    }
+
+    fn rewrite_authority(&self, url: &mut String) {
+        let Some(scheme_end) = url.find("://") else {
+            return;
+        };
+        let start = scheme_end + 3;
+        let end = url[start..]
+            .find(['/', '?', '#'])
+            .map_or(url.len(), |at| start + at);
+        let host_start = url[start..end]
+            .rfind('@')
+            .map_or(start, |at| start + at + 1);
+        // The port follows the last colon, unless that colon is inside an IPv6 address
+        let port_start = url[host_start..end]
+            .rfind(':')
+            .map(|at| host_start + at)
+            .filter(|&at| !url[at..end].contains(']'));
+
+        if self.lowercase_host {
+            let host_end = port_start.unwrap_or(end);
+            let host = url[host_start..host_end].to_ascii_lowercase();
+            url.replace_range(host_start..host_end, &host);
+            url[..scheme_end].make_ascii_lowercase();
+        }
+        if self.strip_default_ports {
+            let Some(port_start) = port_start else {
+                return;
+            };
+            let default_port = match url[..scheme_end].to_ascii_lowercase().as_str() {
+                "http" | "ws" => "80",
+                "https" | "wss" => "443",
+                _ => return,
+            };
+            if url[port_start + 1..end] == *default_port {
+                url.replace_range(port_start..end, "");
+            }
+        }
+    }
+}
+
+impl Filter for UrlRewriteFilter {
+    fn filter_request(&self, request: &mut SerializableRequest) {
+        request.url = self.rewrite(&request.url);
+    }
+
+    fn filter_response(&self, _response: &mut SerializableResponse) {
+        // URL rewriting only applies to requests
+    }
+}
+
+/// Redacts credential fields in form and multipart request bodies. By default values
+/// are replaced with typed placeholders such as `[FILTERED:password]`.
 #[derive(Debug)]
 pub struct SmartFormFilter {
-    replacement_pattern: String,
+    replacement_pattern: Option<String>,
     verbose: bool,
 }
 
 impl SmartFormFilter {
     pub fn new() -> Self {
         Self {
-            replacement_pattern: "[FILTERED]".to_string(),
+            replacement_pattern: None,
             verbose: false,
         }
     }
 
+    /// Replace credentials with `<pattern>_<FIELD NAME>` instead of typed placeholders
     pub fn with_replacement_pattern(mut self, pattern: impl Into<String>) -> Self {
-        self.replacement_pattern = pattern.into();
+        self.replacement_pattern = Some(pattern.into());
         self
     }
 
@@ -327,6 +900,22 @@ impl SmartFormFilter {
         self
     }
 
+    fn filter_multipart_body(&self, body: &mut Option<String>, boundary: &str) {
+        if let Some(body_str) = body {
+            let filtered = match &self.replacement_pattern {
+                Some(pattern) => filter_multipart_data(body_str, boundary, pattern),
+                None => redact_multipart_data(body_str, boundary),
+            };
+            if let Some(filtered) = filtered {
+                *body_str = filtered;
+
+                if self.verbose {
+                    println!("✅ Multipart form data filtered");
+                }
+            }
+        }
+    }
+
     fn filter_form_body(&self, body: &mut Option<String>) {
         if let Some(body_str) = body {
             // Check if this looks like form data
@@ -337,7 +926,10 @@ impl SmartFormFilter {
                     analysis.print_summary();
                 }
 
-                let filtered = filter_form_data(body_str, &self.replacement_pattern);
+                let filtered = match &self.replacement_pattern {
+                    Some(pattern) => filter_form_data(body_str, pattern),
+                    None => redact_form_data(body_str),
+                };
                 *body_str = filtered;
 
                 if self.verbose {
@@ -350,7 +942,16 @@ impl SmartFormFilter {
 
 impl Filter for SmartFormFilter {
     fn filter_request(&self, request: &mut SerializableRequest) {
-        self.filter_form_body(&mut request.body);
+        let boundary = request
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .and_then(|(_, values)| values.iter().find_map(|v| multipart_boundary(v)));
+
+        match boundary {
+            Some(boundary) => self.filter_multipart_body(&mut request.body, &boundary),
+            None => self.filter_form_body(&mut request.body),
+        }
     }
 
     fn filter_response(&self, _response: &mut SerializableResponse) {
@@ -364,6 +965,81 @@ impl Default for SmartFormFilter {
     }
 }
 
+/// Drops or truncates the bodies of error responses, which often contain stack traces
+/// and internal hostnames. Status and headers are kept.
+#[derive(Debug)]
+pub struct ErrorBodyFilter {
+    statuses: RangeInclusive<u16>,
+    truncate_to: Option<usize>,
+}
+
+impl ErrorBodyFilter {
+    /// Drop the bodies of all 4xx and 5xx responses
+    pub fn new() -> Self {
+        Self {
+            statuses: 400..=599,
+            truncate_to: None,
+        }
+    }
+
+    /// Only touch 5xx responses
+    pub fn server_errors_only(self) -> Self {
+        self.with_status_range(500..=599)
+    }
+
+    /// Touch responses whose status falls in `statuses`
+    pub fn with_status_range(mut self, statuses: RangeInclusive<u16>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+
+    /// Keep the first `bytes` of the body instead of dropping it
+    pub fn truncate_to(mut self, bytes: usize) -> Self {
+        self.truncate_to = Some(bytes);
+        self
+    }
+
+    /// Whether `response` falls in the filtered status range
+    pub fn applies_to(&self, response: &SerializableResponse) -> bool {
+        self.statuses.contains(&response.status)
+    }
+}
+
+impl Filter for ErrorBodyFilter {
+    fn filter_request(&self, _request: &mut SerializableRequest) {
+        // Only response bodies are scrubbed
+    }
+
+    fn filter_response(&self, response: &mut SerializableResponse) {
+        if !self.applies_to(response) {
+            return;
+        }
+
+        match (self.truncate_to, &mut response.body) {
+            (Some(limit), Some(body)) if body.len() > limit => {
+                let mut end = limit;
+                while !body.is_char_boundary(end) {
+                    end -= 1;
+                }
+                let body = body.to_mut();
+                body.truncate(end);
+                body.push_str("...[truncated]");
+            }
+            (Some(_), Some(_)) => {}
+            _ => {
+                response.body = None;
+                response.body_base64 = None;
+            }
+        }
+    }
+}
+
+impl Default for ErrorBodyFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug)]
 pub struct CustomFilter<F>
 where
@@ -388,7 +1064,7 @@ where
     fn filter_request(&self, request: &mut SerializableRequest) {
         let mut dummy_response = SerializableResponse {
             status: 200,
-            headers: HashMap::new(),
+            headers: Headers::new(),
             body: None,
             body_base64: None,
             version: "Http1_1".to_string(),
@@ -400,7 +1076,7 @@ where
         let mut dummy_request = SerializableRequest {
             method: "GET".to_string(),
             url: "https://example.com".to_string(),
-            headers: HashMap::new(),
+            headers: Headers::new(),
             body: None,
             body_base64: None,
             version: "Http1_1".to_string(),
@@ -408,3 +1084,351 @@ where
         (self.filter_fn)(&mut dummy_request, response);
     }
 }
+
+/// Replaces a dynamically computed secret with a fixed placeholder anywhere it
+/// appears in URLs, headers and bodies, and puts the value back on replay.
+///
+/// The value is computed on every use, so secrets read from the environment
+/// pick up whatever is set when the filter runs.
+pub struct SensitiveDataFilter {
+    value: Box<dyn Fn() -> String + Send + Sync>,
+    placeholder: String,
+}
+
+impl SensitiveDataFilter {
+    pub fn new<F>(value: F, placeholder: impl Into<String>) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        Self {
+            value: Box::new(value),
+            placeholder: placeholder.into(),
+        }
+    }
+
+    fn current_value(&self) -> Option<String> {
+        Some((self.value)()).filter(|value| !value.is_empty() && *value != self.placeholder)
+    }
+
+    fn replace_in<T: AsRef<str> + From<String>>(&self, text: &mut T, value: &str) {
+        if text.as_ref().contains(value) {
+            *text = text.as_ref().replace(value, &self.placeholder).into();
+        }
+
+        // Secrets in query strings are usually percent-encoded
+        let encoded = urlencoding::encode(value);
+        if encoded != value && text.as_ref().contains(encoded.as_ref()) {
+            *text = text
+                .as_ref()
+                .replace(encoded.as_ref(), &self.placeholder)
+                .into();
+        }
+    }
+}
+
+impl Debug for SensitiveDataFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SensitiveDataFilter")
+            .field("placeholder", &self.placeholder)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Filter for SensitiveDataFilter {
+    fn filter_request(&self, request: &mut SerializableRequest) {
+        let Some(value) = self.current_value() else {
+            return;
+        };
+
+        self.replace_in(&mut request.url, &value);
+        for values in request.headers.values_mut() {
+            for header_value in values.iter_mut() {
+                self.replace_in(header_value, &value);
+            }
+        }
+        if let Some(body) = &mut request.body {
+            self.replace_in(body, &value);
+        }
+    }
+
+    fn filter_response(&self, response: &mut SerializableResponse) {
+        let Some(value) = self.current_value() else {
+            return;
+        };
+
+        for values in response.headers.values_mut() {
+            for header_value in values.iter_mut() {
+                self.replace_in(header_value, &value);
+            }
+        }
+        if let Some(body) = &mut response.body {
+            self.replace_in(body, &value);
+        }
+    }
+
+    fn restore_response(&self, response: &mut SerializableResponse) {
+        let Some(value) = self.current_value() else {
+            return;
+        };
+
+        for values in response.headers.values_mut() {
+            for header_value in values.iter_mut() {
+                *header_value = header_value.replace(&self.placeholder, &value);
+            }
+        }
+        if let Some(body) = &mut response.body {
+            if body.contains(self.placeholder.as_str()) {
+                *body = body.replace(&self.placeholder, &value).into();
+            }
+        }
+    }
+}
+
+/// Replaces secret values with stable placeholders such as `<<API_KEY>>` when
+/// recording and substitutes test values back in when replaying, similar to
+/// Ruby VCR's `define_cassette_placeholder`.
+///
+/// Values matched by [`PlaceholderFilter::capture_regex`] are numbered in the order
+/// the filter first sees them (`<<TOKEN_1>>`, `<<TOKEN_2>>`), so a value seen again
+/// gets the same placeholder and a replay that sees its values in the recorded order
+/// matches. The names say nothing about the values themselves. When a recording is
+/// appended to a cassette, numbering continues after the highest number stored in
+/// its metadata, so new values never take a placeholder that already stands for
+/// another one. On replay, captured placeholders are swapped back for values seen in
+/// this session's requests; the rest are left as-is.
+#[derive(Debug, Default)]
+pub struct PlaceholderFilter {
+    placeholders: Vec<Placeholder>,
+    patterns: Vec<(String, Regex)>,
+    captured: Mutex<Vec<Captured>>,
+    // Numbers already taken in the cassette being appended to, by prefix
+    taken: Mutex<BTreeMap<String, usize>>,
+}
+
+/// A value matched by a capture pattern and the placeholder it was given
+#[derive(Debug)]
+struct Captured {
+    prefix: String,
+    value: String,
+    number: usize,
+    placeholder: String,
+}
+
+#[derive(Debug)]
+struct Placeholder {
+    placeholder: String,
+    secret: Option<String>,
+    replay_value: Option<String>,
+}
+
+impl PlaceholderFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace `secret` with `<<name>>` and restore it on replay
+    pub fn define(self, name: &str, secret: impl Into<String>) -> Self {
+        let secret = secret.into();
+        self.define_with_replay_value(name, secret.clone(), secret)
+    }
+
+    /// Replace `secret` with `<<name>>` and substitute `replay_value` on replay
+    pub fn define_with_replay_value(
+        mut self,
+        name: &str,
+        secret: impl Into<String>,
+        replay_value: impl Into<String>,
+    ) -> Self {
+        self.placeholders.push(Placeholder {
+            placeholder: format!("<<{name}>>"),
+            secret: Some(secret.into()).filter(|s| !s.is_empty()),
+            replay_value: Some(replay_value.into()),
+        });
+        self
+    }
+
+    /// Replace the current value of the environment variable `var` with `<<name>>`,
+    /// substituting whatever the variable holds at replay time
+    pub fn define_from_env(mut self, name: &str, var: &str) -> Self {
+        let value = std::env::var(var).ok().filter(|v| !v.is_empty());
+        self.placeholders.push(Placeholder {
+            placeholder: format!("<<{name}>>"),
+            secret: value.clone(),
+            replay_value: value,
+        });
+        self
+    }
+
+    /// Replace every match of `pattern` with a numbered `<<PREFIX_n>>` placeholder, the
+    /// same one each time a value comes up again. If the pattern has a capture group,
+    /// only the first group is replaced.
+    pub fn capture_regex(mut self, prefix: &str, pattern: &str) -> Result<Self, regex::Error> {
+        self.patterns
+            .push((prefix.to_string(), Regex::new(pattern)?));
+        Ok(self)
+    }
+
+    /// The placeholder a captured or defined secret was mapped to, if any
+    pub fn placeholder_for(&self, secret: &str) -> Option<String> {
+        self.placeholders
+            .iter()
+            .find(|p| p.secret.as_deref() == Some(secret))
+            .map(|p| p.placeholder.clone())
+            .or_else(|| {
+                let captured = self.captured.lock().unwrap();
+                captured
+                    .iter()
+                    .find(|captured| captured.value == secret)
+                    .map(|captured| captured.placeholder.clone())
+            })
+    }
+
+    fn is_placeholder(value: &str) -> bool {
+        value.starts_with("<<") && value.ends_with(">>")
+    }
+
+    fn substitute<T: AsRef<str> + From<String>>(&self, text: &mut T) {
+        for placeholder in &self.placeholders {
+            // Both the real secret and the replay value map to the placeholder, so
+            // requests sent during replay still match what was recorded
+            for value in [&placeholder.secret, &placeholder.replay_value]
+                .into_iter()
+                .flatten()
+            {
+                if !value.is_empty() && text.as_ref().contains(value.as_str()) {
+                    *text = text
+                        .as_ref()
+                        .replace(value.as_str(), &placeholder.placeholder)
+                        .into();
+                }
+            }
+        }
+
+        for (prefix, regex) in &self.patterns {
+            let replaced = regex.replace_all(text.as_ref(), |caps: &Captures| {
+                let whole = caps.get(0).unwrap();
+                let secret = caps.get(1).unwrap_or(whole);
+                if Self::is_placeholder(secret.as_str()) {
+                    return whole.as_str().to_string();
+                }
+
+                let placeholder = self.captured_placeholder(prefix, secret.as_str());
+                let start = secret.start() - whole.start();
+                let end = secret.end() - whole.start();
+                format!(
+                    "{}{placeholder}{}",
+                    &whole.as_str()[..start],
+                    &whole.as_str()[end..]
+                )
+            });
+            if let Cow::Owned(replaced) = replaced {
+                *text = replaced.into();
+            }
+        }
+    }
+
+    fn captured_placeholder(&self, prefix: &str, secret: &str) -> String {
+        let mut captured = self.captured.lock().unwrap();
+        if let Some(existing) = captured
+            .iter()
+            .find(|captured| captured.prefix == prefix && captured.value == secret)
+        {
+            return existing.placeholder.clone();
+        }
+
+        let taken = self.taken.lock().unwrap().get(prefix).copied();
+        let number = captured
+            .iter()
+            .filter(|captured| captured.prefix == prefix)
+            .count()
+            + taken.unwrap_or(0)
+            + 1;
+        let placeholder = format!("<<{prefix}_{number}>>");
+        captured.push(Captured {
+            prefix: prefix.to_string(),
+            value: secret.to_string(),
+            number,
+            placeholder: placeholder.clone(),
+        });
+        placeholder
+    }
+
+    fn restore<T: AsRef<str> + From<String>>(&self, text: &mut T) {
+        for placeholder in &self.placeholders {
+            if let Some(replay_value) = &placeholder.replay_value {
+                if text.as_ref().contains(&placeholder.placeholder) {
+                    *text = text
+                        .as_ref()
+                        .replace(&placeholder.placeholder, replay_value)
+                        .into();
+                }
+            }
+        }
+
+        for captured in self.captured.lock().unwrap().iter() {
+            if text.as_ref().contains(captured.placeholder.as_str()) {
+                *text = text
+                    .as_ref()
+                    .replace(captured.placeholder.as_str(), &captured.value)
+                    .into();
+            }
+        }
+    }
+
+    fn substitute_headers(&self, headers: &mut Headers) {
+        for values in headers.values_mut() {
+            for value in values.iter_mut() {
+                self.substitute(value);
+            }
+        }
+    }
+}
+
+impl Filter for PlaceholderFilter {
+    fn filter_request(&self, request: &mut SerializableRequest) {
+        self.substitute(&mut request.url);
+        self.substitute_headers(&mut request.headers);
+        if let Some(body) = &mut request.body {
+            self.substitute(body);
+        }
+    }
+
+    fn filter_response(&self, response: &mut SerializableResponse) {
+        self.substitute_headers(&mut response.headers);
+        if let Some(body) = &mut response.body {
+            self.substitute(body);
+        }
+    }
+
+    fn restore_response(&self, response: &mut SerializableResponse) {
+        for values in response.headers.values_mut() {
+            for value in values.iter_mut() {
+                self.restore(value);
+            }
+        }
+        if let Some(body) = &mut response.body {
+            self.restore(body);
+        }
+    }
+
+    fn resume(&self, metadata: &CassetteMetadata) {
+        let mut taken = self.taken.lock().unwrap();
+        for (prefix, _) in &self.patterns {
+            if let Some(&highest) = metadata.placeholders.get(prefix) {
+                taken.insert(prefix.clone(), highest);
+            }
+        }
+    }
+
+    fn annotate(&self, metadata: &mut CassetteMetadata) {
+        let mut highest = self.taken.lock().unwrap().clone();
+        for captured in self.captured.lock().unwrap().iter() {
+            let number = highest.entry(captured.prefix.clone()).or_insert(0);
+            *number = (*number).max(captured.number);
+        }
+        for (prefix, number) in highest {
+            let stored = metadata.placeholders.entry(prefix).or_insert(0);
+            *stored = (*stored).max(number);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::placeholder::PlaceholderKind;
 use std::collections::HashMap;
 
 /// Parse URL-encoded form data into key-value pairs
@@ -16,10 +17,13 @@ pub fn parse_form_data(data: &str) -> HashMap<String, String> {
     params
 }
 
-/// Encode form data back to URL-encoded string
+/// Encode form data back to URL-encoded string, with the fields sorted by name so
+/// the same fields always encode the same way
 pub fn encode_form_data(params: &HashMap<String, String>) -> String {
+    let mut params: Vec<_> = params.iter().collect();
+    params.sort();
     params
-        .iter()
+        .into_iter()
         .map(|(key, value)| {
             format!(
                 "{}={}",
@@ -31,56 +35,62 @@ pub fn encode_form_data(params: &HashMap<String, String>) -> String {
         .join("&")
 }
 
+/// Common field names that might contain credentials
+const CREDENTIAL_PATTERNS: &[&str] = &[
+    // Username patterns
+    "username",
+    "user",
+    "login",
+    "email",
+    "username_or_email",
+    "user_name",
+    // Password patterns
+    "password",
+    "pass",
+    "passwd",
+    "pwd",
+    "secret",
+    // Token/CSRF patterns
+    "_token",
+    // Session patterns
+    "session",
+    "sessionid",
+    "sid",
+    "auth",
+    "authorization",
+    // API key patterns
+    "api_key",
+    "apikey",
+    "key",
+    "client_secret",
+    "access_token",
+    "refresh_token",
+];
+
+fn is_credential_name(key: &str) -> bool {
+    let key_lower = key.to_lowercase();
+    CREDENTIAL_PATTERNS
+        .iter()
+        .any(|pattern| key_lower.contains(pattern))
+}
+
+/// Long alphanumeric strings that might be tokens or hashes
+fn looks_like_token(value: &str) -> bool {
+    value.len() > 10 && value.chars().all(|c| c.is_alphanumeric())
+}
+
 /// Detect potential credential fields in form data
 pub fn find_credential_fields(params: &HashMap<String, String>) -> Vec<(String, String)> {
     let mut credentials = Vec::new();
 
-    // Common field names that might contain credentials
-    let credential_patterns = [
-        // Username patterns
-        "username",
-        "user",
-        "login",
-        "email",
-        "username_or_email",
-        "user_name",
-        // Password patterns
-        "password",
-        "pass",
-        "passwd",
-        "pwd",
-        "secret",
-        // Token/CSRF patterns
-        "_token",
-        // Session patterns
-        "session",
-        "sessionid",
-        "sid",
-        "auth",
-        "authorization",
-        // API key patterns
-        "api_key",
-        "apikey",
-        "key",
-        "client_secret",
-        "access_token",
-        "refresh_token",
-    ];
-
     for (key, value) in params {
-        let key_lower = key.to_lowercase();
-
         // Check if the key matches any credential pattern
-        for pattern in &credential_patterns {
-            if key_lower.contains(pattern) {
-                credentials.push((key.clone(), value.clone()));
-                break;
-            }
+        if is_credential_name(key) {
+            credentials.push((key.clone(), value.clone()));
         }
 
-        // Also check for suspicious values (long alphanumeric strings that might be tokens)
-        if value.len() > 10 && value.chars().all(|c| c.is_alphanumeric()) {
-            // This might be a token or hash
+        // Also check for suspicious values
+        if looks_like_token(value) {
             credentials.push((key.clone(), value.clone()));
         }
     }
@@ -90,17 +100,218 @@ pub fn find_credential_fields(params: &HashMap<String, String>) -> Vec<(String,
 
 /// Filter sensitive form data by replacing credential values
 pub fn filter_form_data(data: &str, replacement_pattern: &str) -> String {
-    let mut params = parse_form_data(data);
-    let credentials = find_credential_fields(&params);
+    replace_form_credentials(data, |key, _| {
+        format!("{replacement_pattern}_{}", key.to_uppercase())
+    })
+}
+
+/// Replace credential values in form data with typed placeholders such as
+/// `[FILTERED:password]`
+pub fn redact_form_data(data: &str) -> String {
+    replace_form_credentials(data, |key, value| {
+        PlaceholderKind::for_field(key, value).placeholder()
+    })
+}
+
+/// Replace the values of credential fields in place, leaving every other field and
+/// the field order untouched so filtering the same body always gives the same result
+fn replace_form_credentials(data: &str, replace: impl Fn(&str, &str) -> String) -> String {
+    let credentials: HashMap<String, String> = find_credential_fields(&parse_form_data(data))
+        .into_iter()
+        .collect();
+
+    data.split('&')
+        .map(|pair| {
+            let Some((key, value)) = pair.split_once('=') else {
+                return pair.to_string();
+            };
+            let decoded_key = urlencoding::decode(key).unwrap_or_else(|_| key.into());
+            if !credentials.contains_key(decoded_key.as_ref()) {
+                return pair.to_string();
+            }
+            let decoded_value = urlencoding::decode(value).unwrap_or_else(|_| value.into());
+            format!(
+                "{key}={}",
+                urlencoding::encode(&replace(&decoded_key, &decoded_value))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Extract the boundary from a `multipart/form-data` content type
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    params.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
 
-    // Replace sensitive values
-    for (key, _) in credentials {
-        if let Some(value) = params.get_mut(&key) {
-            *value = format!("{replacement_pattern}_{}", key.to_uppercase());
+/// A single part of a `multipart/form-data` body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartPart {
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MultipartPart {
+    /// The `name` parameter of the part's `Content-Disposition`
+    pub fn name(&self) -> Option<String> {
+        self.disposition_param("name")
+    }
+
+    /// The `filename` parameter of the part's `Content-Disposition`
+    pub fn filename(&self) -> Option<String> {
+        self.disposition_param("filename")
+    }
+
+    /// Whether this part is a file upload rather than a plain field
+    pub fn is_file(&self) -> bool {
+        self.filename().is_some()
+    }
+
+    fn disposition_param(&self, param: &str) -> Option<String> {
+        let (_, disposition) = self
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-disposition"))?;
+
+        disposition.split(';').skip(1).find_map(|p| {
+            let (key, value) = p.split_once('=')?;
+            (key.trim() == param).then(|| value.trim().trim_matches('"').to_string())
+        })
+    }
+}
+
+/// A parsed `multipart/form-data` body that can be modified part by part and
+/// encoded back with the original boundary, preamble and line endings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartBody {
+    pub boundary: String,
+    pub parts: Vec<MultipartPart>,
+    preamble: String,
+    epilogue: String,
+    newline: &'static str,
+}
+
+impl MultipartBody {
+    /// Parse a body, returning `None` if it isn't delimited by `boundary`
+    pub fn parse(body: &str, boundary: &str) -> Option<Self> {
+        let delimiter = format!("--{boundary}");
+        let mut segments = body.split(delimiter.as_str());
+        let preamble = segments.next()?.to_string();
+        let newline = if body.contains("\r\n") { "\r\n" } else { "\n" };
+
+        let mut parts = Vec::new();
+        let mut epilogue = None;
+        for segment in segments {
+            if let Some(rest) = segment.strip_prefix("--") {
+                epilogue = Some(rest.to_string());
+                break;
+            }
+
+            let segment = segment.strip_prefix(newline)?;
+            let segment = segment.strip_suffix(newline).unwrap_or(segment);
+            let separator = format!("{newline}{newline}");
+            let (header_block, part_body) = if let Some(body) = segment.strip_prefix(newline) {
+                ("", body)
+            } else {
+                segment.split_once(separator.as_str())?
+            };
+
+            let headers = header_block
+                .split(newline)
+                .filter_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    Some((name.trim().to_string(), value.trim().to_string()))
+                })
+                .collect();
+
+            parts.push(MultipartPart {
+                headers,
+                body: part_body.to_string(),
+            });
+        }
+
+        Some(Self {
+            boundary: boundary.to_string(),
+            parts,
+            preamble,
+            epilogue: epilogue?,
+            newline,
+        })
+    }
+
+    /// Encode the body back to a string
+    pub fn encode(&self) -> String {
+        let newline = self.newline;
+        let mut out = self.preamble.clone();
+        for part in &self.parts {
+            out.push_str(&format!("--{}{newline}", self.boundary));
+            for (name, value) in &part.headers {
+                out.push_str(&format!("{name}: {value}{newline}"));
+            }
+            out.push_str(newline);
+            out.push_str(&part.body);
+            out.push_str(newline);
         }
+        out.push_str(&format!("--{}--", self.boundary));
+        out.push_str(&self.epilogue);
+        out
     }
+}
 
-    encode_form_data(&params)
+/// Filter sensitive parts of a `multipart/form-data` body, leaving the boundary and
+/// other parts untouched. Field and file parts are redacted when their name looks
+/// like a credential; field parts also when their value looks like a token.
+///
+/// Returns `None` if the body can't be parsed with the given boundary.
+pub fn filter_multipart_data(
+    body: &str,
+    boundary: &str,
+    replacement_pattern: &str,
+) -> Option<String> {
+    replace_multipart_credentials(body, boundary, |name, _| {
+        format!("{replacement_pattern}_{}", name.to_uppercase())
+    })
+}
+
+/// Replace credential fields in a multipart body with typed placeholders, returning
+/// `None` if the body can't be parsed with `boundary`
+pub fn redact_multipart_data(body: &str, boundary: &str) -> Option<String> {
+    replace_multipart_credentials(body, boundary, |name, value| {
+        PlaceholderKind::for_field(name, value).placeholder()
+    })
+}
+
+fn replace_multipart_credentials(
+    body: &str,
+    boundary: &str,
+    replace: impl Fn(&str, &str) -> String,
+) -> Option<String> {
+    let mut multipart = MultipartBody::parse(body, boundary)?;
+
+    for part in &mut multipart.parts {
+        let Some(name) = part.name() else {
+            continue;
+        };
+
+        let sensitive =
+            is_credential_name(&name) || (!part.is_file() && looks_like_token(&part.body));
+        if sensitive {
+            part.body = replace(&name, &part.body);
+        }
+    }
+
+    Some(multipart.encode())
 }
 
 /// Analyze form data and return a report of what was found
@@ -195,4 +406,44 @@ mod tests {
         assert!(filtered.contains("%5BFILTERED%5D_PASSWORD"));
         assert!(filtered.contains("normal=value"));
     }
+
+    #[test]
+    fn test_filtering_keeps_field_order() {
+        let data = "zeta=1&password=secret123&alpha=2";
+        let filtered = filter_form_data(data, "[FILTERED]");
+        assert_eq!(filtered, "zeta=1&password=%5BFILTERED%5D_PASSWORD&alpha=2");
+        assert_eq!(filter_form_data(data, "[FILTERED]"), filtered);
+
+        let mut params = HashMap::new();
+        params.insert("zeta".to_string(), "1".to_string());
+        params.insert("alpha".to_string(), "2".to_string());
+        assert_eq!(encode_form_data(&params), "alpha=2&zeta=1");
+    }
+
+    #[test]
+    fn test_filter_multipart_data() {
+        let content_type = "multipart/form-data; boundary=\"XyZ\"";
+        let boundary = multipart_boundary(content_type).unwrap();
+        assert_eq!(boundary, "XyZ");
+
+        let body = "--XyZ\r\n\
+Content-Disposition: form-data; name=\"password\"\r\n\r\n\
+hunter2\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+My upload\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+Content-Type: text/plain\r\n\r\n\
+file contents\r\n\
+--XyZ--\r\n";
+
+        let filtered = filter_multipart_data(body, &boundary, "[FILTERED]").unwrap();
+        assert_eq!(filtered, body.replace("hunter2", "[FILTERED]_PASSWORD"));
+
+        let parsed = MultipartBody::parse(&filtered, &boundary).unwrap();
+        assert_eq!(parsed.parts.len(), 3);
+        assert_eq!(parsed.parts[2].filename().as_deref(), Some("a.txt"));
+        assert_eq!(parsed.encode(), filtered);
+    }
 }
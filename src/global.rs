@@ -0,0 +1,316 @@
+use crate::{PlaybackStats, VcrClient, VcrClientBuilder, VcrMode};
+use async_lock::Mutex;
+use async_trait::async_trait;
+use http_client::{Config, Error, HttpClient, Request, Response};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+type ConfigureFn = dyn Fn(VcrClientBuilder) -> VcrClientBuilder + Send + Sync;
+
+/// Process-wide VCR setup, so a test binary can configure VCR once and each test
+/// only says which cassette to use, like Ruby's `VCR.configure` and
+/// `VCR.use_cassette`. Clients deep inside the code under test are wrapped once with
+/// [`wrap`] and send through whichever cassette is in use at the time.
+///
+/// All wrapped clients share the cassette in use. It is recorded through the real
+/// client of the first wrapped client to send a request while it is in use.
+///
+/// ```no_run
+/// # use http_client_vcr::{DefaultMatcher, NoOpClient, VcrMode};
+/// # use http_client::HttpClient;
+/// # async fn run() -> Result<(), http_client::Error> {
+/// http_client_vcr::global()
+///     .configure(|builder| builder.matcher(Box::new(DefaultMatcher::without_cookies())));
+/// // Usually done where the application builds its client
+/// let client = http_client_vcr::wrap(NoOpClient::new());
+///
+/// http_client_vcr::global()
+///     .use_cassette("tests/fixtures/login.yaml", VcrMode::Once, async {
+///         let url = http_types::Url::parse("https://example.com/login")?;
+///         client.send(http_types::Request::new(http_types::Method::Post, url)).await
+///     })
+///     .await??;
+/// # Ok(())
+/// # }
+/// ```
+pub struct GlobalVcr {
+    configure: std::sync::RwLock<Option<Arc<ConfigureFn>>>,
+    allow_requests_without_cassette: AtomicBool,
+    session: Mutex<Option<Session>>,
+    // Held by `use_cassette`, so tests running in parallel take turns
+    turn: Mutex<()>,
+}
+
+/// The cassette in use and, once a wrapped client sent a request, the client for it
+struct Session {
+    path: PathBuf,
+    mode: VcrMode,
+    client: Option<Arc<VcrClient>>,
+}
+
+/// The process-wide [`GlobalVcr`]
+pub fn global() -> &'static GlobalVcr {
+    static GLOBAL: OnceLock<GlobalVcr> = OnceLock::new();
+    GLOBAL.get_or_init(GlobalVcr::new)
+}
+
+/// Wrap `client` so its requests go through the cassette [`global`] has in use
+pub fn wrap<C: HttpClient>(client: C) -> GlobalVcrClient<C> {
+    GlobalVcrClient {
+        inner: Arc::new(client),
+    }
+}
+
+impl GlobalVcr {
+    fn new() -> Self {
+        Self {
+            configure: std::sync::RwLock::new(None),
+            allow_requests_without_cassette: AtomicBool::new(false),
+            session: Mutex::new(None),
+            turn: Mutex::new(()),
+        }
+    }
+
+    /// Set up the builder of every cassette's client with `configure`, e.g. to add a
+    /// matcher and filters. Replaces an earlier configuration, and applies to the
+    /// cassettes inserted from now on. A `vcr.toml` is only used if it is passed in
+    /// here, e.g. `builder.with_config(config.clone())` with a config from
+    /// `VcrConfig::discover()`.
+    pub fn configure<F>(&self, configure: F)
+    where
+        F: Fn(VcrClientBuilder) -> VcrClientBuilder + Send + Sync + 'static,
+    {
+        *self.configure.write().unwrap() = Some(Arc::new(configure));
+    }
+
+    /// Let wrapped clients send requests straight to their real client while no
+    /// cassette is in use, instead of failing them
+    pub fn allow_requests_without_cassette(&self, allow: bool) {
+        self.allow_requests_without_cassette
+            .store(allow, Ordering::SeqCst);
+    }
+
+    /// Use the cassette at `path` in `mode` for wrapped clients' requests, ejecting
+    /// the cassette in use first
+    pub async fn insert_cassette<P: Into<PathBuf>>(
+        &self,
+        path: P,
+        mode: VcrMode,
+    ) -> Result<(), Error> {
+        let previous = self.session.lock().await.replace(Session {
+            path: path.into(),
+            mode,
+            client: None,
+        });
+        finish(previous).await.map(|_| ())
+    }
+
+    /// Stop using the current cassette, saving it if it was recorded to. Returns what
+    /// happened to it, or `None` if no cassette was in use or no request was sent.
+    ///
+    /// Fails like [`VcrClient::finalize`], e.g. when `strict_playback` is configured
+    /// and interactions weren't replayed.
+    pub async fn eject_cassette(&self) -> Result<Option<PlaybackStats>, Error> {
+        let session = self.session.lock().await.take();
+        finish(session).await
+    }
+
+    /// The path of the cassette in use
+    pub async fn cassette_path(&self) -> Option<PathBuf> {
+        let session = self.session.lock().await;
+        session.as_ref().map(|session| session.path.clone())
+    }
+
+    /// Run `scenario` with the cassette at `path` in `mode` in use, then eject it.
+    ///
+    /// Only one `use_cassette` runs at a time, so tests sharing the global cassette
+    /// slot can run in parallel.
+    pub async fn use_cassette<P, F, T>(
+        &self,
+        path: P,
+        mode: VcrMode,
+        scenario: F,
+    ) -> Result<T, Error>
+    where
+        P: Into<PathBuf>,
+        F: Future<Output = T>,
+    {
+        let _turn = self.turn.lock().await;
+        self.insert_cassette(path, mode).await?;
+        let result = scenario.await;
+        self.eject_cassette().await?;
+        Ok(result)
+    }
+
+    /// The client for the cassette in use, built around `inner` on first use
+    async fn client(
+        &self,
+        inner: impl FnOnce() -> Box<dyn HttpClient> + Send + 'static,
+    ) -> Result<Option<Arc<VcrClient>>, Error> {
+        let mut session = self.session.lock().await;
+        let Some(session) = session.as_mut() else {
+            return Ok(None);
+        };
+        if let Some(client) = &session.client {
+            return Ok(Some(Arc::clone(client)));
+        }
+
+        let mut builder = VcrClient::builder(session.path.clone())
+            .mode(session.mode.clone())
+            .inner_client_factory(inner);
+        let configure = self.configure.read().unwrap().clone();
+        if let Some(configure) = configure {
+            builder = configure(builder);
+        }
+        let client = Arc::new(builder.build().await?);
+        session.client = Some(Arc::clone(&client));
+        Ok(Some(client))
+    }
+}
+
+impl std::fmt::Debug for GlobalVcr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GlobalVcr").finish_non_exhaustive()
+    }
+}
+
+async fn finish(session: Option<Session>) -> Result<Option<PlaybackStats>, Error> {
+    match session.and_then(|session| session.client) {
+        Some(client) => client.finalize().await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// A client wrapped with [`wrap`]
+#[derive(Debug)]
+pub struct GlobalVcrClient<C: HttpClient> {
+    inner: Arc<C>,
+}
+
+impl<C: HttpClient> Clone for GlobalVcrClient<C> {
+    fn clone(&self) -> Self {
+        GlobalVcrClient {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: HttpClient> HttpClient for GlobalVcrClient<C> {
+    async fn send(&self, req: Request) -> Result<Response, Error> {
+        let inner = SharedClient(Arc::clone(&self.inner));
+        match global().client(move || Box::new(inner)).await? {
+            Some(client) => client.send(req).await,
+            None if global()
+                .allow_requests_without_cassette
+                .load(Ordering::SeqCst) =>
+            {
+                self.inner.send(req).await
+            }
+            None => Err(Error::from_str(
+                500,
+                format!(
+                    "{} {} was sent through a VCR-wrapped client while no cassette is in use. \
+                     Insert one with http_client_vcr::global().use_cassette(...).",
+                    req.method(),
+                    req.url()
+                ),
+            )),
+        }
+    }
+
+    /// Only possible while no clone of this client exists
+    fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        match Arc::get_mut(&mut self.inner) {
+            Some(client) => client.set_config(config),
+            None => Err(Error::from_str(
+                500,
+                "Can't change the config of a wrapped client shared by its clones",
+            )),
+        }
+    }
+
+    fn config(&self) -> &Config {
+        self.inner.config()
+    }
+}
+
+/// The real client of a wrapped client, handed to the cassette's `VcrClient`
+#[derive(Debug)]
+struct SharedClient<C>(Arc<C>);
+
+#[async_trait]
+impl<C: HttpClient> HttpClient for SharedClient<C> {
+    async fn send(&self, req: Request) -> Result<Response, Error> {
+        self.0.send(req).await
+    }
+
+    fn config(&self) -> &Config {
+        self.0.config()
+    }
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::*;
+    use crate::{Cassette, DefaultMatcher, LockstepClient, SerializableRequest};
+    use crate::{Headers, SerializableResponse};
+    use http_types::{Method, Url};
+
+    #[tokio::test]
+    async fn test_wrapped_clients_use_the_global_cassette() -> Result<(), Error> {
+        let mut upstream = Cassette::new();
+        upstream
+            .record_interaction(
+                SerializableRequest {
+                    method: "GET".to_string(),
+                    url: "https://example.com/data".to_string(),
+                    headers: Headers::new(),
+                    body: None,
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+                SerializableResponse {
+                    status: 200,
+                    headers: Headers::new(),
+                    body: Some("live".into()),
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+            )
+            .await?;
+        let client = wrap(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        );
+        global().configure(|builder| {
+            builder.matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        });
+        let request = || Request::new(Method::Get, Url::parse("https://example.com/data").unwrap());
+
+        assert!(client.send(request()).await.is_err());
+
+        let path = std::env::temp_dir().join(format!("vcr-global-{}.yaml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let recorded = global()
+            .use_cassette(&path, VcrMode::Record, async {
+                client.send(request()).await?.body_string().await
+            })
+            .await??;
+        assert_eq!(recorded, "live");
+        assert!(global().cassette_path().await.is_none());
+
+        // The upstream has nothing left to give, so this can only come from the cassette
+        let replayed = global()
+            .use_cassette(&path, VcrMode::Replay, async {
+                client.send(request()).await?.body_string().await
+            })
+            .await??;
+        assert_eq!(replayed, "live");
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+}
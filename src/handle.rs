@@ -0,0 +1,82 @@
+use crate::VcrClient;
+use async_trait::async_trait;
+use http_client::{Config, Error, HttpClient, Request, Response};
+use std::sync::Arc;
+
+/// A cheap, cloneable handle to a `VcrClient`. Every clone sends through the same
+/// client, sharing its cassette, matcher, filters and replay state, so one recording
+/// can be handed to several consumers that each want an owned `Box<dyn HttpClient>`.
+///
+/// Dereferences to the client for everything else, like `finalize`. The cassette is
+/// saved on drop once the last handle is gone.
+///
+/// ```rust,no_run
+/// # use http_client_vcr::VcrClient;
+/// # use http_client::HttpClient;
+/// # async fn example() -> Result<(), http_types::Error> {
+/// let handle = VcrClient::builder("tests/fixtures/api.yaml")
+///     .build()
+///     .await?
+///     .into_handle();
+/// let users_api: Box<dyn HttpClient> = Box::new(handle.clone());
+/// let billing_api: Box<dyn HttpClient> = Box::new(handle.clone());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct VcrHandle<C: HttpClient = Box<dyn HttpClient>>(Arc<VcrClient<C>>);
+
+impl<C: HttpClient> Clone for VcrHandle<C> {
+    fn clone(&self) -> Self {
+        VcrHandle(Arc::clone(&self.0))
+    }
+}
+
+impl<C: HttpClient> VcrClient<C> {
+    /// Turn the client into a `VcrHandle` that can be cloned for each consumer
+    pub fn into_handle(self) -> VcrHandle<C> {
+        VcrHandle(Arc::new(self))
+    }
+
+    /// A `VcrHandle` to a client that is already shared in an `Arc`
+    pub fn handle(self: &Arc<Self>) -> VcrHandle<C> {
+        VcrHandle(Arc::clone(self))
+    }
+}
+
+impl<C: HttpClient> From<Arc<VcrClient<C>>> for VcrHandle<C> {
+    fn from(client: Arc<VcrClient<C>>) -> Self {
+        VcrHandle(client)
+    }
+}
+
+impl<C: HttpClient> std::ops::Deref for VcrHandle<C> {
+    type Target = VcrClient<C>;
+
+    fn deref(&self) -> &VcrClient<C> {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<C: HttpClient> HttpClient for VcrHandle<C> {
+    async fn send(&self, req: Request) -> Result<Response, Error> {
+        self.0.send(req).await
+    }
+
+    /// Only possible while this is the client's only handle, since the config is
+    /// shared with every clone
+    fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        match Arc::get_mut(&mut self.0) {
+            Some(client) => client.set_config(config),
+            None => Err(Error::from_str(
+                500,
+                "Can't change the config of a VcrClient shared by other handles",
+            )),
+        }
+    }
+
+    fn config(&self) -> &Config {
+        self.0.config()
+    }
+}
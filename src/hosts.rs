@@ -0,0 +1,141 @@
+use crate::cassette::Cassette;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+
+/// An origin seen in recorded traffic
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct RecordedHost {
+    pub scheme: String,
+    pub host: String,
+    pub port: u16,
+}
+
+/// Maps the hosts in a cassette to a local listener that serves the replayed traffic,
+/// so applications with hard-coded absolute URLs can be redirected without code changes.
+///
+/// The catalog can be rendered as a hosts-file snippet (for listeners bound to the
+/// recorded ports) or as a proxy auto-config (PAC) file that sends recorded hosts to
+/// the listener and everything else direct.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostCatalog {
+    /// Address of the local listener, e.g. `127.0.0.1:8080`
+    pub listener: String,
+    pub hosts: Vec<RecordedHost>,
+}
+
+impl HostCatalog {
+    pub fn from_cassette(cassette: &Cassette, listener: impl Into<String>) -> Self {
+        let hosts: BTreeSet<RecordedHost> = cassette
+            .interactions
+            .iter()
+            .filter_map(|interaction| url::Url::parse(&interaction.request.url).ok())
+            .filter_map(|url| {
+                Some(RecordedHost {
+                    scheme: url.scheme().to_string(),
+                    host: url.host_str()?.to_string(),
+                    port: url.port_or_known_default()?,
+                })
+            })
+            .collect();
+
+        Self {
+            listener: listener.into(),
+            hosts: hosts.into_iter().collect(),
+        }
+    }
+
+    /// Unique host names, without scheme or port
+    pub fn host_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.hosts.iter().map(|h| h.host.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    fn listener_ip(&self) -> &str {
+        self.listener
+            .rsplit_once(':')
+            .map(|(ip, _)| ip)
+            .unwrap_or(&self.listener)
+            .trim_matches(|c| c == '[' || c == ']')
+    }
+
+    /// `/etc/hosts` lines pointing every recorded host at the listener's IP
+    pub fn hosts_file(&self) -> String {
+        let ip = self.listener_ip();
+        let mut out = String::from("# Generated by http-client-vcr from recorded traffic\n");
+        for host in self.host_names() {
+            // Literal IPs can't be redirected through the hosts file
+            if host.parse::<std::net::IpAddr>().is_err() {
+                let _ = writeln!(out, "{ip}\t{host}");
+            }
+        }
+        out
+    }
+
+    /// A PAC file routing recorded hosts through the listener as an HTTP proxy
+    pub fn pac_file(&self) -> String {
+        let mut out = String::from(
+            "// Generated by http-client-vcr from recorded traffic\nfunction FindProxyForURL(url, host) {\n",
+        );
+        for host in self.host_names() {
+            let _ = writeln!(
+                out,
+                "    if (host == \"{host}\") return \"PROXY {}\";",
+                self.listener
+            );
+        }
+        out.push_str("    return \"DIRECT\";\n}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+
+    #[tokio::test]
+    async fn test_catalog_from_cassette() {
+        let mut cassette = Cassette::new();
+        for url in [
+            "https://api.example.com/a",
+            "https://api.example.com/b",
+            "http://auth.example.com:8080/login",
+        ] {
+            let request = SerializableRequest {
+                method: "GET".to_string(),
+                url: url.to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            };
+            let response = SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            };
+            cassette
+                .record_interaction(request, response)
+                .await
+                .unwrap();
+        }
+
+        let catalog = HostCatalog::from_cassette(&cassette, "127.0.0.1:9000");
+        assert_eq!(catalog.hosts.len(), 2);
+        assert_eq!(
+            catalog.host_names(),
+            vec!["api.example.com", "auth.example.com"]
+        );
+        assert!(catalog
+            .hosts_file()
+            .contains("127.0.0.1\tapi.example.com\n"));
+        assert!(catalog
+            .pac_file()
+            .contains("if (host == \"auth.example.com\") return \"PROXY 127.0.0.1:9000\";"));
+    }
+}
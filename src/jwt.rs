@@ -0,0 +1,235 @@
+use crate::filter::Filter;
+use crate::placeholder::PlaceholderKind;
+use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde_json::Value;
+use sha2::Sha256;
+
+/// Signature used for redacted tokens when no signing key is configured
+pub const JWT_SIGNATURE_PLACEHOLDER: &str = "FILTERED";
+
+/// Redacts JSON Web Tokens while keeping them structurally valid.
+///
+/// The header and claim structure are preserved so client code that decodes the
+/// token (to check `exp`, read scopes, etc.) keeps working. By default identifying
+/// claims such as `sub` and `email` are replaced and the signature is swapped for a
+/// placeholder; with `with_signing_key` the token is re-signed as HS256 instead.
+#[derive(Debug)]
+pub struct JwtFilter {
+    pattern: Regex,
+    redacted_claims: Vec<String>,
+    signing_key: Option<Vec<u8>>,
+    replacement: Option<String>,
+}
+
+impl JwtFilter {
+    pub fn new() -> Self {
+        Self {
+            pattern: Regex::new(r"eyJ[A-Za-z0-9_-]*\.eyJ[A-Za-z0-9_-]*\.[A-Za-z0-9_-]*")
+                .expect("JWT pattern is valid"),
+            redacted_claims: [
+                "sub",
+                "email",
+                "name",
+                "given_name",
+                "family_name",
+                "preferred_username",
+                "phone_number",
+                "upn",
+                "oid",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            signing_key: None,
+            replacement: None,
+        }
+    }
+
+    /// Also redact this claim
+    pub fn redact_claim(mut self, claim: impl Into<String>) -> Self {
+        self.redacted_claims.push(claim.into());
+        self
+    }
+
+    /// Replace the set of redacted claims
+    pub fn with_redacted_claims<I, S>(mut self, claims: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.redacted_claims = claims.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Value string claims are replaced with, instead of a typed placeholder such as
+    /// `[FILTERED:email]`
+    pub fn with_replacement(mut self, replacement: impl Into<String>) -> Self {
+        self.replacement = Some(replacement.into());
+        self
+    }
+
+    /// Re-sign redacted tokens with HS256 using a test key, so clients that verify
+    /// signatures can be configured with the same key
+    pub fn with_signing_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.signing_key = Some(key.into());
+        self
+    }
+
+    /// Redact a single token, returning `None` if it doesn't decode as a JWT
+    pub fn redact_token(&self, token: &str) -> Option<String> {
+        let mut parts = token.splitn(3, '.');
+        let header = parts.next()?;
+        let payload = parts.next()?;
+
+        let mut header: Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).ok()?).ok()?;
+        let mut claims: Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+
+        if let Value::Object(claims) = &mut claims {
+            for claim in &self.redacted_claims {
+                if let Some(value) = claims.get_mut(claim) {
+                    *value = match value {
+                        Value::Number(_) => Value::from(0),
+                        Value::Bool(_) => value.clone(),
+                        Value::String(text) => Value::String(self.replacement_for(claim, text)),
+                        _ => Value::String(
+                            self.replacement
+                                .clone()
+                                .unwrap_or_else(|| PlaceholderKind::Any.placeholder()),
+                        ),
+                    };
+                }
+            }
+        }
+
+        if self.signing_key.is_some() {
+            if let Value::Object(header) = &mut header {
+                header.insert("alg".to_string(), Value::String("HS256".to_string()));
+            }
+        }
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).ok()?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).ok()?)
+        );
+
+        let signature = match &self.signing_key {
+            Some(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).ok()?;
+                mac.update(signing_input.as_bytes());
+                URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+            }
+            None => JWT_SIGNATURE_PLACEHOLDER.to_string(),
+        };
+
+        Some(format!("{signing_input}.{signature}"))
+    }
+
+    fn replacement_for(&self, claim: &str, value: &str) -> String {
+        self.replacement
+            .clone()
+            .unwrap_or_else(|| PlaceholderKind::for_field(claim, value).placeholder())
+    }
+
+    fn filter_text<T: AsRef<str> + From<String>>(&self, text: &mut T) {
+        if !self.pattern.is_match(text.as_ref()) {
+            return;
+        }
+
+        *text = self
+            .pattern
+            .replace_all(text.as_ref(), |caps: &regex::Captures| {
+                let token = &caps[0];
+                self.redact_token(token)
+                    .unwrap_or_else(|| token.to_string())
+            })
+            .into_owned()
+            .into();
+    }
+
+    fn filter_headers(&self, headers: &mut Headers) {
+        for values in headers.values_mut() {
+            for value in values.iter_mut() {
+                self.filter_text(value);
+            }
+        }
+    }
+}
+
+impl Default for JwtFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Filter for JwtFilter {
+    fn filter_request(&self, request: &mut SerializableRequest) {
+        self.filter_text(&mut request.url);
+        self.filter_headers(&mut request.headers);
+        if let Some(body) = &mut request.body {
+            self.filter_text(body);
+        }
+    }
+
+    fn filter_response(&self, response: &mut SerializableResponse) {
+        self.filter_headers(&mut response.headers);
+        if let Some(body) = &mut response.body {
+            self.filter_text(body);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(claims: &str) -> String {
+        format!(
+            "{}.{}.c2lnbmF0dXJl",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#),
+            URL_SAFE_NO_PAD.encode(claims)
+        )
+    }
+
+    fn decode_claims(token: &str) -> Value {
+        let payload = token.split('.').nth(1).unwrap();
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_redacts_claims_and_keeps_expiry() {
+        let filter = JwtFilter::new();
+        let mut header = format!(
+            "Bearer {}",
+            token(r#"{"sub":"user-123","email":"a@b.c","exp":1700000000}"#)
+        );
+
+        filter.filter_text(&mut header);
+
+        let redacted = header.strip_prefix("Bearer ").unwrap();
+        assert!(redacted.ends_with(".FILTERED"));
+        let claims = decode_claims(redacted);
+        assert_eq!(claims["sub"], "[FILTERED]");
+        assert_eq!(claims["email"], "[FILTERED:email]");
+        assert_eq!(claims["exp"], 1700000000);
+    }
+
+    #[test]
+    fn test_resigns_with_test_key() {
+        let filter = JwtFilter::new().with_signing_key("test-key");
+        let redacted = filter.redact_token(&token(r#"{"sub":"x"}"#)).unwrap();
+
+        let (signing_input, signature) = redacted.rsplit_once('.').unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"test-key").unwrap();
+        mac.update(signing_input.as_bytes());
+        assert_eq!(
+            URL_SAFE_NO_PAD.decode(signature).unwrap(),
+            mac.finalize().into_bytes().to_vec()
+        );
+    }
+}
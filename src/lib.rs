@@ -1,27 +1,148 @@
+use crate::background::SaveSequence;
+use crate::connection::{connection_key, hold_until_read, ConnectionGate};
+use crate::diagnostics::NoMatchDiagnostics;
+use crate::events::EventHandlers;
+#[cfg(feature = "fs")]
+use crate::lock::SessionLock;
+use crate::match_index::MatchIndex;
+use crate::mode_override::{ModeOverrideGuard, ModeOverrides};
+use crate::pacing::LatencyProfile;
+use crate::record_hook::run_record_hooks;
+use crate::recording_guard::RecordingGuard;
+use crate::redirect::RedirectHop;
+use crate::replay_transformer::run_replay_transformers;
+use crate::response_template::TemplateContext;
+use crate::streaming::StreamCapture;
+use async_lock::{Mutex, RwLock};
 use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
 use http_client::{Error, HttpClient, Request, Response};
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
+#[cfg(any(feature = "reqwest", feature = "surf", feature = "isahc"))]
+pub mod adapters;
+mod background;
+mod body_text;
 mod cassette;
+mod cassette_path;
+mod config;
+mod connection;
+mod contract;
+mod cookie;
+mod diagnostics;
+mod encoding;
+mod environment;
+mod events;
+mod fault;
+#[cfg(feature = "fs")]
+mod filesystem;
 mod filter;
 mod form_data;
+mod global;
+mod handle;
+mod hosts;
+mod jwt;
+mod limits;
+#[cfg(feature = "fs")]
+mod lock;
+mod match_index;
 mod matcher;
+mod migration;
+mod mode_override;
 mod noop_client;
+mod pacing;
+mod placeholder;
+mod presets;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod record_hook;
+mod recording_guard;
+mod redirect;
+mod replay_transformer;
+mod response_template;
+mod runtime;
+#[cfg(feature = "s3")]
+mod s3;
 mod serializable;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod storage;
+mod streaming;
+mod template;
+#[cfg(feature = "fs")]
+pub mod testing;
+mod time_shift;
+#[cfg(feature = "tower")]
+mod tower;
+mod transport_error;
+mod upload;
 mod utils;
 
-pub use cassette::{Cassette, CassetteFormat, Interaction};
+pub use background::SaveHandle;
+pub use body_text::BodyText;
+pub use cassette::{
+    BodyCompression, Cassette, CassetteFormat, CassetteMetadata, Interaction, InteractionStream,
+    Rotation, SCENARIO_STARTED,
+};
+pub use cassette_path::{current_test_name, TEST_NAME_ENV};
+pub use config::{CliConfig, FilterProfile, MatcherProfile, VcrConfig, CONFIG_FILE_NAME};
+pub use connection::{ConnectionReplay, ReplayOrdering};
+pub use contract::LockstepClient;
+pub use cookie::Cookie;
+pub use encoding::ContentEncoding;
+pub use environment::{capture_environment, is_secret_env_name};
+pub use events::VcrEvent;
+pub use fault::{Fault, FaultInjector};
+#[cfg(feature = "fs")]
+pub use filesystem::{DirectoryStorage, FileStorage, FilesystemStorage};
 pub use filter::{
-    BodyFilter, CustomFilter, Filter, FilterChain, HeaderFilter, SmartFormFilter, UrlFilter,
+    BodyFilter, CustomFilter, ErrorBodyFilter, Filter, FilterChain, FilterEntry, FilterScope,
+    FilterStage, HeaderFilter, PlaceholderFilter, SensitiveDataFilter, SmartFormFilter, UrlFilter,
+    UrlRewriteFilter,
 };
 pub use form_data::{
-    analyze_form_data, filter_form_data, find_credential_fields, parse_form_data, FormDataAnalysis,
+    analyze_form_data, filter_form_data, filter_multipart_data, find_credential_fields,
+    multipart_boundary, parse_form_data, redact_form_data, redact_multipart_data, FormDataAnalysis,
+    MultipartBody, MultipartPart,
 };
-pub use matcher::{DefaultMatcher, ExactMatcher, RequestMatcher};
+pub use global::{global, wrap, GlobalVcr, GlobalVcrClient};
+pub use handle::VcrHandle;
+pub use hosts::{HostCatalog, RecordedHost};
+#[cfg(feature = "macros")]
+pub use http_client_vcr_macros::vcr_test;
+pub use jwt::{JwtFilter, JWT_SIGNATURE_PLACEHOLDER};
+pub use limits::{CassetteLimits, LimitAction};
+pub use matcher::{DefaultMatcher, ExactMatcher, RequestMatcher, TemplateMatcher};
+pub use migration::CURRENT_FORMAT_VERSION;
 pub use noop_client::{NoOpClient, PanickingNoOpClient};
-pub use serializable::{SerializableRequest, SerializableResponse};
+pub use pacing::{ChunkedReplay, LatencyMode};
+pub use placeholder::{
+    contains_placeholder, matches_with_placeholders, redact, PlaceholderKind, FILTERED_PLACEHOLDER,
+};
+pub use presets::Presets;
+#[cfg(feature = "protobuf")]
+pub use protobuf::{ProtobufCodec, ProtobufFilter, ProtobufMatcher};
+pub use record_hook::RecordHook;
+pub use recording_guard::SharedCassetteAction;
+pub use replay_transformer::ReplayTransformer;
+#[cfg(feature = "s3")]
+pub use s3::S3Storage;
+pub use serializable::{Headers, SerializableRequest, SerializableResponse};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+pub use storage::{CassetteStorage, InteractionIter, MemoryStorage};
+pub use streaming::StreamChunk;
+pub use template::{group_by_template, template_for, UrlTemplate};
+pub use time_shift::TimeShift;
+#[cfg(feature = "tower")]
+pub use tower::{VcrLayer, VcrService};
+pub use transport_error::{RecordedError, TransportErrorKind};
+pub use upload::{
+    is_presigned_url, PresignedUploadFilter, PRESIGNED_PLACEHOLDER, PRESIGNED_QUERY_PARAMS,
+};
 pub use utils::CassetteAnalysis;
 
 #[derive(Debug, Clone)]
@@ -33,16 +154,410 @@ pub enum VcrMode {
     Filter,
 }
 
+impl std::str::FromStr for VcrMode {
+    type Err = String;
+
+    /// Parse a mode name, case-insensitively. `all` is accepted for `Record` and `off`
+    /// for `None`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "record" | "all" => Ok(VcrMode::Record),
+            "replay" => Ok(VcrMode::Replay),
+            "once" => Ok(VcrMode::Once),
+            "none" | "off" => Ok(VcrMode::None),
+            "filter" => Ok(VcrMode::Filter),
+            other => Err(format!(
+                "Unknown VCR mode {other:?}; expected record, replay, once, none or filter"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for VcrMode {
+    /// The lowercase mode name `from_str` parses
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            VcrMode::Record => "record",
+            VcrMode::Replay => "replay",
+            VcrMode::Once => "once",
+            VcrMode::None => "none",
+            VcrMode::Filter => "filter",
+        })
+    }
+}
+
+/// Request header overriding the client's mode for that request alone, e.g.
+/// `x-vcr: bypass` to always send it to the inner client. Takes a mode name, or
+/// `bypass` for `VcrMode::None`, and is removed before the request is sent or
+/// recorded. A `VcrMode` inserted as a request extension works the same way.
+pub const MODE_HEADER: &str = "x-vcr";
+
+/// What Record mode does with the interactions already in the cassette
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordBehavior {
+    /// Discard them when the first new interaction is recorded, so the cassette holds
+    /// only the latest recording
+    #[default]
+    Replace,
+    /// Keep them and add the new interactions after them
+    Append,
+}
+
+/// Where a client's cassette lives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Persistence {
+    /// Loaded from and saved to the cassette path
+    #[default]
+    Disk,
+    /// Kept in memory only: nothing is loaded or saved, not even on drop. Read the
+    /// recorded interactions back with `VcrClient::interactions` or `eject_cassette`.
+    InMemory,
+}
+
+/// When a recording client flushes its cassette to storage, on top of saving on drop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoSave {
+    /// After every `n` recorded interactions
+    Interactions(usize),
+    /// After recording an interaction once this long has passed since the last save
+    Interval(std::time::Duration),
+}
+
+impl From<usize> for AutoSave {
+    fn from(interactions: usize) -> Self {
+        AutoSave::Interactions(interactions)
+    }
+}
+
+impl From<std::time::Duration> for AutoSave {
+    fn from(interval: std::time::Duration) -> Self {
+        AutoSave::Interval(interval)
+    }
+}
+
+/// Which interactions a client has already replayed, from
+/// `VcrClient::snapshot_replay_state`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayState {
+    used_interactions: std::collections::HashSet<usize>,
+    used_shared_interactions: std::collections::HashSet<(usize, usize)>,
+    served_connections: std::collections::HashSet<usize>,
+    scenario_state: Option<String>,
+}
+
+impl ReplayState {
+    /// Indices of the replayed interactions of the client's cassette
+    pub fn used_interactions(&self) -> &std::collections::HashSet<usize> {
+        &self.used_interactions
+    }
+
+    /// The client's scenario state
+    pub fn scenario_state(&self) -> &str {
+        self.scenario_state.as_deref().unwrap_or(SCENARIO_STARTED)
+    }
+}
+
+/// Request header listing tags, separated by commas, for the interaction recorded
+/// from the request. It is removed from the recorded request.
+pub const TAGS_HEADER: &str = "x-vcr-tags";
+
+/// What a client did with its cassette, returned by `VcrClient::playback_stats` and
+/// `VcrClient::finalize`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaybackStats {
+    /// Interactions in the cassette when it was finalized
+    pub interactions: usize,
+    /// Interactions recorded by this client
+    pub recorded: usize,
+    /// Interactions replayed from the cassette
+    pub replayed: usize,
+    /// Interactions replayed from additional cassettes
+    pub replayed_shared: usize,
+    /// Indices of previously recorded interactions that were never replayed
+    pub unused: Vec<usize>,
+    /// How many times each interaction of the cassette was replayed, by index
+    pub play_counts: Vec<usize>,
+    /// Requests that matched nothing and were forwarded to the inner client, like
+    /// `GET https://example.com/users`
+    pub passed_through: Vec<String>,
+    /// Whether the cassette was saved
+    pub saved: bool,
+}
+
 #[derive(Debug)]
-pub struct VcrClient {
-    inner: Box<dyn HttpClient>,
-    cassette: Arc<Mutex<Cassette>>,
+pub struct VcrClient<C: HttpClient = Box<dyn HttpClient>> {
+    // Only taken by `into_inner`, which consumes the client
+    inner: Option<C>,
+    // Built without an inner client, so `inner` is a `NoOpClient` standing in
+    placeholder_inner: bool,
+    cassette: Arc<RwLock<Cassette>>,
+    // Read-only cassettes searched after the topmost one, in order
+    additional_cassettes: Vec<Cassette>,
+    used_shared_interactions: Mutex<std::collections::HashSet<(usize, usize)>>,
+    // Lookup indexes over the topmost and additional cassettes, built on first use
+    match_index: std::sync::Mutex<MatchIndex>,
+    shared_match_indexes: std::sync::Mutex<Vec<MatchIndex>>,
     mode: VcrMode,
+    // Temporary modes from `with_mode`, which take precedence over `mode`
+    mode_overrides: std::sync::Mutex<ModeOverrides>,
+    record_behavior: RecordBehavior,
+    persistence: Persistence,
+    // Relative paths given to `use_cassette` are resolved against this
+    cassette_root: Option<PathBuf>,
+    storage: Arc<dyn CassetteStorage>,
+    // Set with `lazy_load`, when interactions are fetched from storage on a miss
+    lazy_lookups: Option<Mutex<LazyLookups>>,
+    auto_save: Option<AutoSave>,
+    // Interactions recorded and time of the last save, for auto-save
+    auto_save_state: Mutex<(usize, runtime::Instant)>,
+    save_on_drop: bool,
+    // Save off the request path, on blocking tasks
+    background_save: bool,
+    pending_saves: Mutex<Vec<SaveHandle>>,
+    save_sequence: Arc<SaveSequence>,
+    // Held for the client's lifetime with `exclusive_lock`
+    #[cfg(feature = "fs")]
+    session_lock: Option<SessionLock>,
+    // Registers the cassette as being recorded by this client, for `on_shared_cassette`
+    recording_guard: Option<RecordingGuard>,
+    recorded_interactions: std::sync::atomic::AtomicUsize,
     matcher: Box<dyn RequestMatcher>,
     filter_chain: FilterChain,
     recording_started: Arc<Mutex<bool>>,
     // Track which interactions have been used in replay mode (by index)
     used_interactions: Arc<Mutex<std::collections::HashSet<usize>>>,
+    // Times each interaction was replayed, kept when the replay state is reset
+    play_counts: std::sync::Mutex<std::collections::HashMap<usize, usize>>,
+    // Only interactions with this tag are replayed, and new ones get it
+    tag_scope: std::sync::RwLock<Option<String>>,
+    // Scenario state interactions' `requires_state` is checked against, `None` until
+    // set explicitly or by a played interaction, which means `SCENARIO_STARTED`
+    scenario_state: std::sync::RwLock<Option<String>>,
+    chunked_replay: Option<ChunkedReplay>,
+    // Expand `{{...}}` directives in replayed responses
+    response_templating: bool,
+    // Add the URL template of each recorded request to the cassette metadata
+    infer_url_templates: bool,
+    content_encoding: ContentEncoding,
+    // Follow redirects here, at most this many per request, recording each hop
+    follow_redirects: Option<usize>,
+    // Record inner client errors as interactions instead of only returning them
+    record_errors: bool,
+    // Event streams still being read by callers, by the sequence of their request
+    stream_captures: std::sync::Mutex<Vec<(u64, StreamCapture)>>,
+    stream_replay_speed: f64,
+    time_shift: Option<TimeShift>,
+    latency: LatencyProfile,
+    connection_replay: Option<ConnectionReplay>,
+    connection_gate: ConnectionGate,
+    fault_injector: Option<FaultInjector>,
+    replay_validator: Option<ReplayValidator>,
+    // Requests to these hosts go straight to the inner client in every mode
+    ignored_hosts: Vec<String>,
+    record_predicate: Option<RecordPredicate>,
+    miss_behavior: MissBehavior,
+    // Environment variables captured into the cassette metadata when recording
+    env_snapshot: Vec<String>,
+    // Check for secrets that survived filtering before saving
+    strict_sanitization: Option<UnsanitizedAction>,
+    limits: CassetteLimits,
+    // What went over the limits, reported when saving with `LimitAction::Error`
+    limits_exceeded: std::sync::Mutex<Vec<String>>,
+    // Report unplayed interactions and forwarded misses when finalizing or dropping
+    strict_playback: bool,
+    passed_through: std::sync::Mutex<Vec<String>>,
+    finalized: std::sync::atomic::AtomicBool,
+    events: EventHandlers,
+    record_hooks: Vec<Box<dyn RecordHook>>,
+    replay_transformers: Vec<Box<dyn ReplayTransformer>>,
+}
+
+type ValidateFn = dyn Fn(&Interaction) -> Result<(), Error> + Send + Sync;
+
+/// Callback run against every replayed interaction before its response is returned
+pub struct ReplayValidator(Box<ValidateFn>);
+
+impl ReplayValidator {
+    pub fn new<F>(validate: F) -> Self
+    where
+        F: Fn(&Interaction) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        Self(Box::new(validate))
+    }
+}
+
+impl std::fmt::Debug for ReplayValidator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReplayValidator")
+    }
+}
+
+type RecordFn = dyn Fn(&SerializableRequest) -> bool + Send + Sync;
+
+type InnerClientFn = dyn FnOnce() -> Box<dyn HttpClient> + Send;
+
+/// Builds the inner client when the builder's mode turns out to need one
+struct InnerClientFactory(Box<InnerClientFn>);
+
+impl std::fmt::Debug for InnerClientFactory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("InnerClientFactory")
+    }
+}
+
+/// Decides whether a request is saved to the cassette when recording
+pub struct RecordPredicate(Box<RecordFn>);
+
+impl RecordPredicate {
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&SerializableRequest) -> bool + Send + Sync + 'static,
+    {
+        Self(Box::new(predicate))
+    }
+}
+
+impl std::fmt::Debug for RecordPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RecordPredicate")
+    }
+}
+
+/// What to do with a request that matched no recorded interaction
+pub enum MissAction {
+    /// Return this response instead
+    Respond(Box<Response>),
+    /// Send the request to the inner client, without recording it
+    Forward,
+    /// Fail the request with this error
+    Fail(Error),
+}
+
+type MissFn = dyn Fn(&Request) -> MissAction + Send + Sync;
+
+/// Callback deciding what happens to unmatched requests
+pub struct MissHandler(Box<MissFn>);
+
+impl MissHandler {
+    pub fn new<F>(handle: F) -> Self
+    where
+        F: Fn(&Request) -> MissAction + Send + Sync + 'static,
+    {
+        Self(Box::new(handle))
+    }
+}
+
+impl std::fmt::Debug for MissHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MissHandler")
+    }
+}
+
+/// How requests that match no recorded interaction are handled when replaying
+#[derive(Debug, Default)]
+pub enum MissBehavior {
+    /// Fail with a diagnostic listing the closest recorded interactions
+    #[default]
+    Error,
+    /// Send the request to the inner client, without recording it
+    Forward,
+    /// Like `Forward`, but log a warning for every miss and append it to `report`,
+    /// one `<cassette>\t<METHOD> <url>` line each, so a suite that is still being
+    /// moved onto cassettes can see what is missing. URLs are filtered like recorded ones.
+    /// Writing the report needs the `fs` feature.
+    PassthroughWithWarning { report: Option<PathBuf> },
+    /// Let a callback synthesize a response, forward the request or fail it
+    Handler(MissHandler),
+}
+
+impl MissBehavior {
+    /// Shorthand for `MissBehavior::Handler(MissHandler::new(handle))`
+    pub fn handler<F>(handle: F) -> Self
+    where
+        F: Fn(&Request) -> MissAction + Send + Sync + 'static,
+    {
+        MissBehavior::Handler(MissHandler::new(handle))
+    }
+
+    /// Shorthand for `MissBehavior::PassthroughWithWarning` reporting misses to `report`
+    #[cfg(feature = "fs")]
+    pub fn passthrough_with_warning(report: impl Into<PathBuf>) -> Self {
+        MissBehavior::PassthroughWithWarning {
+            report: Some(report.into()),
+        }
+    }
+}
+
+/// Append a line to the misses report, off the executor
+#[cfg(feature = "fs")]
+async fn append_to_miss_report(report: PathBuf, line: String) {
+    runtime::unblock(move || {
+        let appended = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&report)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, line.as_bytes()));
+        if let Err(e) = appended {
+            log::warn!("Failed to append to the VCR miss report {report:?}: {e}");
+        }
+    })
+    .await
+}
+
+#[cfg(not(feature = "fs"))]
+async fn append_to_miss_report(report: PathBuf, _line: String) {
+    log::warn!("Not appending to the VCR miss report {report:?}: it needs the fs feature");
+}
+
+/// Numbers live requests in the order they are sent, across all clients so a cassette
+/// handed from one client to another stays ordered
+static NEXT_REQUEST_SEQUENCE: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// When a live request was sent, for recording its timing and order
+struct RequestTiming {
+    started_at: time::OffsetDateTime,
+    started: runtime::Instant,
+    sequence: u64,
+}
+
+impl RequestTiming {
+    fn start() -> Self {
+        Self {
+            started_at: time::OffsetDateTime::now_utc(),
+            started: runtime::Instant::now(),
+            sequence: NEXT_REQUEST_SEQUENCE.fetch_add(1, std::sync::atomic::Ordering::SeqCst),
+        }
+    }
+}
+
+/// The requests a lazily loaded cassette was already searched for
+#[derive(Debug, Default)]
+struct LazyLookups {
+    // Method and URL of each request looked up in the storage
+    fetched: std::collections::HashSet<(String, String)>,
+    // Set once the cassette holds all of its interactions: a storage without lookups
+    // was loaded in full, or another cassette was inserted
+    complete: bool,
+}
+
+/// Load the cassette `storage` has at `path` on a blocking task, since storage calls
+/// block
+async fn load_cassette(
+    storage: &Arc<dyn CassetteStorage>,
+    path: &Path,
+) -> Result<Option<Cassette>, Error> {
+    let storage = Arc::clone(storage);
+    let path = path.to_path_buf();
+    runtime::unblock(move || storage.load(&path)).await
+}
+
+/// Where cassettes go when no storage is configured: the local filesystem, or memory
+/// when the `fs` feature is off
+fn default_storage() -> Box<dyn CassetteStorage> {
+    #[cfg(feature = "fs")]
+    return Box::new(FilesystemStorage);
+    #[cfg(not(feature = "fs"))]
+    return Box::new(MemoryStorage::new());
 }
 
 /// Duplicate a request while preserving the body.
@@ -72,140 +587,93 @@ async fn duplicate_request_with_body(mut req: Request) -> Result<(Request, Reque
 
 impl VcrClient {
     pub fn new(inner: Box<dyn HttpClient>, mode: VcrMode, cassette: Cassette) -> Self {
+        Self::with_inner(inner, mode, cassette)
+    }
+
+    pub fn builder<P: Into<PathBuf>>(cassette_path: P) -> VcrClientBuilder {
+        VcrClientBuilder::new(cassette_path)
+    }
+
+    /// Create a builder for a client whose cassette only lives in memory
+    pub fn in_memory() -> VcrClientBuilder {
+        VcrClientBuilder::in_memory()
+    }
+}
+
+impl<C: HttpClient> VcrClient<C> {
+    /// Create a client around an inner client of a concrete type, which stays
+    /// reachable through `inner` and `inner_mut` and is called without dynamic
+    /// dispatch
+    pub fn with_inner(inner: C, mode: VcrMode, cassette: Cassette) -> Self {
         Self {
-            inner,
-            cassette: Arc::new(Mutex::new(cassette)),
+            inner: Some(inner),
+            placeholder_inner: false,
+            cassette: Arc::new(RwLock::new(cassette)),
+            additional_cassettes: Vec::new(),
+            used_shared_interactions: Mutex::new(std::collections::HashSet::new()),
+            match_index: std::sync::Mutex::new(MatchIndex::default()),
+            shared_match_indexes: std::sync::Mutex::new(Vec::new()),
             mode,
+            mode_overrides: std::sync::Mutex::new(ModeOverrides::default()),
+            record_behavior: RecordBehavior::default(),
+            persistence: Persistence::Disk,
+            cassette_root: None,
+            storage: default_storage().into(),
+            lazy_lookups: None,
+            auto_save: None,
+            auto_save_state: Mutex::new((0, runtime::Instant::now())),
+            save_on_drop: true,
+            background_save: false,
+            pending_saves: Mutex::new(Vec::new()),
+            save_sequence: Arc::new(SaveSequence::default()),
+            #[cfg(feature = "fs")]
+            session_lock: None,
+            recording_guard: None,
+            recorded_interactions: std::sync::atomic::AtomicUsize::new(0),
             matcher: Box::new(DefaultMatcher::new()),
             filter_chain: FilterChain::new(),
             recording_started: Arc::new(Mutex::new(false)),
             used_interactions: Arc::new(Mutex::new(std::collections::HashSet::new())),
+            play_counts: std::sync::Mutex::new(std::collections::HashMap::new()),
+            tag_scope: std::sync::RwLock::new(None),
+            scenario_state: std::sync::RwLock::new(None),
+            chunked_replay: None,
+            response_templating: false,
+            infer_url_templates: false,
+            content_encoding: ContentEncoding::default(),
+            follow_redirects: None,
+            record_errors: false,
+            stream_captures: std::sync::Mutex::new(Vec::new()),
+            stream_replay_speed: 1.0,
+            time_shift: None,
+            latency: LatencyProfile::default(),
+            connection_replay: None,
+            connection_gate: ConnectionGate::default(),
+            fault_injector: None,
+            replay_validator: None,
+            ignored_hosts: Vec::new(),
+            record_predicate: None,
+            miss_behavior: MissBehavior::default(),
+            env_snapshot: Vec::new(),
+            strict_sanitization: None,
+            limits: CassetteLimits::default(),
+            limits_exceeded: std::sync::Mutex::new(Vec::new()),
+            strict_playback: false,
+            passed_through: std::sync::Mutex::new(Vec::new()),
+            finalized: std::sync::atomic::AtomicBool::new(false),
+            events: EventHandlers::default(),
+            record_hooks: Vec::new(),
+            replay_transformers: Vec::new(),
         }
     }
 
-    /// Synchronous version of directory save for use in Drop
-    fn save_cassette_as_directory_sync(
-        cassette: &Cassette,
-        path: &PathBuf,
-    ) -> Result<(), std::io::Error> {
-        use serde::Serialize;
-
-        // Create the cassette directory and bodies subdirectory
-        std::fs::create_dir_all(path)?;
-        let bodies_dir = path.join("bodies");
-        std::fs::create_dir_all(&bodies_dir)?;
-
-        // Create directory format structures for serialization
-        #[derive(Serialize)]
-        struct DirectoryInteraction {
-            request: DirectorySerializableRequest,
-            response: DirectorySerializableResponse,
-        }
-
-        #[derive(Serialize)]
-        struct DirectorySerializableRequest {
-            method: String,
-            url: String,
-            headers: std::collections::HashMap<String, Vec<String>>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            body_file: Option<String>,
-            version: String,
-        }
-
-        #[derive(Serialize)]
-        struct DirectorySerializableResponse {
-            status: u16,
-            headers: std::collections::HashMap<String, Vec<String>>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            body_file: Option<String>,
-            version: String,
-        }
-
-        let mut dir_interactions = Vec::new();
-
-        for (i, interaction) in cassette.interactions.iter().enumerate() {
-            let interaction_num = format!("{:03}", i + 1);
-
-            // Handle request body
-            let request_body_file = if let Some(ref body) = interaction.request.body {
-                if !body.is_empty() {
-                    let filename = format!("req_{interaction_num}.txt");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body)?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else if let Some(ref body_base64) = interaction.request.body_base64 {
-                if !body_base64.is_empty() {
-                    let filename = format!("req_{interaction_num}.b64");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body_base64)?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            // Handle response body
-            let response_body_file = if let Some(ref body) = interaction.response.body {
-                if !body.is_empty() {
-                    let filename = format!("resp_{interaction_num}.txt");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body)?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else if let Some(ref body_base64) = interaction.response.body_base64 {
-                if !body_base64.is_empty() {
-                    let filename = format!("resp_{interaction_num}.b64");
-                    let body_path = bodies_dir.join(&filename);
-                    std::fs::write(&body_path, body_base64)?;
-                    Some(filename)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-
-            let dir_interaction = DirectoryInteraction {
-                request: DirectorySerializableRequest {
-                    method: interaction.request.method.clone(),
-                    url: interaction.request.url.clone(),
-                    headers: interaction.request.headers.clone(),
-                    body_file: request_body_file,
-                    version: interaction.request.version.clone(),
-                },
-                response: DirectorySerializableResponse {
-                    status: interaction.response.status,
-                    headers: interaction.response.headers.clone(),
-                    body_file: response_body_file,
-                    version: interaction.response.version.clone(),
-                },
-            };
-
-            dir_interactions.push(dir_interaction);
-        }
-
-        // Write the interactions.yaml file
-        let interactions_yaml = serde_yaml::to_string(&dir_interactions)
-            .map_err(|e| std::io::Error::other(format!("Failed to serialize interactions: {e}")))?;
-
-        let interactions_file = path.join("interactions.yaml");
-        std::fs::write(&interactions_file, interactions_yaml)?;
-
-        Ok(())
-    }
-
-    /// Create a pristine response from extracted data, completely independent of VCR processing
+    /// Create a pristine response from extracted data, completely independent of VCR processing.
+    /// The body reads from `body` without copying it.
     fn create_pristine_response(
         status: http_types::StatusCode,
-        headers: &std::collections::HashMap<String, Vec<String>>,
-        body_content: Option<&str>,
+        headers: &Headers,
+        body: Option<http_types::Body>,
+        mime: Option<http_types::mime::Mime>,
     ) -> Response {
         let mut return_response = http_types::Response::new(status);
 
@@ -217,352 +685,2005 @@ impl VcrClient {
         }
 
         // Set the body if we have content
-        if let Some(body) = body_content {
+        if let Some(mut body) = body {
+            body.set_mime(mime);
             return_response.set_body(body);
         }
 
         return_response
     }
 
-    pub fn set_mode(&mut self, mode: VcrMode) {
-        self.mode = mode;
+    /// The client requests are sent to when they aren't replayed
+    pub fn inner(&self) -> &C {
+        self.inner
+            .as_ref()
+            .expect("the inner client is only taken by into_inner")
     }
 
-    pub fn set_matcher(&mut self, matcher: Box<dyn RequestMatcher>) {
-        self.matcher = matcher;
+    /// Mutable access to the inner client, e.g. to change its configuration
+    pub fn inner_mut(&mut self) -> &mut C {
+        self.inner
+            .as_mut()
+            .expect("the inner client is only taken by into_inner")
     }
 
-    pub fn set_filter_chain(&mut self, filter_chain: FilterChain) {
-        self.filter_chain = filter_chain;
+    /// Drop the client, saving the cassette as dropping it would, and return the
+    /// inner client
+    pub fn into_inner(mut self) -> C {
+        self.inner
+            .take()
+            .expect("the inner client is only taken by into_inner")
     }
 
-    pub fn add_filter(&mut self, filter: Box<dyn Filter>) {
-        self.filter_chain = std::mem::take(&mut self.filter_chain).add_filter(filter);
+    pub fn set_mode(&mut self, mode: VcrMode) {
+        self.mode = mode;
     }
 
-    async fn find_match<'a>(
-        &self,
-        request: &Request,
-        cassette: &'a Cassette,
-    ) -> Option<(usize, &'a Interaction)> {
-        let used_interactions = self.used_interactions.lock().await;
-
-        // Create a filtered copy of the request for matching against stored filtered interactions
-        if let Ok(mut filtered_request) = SerializableRequest::from_request(request.clone()).await {
-            self.filter_chain.filter_request(&mut filtered_request);
-
-            cassette
-                .interactions
-                .iter()
-                .enumerate()
-                .find(|(index, interaction)| {
-                    !used_interactions.contains(index)
-                        && self
-                            .matcher
-                            .matches_serializable(&filtered_request, &interaction.request)
-                })
-        } else {
-            // Fallback to matching against stored interactions directly
-            cassette
-                .interactions
-                .iter()
-                .enumerate()
-                .find(|(index, interaction)| {
-                    !used_interactions.contains(index)
-                        && self.matcher.matches(request, &interaction.request)
-                })
-        }
+    /// The client's own mode, ignoring any temporary one from `with_mode`
+    pub fn mode(&self) -> &VcrMode {
+        &self.mode
     }
 
-    /// Find similar URLs using Levenshtein distance when exact match fails
-    async fn find_similar_urls(
-        &self,
-        request: &Request,
-        cassette: &Cassette,
-    ) -> Vec<(String, usize)> {
-        let request_url = request.url().to_string();
-        let mut similarities = Vec::new();
-
-        for interaction in &cassette.interactions {
-            let recorded_url = &interaction.request.url;
-            let distance = levenshtein::levenshtein(&request_url, recorded_url);
-            similarities.push((recorded_url.clone(), distance));
-        }
-
-        // Sort by distance (smaller distance = more similar)
-        similarities.sort_by_key(|(_, distance)| *distance);
-
-        // Return only the top 5 most similar URLs
-        similarities.into_iter().take(5).collect()
+    /// The mode requests are sent in right now, taking `with_mode` into account
+    pub fn current_mode(&self) -> VcrMode {
+        self.mode_overrides
+            .lock()
+            .unwrap()
+            .current()
+            .unwrap_or(&self.mode)
+            .clone()
     }
 
-    /// Generate enhanced error message with URL similarity information
-    async fn generate_no_match_error(&self, request: &Request, mode_description: &str) -> Error {
-        let cassette = self.cassette.lock().await;
-        let request_url = request.url().to_string();
-        let request_method = request.method().to_string();
-
-        let error_msg = {
-            let mut msg = format!(
-                "No matching interaction found in cassette ({mode_description})\n\nRequest details:\n  Method: {request_method}\n  URL: {request_url}"
-            );
+    /// Send requests in `mode` while `scenario` runs, then go back to the previous
+    /// mode, even if `scenario` panics or is cancelled. Unlike `set_mode` this works
+    /// through a shared reference, e.g. on a client in an `Arc`.
+    ///
+    /// The mode applies to every request the client sends meanwhile, including those
+    /// from other tasks. Blocks can nest or overlap; the most recently started one
+    /// that is still running decides the mode. Interactions recorded in a temporary
+    /// Record mode are added to the cassette rather than replacing it. A client built
+    /// without an `inner_client` rejects requests in a mode that would send them.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{VcrClient, VcrMode};
+    /// # use http_client::HttpClient;
+    /// # async fn example(client: std::sync::Arc<VcrClient>) -> Result<(), http_types::Error> {
+    /// let url = http_types::Url::parse("https://example.com/new-endpoint")?;
+    /// let response = client
+    ///     .with_mode(VcrMode::Record, async |client| {
+    ///         client.send(http_types::Request::new(http_types::Method::Get, url)).await
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn with_mode<F, T>(&self, mode: VcrMode, scenario: F) -> T
+    where
+        F: AsyncFnOnce(&VcrClient<C>) -> T,
+    {
+        let _guard = ModeOverrideGuard::push(&self.mode_overrides, mode);
+        scenario(self).await
+    }
 
-            if cassette.interactions.is_empty() {
-                msg.push_str("\n\nCassette is empty - no recorded interactions available.");
-            } else {
-                msg.push_str(&format!(
-                    "\n\nCassette contains {} recorded interactions.",
-                    cassette.interactions.len()
-                ));
+    pub fn set_record_behavior(&mut self, record_behavior: RecordBehavior) {
+        self.record_behavior = record_behavior;
+    }
 
-                // Find similar URLs
-                let similar_urls = self.find_similar_urls(request, &cassette).await;
+    pub fn set_persistence(&mut self, persistence: Persistence) {
+        self.persistence = persistence;
+    }
 
-                if !similar_urls.is_empty() {
-                    msg.push_str("\n\nMost similar recorded URLs (by Levenshtein distance):");
-                    for (i, (url, distance)) in similar_urls.iter().enumerate() {
-                        msg.push_str(&format!("\n  {}. {} (distance: {})", i + 1, url, distance));
-                    }
-                }
+    pub fn set_cassette_root(&mut self, cassette_root: Option<PathBuf>) {
+        self.cassette_root = cassette_root;
+    }
 
-                // Show unique methods in cassette
-                let mut methods: Vec<String> = cassette
-                    .interactions
-                    .iter()
-                    .map(|i| i.request.method.clone())
-                    .collect();
-                methods.sort();
-                methods.dedup();
+    pub fn set_storage(&mut self, storage: Box<dyn CassetteStorage>) {
+        self.storage = Arc::from(storage);
+    }
 
-                msg.push_str(&format!("\n\nRecorded methods: {}", methods.join(", ")));
-            }
+    pub fn set_auto_save(&mut self, auto_save: Option<AutoSave>) {
+        self.auto_save = auto_save;
+    }
 
-            msg
-        };
+    /// Whether a modified cassette is saved, best effort, when the client is dropped
+    pub fn set_save_on_drop(&mut self, save_on_drop: bool) {
+        self.save_on_drop = save_on_drop;
+    }
 
-        // Convert to a static string by leaking memory (acceptable for error cases)
-        Error::from_str(404, Box::leak(error_msg.into_boxed_str()))
+    /// Run auto-saves on background tasks instead of in the request path
+    pub fn set_background_save(&mut self, background_save: bool) {
+        self.background_save = background_save;
     }
 
-    pub async fn save_cassette(&self) -> Result<(), Error> {
-        let cassette = self.cassette.lock().await;
-        cassette.save_to_file().await
+    /// Cassettes searched, in order, when the topmost cassette has no match. New
+    /// interactions are only ever recorded into the topmost cassette.
+    pub fn set_additional_cassettes(&mut self, cassettes: Vec<Cassette>) {
+        self.shared_match_indexes =
+            std::sync::Mutex::new(cassettes.iter().map(|_| MatchIndex::default()).collect());
+        self.additional_cassettes = cassettes;
     }
 
-    /// Apply filters to all interactions in the cassette
-    /// This modifies the cassette in-place by applying the configured filter chain to all interactions
-    pub async fn apply_filters_to_cassette(&self) -> Result<(), Error> {
-        let mut cassette = self.cassette.lock().await;
+    pub fn set_matcher(&mut self, matcher: Box<dyn RequestMatcher>) {
+        self.matcher = matcher;
+        self.invalidate_match_indexes();
+    }
 
-        // Apply filters to each interaction
-        for interaction in &mut cassette.interactions {
-            self.filter_chain.filter_request(&mut interaction.request);
-            self.filter_chain.filter_response(&mut interaction.response);
+    /// Drop the lookup indexes after the matcher changed or interactions were
+    /// modified in place
+    fn invalidate_match_indexes(&self) {
+        self.match_index.lock().unwrap().invalidate();
+        for index in self.shared_match_indexes.lock().unwrap().iter_mut() {
+            index.invalidate();
         }
-
-        log::debug!(
-            "Applied filters to {} interactions",
-            cassette.interactions.len()
-        );
-        Ok(())
     }
 
-    /// Apply filters to all interactions in the cassette and save the filtered version
-    pub async fn filter_and_save_cassette(&self) -> Result<(), Error> {
-        self.apply_filters_to_cassette().await?;
-        self.save_cassette().await
+    pub fn set_filter_chain(&mut self, filter_chain: FilterChain) {
+        self.filter_chain = filter_chain;
     }
 
-    pub fn builder<P: Into<PathBuf>>(cassette_path: P) -> VcrClientBuilder {
-        VcrClientBuilder::new(cassette_path)
+    /// When recordings are appended to the cassette, let filters continue from what
+    /// its earlier recordings left in its metadata, e.g. placeholder numbers
+    async fn resume_filters(&self) {
+        if matches!(self.mode, VcrMode::Record) && self.record_behavior == RecordBehavior::Append {
+            self.filter_chain
+                .resume(&self.cassette.read().await.metadata);
+        }
     }
 
-    // Helper methods for each VCR mode
+    pub fn add_filter(&mut self, filter: Box<dyn Filter>) {
+        self.filter_chain = std::mem::take(&mut self.filter_chain).add_filter(filter);
+    }
 
-    /// Common logic for recording a request/response and returning the pristine response
-    async fn record_and_return_response(
-        &self,
-        req_for_recording: Request,
-        response: &mut Response,
-    ) -> Result<Response, Error> {
-        // IMMEDIATELY create a pristine copy for the caller before any VCR processing
-        let status = response.status();
-        let version = format!("{:?}", response.version());
+    /// Replay recorded bodies in chunks, or `None` to return them in one piece
+    pub fn set_chunked_replay(&mut self, chunked_replay: Option<ChunkedReplay>) {
+        self.chunked_replay = chunked_replay;
+    }
 
-        let mut headers = std::collections::HashMap::new();
-        for (name, values) in response.iter() {
-            let header_values: Vec<String> =
-                values.iter().map(|v| v.as_str().to_string()).collect();
-            headers.insert(name.as_str().to_string(), header_values);
-        }
+    pub fn set_response_templating(&mut self, response_templating: bool) {
+        self.response_templating = response_templating;
+    }
 
-        // Read the body once - this consumes it from the original response
-        let body_string = match response.body_string().await {
-            Ok(body) if !body.is_empty() => Some(body),
-            Ok(_) => None, // Empty body
-            Err(e) => {
-                // If we can't read the body, log it but don't fail the whole request
-                eprintln!("Warning: Failed to read response body for VCR: {e}");
-                None
-            }
-        };
+    pub fn set_infer_url_templates(&mut self, infer_url_templates: bool) {
+        self.infer_url_templates = infer_url_templates;
+    }
 
-        // Create the pristine return response immediately, before any VCR processing
-        let return_response =
-            Self::create_pristine_response(status, &headers, body_string.as_deref());
+    pub fn set_content_encoding(&mut self, content_encoding: ContentEncoding) {
+        self.content_encoding = content_encoding;
+    }
 
-        // Now do VCR processing with the data we already extracted
-        let mut serializable_request = SerializableRequest::from_request(req_for_recording).await?;
-        let mut serializable_response = crate::SerializableResponse {
-            status: status.into(),
-            headers,
-            body: body_string.clone(),
-            body_base64: None,
-            version,
-        };
+    /// Follow up to `max_redirects` redirects per request, or `None` to return
+    /// redirect responses as they are
+    pub fn set_follow_redirects(&mut self, max_redirects: Option<usize>) {
+        self.follow_redirects = max_redirects;
+    }
 
-        // Apply filters ONLY to what gets stored
-        self.filter_chain.filter_request(&mut serializable_request);
-        self.filter_chain
-            .filter_response(&mut serializable_response);
+    pub fn set_record_errors(&mut self, record_errors: bool) {
+        self.record_errors = record_errors;
+    }
 
-        let mut cassette = self.cassette.lock().await;
+    pub fn set_stream_replay_speed(&mut self, speed: f64) {
+        self.stream_replay_speed = speed;
+    }
 
-        // In Record mode, clear cassette on first interaction to fully replace
-        if matches!(self.mode, VcrMode::Record) {
-            let mut recording_started = self.recording_started.lock().await;
-            if !*recording_started {
-                cassette.clear();
-                *recording_started = true;
-            }
+    /// Copy the events streamed so far into their interactions, filtered like the
+    /// rest of the recording, and forget streams their callers have dropped
+    fn collect_streams(&self, interactions: &mut [Interaction]) {
+        let mut captures = self.stream_captures.lock().unwrap();
+        for (sequence, capture) in captures.iter() {
+            let Some(interaction) = interactions
+                .iter_mut()
+                .find(|interaction| interaction.sequence == Some(*sequence))
+            else {
+                continue;
+            };
+            let chunks = capture.lock().unwrap().clone();
+            let filtered = chunks
+                .into_iter()
+                .map(|chunk| {
+                    let mut response = interaction.response.clone();
+                    response.set_bytes(chunk.bytes());
+                    self.filter_chain
+                        .filter_response_for(&interaction.request, &mut response);
+                    StreamChunk::new(chunk.at_ms, response.bytes().unwrap_or_default())
+                })
+                .collect();
+            interaction.stream = Some(filtered);
         }
+        captures.retain(|(_, capture)| Arc::strong_count(capture) > 1);
+    }
 
-        cassette
-            .record_interaction(serializable_request, serializable_response)
-            .await?;
+    pub fn set_time_shift(&mut self, time_shift: Option<TimeShift>) {
+        self.time_shift = time_shift;
+    }
 
-        // Return the pristine response we created before any VCR processing
-        Ok(return_response)
+    /// Delay replayed responses, or `None` to return them immediately
+    pub fn set_latency(&mut self, latency: Option<LatencyMode>) {
+        self.latency.set_default(latency);
     }
 
-    async fn handle_none_mode(&self, req: Request) -> Result<Response, Error> {
-        self.inner.send(req).await
+    /// Delay replayed responses from `host` differently from other hosts
+    pub fn set_host_latency(&mut self, host: &str, latency: LatencyMode) {
+        self.latency.set_host(host, latency);
     }
 
-    async fn handle_replay_mode(&self, req: Request) -> Result<Response, Error> {
-        let cassette = self.cassette.lock().await;
-        if let Some((index, _interaction)) = self.find_match(&req, &cassette).await {
-            // Mark this interaction as used
-            drop(cassette); // Release cassette lock before acquiring used_interactions lock
-            let mut used_interactions = self.used_interactions.lock().await;
-            used_interactions.insert(index);
-            drop(used_interactions); // Release used_interactions lock
-
-            // Re-acquire cassette lock to access the interaction
-            let cassette = self.cassette.lock().await;
-            let interaction = &cassette.interactions[index];
-            Ok(interaction.response.to_response().await)
-        } else {
-            drop(cassette); // Release the lock before calling generate_no_match_error
-            Err(self.generate_no_match_error(&req, "Replay mode").await)
-        }
+    /// Emulate per-connection response ordering on replay, or `None` to replay
+    /// concurrent requests independently
+    pub fn set_connection_replay(&mut self, connection_replay: Option<ConnectionReplay>) {
+        self.connection_replay = connection_replay;
     }
 
-    async fn handle_record_mode(&self, req: Request) -> Result<Response, Error> {
-        // Duplicate the request to preserve the body for both sending and recording
-        let (req_for_sending, req_for_recording) = duplicate_request_with_body(req).await?;
+    pub fn set_fault_injector(&mut self, fault_injector: Option<FaultInjector>) {
+        self.fault_injector = fault_injector;
+    }
 
-        // Make the real request with original sensitive data - never match existing interactions
-        let mut response = self.inner.send(req_for_sending).await?;
-        self.record_and_return_response(req_for_recording, &mut response)
-            .await
+    pub fn set_replay_validator(&mut self, replay_validator: Option<ReplayValidator>) {
+        self.replay_validator = replay_validator;
     }
 
-    async fn handle_once_mode(&self, req: Request) -> Result<Response, Error> {
-        let cassette = self.cassette.lock().await;
-        if let Some((index, _interaction)) = self.find_match(&req, &cassette).await {
-            // Mark this interaction as used
-            drop(cassette); // Release cassette lock before acquiring used_interactions lock
-            let mut used_interactions = self.used_interactions.lock().await;
-            used_interactions.insert(index);
-            drop(used_interactions); // Release used_interactions lock
+    /// Only save requests accepted by the predicate when recording, or `None` to save all
+    pub fn set_record_predicate(&mut self, record_predicate: Option<RecordPredicate>) {
+        self.record_predicate = record_predicate;
+    }
 
-            // Re-acquire cassette lock to access the interaction
-            let cassette = self.cassette.lock().await;
-            let interaction = &cassette.interactions[index];
-            return Ok(interaction.response.to_response().await);
-        }
+    fn should_record(&self, request: &SerializableRequest) -> bool {
+        self.record_predicate
+            .as_ref()
+            .is_none_or(|predicate| (predicate.0)(request))
+    }
 
-        if !cassette.is_empty() {
-            drop(cassette); // Release the lock before calling generate_no_match_error
-            return Err(self.generate_no_match_error(&req, "Once mode").await);
+    pub fn set_miss_behavior(&mut self, miss_behavior: MissBehavior) {
+        self.miss_behavior = miss_behavior;
+    }
+
+    /// Apply the configured `MissBehavior` to a request no interaction matched
+    async fn handle_miss(&self, req: Request, mode_description: &str) -> Result<Response, Error> {
+        self.events.emit(VcrEvent::Miss {
+            request_summary: format!("{} {}", req.method(), req.url()),
+        });
+        let action = match &self.miss_behavior {
+            MissBehavior::Error => {
+                return Err(self.generate_no_match_error(&req, mode_description).await)
+            }
+            MissBehavior::Forward => MissAction::Forward,
+            MissBehavior::PassthroughWithWarning { report } => {
+                self.warn_about_miss(&req, mode_description, report.as_deref())
+                    .await;
+                MissAction::Forward
+            }
+            MissBehavior::Handler(handler) => (handler.0)(&req),
+        };
+        match action {
+            MissAction::Respond(response) => Ok(*response),
+            MissAction::Forward => {
+                self.passed_through
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {}", req.method(), req.url()));
+                self.inner().send(req).await
+            }
+            MissAction::Fail(error) => Err(error),
+        }
+    }
+
+    /// Log a miss that is passed through, and append it to `report`
+    async fn warn_about_miss(&self, req: &Request, mode_description: &str, report: Option<&Path>) {
+        let url = match self.filtered_request(req).await {
+            Some(filtered) => filtered.url,
+            None => req.url().to_string(),
+        };
+        let cassette = match &self.cassette.read().await.path {
+            Some(path) => path.display().to_string(),
+            None => "<in-memory cassette>".to_string(),
+        };
+        log::warn!(
+            "VCR MISS: {} {url} matched no interaction in {cassette} ({mode_description}); \
+             passing it through to the real service without recording it",
+            req.method()
+        );
+
+        let Some(report) = report else {
+            return;
+        };
+        let line = format!("{cassette}\t{} {url}\n", req.method());
+        append_to_miss_report(report.to_path_buf(), line).await;
+    }
+
+    /// Send requests to these hosts straight to the inner client, without recording or
+    /// matching them. A leading `*.` matches any subdomain.
+    pub fn set_ignored_hosts<I, S>(&mut self, hosts: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.ignored_hosts = hosts
+            .into_iter()
+            .map(|host| host.as_ref().to_lowercase())
+            .collect();
+    }
+
+    fn is_ignored(&self, url: &http_types::Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        // IPv6 hosts come bracketed, but are listed without brackets
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let host = host.to_lowercase();
+        self.ignored_hosts
+            .iter()
+            .any(|pattern| filter::host_matches(pattern, &host))
+    }
+
+    /// Environment variables to capture (sanitized) into the cassette when recording
+    pub fn set_env_snapshot(&mut self, names: Vec<String>) {
+        self.env_snapshot = names;
+    }
+
+    /// Analyze the cassette before saving and handle interactions that still contain
+    /// credentials, auth headers or tokens, or `None` to save without checking
+    pub fn set_strict_sanitization(&mut self, action: Option<UnsanitizedAction>) {
+        self.strict_sanitization = action;
+    }
+
+    /// Limits on body sizes and the number of recorded interactions
+    pub fn set_limits(&mut self, limits: CassetteLimits) {
+        self.limits = limits;
+    }
+
+    /// Make `finalize` fail, and dropping the client print an error, when recorded
+    /// interactions were never replayed or unmatched requests were forwarded
+    pub fn set_strict_playback(&mut self, enabled: bool) {
+        self.strict_playback = enabled;
+    }
+
+    /// Call `handler` with every `VcrEvent`, after any handlers added before it
+    pub fn add_event_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(VcrEvent) + Send + Sync + 'static,
+    {
+        self.events.push(handler);
+    }
+
+    pub(crate) fn set_event_handlers(&mut self, events: EventHandlers) {
+        self.events = events;
+    }
+
+    /// Run `hook` on every interaction before it is recorded, after any hooks added
+    /// before it
+    pub fn add_record_hook(&mut self, hook: Box<dyn RecordHook>) {
+        self.record_hooks.push(hook);
+    }
+
+    /// Run `transformer` on every replayed response before it is returned, after any
+    /// transformers added before it
+    pub fn add_replay_transformer(&mut self, transformer: Box<dyn ReplayTransformer>) {
+        self.replay_transformers.push(transformer);
+    }
+
+    /// The environment snapshot stored in the cassette when it was recorded, so a
+    /// replaying test can reconstruct the configuration the fixtures were captured under
+    pub async fn recorded_environment(&self) -> std::collections::BTreeMap<String, String> {
+        self.cassette.read().await.environment().clone()
+    }
+
+    /// Serialize `request` and filter it like the recorded interactions. Done once per
+    /// request and shared by every lookup; `None` if it can't be serialized, in which
+    /// case lookups fall back to `RequestMatcher::matches`.
+    async fn filtered_request(&self, request: &Request) -> Option<SerializableRequest> {
+        let mut filtered_request = SerializableRequest::from_request(request.clone())
+            .await
+            .ok()?;
+        self.filter_chain.filter_request(&mut filtered_request);
+        Some(filtered_request)
+    }
+
+    /// Indices of the interactions that may match the request, in order
+    fn candidates(
+        &self,
+        filtered_request: Option<&SerializableRequest>,
+        interactions: &[Interaction],
+        match_index: &mut MatchIndex,
+    ) -> Vec<usize> {
+        let mut candidates = match filtered_request {
+            Some(filtered_request) => {
+                match_index.candidates(self.matcher.as_ref(), interactions, filtered_request)
+            }
+            None => (0..interactions.len()).collect(),
+        };
+        if let Some(tag) = &*self.tag_scope.read().unwrap() {
+            candidates.retain(|&index| interactions[index].has_tag(tag));
+        }
+        let state = self.scenario_state();
+        candidates.retain(|&index| interactions[index].available_in_state(&state));
+        candidates
+    }
+
+    /// With `lazy_load`, add the interactions the storage has for the request's method
+    /// and URL to the cassette, once per method and URL. Returns whether any were
+    /// added.
+    async fn fetch_candidates(
+        &self,
+        filtered_request: Option<&SerializableRequest>,
+    ) -> Result<bool, Error> {
+        let (Some(lookups), Some(request)) = (&self.lazy_lookups, filtered_request) else {
+            return Ok(false);
+        };
+        // Held while fetching, so a concurrent request for the same URL waits for
+        // the interactions instead of missing
+        let mut lookups = lookups.lock().await;
+        let key = (request.method.clone(), request.url.clone());
+        if lookups.complete || !lookups.fetched.insert(key.clone()) {
+            return Ok(false);
+        }
+        let Some(path) = self.cassette.read().await.path.clone() else {
+            return Ok(false);
+        };
+
+        let storage = Arc::clone(&self.storage);
+        let (found, complete) = runtime::unblock(move || {
+            let (method, url) = key;
+            match storage.find(&path, &method, &url)? {
+                Some(found) => Ok::<_, Error>((found, false)),
+                None => {
+                    let cassette = storage.load(&path)?;
+                    Ok((cassette.map(|c| c.interactions).unwrap_or_default(), true))
+                }
+            }
+        })
+        .await?;
+
+        let mut cassette = self.cassette.write().await;
+        lookups.complete = complete;
+        if complete {
+            // Nothing was fetched before, since the storage can't look anything up
+            cassette.interactions = found;
+        } else {
+            cassette.interactions.extend(found);
+        }
+        Ok(!cassette.interactions.is_empty())
+    }
+
+    /// Only replay interactions tagged with `tag`, and tag the interactions recorded
+    /// from now on with it, until the scope is changed or cleared. Lets a large shared
+    /// cassette be split into flows.
+    pub fn scope_to_tag(&self, tag: impl Into<String>) {
+        *self.tag_scope.write().unwrap() = Some(tag.into());
+    }
+
+    /// Replay and record without a tag scope again
+    pub fn clear_tag_scope(&self) {
+        *self.tag_scope.write().unwrap() = None;
+    }
+
+    /// The scenario state interactions' `requires_state` is checked against.
+    /// Starts out as [`SCENARIO_STARTED`] and changes when an interaction with a
+    /// `sets_state` is played or recorded.
+    pub fn scenario_state(&self) -> String {
+        self.scenario_state
+            .read()
+            .unwrap()
+            .as_deref()
+            .unwrap_or(SCENARIO_STARTED)
+            .to_string()
+    }
+
+    /// Move to scenario `state`. Interactions recorded from now on require it, until
+    /// the state changes again.
+    pub fn set_scenario_state(&self, state: impl Into<String>) {
+        *self.scenario_state.write().unwrap() = Some(state.into());
+    }
+
+    fn is_match(
+        &self,
+        request: &Request,
+        filtered_request: Option<&SerializableRequest>,
+        recorded: &SerializableRequest,
+    ) -> bool {
+        match filtered_request {
+            Some(filtered_request) => self
+                .matcher
+                .matches_serializable(filtered_request, recorded),
+            None => self.matcher.matches(request, recorded),
+        }
+    }
+
+    /// Find the first unused interaction matching the request and mark it used.
+    ///
+    /// An interaction is only taken if it is still unused when marked, so concurrent
+    /// requests never replay the same one. Matching itself happens without holding the
+    /// lock on the used interactions.
+    async fn claim_match(
+        &self,
+        request: &Request,
+        filtered_request: Option<&SerializableRequest>,
+        cassette: &Cassette,
+    ) -> Option<usize> {
+        let candidates = self.candidates(
+            filtered_request,
+            &cassette.interactions,
+            &mut self.match_index.lock().unwrap(),
+        );
+        let candidates: Vec<usize> = {
+            let used = self.used_interactions.lock().await;
+            candidates
+                .into_iter()
+                .filter(|index| !used.contains(index))
+                .collect()
+        };
+        for index in candidates {
+            if self.is_match(
+                request,
+                filtered_request,
+                &cassette.interactions[index].request,
+            ) && self.used_interactions.lock().await.insert(index)
+            {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Search the additional cassettes, in the order they were added, for an unused
+    /// interaction matching `request` and mark it used like `claim_match`. Returns the
+    /// cassette's layer and the index in it.
+    async fn claim_shared_match(
+        &self,
+        request: &Request,
+        filtered_request: Option<&SerializableRequest>,
+    ) -> Option<(usize, usize)> {
+        for (layer, cassette) in self.additional_cassettes.iter().enumerate() {
+            let candidates = self.candidates(
+                filtered_request,
+                &cassette.interactions,
+                &mut self.shared_match_indexes.lock().unwrap()[layer],
+            );
+            let candidates: Vec<usize> = {
+                let used = self.used_shared_interactions.lock().await;
+                candidates
+                    .into_iter()
+                    .filter(|index| !used.contains(&(layer, *index)))
+                    .collect()
+            };
+            for index in candidates {
+                if self.is_match(
+                    request,
+                    filtered_request,
+                    &cassette.interactions[index].request,
+                ) && self
+                    .used_shared_interactions
+                    .lock()
+                    .await
+                    .insert((layer, index))
+                {
+                    return Some((layer, index));
+                }
+            }
+        }
+        None
+    }
+
+    /// Build the error for a request with no matching interaction. The diagnostics
+    /// are only rendered if the error is displayed.
+    async fn generate_no_match_error(&self, request: &Request, mode_description: &str) -> Error {
+        let cassette = self.cassette.read().await;
+        let diagnostics = NoMatchDiagnostics::new(
+            mode_description,
+            request.method().to_string(),
+            request.url().to_string(),
+            &cassette,
+        );
+        Error::from_str(404, diagnostics)
+    }
+
+    /// Save the cassette to its path. Does nothing for in-memory clients.
+    pub async fn save_cassette(&self) -> Result<(), Error> {
+        if self.persistence == Persistence::InMemory {
+            return Ok(());
+        }
+        let save = {
+            let mut cassette = self.cassette.write().await;
+            self.collect_streams(&mut cassette.interactions);
+            let saved = self.check_before_save(&cassette)?;
+            self.spawn_store(&saved)?
+        };
+        // The cassette is unlocked while it is written
+        save.flush().await
+    }
+
+    /// Snapshot the cassette and save it on a background task, so the caller doesn't
+    /// wait for a large cassette to be serialized and written. Does nothing for
+    /// in-memory clients.
+    pub async fn save_in_background(&self) -> Result<SaveHandle, Error> {
+        if self.persistence == Persistence::InMemory {
+            return Ok(SaveHandle::done());
+        }
+        let mut cassette = self.cassette.write().await;
+        self.collect_streams(&mut cassette.interactions);
+        let saved = self.check_before_save(&cassette)?;
+        self.spawn_store(&saved)
+    }
+
+    /// Wait for the background auto-saves started so far, returning the first error
+    pub async fn flush(&self) -> Result<(), Error> {
+        let pending = std::mem::take(&mut *self.pending_saves.lock().await);
+        let mut result = Ok(());
+        for save in pending {
+            if let Err(e) = save.flush().await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Save the cassette if it was recorded to, as dropping the client would, and report
+    /// what happened to it. Unlike the save on drop this can't be skipped because the
+    /// cassette is in use, and errors are returned instead of printed.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::VcrClient;
+    /// # async fn example(client: VcrClient) -> Result<(), http_types::Error> {
+    /// let stats = client.finalize().await?;
+    /// assert!(stats.unused.is_empty(), "stale interactions: {:?}", stats.unused);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn finalize(&self) -> Result<PlaybackStats, Error> {
+        self.finalized
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        self.flush().await?;
+        let save = {
+            let mut cassette = self.cassette.write().await;
+            if self.persistence == Persistence::Disk
+                && self.persists_recordings()
+                && cassette.modified_since_load
+            {
+                self.collect_streams(&mut cassette.interactions);
+                let saved = self.check_before_save(&cassette)?;
+                let save = self.spawn_store(&saved)?;
+                // Nothing left for Drop to save
+                cassette.modified_since_load = false;
+                Some(save)
+            } else {
+                None
+            }
+        };
+        let saved = save.is_some();
+        if let Some(save) = save {
+            if let Err(e) = save.flush().await {
+                self.cassette.write().await.modified_since_load = true;
+                return Err(e);
+            }
+        }
+
+        let cassette = self.cassette.read().await;
+        let stats = PlaybackStats {
+            saved,
+            ..self.stats_for(&cassette).await
+        };
+        if let Some(report) = self.strict_playback_report(&cassette, &stats.unused) {
+            return Err(Error::from_str(500, report));
+        }
+        Ok(stats)
+    }
+
+    /// Report what the client has done with its cassette so far, without saving it
+    pub async fn playback_stats(&self) -> PlaybackStats {
+        let cassette = self.cassette.read().await;
+        self.stats_for(&cassette).await
+    }
+
+    async fn stats_for(&self, cassette: &Cassette) -> PlaybackStats {
+        let recorded = self
+            .recorded_interactions
+            .load(std::sync::atomic::Ordering::SeqCst);
+        let play_counts: Vec<usize> = {
+            let counts = self.play_counts.lock().unwrap();
+            (0..cassette.interactions.len())
+                .map(|index| counts.get(&index).copied().unwrap_or(0))
+                .collect()
+        };
+        PlaybackStats {
+            interactions: cassette.interactions.len(),
+            recorded,
+            replayed: self.used_interactions.lock().await.len(),
+            replayed_shared: self.used_shared_interactions.lock().await.len(),
+            unused: self.unused_indices(cassette),
+            play_counts,
+            passed_through: self.passed_through.lock().unwrap().clone(),
+            saved: false,
+        }
+    }
+
+    /// Whether changes to the cassette are saved: in Record and Once mode, or once a
+    /// request was recorded through `MODE_HEADER` in another mode
+    fn persists_recordings(&self) -> bool {
+        matches!(self.mode, VcrMode::Record | VcrMode::Once)
+            || self
+                .recorded_interactions
+                .load(std::sync::atomic::Ordering::SeqCst)
+                > 0
+    }
+
+    /// Indices of the interactions loaded with the cassette that were never replayed
+    fn unused_indices(&self, cassette: &Cassette) -> Vec<usize> {
+        let recorded = self
+            .recorded_interactions
+            .load(std::sync::atomic::Ordering::SeqCst);
+        // Recorded interactions are appended, so everything before them was loaded
+        let loaded = cassette.interactions.len().saturating_sub(recorded);
+        let counts = self.play_counts.lock().unwrap();
+        (0..loaded)
+            .filter(|index| !counts.contains_key(index))
+            .collect()
+    }
+
+    /// With strict playback, describe the interactions that were never replayed and
+    /// the requests forwarded to the inner client, or `None` if there were none
+    fn strict_playback_report(&self, cassette: &Cassette, unused: &[usize]) -> Option<String> {
+        if !self.strict_playback
+            || !matches!(self.mode, VcrMode::Replay | VcrMode::Once | VcrMode::Filter)
+        {
+            return None;
+        }
+        let passed_through = self.passed_through.lock().unwrap();
+        if unused.is_empty() && passed_through.is_empty() {
+            return None;
+        }
+        let mut report = String::from("Strict playback failed");
+        if !unused.is_empty() {
+            report.push_str(&format!(
+                "\n{} recorded interaction(s) were never replayed:\n{}",
+                unused.len(),
+                describe_interactions(cassette, unused)
+            ));
+        }
+        if !passed_through.is_empty() {
+            report.push_str(&format!(
+                "\n{} request(s) matched nothing and were forwarded to the inner client:",
+                passed_through.len()
+            ));
+            for request in passed_through.iter() {
+                report.push_str(&format!("\n  {request}"));
+            }
+        }
+        Some(report)
+    }
+
+    /// The previously recorded interactions that haven't been replayed, which usually
+    /// means the code path that made them is gone
+    pub async fn unused_interactions(&self) -> Vec<Interaction> {
+        let unused = self.playback_stats().await.unused;
+        let cassette = self.cassette.read().await;
+        unused
+            .into_iter()
+            .filter_map(|index| cassette.interactions.get(index).cloned())
+            .collect()
+    }
+
+    /// Panic, listing them, if any previously recorded interaction hasn't been replayed
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::VcrClient;
+    /// # async fn example(client: VcrClient) {
+    /// // ... run the test ...
+    /// client.assert_all_played().await;
+    /// # }
+    /// ```
+    pub async fn assert_all_played(&self) {
+        let stats = self.playback_stats().await;
+        if stats.unused.is_empty() {
+            return;
+        }
+        let cassette = self.cassette.read().await;
+        panic!(
+            "{} of {} recorded interactions were never replayed:\n{}",
+            stats.unused.len(),
+            cassette.interactions.len(),
+            describe_interactions(&cassette, &stats.unused)
+        );
+    }
+
+    /// Save `cassette` if the auto-save threshold was reached by the interaction just
+    /// recorded. Failures are logged rather than failing the live request; the final
+    /// save reports them. Unless saves run in the background, returns the save for the
+    /// caller to wait on once it has unlocked the cassette.
+    async fn auto_save(&self, cassette: &mut Cassette) -> Option<SaveHandle> {
+        let auto_save = self.auto_save?;
+        if self.persistence == Persistence::InMemory {
+            return None;
+        }
+
+        let mut state = self.auto_save_state.lock().await;
+        let (recorded, last_saved) = &mut *state;
+        *recorded += 1;
+        let due = match auto_save {
+            AutoSave::Interactions(n) => *recorded >= n.max(1),
+            AutoSave::Interval(interval) => last_saved.elapsed() >= interval,
+        };
+        if !due {
+            return None;
+        }
+        self.collect_streams(&mut cassette.interactions);
+
+        if self.background_save {
+            match self
+                .check_before_save(cassette)
+                .and_then(|saved| self.spawn_store(&saved))
+            {
+                Ok(save) => {
+                    let mut pending = self.pending_saves.lock().await;
+                    // Collect saves that already finished so the list doesn't grow
+                    for finished in pending.extract_if(.., |save| save.is_finished()) {
+                        if let Err(e) = finished.flush().await {
+                            log::warn!("Failed to auto-save cassette: {e}");
+                        }
+                    }
+                    pending.push(save);
+                }
+                Err(e) => log::warn!("Failed to auto-save cassette: {e}"),
+            }
+            *state = (0, runtime::Instant::now());
+            return None;
+        }
+
+        *state = (0, runtime::Instant::now());
+        match self
+            .check_before_save(cassette)
+            .and_then(|saved| self.spawn_store(&saved))
+        {
+            Ok(save) => Some(save),
+            Err(e) => {
+                log::warn!("Failed to auto-save cassette: {e}");
+                None
+            }
+        }
+    }
+
+    /// Write `cassette` to the configured storage at its path on the calling thread.
+    /// Only for Drop, which can't wait on a task; async paths use `spawn_store`.
+    fn store(&self, cassette: &Cassette) -> Result<(), Error> {
+        let path = cassette
+            .path
+            .as_ref()
+            .ok_or_else(|| Error::from_str(400, "No path specified for cassette"))?;
+        let ticket = self.save_sequence.ticket();
+        self.save_sequence
+            .write(ticket, path, || self.storage.save(path, cassette))?;
+        self.events.emit(VcrEvent::Saved { path: path.clone() });
+        Ok(())
+    }
+
+    /// Like `store`, but on a blocking task with a snapshot of `cassette`
+    fn spawn_store(&self, cassette: &Cassette) -> Result<SaveHandle, Error> {
+        let path = cassette
+            .path
+            .clone()
+            .ok_or_else(|| Error::from_str(400, "No path specified for cassette"))?;
+        let snapshot = cassette.clone();
+        let ticket = self.save_sequence.ticket();
+        let storage = Arc::clone(&self.storage);
+        let sequence = Arc::clone(&self.save_sequence);
+        let events = self.events.clone();
+        Ok(SaveHandle::spawn(move || {
+            sequence.write(ticket, &path, || storage.save(&path, &snapshot))?;
+            events.emit(VcrEvent::Saved { path });
+            Ok(())
+        }))
+    }
+
+    /// A copy of the interactions currently in the cassette, including any recorded
+    pub async fn interactions(&self) -> Vec<Interaction> {
+        let mut interactions = self.cassette.read().await.interactions.clone();
+        self.collect_streams(&mut interactions);
+        interactions
+    }
+
+    /// Swap in another cassette, keeping the inner client, matcher, filters and other
+    /// configuration. Returns the previous cassette without saving it.
+    pub async fn insert_cassette(&self, cassette: Cassette) -> Cassette {
+        let previous = std::mem::replace(&mut *self.cassette.write().await, cassette);
+        if let Some(lookups) = &self.lazy_lookups {
+            *lookups.lock().await = LazyLookups {
+                complete: true,
+                ..LazyLookups::default()
+            };
+        }
+        self.match_index.lock().unwrap().invalidate();
+        self.limits_exceeded.lock().unwrap().clear();
+        self.play_counts.lock().unwrap().clear();
+        self.passed_through.lock().unwrap().clear();
+        self.reset_replay_state().await;
+        *self.recording_started.lock().await = false;
+        *self.auto_save_state.lock().await = (0, runtime::Instant::now());
+        self.recorded_interactions
+            .store(0, std::sync::atomic::Ordering::SeqCst);
+        self.resume_filters().await;
+        previous
+    }
+
+    /// Make every interaction replayable again, as if the client had just been built.
+    /// Lets a test harness share one client between tests.
+    pub async fn reset_replay_state(&self) {
+        self.restore_replay_state(ReplayState::default()).await;
+    }
+
+    /// Capture which interactions have been replayed so far
+    pub async fn snapshot_replay_state(&self) -> ReplayState {
+        ReplayState {
+            used_interactions: self.used_interactions.lock().await.clone(),
+            used_shared_interactions: self.used_shared_interactions.lock().await.clone(),
+            served_connections: self.connection_gate.served(),
+            scenario_state: self.scenario_state.read().unwrap().clone(),
+        }
+    }
+
+    /// Go back to a state captured with `snapshot_replay_state`: interactions replayed
+    /// since then can be replayed again
+    pub async fn restore_replay_state(&self, state: ReplayState) {
+        *self.used_interactions.lock().await = state.used_interactions;
+        *self.used_shared_interactions.lock().await = state.used_shared_interactions;
+        self.connection_gate.restore(state.served_connections);
+        *self.scenario_state.write().unwrap() = state.scenario_state;
+    }
+
+    /// Take the current cassette out of the client, leaving an empty one in its place.
+    /// The cassette is not saved; call `save_cassette` first or save the returned one.
+    pub async fn eject_cassette(&self) -> Cassette {
+        self.insert_cassette(Cassette::new()).await
+    }
+
+    /// Run `scenario` against the cassette at `path` in `mode`, then save it if it was
+    /// recorded to and switch back to the previous cassette and mode.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{VcrClient, VcrMode};
+    /// # use http_client::HttpClient;
+    /// # async fn example(client: &mut VcrClient) -> Result<(), http_types::Error> {
+    /// let status = client
+    ///     .use_cassette("tests/fixtures/login.yaml", VcrMode::Once, async |client| {
+    ///         let url = http_types::Url::parse("https://example.com/login")?;
+    ///         let request = http_types::Request::new(http_types::Method::Post, url);
+    ///         Ok::<_, http_types::Error>(client.send(request).await?.status())
+    ///     })
+    ///     .await??;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn use_cassette<P, F, T>(
+        &mut self,
+        path: P,
+        mode: VcrMode,
+        scenario: F,
+    ) -> Result<T, Error>
+    where
+        P: Into<PathBuf>,
+        F: AsyncFnOnce(&VcrClient<C>) -> T,
+    {
+        let path = config::resolve_cassette_path(self.cassette_root.as_deref(), path.into());
+        let cassette = match load_cassette(&self.storage, &path).await? {
+            Some(cassette) => cassette,
+            None => Cassette::new().with_path(path),
+        };
+
+        let persist = matches!(mode, VcrMode::Record | VcrMode::Once);
+        // The mode is switched first, so inserting the cassette sees it
+        let previous_mode = std::mem::replace(&mut self.mode, mode);
+        let previous_cassette = self.insert_cassette(cassette).await;
+
+        let result = scenario(self).await;
+
+        self.mode = previous_mode;
+        let cassette = self.insert_cassette(previous_cassette).await;
+        if persist && cassette.modified_since_load {
+            let saved = self.check_before_save(&cassette)?;
+            self.spawn_store(&saved)?.flush().await?;
+        }
+
+        Ok(result)
+    }
+
+    /// Refuse to save a cassette only partly fetched with `lazy_load`, or when a limit
+    /// was exceeded with `LimitAction::Error`, then run the sanitization check. Returns
+    /// what to store.
+    fn check_before_save<'a>(&self, cassette: &'a Cassette) -> Result<Cow<'a, Cassette>, Error> {
+        let partial = self
+            .lazy_lookups
+            .as_ref()
+            .is_some_and(|lookups| !lookups.try_lock().is_some_and(|lookups| lookups.complete));
+        if partial {
+            // Saving would replace the stored cassette with the fetched interactions
+            return Err(Error::from_str(
+                400,
+                "Refusing to save cassette: it was loaded with lazy_load and only holds the interactions fetched so far",
+            ));
+        }
+        if self.limits.action == LimitAction::Error {
+            let exceeded = self.limits_exceeded.lock().unwrap();
+            if !exceeded.is_empty() {
+                return Err(Error::from_str(
+                    500,
+                    format!(
+                        "Refusing to save cassette: recording exceeded its limits: {}",
+                        exceeded.join("; ")
+                    ),
+                ));
+            }
+        }
+        self.enforce_sanitization(cassette)
+    }
+
+    /// Run the analyzer over the cassette when strict sanitization is enabled.
+    /// Interactions dropped by `UnsanitizedAction::Drop` are left out of a copy to
+    /// store; the client's cassette keeps them, since replay state, play counts and the
+    /// match index refer to interactions by position.
+    fn enforce_sanitization<'a>(&self, cassette: &'a Cassette) -> Result<Cow<'a, Cassette>, Error> {
+        let Some(action) = self.strict_sanitization else {
+            return Ok(Cow::Borrowed(cassette));
+        };
+
+        let unsanitized = analyze_cassette(cassette).unsanitized_interactions();
+        if unsanitized.is_empty() {
+            return Ok(Cow::Borrowed(cassette));
         }
+
+        match action {
+            UnsanitizedAction::Error => Err(Error::from_str(
+                500,
+                format!(
+                    "Refusing to save cassette: interactions {unsanitized:?} still contain credentials, auth headers or tokens after filtering"
+                ),
+            )),
+            UnsanitizedAction::Drop => {
+                log::warn!(
+                    "Dropping interactions {unsanitized:?} from cassette: they still contain credentials, auth headers or tokens after filtering"
+                );
+                let mut sanitized = cassette.clone();
+                let mut index = 0;
+                sanitized.interactions.retain(|_| {
+                    let keep = !unsanitized.contains(&index);
+                    index += 1;
+                    keep
+                });
+                Ok(Cow::Owned(sanitized))
+            }
+        }
+    }
+
+    /// Apply filters to all interactions in the cassette
+    /// This modifies the cassette in-place by applying the configured filter chain to all interactions
+    pub async fn apply_filters_to_cassette(&self) -> Result<(), Error> {
+        let mut cassette = self.cassette.write().await;
+
+        // Apply filters to each interaction
+        for interaction in &mut cassette.interactions {
+            self.filter_chain
+                .filter_interaction(&mut interaction.request, &mut interaction.response);
+        }
+        self.match_index.lock().unwrap().invalidate();
+
+        log::debug!(
+            "Applied filters to {} interactions",
+            cassette.interactions.len()
+        );
+        Ok(())
+    }
+
+    /// Apply filters to all interactions in the cassette and save the filtered version
+    pub async fn filter_and_save_cassette(&self) -> Result<(), Error> {
+        self.apply_filters_to_cassette().await?;
+        self.save_cassette().await
+    }
+
+    // Helper methods for each VCR mode
+
+    /// Build the response handed back to the caller for a replayed interaction
+    async fn replay_response(
+        &self,
+        interaction: &Interaction,
+        request: &Request,
+    ) -> Result<Response, Error> {
+        // Give filters a chance to substitute real values back into the response and
+        // adjust it for playback, without touching the stored interaction
+        let mut replayed = interaction.clone();
+        if self.content_encoding == ContentEncoding::Decode {
+            encoding::decode_response(&mut replayed.response);
+        }
+        self.filter_chain
+            .prepare_playback(&replayed.request, &mut replayed.response);
+
+        if let (Some(time_shift), Some(recorded_at)) = (&self.time_shift, replayed.recorded_at) {
+            time_shift.shift_since(&mut replayed.response, recorded_at);
+        }
+
+        if self.response_templating {
+            TemplateContext::new(request).expand_response(&mut replayed.response);
+        }
+        // Cassettes edited by hand or by older versions may have stale length headers
+        replayed.response.fix_body_headers();
+
+        if let Some(validator) = &self.replay_validator {
+            (validator.0)(&replayed)?;
+        }
+
+        let mut response = replayed.response.to_response().await;
+
+        if let Some(chunks) = &replayed.stream {
+            let mime = response.content_type();
+            let mut body = streaming::replay_body(chunks, self.stream_replay_speed);
+            body.set_mime(mime);
+            response.set_body(body);
+        } else if let Some(chunked_replay) = &self.chunked_replay {
+            let body = response.take_body();
+            let mime = body.mime().cloned();
+            let bytes = body.into_bytes().await.map_err(|e| {
+                Error::from_str(500, format!("Failed to read recorded response body: {e}"))
+            })?;
+            let mut paced_body = chunked_replay.body(bytes);
+            paced_body.set_mime(mime);
+            response.set_body(paced_body);
+        }
+        run_replay_transformers(&self.replay_transformers, &mut response, &replayed);
+
+        Ok(response)
+    }
+
+    /// Replay a claimed interaction, waiting for its connection first when connection
+    /// replay is configured
+    async fn replay_interaction(&self, index: usize, request: &Request) -> Result<Response, Error> {
+        let (interaction, connection) = {
+            let cassette = self.cassette.read().await;
+            let mut interaction = cassette.interactions[index].clone();
+            // Interactions recorded before per-interaction timing fall back to the cassette's
+            interaction.recorded_at = interaction.recorded_at.or(cassette.recorded_at());
+            let connection = self.connection_replay.as_ref().map(|config| {
+                let host = connection_key(&interaction.request.url);
+                let earlier: Vec<usize> = cassette.interactions[..index]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| connection_key(&other.request.url) == host)
+                    .map(|(i, _)| i)
+                    .collect();
+                (config, host, earlier)
+            });
+            (interaction, connection)
+        };
+
+        // The cassette lock must not be held here, or requests queued behind this one
+        // could never be matched
+        let guard = match connection {
+            Some((config, host, earlier)) => Some(
+                self.connection_gate
+                    .acquire(config, &host, index, &earlier)
+                    .await,
+            ),
+            None => None,
+        };
+
+        *self.play_counts.lock().unwrap().entry(index).or_insert(0) += 1;
+        self.events.emit(VcrEvent::Replayed { index });
+        self.play(&interaction, Some(index), guard, request).await
+    }
+
+    /// Replay an interaction from one of the additional cassettes below the topmost one.
+    /// These are shared read-only, so connection ordering doesn't apply to them.
+    async fn replay_shared_interaction(
+        &self,
+        layer: usize,
+        index: usize,
+        request: &Request,
+    ) -> Result<Response, Error> {
+        let cassette = &self.additional_cassettes[layer];
+        let mut interaction = cassette.interactions[index].clone();
+        interaction.recorded_at = interaction.recorded_at.or(cassette.recorded_at());
+
+        self.play(&interaction, None, None, request).await
+    }
+
+    /// Apply latency and fault injection to a matched interaction and build its response.
+    /// `index` is the interaction's position in the topmost cassette, if it came from there.
+    async fn play(
+        &self,
+        interaction: &Interaction,
+        index: Option<usize>,
+        guard: Option<async_lock::MutexGuardArc<()>>,
+        request: &Request,
+    ) -> Result<Response, Error> {
+        if let Some(state) = &interaction.sets_state {
+            self.set_scenario_state(state.clone());
+        }
+        if let Some(delay) = self.latency.delay_for(interaction) {
+            runtime::Timer::after(delay).await;
+        }
+        if let Some(error) = &interaction.error {
+            return Err(error.to_error());
+        }
+
+        let mut response = self.replay_response(interaction, request).await?;
+        if let Some(fault) = self
+            .fault_injector
+            .as_ref()
+            .and_then(|injector| injector.pick(index))
+        {
+            log::debug!(
+                "Injecting {fault:?} into replayed {} {}",
+                interaction.request.method,
+                interaction.request.url
+            );
+            response = FaultInjector::apply(fault, response).await?;
+        }
+        if let Some(guard) = guard {
+            let body = response.take_body();
+            response.set_body(hold_until_read(body, guard));
+        }
+        Ok(response)
+    }
+
+    /// Common logic for recording a request/response and returning the pristine response
+    async fn record_and_return_response(
+        &self,
+        mut serializable_request: SerializableRequest,
+        response: &mut Response,
+        timing: RequestTiming,
+    ) -> Result<Response, Error> {
+        // IMMEDIATELY create a pristine copy for the caller before any VCR processing
+        let status = response.status();
+        let version = format!("{:?}", response.version());
+
+        let headers = serializable::headers_from(response.as_ref());
+        if streaming::is_event_stream(&headers) {
+            return Ok(self
+                .record_event_stream(serializable_request, response, headers, timing)
+                .await);
+        }
+
+        // Read the body once - this consumes it from the original response. The caller's
+        // response shares the buffer with the recording: text is kept as the `BodyText`
+        // that gets recorded, and binary bodies only get the copy base64 makes.
+        let body = response.take_body();
+        let mime = body.mime().cloned();
+        let body = match body.into_bytes().await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                // If we can't read the body, log it but don't fail the whole request
+                eprintln!("Warning: Failed to read response body for VCR: {e}");
+                Vec::new()
+            }
+        };
+        let elapsed = timing.started.elapsed();
+        let (text, binary) = match String::from_utf8(body) {
+            Ok(text) if text.is_empty() => (None, None),
+            Ok(text) => (Some(BodyText::from(text)), None),
+            Err(e) => (None, Some(Bytes::from(e.into_bytes()))),
+        };
+        let return_body = match (&text, &binary) {
+            (Some(text), _) => Some(serializable::shared_body(text.clone())),
+            (None, Some(bytes)) => Some(serializable::shared_body(bytes.clone())),
+            (None, None) => None,
+        };
+
+        // Create the pristine return response immediately, before any VCR processing
+        let return_response = Self::create_pristine_response(status, &headers, return_body, mime);
+
+        if !self.should_record(&serializable_request) {
+            log::debug!(
+                "Not recording {} {}: excluded by record_if",
+                serializable_request.method,
+                serializable_request.url
+            );
+            return Ok(return_response);
+        }
+
+        // Text is recorded as is and binary bodies byte for byte as base64
+        let body_base64 = binary.map(|bytes| general_purpose::STANDARD.encode(bytes));
+
+        // Now do VCR processing with the data we already extracted
+        let mut serializable_response = crate::SerializableResponse {
+            status: status.into(),
+            headers,
+            body: text,
+            body_base64,
+            version,
+        };
+
+        if self.content_encoding == ContentEncoding::Decode {
+            encoding::decode_response(&mut serializable_response);
+        }
+
+        // Apply filters ONLY to what gets stored
+        self.filter_chain
+            .filter_interaction(&mut serializable_request, &mut serializable_response);
+
+        self.store_interaction(
+            serializable_request,
+            serializable_response,
+            None,
+            None,
+            timing,
+            elapsed,
+        )
+        .await;
+
+        // Return the pristine response we created before any VCR processing
+        Ok(return_response)
+    }
+
+    /// Record a Server-Sent Events response without waiting for the stream to end.
+    /// The caller reads the live stream, and each event is added to the interaction
+    /// as it passes by.
+    async fn record_event_stream(
+        &self,
+        mut serializable_request: SerializableRequest,
+        response: &mut Response,
+        headers: Headers,
+        timing: RequestTiming,
+    ) -> Response {
+        let elapsed = timing.started.elapsed();
+        let mut returned = std::mem::replace(response, Response::new(response.status()));
+        if !self.should_record(&serializable_request) {
+            return returned;
+        }
+
+        let capture = StreamCapture::default();
+        let body = returned.take_body();
+        returned.set_body(streaming::record_body(body, capture.clone()));
+
+        let mut serializable_response = SerializableResponse {
+            status: returned.status().into(),
+            headers,
+            body: None,
+            body_base64: None,
+            version: format!("{:?}", returned.version()),
+        };
+        self.filter_chain
+            .filter_interaction(&mut serializable_request, &mut serializable_response);
+        let sequence = timing.sequence;
+        self.store_interaction(
+            serializable_request,
+            serializable_response,
+            None,
+            Some(Vec::new()),
+            timing,
+            elapsed,
+        )
+        .await;
+        self.stream_captures
+            .lock()
+            .unwrap()
+            .push((sequence, capture));
+        returned
+    }
+
+    /// Record the error the inner client failed a request with, if `record_errors`
+    /// is on, and hand it back to the caller
+    async fn record_error(
+        &self,
+        mut serializable_request: SerializableRequest,
+        error: Error,
+        timing: RequestTiming,
+    ) -> Result<Response, Error> {
+        if !self.record_errors || !self.should_record(&serializable_request) {
+            return Err(error);
+        }
+        let elapsed = timing.started.elapsed();
+        let recorded = RecordedError::from_error(&error);
+        log::debug!(
+            "Recording {:?} error for {} {}",
+            recorded.kind,
+            serializable_request.method,
+            serializable_request.url
+        );
+
+        let mut response = cassette::no_response();
+        self.filter_chain
+            .filter_interaction(&mut serializable_request, &mut response);
+        self.store_interaction(
+            serializable_request,
+            response,
+            Some(recorded),
+            None,
+            timing,
+            elapsed,
+        )
+        .await;
+        Err(error)
+    }
+
+    /// Add a recorded interaction to the cassette in the order its request was sent
+    async fn store_interaction(
+        &self,
+        mut serializable_request: SerializableRequest,
+        serializable_response: SerializableResponse,
+        error: Option<RecordedError>,
+        stream: Option<Vec<StreamChunk>>,
+        timing: RequestTiming,
+        elapsed: std::time::Duration,
+    ) {
+        let mut tags: Vec<String> = serializable_request
+            .headers
+            .shift_remove(TAGS_HEADER)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+        tags.extend(self.tag_scope.read().unwrap().clone());
+        let mut interaction = Interaction {
+            request: serializable_request,
+            response: serializable_response,
+            recorded_at: Some(timing.started_at),
+            elapsed_ms: Some(elapsed.as_millis() as u64),
+            sequence: Some(timing.sequence),
+            error,
+            stream,
+            metadata: Default::default(),
+            tags: Vec::new(),
+            requires_state: self.scenario_state.read().unwrap().clone(),
+            sets_state: None,
+        };
+        for tag in tags {
+            interaction.add_tag(tag);
+        }
+        let Some(mut interaction) = run_record_hooks(&self.record_hooks, interaction) else {
+            log::debug!("Record hook dropped interaction; not recording it");
+            return;
+        };
+        // Hooks can't reorder interactions
+        interaction.sequence = Some(timing.sequence);
+        if let Some(state) = &interaction.sets_state {
+            self.set_scenario_state(state.clone());
+        }
+        for exceeded in self.limits.truncate_bodies(&mut interaction) {
+            self.limit_exceeded(exceeded);
+        }
+
+        let mut cassette = self.cassette.write().await;
+
+        // In Record mode, clear cassette on first interaction to fully replace it, unless appending
+        if matches!(self.mode, VcrMode::Record) && self.record_behavior == RecordBehavior::Replace {
+            let mut recording_started = self.recording_started.lock().await;
+            if !*recording_started {
+                cassette.clear();
+                self.match_index.lock().unwrap().invalidate();
+                *recording_started = true;
+            }
+        }
+
+        if let Some(max) = self.limits.max_interactions {
+            if cassette.interactions.len() >= max {
+                self.limit_exceeded(format!(
+                    "not recording {} {}: the cassette already has the maximum of {max} interactions",
+                    interaction.request.method, interaction.request.url
+                ));
+                return;
+            }
+        }
+
+        if !self.env_snapshot.is_empty() {
+            cassette.record_environment(capture_environment(&self.env_snapshot));
+        }
+
+        // Concurrent requests finish in any order, so record by when they were sent
+        let position = cassette.record_sequenced_interaction(interaction);
+        self.filter_chain.annotate(&mut cassette.metadata);
+        if self.infer_url_templates {
+            cassette.infer_url_template_at(position);
+        }
+        if !self.events.is_empty() {
+            let interaction = &cassette.interactions[position];
+            let outcome = match &interaction.error {
+                Some(error) => format!("{:?}", error.kind),
+                None => interaction.response.status.to_string(),
+            };
+            self.events.emit(VcrEvent::Recorded {
+                index: position,
+                summary: format!(
+                    "{} {} -> {outcome}",
+                    interaction.request.method, interaction.request.url
+                ),
+            });
+        }
+        if position + 1 < cassette.interactions.len() {
+            // Interactions after it moved up one index
+            self.match_index.lock().unwrap().invalidate();
+        }
+        self.recorded_interactions
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let save = self.auto_save(&mut cassette).await;
+        drop(cassette);
+        // Wait for a due save outside the lock, so other requests aren't held up
+        if let Some(save) = save {
+            match save.flush().await {
+                Ok(()) => log::debug!("Auto-saved cassette"),
+                Err(e) => log::warn!("Failed to auto-save cassette: {e}"),
+            }
+        }
+    }
+
+    fn limit_exceeded(&self, exceeded: String) {
+        log::warn!("Cassette limit exceeded: {exceeded}");
+        self.limits_exceeded.lock().unwrap().push(exceeded);
+    }
+
+    async fn handle_none_mode(&self, req: Request) -> Result<Response, Error> {
+        self.inner().send(req).await
+    }
+
+    async fn handle_replay_mode(&self, req: Request) -> Result<Response, Error> {
+        let filtered_request = self.filtered_request(&req).await;
+        let cassette = self.cassette.read().await;
+        let mut claimed = self
+            .claim_match(&req, filtered_request.as_ref(), &cassette)
+            .await;
+        drop(cassette); // Release the lock before fetching more interactions
+        if claimed.is_none() && self.fetch_candidates(filtered_request.as_ref()).await? {
+            let cassette = self.cassette.read().await;
+            claimed = self
+                .claim_match(&req, filtered_request.as_ref(), &cassette)
+                .await;
+        }
+        if let Some(index) = claimed {
+            self.replay_interaction(index, &req).await
+        } else {
+            if let Some((layer, index)) = self
+                .claim_shared_match(&req, filtered_request.as_ref())
+                .await
+            {
+                return self.replay_shared_interaction(layer, index, &req).await;
+            }
+            self.handle_miss(req, "Replay mode").await
+        }
+    }
+
+    async fn handle_record_mode(&self, req: Request) -> Result<Response, Error> {
+        // Duplicate the request to preserve the body for both sending and recording
+        let (req_for_sending, req_for_recording) = duplicate_request_with_body(req).await?;
+        let req_for_recording = SerializableRequest::from_request(req_for_recording).await?;
+
+        // Make the real request with original sensitive data - never match existing interactions
+        let timing = RequestTiming::start();
+        let mut response = match self.inner().send(req_for_sending).await {
+            Ok(response) => response,
+            Err(e) => return self.record_error(req_for_recording, e, timing).await,
+        };
+        self.record_and_return_response(req_for_recording, &mut response, timing)
+            .await
+    }
+
+    async fn handle_once_mode(&self, req: Request) -> Result<Response, Error> {
+        let filtered_request = self.filtered_request(&req).await;
+        let cassette = self.cassette.read().await;
+        if let Some(index) = self
+            .claim_match(&req, filtered_request.as_ref(), &cassette)
+            .await
+        {
+            drop(cassette); // Release the lock before replaying the claimed interaction
+            return self.replay_interaction(index, &req).await;
+        }
+
+        let cassette_is_empty = cassette.is_empty();
         drop(cassette); // Release the lock before making the request
 
+        if let Some((layer, index)) = self
+            .claim_shared_match(&req, filtered_request.as_ref())
+            .await
+        {
+            return self.replay_shared_interaction(layer, index, &req).await;
+        }
+
         // Duplicate the request to preserve the body for both sending and recording
         let (req_for_sending, req_for_recording) = duplicate_request_with_body(req).await?;
+        let req_for_recording = SerializableRequest::from_request(req_for_recording).await?;
+
+        if !cassette_is_empty {
+            if self.should_record(&req_for_recording) {
+                return self.handle_miss(req_for_sending, "Once mode").await;
+            }
+            // Requests excluded from recording are passed straight through
+            return self.inner().send(req_for_sending).await;
+        }
 
         // Make the real request with original sensitive data
-        let mut response = self.inner.send(req_for_sending).await?;
-        self.record_and_return_response(req_for_recording, &mut response)
+        let timing = RequestTiming::start();
+        let mut response = match self.inner().send(req_for_sending).await {
+            Ok(response) => response,
+            Err(e) => return self.record_error(req_for_recording, e, timing).await,
+        };
+        self.record_and_return_response(req_for_recording, &mut response, timing)
             .await
     }
 
     async fn handle_filter_mode(&self, req: Request) -> Result<Response, Error> {
-        let cassette = self.cassette.lock().await;
-        if let Some((index, _interaction)) = self.find_match(&req, &cassette).await {
-            // Mark this interaction as used
-            drop(cassette); // Release cassette lock before acquiring used_interactions lock
-            let mut used_interactions = self.used_interactions.lock().await;
-            used_interactions.insert(index);
-            drop(used_interactions); // Release used_interactions lock
-
-            // Re-acquire cassette lock to access the interaction
-            let cassette = self.cassette.lock().await;
-            let interaction = &cassette.interactions[index];
-            // Return the filtered response (filters are already applied when loading)
-            Ok(interaction.response.to_response().await)
+        let filtered_request = self.filtered_request(&req).await;
+        let cassette = self.cassette.read().await;
+        if let Some(index) = self
+            .claim_match(&req, filtered_request.as_ref(), &cassette)
+            .await
+        {
+            drop(cassette); // Release the lock before replaying the claimed interaction
+                            // Return the filtered response (filters are already applied when loading)
+            self.replay_interaction(index, &req).await
         } else {
             drop(cassette); // Release the lock before calling generate_no_match_error
-            Err(self
-                .generate_no_match_error(&req, "Filter mode - no new requests allowed")
-                .await)
+            if let Some((layer, index)) = self
+                .claim_shared_match(&req, filtered_request.as_ref())
+                .await
+            {
+                return self.replay_shared_interaction(layer, index, &req).await;
+            }
+            self.handle_miss(req, "Filter mode - no new requests allowed")
+                .await
         }
     }
 }
 
+/// The mode `request` asks for with `MODE_HEADER`, which is removed, or a `VcrMode`
+/// extension
+fn take_mode_override(request: &mut Request) -> Result<Option<VcrMode>, Error> {
+    let Some(values) = request.remove_header(MODE_HEADER) else {
+        return Ok(request.ext().get::<VcrMode>().cloned());
+    };
+    let value = values.last().as_str();
+    if value.trim().eq_ignore_ascii_case("bypass") {
+        return Ok(Some(VcrMode::None));
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|e: String| Error::from_str(400, format!("Invalid {MODE_HEADER} header: {e}")))
+}
+
+/// List the interactions at `indices`, one `#index: METHOD url` per line
+fn describe_interactions(cassette: &Cassette, indices: &[usize]) -> String {
+    indices
+        .iter()
+        .map(|&index| {
+            let request = &cassette.interactions[index].request;
+            format!("  #{index}: {} {}", request.method, request.url)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 // Re-export utility functions from the utils module
 pub use utils::*;
 
 #[derive(Debug)]
 pub struct VcrClientBuilder {
     inner: Option<Box<dyn HttpClient>>,
+    inner_factory: Option<InnerClientFactory>,
     mode: VcrMode,
-    cassette_path: PathBuf,
+    shared_cassette: SharedCassetteAction,
+    // Environment variable the mode is read from at build time
+    mode_env: Option<String>,
+    record_behavior: RecordBehavior,
+    persistence: Persistence,
+    storage: Option<Box<dyn CassetteStorage>>,
+    lazy_load: bool,
+    auto_save: Option<AutoSave>,
+    save_on_drop: bool,
+    background_save: bool,
+    #[cfg(feature = "fs")]
+    exclusive_lock: bool,
+    // As given, with placeholders; expanded and resolved when building
+    cassette_path: Option<PathBuf>,
+    cassette_dir: Option<PathBuf>,
+    // Relative cassette paths are resolved against this, from `with_config`
+    cassette_root: Option<PathBuf>,
+    test_name: Option<String>,
+    cassette: Option<Cassette>,
+    additional_cassettes: Vec<PathBuf>,
     matcher: Option<Box<dyn RequestMatcher>>,
     filter_chain: FilterChain,
     format: Option<CassetteFormat>,
+    body_compression: Option<BodyCompression>,
+    chunked_replay: Option<ChunkedReplay>,
+    response_templating: bool,
+    infer_url_templates: bool,
+    content_encoding: ContentEncoding,
+    follow_redirects: Option<usize>,
+    record_errors: bool,
+    stream_replay_speed: f64,
+    time_shift: Option<TimeShift>,
+    latency: Option<LatencyMode>,
+    host_latency: Vec<(String, LatencyMode)>,
+    connection_replay: Option<ConnectionReplay>,
+    fault_injector: Option<FaultInjector>,
+    replay_validator: Option<ReplayValidator>,
+    ignored_hosts: Vec<String>,
+    record_predicate: Option<RecordPredicate>,
+    miss_behavior: MissBehavior,
+    env_snapshot: Vec<String>,
+    strict_sanitization: Option<UnsanitizedAction>,
+    limits: CassetteLimits,
+    strict_playback: bool,
+    tag_scope: Option<String>,
+    events: EventHandlers,
+    record_hooks: Vec<Box<dyn RecordHook>>,
+    replay_transformers: Vec<Box<dyn ReplayTransformer>>,
+    re_record_interval: Option<std::time::Duration>,
+    recorded_by: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    rotation: Option<Rotation>,
 }
 
 impl VcrClientBuilder {
+    /// Create a builder for the cassette at `cassette_path`.
+    ///
+    /// No `vcr.toml` is read; pass one to [`with_config`](Self::with_config) to use
+    /// its defaults and `cassette_root`.
+    ///
+    /// The path can contain `{test}`, replaced with the name of the running test (see
+    /// [`test_name`](Self::test_name)), and `{mode}`, replaced with the mode's name,
+    /// e.g. `tests/fixtures/{test}/{mode}.yaml`.
     pub fn new<P: Into<PathBuf>>(cassette_path: P) -> Self {
+        Self::with_defaults(Some(cassette_path.into()), None)
+    }
+
+    /// Create a builder for a cassette in `dir`, named with
+    /// [`cassette_name`](Self::cassette_name) or after the running test
+    /// (`{test}.yaml`) by default
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::VcrClientBuilder;
+    /// let builder = VcrClientBuilder::cassette_dir("tests/fixtures").cassette_name("login.yaml");
+    /// ```
+    pub fn cassette_dir<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            cassette_dir: Some(dir.into()),
+            ..Self::with_defaults(None, None)
+        }
+    }
+
+    /// The cassette's file or directory name, under the `cassette_dir` if one is set.
+    /// Can contain the same placeholders as the path given to [`new`](Self::new).
+    pub fn cassette_name<P: Into<PathBuf>>(mut self, name: P) -> Self {
+        self.cassette_path = Some(name.into());
+        self
+    }
+
+    /// The test name the `{test}` placeholder is replaced with. Defaults to the
+    /// `VCR_TEST_NAME` environment variable, then to the name the test harness gives
+    /// the test's thread, like `checkout::test_pays`. `::` becomes a directory.
+    pub fn test_name(mut self, test_name: impl Into<String>) -> Self {
+        self.test_name = Some(test_name.into());
+        self
+    }
+
+    /// Start from an already constructed cassette instead of loading one from disk.
+    ///
+    /// The cassette keeps its own path (if any) unless overridden with `with_save_path`.
+    pub fn from_cassette(cassette: Cassette) -> Self {
+        Self::with_defaults(None, Some(cassette))
+    }
+
+    /// Start from an empty cassette that is never loaded from or saved to disk, for
+    /// unit tests and doctests that shouldn't need a temporary directory
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{NoOpClient, VcrClient, VcrMode};
+    /// # async fn example() -> Result<(), http_types::Error> {
+    /// let client = VcrClient::in_memory()
+    ///     .inner_client(Box::new(NoOpClient::new()))
+    ///     .mode(VcrMode::Record)
+    ///     .build()
+    ///     .await?;
+    /// // ... send requests ...
+    /// let recorded = client.interactions().await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn in_memory() -> Self {
+        Self::from_cassette(Cassette::new()).persistence(Persistence::InMemory)
+    }
+
+    fn with_defaults(cassette_path: Option<PathBuf>, cassette: Option<Cassette>) -> Self {
         Self {
             inner: None,
+            inner_factory: None,
             mode: VcrMode::Once,
-            cassette_path: cassette_path.into(),
+            shared_cassette: SharedCassetteAction::default(),
+            mode_env: None,
+            record_behavior: RecordBehavior::default(),
+            persistence: Persistence::Disk,
+            storage: None,
+            lazy_load: false,
+            auto_save: None,
+            save_on_drop: true,
+            background_save: false,
+            #[cfg(feature = "fs")]
+            exclusive_lock: false,
+            cassette_path,
+            cassette_dir: None,
+            cassette_root: None,
+            test_name: None,
             matcher: None,
-            filter_chain: FilterChain::new(),
+            filter_chain: FilterChain::default(),
             format: None,
+            body_compression: None,
+            cassette,
+            additional_cassettes: Vec::new(),
+            chunked_replay: None,
+            response_templating: false,
+            infer_url_templates: false,
+            content_encoding: ContentEncoding::default(),
+            follow_redirects: None,
+            record_errors: false,
+            stream_replay_speed: 1.0,
+            time_shift: None,
+            latency: None,
+            host_latency: Vec::new(),
+            connection_replay: None,
+            fault_injector: None,
+            replay_validator: None,
+            ignored_hosts: Vec::new(),
+            record_predicate: None,
+            miss_behavior: MissBehavior::default(),
+            env_snapshot: Vec::new(),
+            strict_sanitization: None,
+            limits: CassetteLimits::default(),
+            strict_playback: false,
+            tag_scope: None,
+            events: EventHandlers::default(),
+            record_hooks: Vec::new(),
+            replay_transformers: Vec::new(),
+            re_record_interval: None,
+            recorded_by: None,
+            description: None,
+            tags: Vec::new(),
+            rotation: None,
         }
     }
 
+    /// Set the path the cassette is saved to
+    pub fn with_save_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.cassette_path = Some(path.into());
+        self
+    }
+
+    /// The client real requests are sent through.
+    ///
+    /// Optional when the mode never sends any: Replay and Filter clients that don't
+    /// forward misses get a [`NoOpClient`] instead, and reject requests that ask for a
+    /// mode that sends them through `MODE_HEADER`.
     pub fn inner_client(mut self, client: Box<dyn HttpClient>) -> Self {
         self.inner = Some(client);
         self
     }
 
+    /// Create the inner client only if the resolved mode needs one, so replaying
+    /// tests don't pay for building a real client. Ignored if `inner_client` is set.
+    pub fn inner_client_factory<F>(mut self, factory: F) -> Self
+    where
+        F: FnOnce() -> Box<dyn HttpClient> + Send + 'static,
+    {
+        self.inner_factory = Some(InnerClientFactory(Box::new(factory)));
+        self
+    }
+
     pub fn mode(mut self, mode: VcrMode) -> Self {
         self.mode = mode;
+        self.mode_env = None;
+        self
+    }
+
+    /// Read the mode from the environment variable `name` when the client is built,
+    /// e.g. `VCR_MODE=record cargo test`. Values are parsed with `VcrMode::from_str`
+    /// and an invalid one fails the build. When the variable is unset or empty, the
+    /// client replays if the cassette exists and records a new one otherwise.
+    pub fn mode_from_env(mut self, name: impl Into<String>) -> Self {
+        self.mode_env = Some(name.into());
+        self
+    }
+
+    /// Whether Record mode replaces the cassette's interactions (the default) or appends
+    /// to them, e.g. to add one new scenario without re-recording the others
+    pub fn record_behavior(mut self, record_behavior: RecordBehavior) -> Self {
+        self.record_behavior = record_behavior;
+        self
+    }
+
+    /// Keep the cassette on disk (the default) or in memory only
+    pub fn persistence(mut self, persistence: Persistence) -> Self {
+        self.persistence = persistence;
+        self
+    }
+
+    /// Load and save cassettes through `storage` instead of the local filesystem.
+    /// The cassette path is passed to the storage as the cassette's key.
+    pub fn storage(mut self, storage: Box<dyn CassetteStorage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Replay without loading the cassette's interactions up front: on a miss, fetch
+    /// the ones recorded for the request's method and URL (after filtering) from the
+    /// storage through [`CassetteStorage::find`], e.g. from a huge `SqliteStorage`
+    /// cassette. Storages that can't look interactions up are loaded in full on the
+    /// first miss instead.
+    ///
+    /// Only for Replay mode, and the cassette can't be saved. Matchers that ignore
+    /// parts of the URL only see interactions recorded with the same URL, and
+    /// `playback_stats` and `unused_interactions` only cover the fetched ones.
+    ///
+    /// ```rust,no_run
+    /// # #[cfg(feature = "sqlite")]
+    /// # async fn example() -> Result<(), http_types::Error> {
+    /// # use http_client_vcr::{SqliteStorage, VcrClient, VcrMode};
+    /// let vcr_client = VcrClient::builder("suite/big.yaml")
+    ///     .storage(Box::new(SqliteStorage::open("tests/fixtures/cassettes.db")?))
+    ///     .mode(VcrMode::Replay)
+    ///     .lazy_load(true)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lazy_load(mut self, lazy_load: bool) -> Self {
+        self.lazy_load = lazy_load;
+        self
+    }
+
+    /// Save the cassette periodically while recording instead of only when the client
+    /// is dropped, so a panic late in a long recording session doesn't lose it all.
+    /// Accepts a number of interactions or a `Duration`:
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::VcrClient;
+    /// # use std::time::Duration;
+    /// let builder = VcrClient::builder("tests/fixtures/crawl.yaml").auto_save_every(50);
+    /// let builder = VcrClient::builder("tests/fixtures/crawl.yaml")
+    ///     .auto_save_every(Duration::from_secs(30));
+    /// ```
+    pub fn auto_save_every(mut self, auto_save: impl Into<AutoSave>) -> Self {
+        self.auto_save = Some(auto_save.into());
+        self
+    }
+
+    /// Whether to save a modified cassette when the client is dropped (the default).
+    /// The save on drop is best effort: it is skipped if the cassette is in use and
+    /// can only report errors on stderr. Turn it off when calling
+    /// [`VcrClient::finalize`] explicitly, so a forgotten call fails loudly instead
+    /// of half-saving.
+    pub fn save_on_drop(mut self, save_on_drop: bool) -> Self {
+        self.save_on_drop = save_on_drop;
+        self
+    }
+
+    /// Run auto-saves on background tasks, so recording isn't slowed down by writing
+    /// a large cassette. `VcrClient::flush` (or `finalize`) waits for them.
+    pub fn background_save(mut self, background_save: bool) -> Self {
+        self.background_save = background_save;
+        self
+    }
+
+    /// In Record mode, hold an exclusive lock on the cassette file from before it is
+    /// loaded until the client is dropped, so parallel test processes recording the
+    /// same cassette (e.g. under nextest) take turns instead of overwriting each
+    /// other. Other processes block until the lock is released.
+    #[cfg(feature = "fs")]
+    pub fn exclusive_lock(mut self, exclusive_lock: bool) -> Self {
+        self.exclusive_lock = exclusive_lock;
+        self
+    }
+
+    /// Also replay from the cassette at `path`, searched after the main cassette and
+    /// any additional cassettes added before it. Lets tests share common interactions,
+    /// e.g. a login flow, instead of duplicating them into every cassette. New
+    /// interactions are only recorded into the main cassette.
+    pub fn additional_cassette<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.additional_cassettes.push(path.into());
+        self
+    }
+
+    /// What to do when another client in this process is still recording the same
+    /// cassette. Building the client fails by default, since whichever of the two saves
+    /// last would silently throw away the other's recordings.
+    pub fn on_shared_cassette(mut self, action: SharedCassetteAction) -> Self {
+        self.shared_cassette = action;
         self
     }
 
@@ -571,6 +2692,42 @@ impl VcrClientBuilder {
         self
     }
 
+    /// Use the defaults of a `vcr.toml` for whatever isn't set on the builder: the
+    /// matcher, the filters, the format of new cassettes, and the `cassette_root`
+    /// relative cassette paths are resolved against.
+    ///
+    /// This is the only way a client picks up a `vcr.toml`. Unlike `vcr-inspect`, the
+    /// library never looks for one by itself, so library users who want the project
+    /// defaults have to call this with `VcrConfig::discover()?` on every builder, or
+    /// once through `global().configure` or `VcrTestContext::with_config`.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{VcrClientBuilder, VcrConfig};
+    /// # fn run() -> Result<(), http_client::Error> {
+    /// let builder = VcrClientBuilder::new("login.yaml").with_config(VcrConfig::discover()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_config(mut self, config: VcrConfig) -> Self {
+        if self.matcher.is_none() {
+            if let Some(profile) = config.matcher {
+                self.matcher = Some(profile.matcher());
+                self.infer_url_templates |= profile == MatcherProfile::Template;
+            }
+        }
+        if self.filter_chain.is_empty() {
+            if let Some(profile) = config.filter_profile {
+                self.filter_chain = profile.filter_chain();
+            }
+        }
+        // A pre-loaded cassette already knows its format
+        if self.format.is_none() && self.cassette.is_none() {
+            self.format = config.format;
+        }
+        self.cassette_root = config.cassette_root;
+        self
+    }
+
     pub fn filter_chain(mut self, filter_chain: FilterChain) -> Self {
         self.filter_chain = filter_chain;
         self
@@ -581,104 +2738,751 @@ impl VcrClientBuilder {
         self
     }
 
+    /// Replace the value returned by `value` with `placeholder` wherever it appears in
+    /// stored URLs, headers and bodies, restoring it in replayed responses.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::VcrClient;
+    /// let builder = VcrClient::builder("tests/fixtures/api.yaml")
+    ///     .filter_sensitive_data(|| std::env::var("API_KEY").unwrap_or_default(), "<API_KEY>");
+    /// ```
+    pub fn filter_sensitive_data<F>(self, value: F, placeholder: impl Into<String>) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.add_filter(Box::new(SensitiveDataFilter::new(value, placeholder)))
+    }
+
     pub fn format(mut self, format: CassetteFormat) -> Self {
         self.format = Some(format);
         self
     }
 
-    pub async fn build(self) -> Result<VcrClient, Error> {
-        let inner = self
-            .inner
-            .ok_or_else(|| Error::from_str(400, "Inner HttpClient is required"))?;
+    /// Compress large body files of directory cassettes when saving them, e.g.
+    /// `BodyCompression::zstd(64 * 1024)` with the `zstd` feature
+    pub fn body_compression(mut self, body_compression: BodyCompression) -> Self {
+        self.body_compression = Some(body_compression);
+        self
+    }
+
+    /// Replay recorded response bodies in paced chunks
+    pub fn chunked_replay(mut self, chunked_replay: ChunkedReplay) -> Self {
+        self.chunked_replay = Some(chunked_replay);
+        self
+    }
 
-        let cassette = if self.cassette_path.exists() {
-            Cassette::load_from_file(self.cassette_path.clone()).await?
-        } else {
-            let mut cassette = Cassette::new().with_path(self.cassette_path);
-            if let Some(format) = self.format {
-                cassette = cassette.with_format(format);
+    /// Expand template directives such as `{{now_iso8601}}`, `{{uuid}}` or
+    /// `{{request.url.param.id}}` in replayed response headers and bodies, so clients
+    /// that check freshness or expect request parameters echoed back keep working.
+    /// Off by default, since recorded responses could contain `{{` by coincidence.
+    pub fn response_templating(mut self, enabled: bool) -> Self {
+        self.response_templating = enabled;
+        self
+    }
+
+    /// Store the URL template of each recorded request, e.g.
+    /// `https://api.example.com/users/{id}`, in the cassette metadata, where
+    /// `vcr-inspect` groups requests by them and `TemplateMatcher::with_templates`
+    /// can be given them. Off by default; the `template` matcher profile of `vcr.toml`
+    /// turns it on.
+    pub fn infer_url_templates(mut self, enabled: bool) -> Self {
+        self.infer_url_templates = enabled;
+        self
+    }
+
+    /// Whether compressed response bodies are stored as received or decoded. See
+    /// [`ContentEncoding`]; the default keeps them as received.
+    pub fn content_encoding(mut self, content_encoding: ContentEncoding) -> Self {
+        self.content_encoding = content_encoding;
+        self
+    }
+
+    /// Follow redirects in the client itself, up to `max_redirects` per request,
+    /// recording every hop (the `301`/`302` with its `Location` and the request
+    /// that followed it) as its own interaction. On replay each hop is matched on
+    /// its own, so cassettes exercise the redirect handling instead of hiding it.
+    ///
+    /// The inner client must not follow redirects itself, e.g. reqwest needs
+    /// `redirect::Policy::none()`, or only the final response is seen here.
+    pub fn follow_redirects(mut self, max_redirects: usize) -> Self {
+        self.follow_redirects = Some(max_redirects);
+        self
+    }
+
+    /// Record errors returned by the inner client, such as timeouts, refused
+    /// connections or DNS failures, as interactions of their own. Replaying one
+    /// returns an error of the same kind instead of a response, so error handling
+    /// can be tested from cassettes. Off by default, so a flaky network while
+    /// recording fails the request without leaving anything in the cassette.
+    pub fn record_errors(mut self, record_errors: bool) -> Self {
+        self.record_errors = record_errors;
+        self
+    }
+
+    /// How fast recorded Server-Sent Events are replayed: `1.0` (the default)
+    /// keeps the recorded gaps between events, `10.0` makes them ten times
+    /// shorter and `f64::INFINITY` sends every event without waiting
+    pub fn stream_replay_speed(mut self, speed: f64) -> Self {
+        self.stream_replay_speed = speed;
+        self
+    }
+
+    /// Shift timestamps in replayed `Date`/`Expires` headers and JSON bodies forward by
+    /// the time elapsed since recording
+    pub fn time_shift(mut self, time_shift: TimeShift) -> Self {
+        self.time_shift = Some(time_shift);
+        self
+    }
+
+    /// Wait before returning replayed responses, e.g. `LatencyMode::Recorded` to sleep
+    /// for as long as the server took when the interaction was recorded
+    pub fn simulate_latency(mut self, latency: LatencyMode) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Like `simulate_latency`, but only for responses from `host`, taking precedence
+    /// over the latency set for all hosts
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{LatencyMode, VcrClient};
+    /// # use std::time::Duration;
+    /// let builder = VcrClient::builder("tests/fixtures/api.yaml").simulate_latency_for_host(
+    ///     "slow.example.com",
+    ///     LatencyMode::Jitter {
+    ///         min: Duration::from_millis(200),
+    ///         max: Duration::from_millis(800),
+    ///     },
+    /// );
+    /// ```
+    pub fn simulate_latency_for_host(
+        mut self,
+        host: impl Into<String>,
+        latency: LatencyMode,
+    ) -> Self {
+        self.host_latency.push((host.into(), latency));
+        self
+    }
+
+    /// Serialize replayed responses per host to emulate HTTP/1.1 head-of-line blocking.
+    /// Without this, concurrent requests are replayed independently, like HTTP/2.
+    pub fn connection_replay(mut self, connection_replay: ConnectionReplay) -> Self {
+        self.connection_replay = Some(connection_replay);
+        self
+    }
+
+    /// Replace some replayed responses with failures, to drive resilience tests from
+    /// the same cassettes as the happy-path tests
+    ///
+    /// ```no_run
+    /// use http_client_vcr::{Fault, FaultInjector, VcrClient};
+    ///
+    /// let builder = VcrClient::builder("tests/fixtures/api.yaml").fault_injector(
+    ///     FaultInjector::new()
+    ///         .at_interactions([2], Fault::ConnectionError)
+    ///         .with_probability(0.1, Fault::Status(http_types::StatusCode::TooManyRequests)),
+    /// );
+    /// ```
+    pub fn fault_injector(mut self, fault_injector: FaultInjector) -> Self {
+        self.fault_injector = Some(fault_injector);
+        self
+    }
+
+    /// Capture these environment variables into the cassette metadata when recording.
+    /// Names that look like secrets are never captured; see [`capture_environment`].
+    pub fn snapshot_env<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.env_snapshot.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Run the cassette analyzer before saving and refuse to save if credential fields,
+    /// auth headers or random-looking tokens survived filtering
+    pub fn strict_sanitization(mut self, enabled: bool) -> Self {
+        self.strict_sanitization = enabled.then_some(UnsanitizedAction::Error);
+        self
+    }
+
+    /// Like `strict_sanitization`, but save the cassette without the offending
+    /// interactions instead of failing
+    pub fn drop_unsanitized_interactions(mut self) -> Self {
+        self.strict_sanitization = Some(UnsanitizedAction::Drop);
+        self
+    }
+
+    /// Cap the size of recorded bodies and the number of recorded interactions,
+    /// warning (or failing the save) when a recording goes over
+    pub fn limits(mut self, limits: CassetteLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Verify the cassette was used exactly as recorded: when replaying, `finalize()`
+    /// fails (and dropping the client without finalizing prints an error) listing the
+    /// recorded interactions that were never replayed and any unmatched requests
+    /// forwarded to the inner client
+    pub fn strict_playback(mut self, enabled: bool) -> Self {
+        self.strict_playback = enabled;
+        self
+    }
+
+    /// Start scoped to interactions tagged with `tag`, like `VcrClient::scope_to_tag`
+    pub fn scope_to_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag_scope = Some(tag.into());
+        self
+    }
+
+    /// Check every replayed interaction before its response is returned, failing the
+    /// request with the returned error. Useful for enforcing invariants across a whole
+    /// suite, e.g. that every replayed response has a `content-type`.
+    pub fn on_replay_validate<F>(mut self, validate: F) -> Self
+    where
+        F: Fn(&Interaction) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.replay_validator = Some(ReplayValidator::new(validate));
+        self
+    }
+
+    /// Call `handler` with a `VcrEvent` whenever an interaction is recorded or
+    /// replayed, a request misses, or the cassette is saved, so test harnesses can
+    /// log, assert on or count them. Can be called more than once; handlers run in
+    /// the order they were added, on the task that caused the event (or the
+    /// background thread, for background saves).
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{VcrClient, VcrEvent};
+    /// let builder = VcrClient::builder("tests/fixtures/api.yaml").on_event(|event| {
+    ///     if let VcrEvent::Miss { request_summary } = event {
+    ///         eprintln!("cassette miss: {request_summary}");
+    ///     }
+    /// });
+    /// ```
+    pub fn on_event<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(VcrEvent) + Send + Sync + 'static,
+    {
+        self.events.push(handler);
+        self
+    }
+
+    /// Pass every interaction through `hook` just before it is written to the
+    /// cassette, to modify it or return `None` to leave it out. Can be called more
+    /// than once; hooks run in the order they were added, and the first to drop an
+    /// interaction stops the rest.
+    pub fn record_hook(mut self, hook: Box<dyn RecordHook>) -> Self {
+        self.record_hooks.push(hook);
+        self
+    }
+
+    /// Pass every replayed response through `transformer` right before it is
+    /// returned, e.g. to give it a fresh request ID, without editing the cassette.
+    /// Can be called more than once; transformers run in the order they were added.
+    pub fn replay_transformer(mut self, transformer: Box<dyn ReplayTransformer>) -> Self {
+        self.replay_transformers.push(transformer);
+        self
+    }
+
+    /// Who is recording, stored in the cassette metadata (e.g. a user or CI job name)
+    pub fn recorded_by(mut self, recorded_by: impl Into<String>) -> Self {
+        self.recorded_by = Some(recorded_by.into());
+        self
+    }
+
+    /// Human-readable description stored in the cassette metadata
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Tag stored in the cassette metadata
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Split a single-file cassette into numbered part files once a part reaches
+    /// `rotation`'s size. Loading the cassette path reads all the parts back.
+    pub fn rotate(mut self, rotation: Rotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// Re-record the cassette instead of replaying it once it is older than `interval`.
+    ///
+    /// Only applies in `Once` mode, where a real inner client is configured; other modes
+    /// log a warning about the stale cassette and carry on.
+    pub fn re_record_interval(mut self, interval: std::time::Duration) -> Self {
+        self.re_record_interval = Some(interval);
+        self
+    }
+
+    /// Only save requests for which `predicate` returns true in Record/Once mode. Other
+    /// requests (health checks, metrics pings, CORS preflights, ...) are passed through
+    /// to the inner client without being saved, even once the cassette exists.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::VcrClient;
+    /// let builder = VcrClient::builder("tests/fixtures/api.yaml")
+    ///     .record_if(|req| req.method != "OPTIONS" && !req.url.ends_with("/healthz"));
+    /// ```
+    pub fn record_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&SerializableRequest) -> bool + Send + Sync + 'static,
+    {
+        self.record_predicate = Some(RecordPredicate::new(predicate));
+        self
+    }
+
+    /// What to do with requests that match no recorded interaction, instead of failing.
+    /// Lets most traffic replay from the cassette while one endpoint is mocked on the fly,
+    /// or, with [`MissBehavior::passthrough_with_warning`], lets tests without complete
+    /// cassettes keep talking to the real service while reporting what they're missing.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{MissAction, MissBehavior, VcrClient};
+    /// # use http_client::Response;
+    /// let builder = VcrClient::builder("tests/fixtures/api.yaml").on_miss(MissBehavior::handler(
+    ///     |req| {
+    ///         if req.url().path() == "/v1/clock" {
+    ///             let mut response = Response::new(200);
+    ///             response.set_body(r#"{"now": 0}"#);
+    ///             MissAction::Respond(Box::new(response))
+    ///         } else {
+    ///             MissAction::Forward
+    ///         }
+    ///     },
+    /// ));
+    /// ```
+    pub fn on_miss(mut self, miss_behavior: MissBehavior) -> Self {
+        self.miss_behavior = miss_behavior;
+        self
+    }
+
+    /// Send requests to these hosts straight to the inner client in every mode, never
+    /// recording or matching them, e.g. for a local test container the code under test
+    /// also talks to. A leading `*.` matches any subdomain.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::VcrClient;
+    /// let builder = VcrClient::builder("tests/fixtures/api.yaml")
+    ///     .ignore_hosts(["localhost", "127.0.0.1", "telemetry.example.com"]);
+    /// ```
+    pub fn ignore_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignored_hosts.extend(hosts.into_iter().map(Into::into));
+        self
+    }
+
+    pub async fn build(mut self) -> Result<VcrClient, Error> {
+        let inner = self.inner.take();
+        let inner_factory = self.inner_factory.take();
+        let mut placeholder_inner = false;
+        let mut vcr_client = self
+            .assemble(|mode, needs_inner| {
+                Ok(match (inner, inner_factory) {
+                    (Some(inner), _) => inner,
+                    (None, Some(factory)) if needs_inner => (factory.0)(),
+                    (None, _) if needs_inner => {
+                        return Err(Error::from_str(
+                            400,
+                            format!("Inner HttpClient is required in {mode:?} mode"),
+                        ))
+                    }
+                    (None, _) => {
+                        placeholder_inner = true;
+                        Box::new(NoOpClient::new())
+                    }
+                })
+            })
+            .await?;
+        vcr_client.placeholder_inner = placeholder_inner;
+        Ok(vcr_client)
+    }
+
+    /// Build a client around `inner` as its concrete type instead of a boxed
+    /// `HttpClient`, ignoring `inner_client`. The client stays reachable through
+    /// `VcrClient::inner`, `inner_mut` and `into_inner`.
+    ///
+    /// ```rust,no_run
+    /// # use http_client_vcr::{NoOpClient, VcrClient, VcrMode};
+    /// # async fn example() -> Result<(), http_types::Error> {
+    /// let vcr_client = VcrClient::builder("tests/fixtures/api.yaml")
+    ///     .mode(VcrMode::Replay)
+    ///     .build_with(NoOpClient::new())
+    ///     .await?;
+    /// let inner: &NoOpClient = vcr_client.inner();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build_with<C: HttpClient>(self, inner: C) -> Result<VcrClient<C>, Error> {
+        self.assemble(|_, _| Ok(inner)).await
+    }
+
+    /// Load the cassette and configure a client around the inner client returned by
+    /// `inner`, which is told the final mode and whether that mode needs one
+    async fn assemble<C, F>(self, inner: F) -> Result<VcrClient<C>, Error>
+    where
+        C: HttpClient,
+        F: FnOnce(&VcrMode, bool) -> Result<C, Error>,
+    {
+        let storage: Arc<dyn CassetteStorage> = self.storage.unwrap_or_else(default_storage).into();
+
+        // None until the cassette is loaded if the mode depends on whether it exists
+        let requested_mode = match &self.mode_env {
+            Some(name) => match std::env::var(name) {
+                Ok(value) if !value.trim().is_empty() => Some(
+                    value
+                        .parse::<VcrMode>()
+                        .map_err(|e| Error::from_str(400, format!("{name}: {e}")))?,
+                ),
+                _ => None,
+            },
+            None => Some(self.mode),
+        };
+
+        let cassette_path = match (self.cassette_path, &self.cassette_dir) {
+            (Some(path), Some(dir)) => Some(dir.join(path)),
+            (None, Some(dir)) => Some(dir.join("{test}.yaml")),
+            (path, None) => path,
+        };
+        let cassette_path = cassette_path
+            .map(|path| {
+                cassette_path::expand(&path, self.test_name.as_deref(), requested_mode.as_ref())
+            })
+            .transpose()?
+            .map(|path| config::resolve_cassette_path(self.cassette_root.as_deref(), path));
+
+        // Taken before loading so nobody else writes the cassette in between
+        #[cfg(feature = "fs")]
+        let lock_path = cassette_path.clone().or(self
+            .cassette
+            .as_ref()
+            .and_then(|cassette| cassette.path.clone()));
+        #[cfg(feature = "fs")]
+        let wants_lock = |mode: &VcrMode| {
+            self.exclusive_lock
+                && self.persistence == Persistence::Disk
+                && matches!(mode, VcrMode::Record)
+        };
+        #[cfg(feature = "fs")]
+        let mut session_lock = match (&lock_path, &requested_mode) {
+            (Some(path), Some(mode)) if wants_lock(mode) => Some(SessionLock::acquire(path)?),
+            _ => None,
+        };
+
+        let mut cassette_exists = true;
+        let mut lazy = false;
+        let cassette = match (self.cassette, cassette_path) {
+            (Some(mut cassette), save_path) => {
+                if let Some(path) = save_path {
+                    cassette = cassette.with_path(path);
+                }
+                if let Some(format) = self.format {
+                    cassette = cassette.with_format(format);
+                }
+                cassette
+            }
+            (None, path) => {
+                let loaded = match &path {
+                    Some(path) if self.persistence == Persistence::Disk && self.lazy_load => {
+                        lazy = true;
+                        let storage = Arc::clone(&storage);
+                        let path = path.clone();
+                        runtime::unblock(move || storage.load_metadata(&path)).await?
+                    }
+                    Some(path) if self.persistence == Persistence::Disk => {
+                        load_cassette(&storage, path).await?
+                    }
+                    _ => None,
+                };
+                match loaded {
+                    Some(cassette) => cassette,
+                    None => {
+                        cassette_exists = false;
+                        let mut cassette = Cassette::new();
+                        if let Some(path) = path {
+                            cassette = cassette.with_path(path);
+                        }
+                        if let Some(format) = self.format {
+                            cassette = cassette.with_format(format);
+                        }
+                        cassette
+                    }
+                }
+            }
+        };
+
+        let mut cassette = cassette;
+        if let Some(body_compression) = self.body_compression {
+            cassette.body_compression = body_compression;
+        }
+        if let Some(recorded_by) = self.recorded_by {
+            cassette.set_recorded_by(recorded_by);
+        }
+        if let Some(description) = self.description {
+            cassette.set_description(description);
+        }
+        for tag in self.tags {
+            cassette.add_tag(tag);
+        }
+        if let Some(rotation) = self.rotation {
+            cassette.set_rotation(Some(rotation));
+        }
+
+        let mut mode = match requested_mode {
+            Some(mode) => mode,
+            None => {
+                let mode = if cassette_exists {
+                    VcrMode::Replay
+                } else {
+                    VcrMode::Record
+                };
+                log::debug!("{:?} selected for cassette {:?}", mode, cassette.path);
+                #[cfg(feature = "fs")]
+                if let (Some(path), None) = (&lock_path, &session_lock) {
+                    if wants_lock(&mode) {
+                        session_lock = Some(SessionLock::acquire(path)?);
+                    }
+                }
+                mode
+            }
+        };
+        if let Some(interval) = self.re_record_interval {
+            if cassette.is_older_than(interval) {
+                if matches!(mode, VcrMode::Once) {
+                    log::info!(
+                        "Cassette {:?} is older than {interval:?}; re-recording",
+                        cassette.path
+                    );
+                    mode = VcrMode::Record;
+                } else if !matches!(mode, VcrMode::Record) {
+                    log::warn!(
+                        "Cassette {:?} is older than {interval:?} but {mode:?} mode can't re-record it",
+                        cassette.path
+                    );
+                }
+            }
+        }
+
+        if lazy && !matches!(mode, VcrMode::Replay) {
+            return Err(Error::from_str(
+                400,
+                format!("lazy_load only works in Replay mode, not {mode:?}"),
+            ));
+        }
+
+        let mut additional_cassettes = Vec::with_capacity(self.additional_cassettes.len());
+        for path in self.additional_cassettes {
+            let path = config::resolve_cassette_path(self.cassette_root.as_deref(), path);
+            let cassette = load_cassette(&storage, &path).await?.ok_or_else(|| {
+                Error::from_str(404, format!("Additional cassette {path:?} does not exist"))
+            })?;
+            additional_cassettes.push(cassette);
+        }
+
+        let needs_inner = !matches!(mode, VcrMode::Replay | VcrMode::Filter)
+            || !matches!(self.miss_behavior, MissBehavior::Error)
+            || !self.ignored_hosts.is_empty();
+        let inner = inner(&mode, needs_inner)?;
+
+        let recording_guard = match &cassette.path {
+            Some(path)
+                if self.persistence == Persistence::Disk
+                    && matches!(mode, VcrMode::Record | VcrMode::Once) =>
+            {
+                Some(RecordingGuard::acquire(path, self.shared_cassette)?)
             }
-            cassette
+            _ => None,
         };
 
-        let mut vcr_client = VcrClient::new(inner, self.mode, cassette);
+        let mut vcr_client = VcrClient::with_inner(inner, mode, cassette);
+        vcr_client.recording_guard = recording_guard;
+        vcr_client.set_record_behavior(self.record_behavior);
+        vcr_client.set_persistence(self.persistence);
+        vcr_client.set_cassette_root(self.cassette_root);
+        vcr_client.storage = storage;
+        if lazy {
+            vcr_client.lazy_lookups = Some(Mutex::new(LazyLookups::default()));
+        }
+        vcr_client.set_auto_save(self.auto_save);
+        vcr_client.set_save_on_drop(self.save_on_drop);
+        vcr_client.set_background_save(self.background_save);
+        #[cfg(feature = "fs")]
+        {
+            vcr_client.session_lock = session_lock;
+        }
+        vcr_client.set_additional_cassettes(additional_cassettes);
 
         if let Some(matcher) = self.matcher {
             vcr_client.set_matcher(matcher);
         }
 
         vcr_client.set_filter_chain(self.filter_chain);
+        vcr_client.resume_filters().await;
+        vcr_client.set_chunked_replay(self.chunked_replay);
+        vcr_client.set_response_templating(self.response_templating);
+        vcr_client.set_infer_url_templates(self.infer_url_templates);
+        vcr_client.set_content_encoding(self.content_encoding);
+        vcr_client.set_follow_redirects(self.follow_redirects);
+        vcr_client.set_record_errors(self.record_errors);
+        vcr_client.set_stream_replay_speed(self.stream_replay_speed);
+        vcr_client.set_time_shift(self.time_shift);
+        vcr_client.set_latency(self.latency);
+        for (host, latency) in self.host_latency {
+            vcr_client.set_host_latency(&host, latency);
+        }
+        vcr_client.set_connection_replay(self.connection_replay);
+        vcr_client.set_fault_injector(self.fault_injector);
+        vcr_client.set_replay_validator(self.replay_validator);
+        vcr_client.set_ignored_hosts(self.ignored_hosts);
+        vcr_client.set_record_predicate(self.record_predicate);
+        vcr_client.set_miss_behavior(self.miss_behavior);
+        vcr_client.set_env_snapshot(self.env_snapshot);
+        vcr_client.set_strict_sanitization(self.strict_sanitization);
+        vcr_client.set_limits(self.limits);
+        vcr_client.set_strict_playback(self.strict_playback);
+        if let Some(tag) = self.tag_scope {
+            vcr_client.scope_to_tag(tag);
+        }
+        vcr_client.set_event_handlers(self.events);
+        for hook in self.record_hooks {
+            vcr_client.add_record_hook(hook);
+        }
+        for transformer in self.replay_transformers {
+            vcr_client.add_replay_transformer(transformer);
+        }
 
         Ok(vcr_client)
     }
 }
 
-impl Drop for VcrClient {
+impl<C: HttpClient> Drop for VcrClient<C> {
     fn drop(&mut self) {
-        if let Ok(cassette) = self.cassette.try_lock() {
-            // Only save if:
-            // 1. We're in a mode that should persist changes (Record or Once)
-            // 2. The cassette was actually modified since loading
-            let should_save = matches!(self.mode, VcrMode::Record | VcrMode::Once)
-                && cassette.modified_since_load;
-
-            if should_save {
-                log::debug!(
-                    "VcrClient dropped - saving modified cassette with {} interactions",
-                    cassette.interactions.len()
-                );
-                // Save respecting the format setting
-                if let Some(path) = &cassette.path {
-                    let result = match cassette.format {
-                        CassetteFormat::File => {
-                            // Save as single YAML file
-                            if let Ok(yaml) = serde_yaml::to_string(&*cassette) {
-                                std::fs::write(path, yaml)
-                            } else {
-                                Err(std::io::Error::other("Failed to serialize cassette"))
-                            }
-                        }
-                        CassetteFormat::Directory => {
-                            // Save as directory format (synchronous version)
-                            Self::save_cassette_as_directory_sync(&cassette, path)
-                        }
-                    };
-
-                    if let Err(e) = result {
-                        eprintln!("Failed to save cassette on drop: {e}");
-                    } else {
-                        log::debug!("Successfully saved cassette to {path:?}");
-                    }
+        if self.strict_playback && !*self.finalized.get_mut() {
+            if let Some(cassette) = self.cassette.try_read() {
+                let unused = self.unused_indices(&cassette);
+                if let Some(report) = self.strict_playback_report(&cassette, &unused) {
+                    eprintln!("{report}");
                 }
-            } else if cassette.modified_since_load {
-                log::debug!(
-                    "VcrClient dropped - not saving cassette (mode: {:?} doesn't persist changes)",
-                    self.mode
+            }
+        }
+        if self.persistence == Persistence::InMemory || !self.save_on_drop {
+            return;
+        }
+        let Some(mut cassette) = self.cassette.try_write() else {
+            if self.persists_recordings() {
+                log::warn!(
+                    "VcrClient dropped while its cassette was in use - not saving; call finalize() before dropping"
                 );
             }
+            return;
+        };
+        // Only save if:
+        // 1. We're in a mode that should persist changes (Record or Once), or a
+        //    request recorded through a mode override
+        // 2. The cassette was actually modified since loading
+        let should_save = self.persists_recordings() && cassette.modified_since_load;
+
+        if should_save {
+            log::debug!(
+                "VcrClient dropped - saving modified cassette with {} interactions",
+                cassette.interactions.len()
+            );
+            self.collect_streams(&mut cassette.interactions);
+            let saved = match self.check_before_save(&cassette) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    eprintln!("Failed to save cassette on drop: {e}");
+                    return;
+                }
+            };
+            if let Some(path) = &cassette.path {
+                if let Err(e) = self.store(&saved) {
+                    eprintln!("Failed to save cassette on drop: {e}");
+                } else {
+                    log::debug!("Successfully saved cassette to {path:?}");
+                }
+            }
+        } else if cassette.modified_since_load {
+            log::debug!(
+                "VcrClient dropped - not saving cassette (mode: {:?} doesn't persist changes)",
+                self.mode
+            );
         }
     }
 }
 
 #[async_trait]
-impl HttpClient for VcrClient {
+impl<C: HttpClient> HttpClient for VcrClient<C> {
     async fn send(&self, req: Request) -> Result<Response, Error> {
-        match &self.mode {
-            VcrMode::None => self.handle_none_mode(req).await,
-            VcrMode::Replay => self.handle_replay_mode(req).await,
-            VcrMode::Record => self.handle_record_mode(req).await,
-            VcrMode::Once => self.handle_once_mode(req).await,
-            VcrMode::Filter => self.handle_filter_mode(req).await,
+        let Some(max_redirects) = self.follow_redirects else {
+            return self.dispatch(req).await;
+        };
+        let mut hop = RedirectHop::from_request(req).await?;
+        for _ in 0..=max_redirects {
+            let response = self.dispatch(hop.request()).await?;
+            if !hop.follow(&response) {
+                return Ok(response);
+            }
         }
+        Err(Error::from_str(
+            508,
+            format!(
+                "Stopped after {max_redirects} redirects, next was to {}",
+                hop.url()
+            ),
+        ))
     }
 
     fn set_config(&mut self, config: http_client::Config) -> Result<(), Error> {
-        self.inner
+        self.inner_mut()
             .set_config(config)
             .map_err(|e| Error::from_str(500, format!("Config error: {e}")))
     }
 
     fn config(&self) -> &http_client::Config {
-        self.inner.config()
+        self.inner().config()
+    }
+}
+
+impl<C: HttpClient> VcrClient<C> {
+    /// Send one request through the current mode, or the one the request asks for
+    async fn dispatch(&self, mut req: Request) -> Result<Response, Error> {
+        let mode = match take_mode_override(&mut req)? {
+            Some(mode) => {
+                log::debug!("{} {} sent in {mode:?} mode", req.method(), req.url());
+                mode
+            }
+            None => self.current_mode(),
+        };
+        if self.placeholder_inner && matches!(mode, VcrMode::Record | VcrMode::Once | VcrMode::None)
+        {
+            return Err(Error::from_str(
+                400,
+                format!(
+                    "{} {} was sent in {mode:?} mode, which needs a real client, but the \
+                     VcrClient was built without an inner_client",
+                    req.method(),
+                    req.url()
+                ),
+            ));
+        }
+        if self.is_ignored(req.url()) {
+            log::debug!("{} {} sent to an ignored host", req.method(), req.url());
+            return self.inner().send(req).await;
+        }
+        match &mode {
+            VcrMode::None => self.handle_none_mode(req).await,
+            VcrMode::Replay => self.handle_replay_mode(req).await,
+            VcrMode::Record => self.handle_record_mode(req).await,
+            VcrMode::Once => self.handle_once_mode(req).await,
+            VcrMode::Filter => self.handle_filter_mode(req).await,
+        }
     }
 }
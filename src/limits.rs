@@ -0,0 +1,142 @@
+use crate::cassette::Interaction;
+use crate::serializable::{body_bytes, body_fields};
+
+/// What happens when a recording goes over one of its [`CassetteLimits`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LimitAction {
+    /// Log a warning and keep recording within the limits
+    #[default]
+    Warn,
+    /// Like `Warn`, but saving the cassette fails, so the test doing it does too
+    Error,
+}
+
+/// Caps on what gets recorded, so a test that accidentally downloads something huge
+/// doesn't produce an unusable cassette.
+///
+/// Bodies over `max_body_size` bytes are cut down to it and end with a
+/// `[TRUNCATED n bytes]` marker; streamed responses aren't affected. Interactions
+/// past `max_interactions` aren't recorded.
+#[derive(Debug, Clone, Default)]
+pub struct CassetteLimits {
+    pub max_body_size: Option<usize>,
+    pub max_interactions: Option<usize>,
+    pub action: LimitAction,
+}
+
+impl CassetteLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_body_size(mut self, bytes: usize) -> Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    pub fn max_interactions(mut self, interactions: usize) -> Self {
+        self.max_interactions = Some(interactions);
+        self
+    }
+
+    /// Make saving fail when a limit was exceeded, instead of only warning
+    pub fn fail_when_exceeded(mut self) -> Self {
+        self.action = LimitAction::Error;
+        self
+    }
+
+    /// Truncate the interaction's bodies to `max_body_size`, describing each one
+    /// that was cut
+    pub(crate) fn truncate_bodies(&self, interaction: &mut Interaction) -> Vec<String> {
+        let Some(max) = self.max_body_size else {
+            return Vec::new();
+        };
+        let request = &mut interaction.request;
+        let response = &mut interaction.response;
+        let target = format!("{} {}", request.method, request.url);
+        let describe = |kind: &str, len: usize| {
+            format!("{kind} body of {target} was {len} bytes, over the {max}-byte limit")
+        };
+        truncate_body(&mut request.body, &mut request.body_base64, max)
+            .map(|len| describe("request", len))
+            .into_iter()
+            .chain(
+                truncate_body(&mut response.body, &mut response.body_base64, max)
+                    .map(|len| describe("response", len)),
+            )
+            .collect()
+    }
+}
+
+/// Cut a body down to `max` bytes plus a marker, returning its original length if it
+/// was over
+fn truncate_body<B: AsRef<str> + From<String>>(
+    body: &mut Option<B>,
+    body_base64: &mut Option<String>,
+    max: usize,
+) -> Option<usize> {
+    let bytes = body_bytes(body, body_base64)?;
+    if bytes.len() <= max {
+        return None;
+    }
+    // Keep text bodies valid UTF-8 by cutting at a character boundary
+    let keep = match std::str::from_utf8(&bytes) {
+        Ok(text) => (0..=max)
+            .rev()
+            .find(|&i| text.is_char_boundary(i))
+            .unwrap_or(0),
+        Err(_) => max,
+    };
+    let removed = bytes.len() - keep;
+    let mut truncated = bytes[..keep].to_vec();
+    truncated.extend_from_slice(format!("[TRUNCATED {removed} bytes]").as_bytes());
+    (*body, *body_base64) = body_fields(truncated);
+    Some(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+
+    #[test]
+    fn test_truncate_bodies_marks_what_was_cut() {
+        // "é" takes two bytes, so a two-byte limit can't keep it
+        let mut interaction = Interaction {
+            request: SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://example.com/big".to_string(),
+                headers: Headers::new(),
+                body: Some("ok".to_string()),
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            response: SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: Some("héllo world".into()),
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            error: None,
+            stream: None,
+            metadata: Default::default(),
+            tags: Vec::new(),
+            requires_state: None,
+            sets_state: None,
+            recorded_at: None,
+            elapsed_ms: None,
+            sequence: None,
+        };
+
+        let exceeded = CassetteLimits::new()
+            .max_body_size(2)
+            .truncate_bodies(&mut interaction);
+        assert_eq!(exceeded.len(), 1);
+        assert_eq!(interaction.request.body.as_deref(), Some("ok"));
+        assert_eq!(
+            interaction.response.body.as_deref(),
+            Some("h[TRUNCATED 11 bytes]")
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use http_client::Error;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Advisory lock on a cassette, taken on a lock file in the system temp directory
+/// named after the cassette's absolute path, so fixture directories stay clean.
+///
+/// Readers take it shared and writers exclusive, so test processes sharing a cassette
+/// never read a half-written one or interleave their writes. It is released when
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct CassetteLock {
+    // None when this process already holds a session lock on the cassette
+    _file: Option<File>,
+}
+
+impl CassetteLock {
+    /// Lock for reading the cassette at `path`
+    pub(crate) fn shared(path: &Path) -> Result<Self, Error> {
+        Self::acquire(path, false)
+    }
+
+    /// Lock for writing the cassette at `path`
+    pub(crate) fn exclusive(path: &Path) -> Result<Self, Error> {
+        Self::acquire(path, true)
+    }
+
+    fn acquire(path: &Path, exclusive: bool) -> Result<Self, Error> {
+        let key = lock_key(path)?;
+        if session_locks().lock().unwrap().contains_key(&key) {
+            return Ok(Self { _file: None });
+        }
+
+        let lock_path = lock_path(&key);
+        if let Some(parent) = lock_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                Error::from_str(500, format!("Failed to create lock directory: {e}"))
+            })?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| {
+                Error::from_str(500, format!("Failed to open lock file {lock_path:?}: {e}"))
+            })?;
+        let locked = if exclusive {
+            file.lock()
+        } else {
+            file.lock_shared()
+        };
+        locked.map_err(|e| Error::from_str(500, format!("Failed to lock {lock_path:?}: {e}")))?;
+        Ok(Self { _file: Some(file) })
+    }
+}
+
+/// An exclusive cassette lock held for a whole recording session.
+///
+/// While it is held, this process's own cassette reads and writes skip locking, so
+/// they don't wait on it; other processes wait until it is dropped. Clients within one
+/// process don't exclude each other.
+#[derive(Debug)]
+pub(crate) struct SessionLock {
+    path: PathBuf,
+    _lock: CassetteLock,
+}
+
+impl SessionLock {
+    pub(crate) fn acquire(path: &Path) -> Result<Self, Error> {
+        let lock = CassetteLock::exclusive(path)?;
+        let path = lock_key(path)?;
+        *session_locks()
+            .lock()
+            .unwrap()
+            .entry(path.clone())
+            .or_default() += 1;
+        Ok(Self { path, _lock: lock })
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        let mut locks = session_locks().lock().unwrap();
+        if let Some(count) = locks.get_mut(&self.path) {
+            *count -= 1;
+            if *count == 0 {
+                locks.remove(&self.path);
+            }
+        }
+    }
+}
+
+/// Paths this process holds session locks on, with the number of holders
+fn session_locks() -> &'static Mutex<HashMap<PathBuf, usize>> {
+    static LOCKS: OnceLock<Mutex<HashMap<PathBuf, usize>>> = OnceLock::new();
+    LOCKS.get_or_init(Default::default)
+}
+
+/// The path a cassette is locked under, the same however the caller spelled it:
+/// canonical, or the canonical parent directory plus the file name when the cassette
+/// doesn't exist yet
+fn lock_key(path: &Path) -> Result<PathBuf, Error> {
+    if let Ok(canonical) = path.canonicalize() {
+        return Ok(canonical);
+    }
+    let absolute = std::path::absolute(path)
+        .map_err(|e| Error::from_str(500, format!("Failed to resolve {path:?}: {e}")))?;
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(parent), Some(name)) => match parent.canonicalize() {
+            Ok(parent) => Ok(parent.join(name)),
+            Err(_) => Ok(absolute),
+        },
+        _ => Ok(absolute),
+    }
+}
+
+fn lock_path(key: &Path) -> PathBuf {
+    let digest = Sha256::digest(key.as_os_str().as_encoded_bytes());
+    let name: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    std::env::temp_dir()
+        .join("http-client-vcr-locks")
+        .join(format!("{name}.lock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_lock_excludes_other_handles_but_not_this_process() {
+        let path = std::env::temp_dir().join(format!("vcr-lock-{}.yaml", std::process::id()));
+        let session = SessionLock::acquire(&path).unwrap();
+
+        let other = File::open(lock_path(&lock_key(&path).unwrap())).unwrap();
+        assert!(other.try_lock_shared().is_err());
+        // Nested locks from this process don't deadlock on the session lock
+        drop(CassetteLock::exclusive(&path).unwrap());
+
+        drop(session);
+        assert!(other.try_lock().is_ok());
+    }
+
+    #[test]
+    fn test_session_lock_covers_other_spellings_of_the_path() {
+        let dir = std::env::temp_dir().join(format!("vcr-lock-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cassette.yaml");
+        let session = SessionLock::acquire(&path).unwrap();
+
+        // Before the cassette exists and after, through a `..` detour
+        let detour = dir
+            .join("..")
+            .join(dir.file_name().unwrap())
+            .join("cassette.yaml");
+        assert_eq!(lock_key(&detour).unwrap(), session.path);
+        drop(CassetteLock::exclusive(&detour).unwrap());
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(lock_key(&detour).unwrap(), session.path);
+        drop(CassetteLock::exclusive(&detour).unwrap());
+
+        drop(session);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
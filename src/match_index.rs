@@ -0,0 +1,127 @@
+use crate::cassette::Interaction;
+use crate::matcher::RequestMatcher;
+use crate::serializable::SerializableRequest;
+use std::collections::HashMap;
+
+/// Recorded interactions grouped by their matcher's `index_key`, so finding a match
+/// only compares the interactions that could possibly match.
+///
+/// The index follows its cassette by length: appended interactions are indexed on the
+/// next lookup and a shorter cassette is reindexed. Changes that keep the length, such
+/// as filtering in place or swapping cassettes, must call `invalidate`.
+#[derive(Debug, Default)]
+pub(crate) struct MatchIndex {
+    indexed: usize,
+    keyed: HashMap<String, Vec<usize>>,
+    // Interactions without a key, compared with every request
+    unkeyed: Vec<usize>,
+}
+
+impl MatchIndex {
+    pub(crate) fn invalidate(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Indices of the interactions that may match `request`, in cassette order
+    pub(crate) fn candidates(
+        &mut self,
+        matcher: &dyn RequestMatcher,
+        interactions: &[Interaction],
+        request: &SerializableRequest,
+    ) -> Vec<usize> {
+        self.sync(matcher, interactions);
+        let Some(key) = matcher.index_key(request) else {
+            return (0..interactions.len()).collect();
+        };
+        let keyed = self.keyed.get(&key).map(Vec::as_slice).unwrap_or_default();
+
+        // Both lists are in cassette order, so merge them to keep it
+        let mut merged = Vec::with_capacity(keyed.len() + self.unkeyed.len());
+        let (mut k, mut u) = (0, 0);
+        while k < keyed.len() || u < self.unkeyed.len() {
+            if u == self.unkeyed.len() || (k < keyed.len() && keyed[k] < self.unkeyed[u]) {
+                merged.push(keyed[k]);
+                k += 1;
+            } else {
+                merged.push(self.unkeyed[u]);
+                u += 1;
+            }
+        }
+        merged
+    }
+
+    fn sync(&mut self, matcher: &dyn RequestMatcher, interactions: &[Interaction]) {
+        if interactions.len() < self.indexed {
+            self.invalidate();
+        }
+        for (index, interaction) in interactions.iter().enumerate().skip(self.indexed) {
+            match matcher.index_key(&interaction.request) {
+                Some(key) => self.keyed.entry(key).or_default().push(index),
+                None => self.unkeyed.push(index),
+            }
+        }
+        self.indexed = interactions.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::DefaultMatcher;
+    use crate::serializable::{Headers, SerializableResponse};
+
+    fn interaction(url: &str) -> Interaction {
+        Interaction {
+            request: SerializableRequest {
+                method: "GET".to_string(),
+                url: url.to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            response: SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            recorded_at: None,
+            elapsed_ms: None,
+            sequence: None,
+            error: None,
+            stream: None,
+            metadata: Default::default(),
+            tags: Vec::new(),
+            requires_state: None,
+            sets_state: None,
+        }
+    }
+
+    #[test]
+    fn test_candidates_keep_cassette_order_and_follow_appends() {
+        let matcher = DefaultMatcher::new();
+        let mut interactions = vec![
+            interaction("https://example.com/a"),
+            interaction("https://example.com/users/[FILTERED]"),
+            interaction("https://example.com/b"),
+            interaction("https://example.com/a"),
+        ];
+        let mut index = MatchIndex::default();
+
+        let request = interaction("https://example.com/a").request;
+        assert_eq!(
+            index.candidates(&matcher, &interactions, &request),
+            [0, 1, 3]
+        );
+        let unrecorded = interaction("https://example.com/c").request;
+        assert_eq!(index.candidates(&matcher, &interactions, &unrecorded), [1]);
+
+        interactions.push(interaction("https://example.com/a"));
+        assert_eq!(
+            index.candidates(&matcher, &interactions, &request),
+            [0, 1, 3, 4]
+        );
+    }
+}
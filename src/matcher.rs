@@ -1,4 +1,8 @@
-use crate::serializable::SerializableRequest;
+use crate::cookie::cookie_names;
+use crate::placeholder::{contains_placeholder, matches_with_placeholders};
+use crate::serializable::{headers_from, parse_version, SerializableRequest};
+use crate::template::{template_for, UrlTemplate};
+use crate::upload::{is_presigned_url, PRESIGNED_QUERY_PARAMS};
 use http_client::Request;
 use std::fmt::Debug;
 
@@ -13,6 +17,16 @@ pub trait RequestMatcher: Debug + Send + Sync {
         // Default implementation compares serialized forms
         request.method == recorded_request.method && request.url == recorded_request.url
     }
+
+    /// A key for looking `request` up in an index of recorded requests, so only
+    /// interactions with the same key are compared with `matches_serializable`.
+    ///
+    /// Requests this matcher considers equal must get equal keys. Return `None` for
+    /// requests that have to be compared with every interaction, such as recorded ones
+    /// containing wildcards. The default indexes nothing.
+    fn index_key(&self, _request: &SerializableRequest) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -21,6 +35,11 @@ pub struct DefaultMatcher {
     match_url: bool,
     match_headers: Vec<String>,
     match_body: bool,
+    match_version: bool,
+    cookie_names_only: bool,
+    ignored_query_params: Vec<String>,
+    // Only strip ignored parameters from presigned URLs
+    ignore_params_on_presigned_only: bool,
 }
 
 impl DefaultMatcher {
@@ -36,6 +55,10 @@ impl DefaultMatcher {
                 "user-agent".to_string(),
             ],
             match_body: false,
+            match_version: false,
+            cookie_names_only: false,
+            ignored_query_params: Vec::new(),
+            ignore_params_on_presigned_only: false,
         }
     }
 
@@ -50,6 +73,10 @@ impl DefaultMatcher {
                 "user-agent".to_string(),
             ],
             match_body: false,
+            match_version: false,
+            cookie_names_only: false,
+            ignored_query_params: Vec::new(),
+            ignore_params_on_presigned_only: false,
         }
     }
 
@@ -72,6 +99,101 @@ impl DefaultMatcher {
         self.match_body = match_body;
         self
     }
+
+    /// Also require the same HTTP version, e.g. to keep HTTP/1.1 and HTTP/2
+    /// recordings of the same endpoint apart. Off by default.
+    pub fn with_version(mut self, match_version: bool) -> Self {
+        self.match_version = match_version;
+        self
+    }
+
+    /// Compare `Cookie` headers by the names of the cookies they send, ignoring
+    /// values and order, so rotating session tokens still match. Only applies
+    /// while `cookie` is one of the matched headers.
+    pub fn with_cookie_names_only(mut self, cookie_names_only: bool) -> Self {
+        self.cookie_names_only = cookie_names_only;
+        self
+    }
+
+    /// Ignore these query parameters (case-insensitively) when comparing URLs
+    pub fn ignore_query_params<I, S>(mut self, params: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignored_query_params
+            .extend(params.into_iter().map(Into::into));
+        self
+    }
+
+    /// Create a matcher that tolerates changing presigned-URL signatures. The
+    /// signature parameters are only ignored on presigned URLs, so a `?st=` on any
+    /// other URL still has to match.
+    pub fn presigned_uploads() -> Self {
+        let mut matcher = Self::new().ignore_query_params(PRESIGNED_QUERY_PARAMS.iter().copied());
+        matcher.ignore_params_on_presigned_only = true;
+        matcher
+    }
+
+    fn urls_match(&self, request_url: &str, recorded_url: &str) -> bool {
+        if self.ignored_query_params.is_empty()
+            || matches_with_placeholders(recorded_url, request_url)
+        {
+            return matches_with_placeholders(recorded_url, request_url);
+        }
+
+        match (
+            self.strip_ignored_params(request_url),
+            self.strip_ignored_params(recorded_url),
+        ) {
+            (Some(request_url), Some(recorded_url)) => {
+                matches_with_placeholders(recorded_url.as_str(), request_url.as_str())
+            }
+            _ => false,
+        }
+    }
+
+    /// Compare header values, treating redaction placeholders in the recording as
+    /// wildcards of their type
+    fn header_values_match(
+        &self,
+        header_name: &str,
+        request_values: &[String],
+        recorded_values: &[String],
+    ) -> bool {
+        if self.cookie_names_only && header_name.eq_ignore_ascii_case("cookie") {
+            return cookie_names(request_values) == cookie_names(recorded_values);
+        }
+        request_values.len() == recorded_values.len()
+            && request_values
+                .iter()
+                .zip(recorded_values)
+                .all(|(request, recorded)| matches_with_placeholders(recorded, request))
+    }
+
+    fn strip_ignored_params(&self, url: &str) -> Option<url::Url> {
+        let presigned = is_presigned_url(url);
+        let mut url = url::Url::parse(url).ok()?;
+        if self.ignore_params_on_presigned_only && !presigned {
+            return Some(url);
+        }
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, _)| {
+                !self
+                    .ignored_query_params
+                    .iter()
+                    .any(|ignored| ignored.eq_ignore_ascii_case(key))
+            })
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        url.set_query(None);
+        if !pairs.is_empty() {
+            url.query_pairs_mut().extend_pairs(pairs);
+        }
+        Some(url)
+    }
 }
 
 impl RequestMatcher for DefaultMatcher {
@@ -93,7 +215,7 @@ impl RequestMatcher for DefaultMatcher {
             return false;
         }
 
-        if self.match_url && request.url().to_string() != recorded_request.url {
+        if self.match_url && !self.urls_match(request.url().as_str(), &recorded_request.url) {
             log::debug!(
                 "URL mismatch: {} != {}",
                 request.url(),
@@ -102,6 +224,15 @@ impl RequestMatcher for DefaultMatcher {
             return false;
         }
 
+        if self.match_version && request.version() != parse_version(&recorded_request.version) {
+            log::debug!(
+                "Version mismatch: {:?} != {}",
+                request.version(),
+                recorded_request.version
+            );
+            return false;
+        }
+
         if !self.match_headers.is_empty() {
             log::debug!("Checking {} headers for matching", self.match_headers.len());
             for header_name in &self.match_headers {
@@ -119,7 +250,7 @@ impl RequestMatcher for DefaultMatcher {
                     (Some(req_val), Some(rec_val)) => {
                         let req_values: Vec<String> =
                             req_val.iter().map(|v| v.as_str().to_string()).collect();
-                        if &req_values != rec_val {
+                        if !self.header_values_match(header_name, &req_values, rec_val) {
                             log::debug!(
                                 "Header '{header_name}' values mismatch: request={req_values:?} != recorded={rec_val:?}"
                             );
@@ -153,7 +284,13 @@ impl RequestMatcher for DefaultMatcher {
             return false;
         }
 
-        if self.match_url && request.url != recorded_request.url {
+        if self.match_url && !self.urls_match(&request.url, &recorded_request.url) {
+            return false;
+        }
+
+        if self.match_version
+            && parse_version(&request.version) != parse_version(&recorded_request.version)
+        {
             return false;
         }
 
@@ -164,7 +301,7 @@ impl RequestMatcher for DefaultMatcher {
 
                 match (request_header, recorded_header) {
                     (Some(req_val), Some(rec_val)) => {
-                        if req_val != rec_val {
+                        if !self.header_values_match(header_name, req_val, rec_val) {
                             return false;
                         }
                     }
@@ -176,6 +313,26 @@ impl RequestMatcher for DefaultMatcher {
 
         true
     }
+
+    fn index_key(&self, request: &SerializableRequest) -> Option<String> {
+        let url = if self.match_url {
+            // Placeholders are wildcards, so those URLs can't be looked up exactly
+            if contains_placeholder(&request.url) || request.url.contains("%5BFILTERED") {
+                return None;
+            }
+            match self.strip_ignored_params(&request.url) {
+                Some(url) if !self.ignored_query_params.is_empty() => url.to_string(),
+                _ => request.url.clone(),
+            }
+        } else {
+            String::new()
+        };
+        match (self.match_method, self.match_url) {
+            (false, false) => None,
+            (true, _) => Some(format!("{} {url}", request.method)),
+            (false, true) => Some(url),
+        }
+    }
 }
 
 impl Default for DefaultMatcher {
@@ -197,14 +354,7 @@ impl RequestMatcher for ExactMatcher {
             return false;
         }
 
-        let mut request_headers = std::collections::HashMap::new();
-        for (name, values) in request.iter() {
-            let header_values: Vec<String> =
-                values.iter().map(|v| v.as_str().to_string()).collect();
-            request_headers.insert(name.as_str().to_string(), header_values);
-        }
-
-        if request_headers != recorded_request.headers {
+        if headers_from(request.as_ref()) != recorded_request.headers {
             return false;
         }
 
@@ -220,4 +370,75 @@ impl RequestMatcher for ExactMatcher {
             && request.url == recorded_request.url
             && request.headers == recorded_request.headers
     }
+
+    fn index_key(&self, request: &SerializableRequest) -> Option<String> {
+        Some(format!("{} {}", request.method, request.url))
+    }
+}
+
+/// Matches requests whose URLs fit the same URL template, so recorded
+/// interactions for `/users/1` can serve a request for `/users/2`.
+///
+/// Templates supplied with `with_templates` (typically `cassette.url_templates()`)
+/// take precedence over ones inferred from the URLs themselves. Query parameter
+/// names must match, but their values are ignored.
+#[derive(Debug)]
+pub struct TemplateMatcher {
+    templates: Vec<UrlTemplate>,
+    match_method: bool,
+}
+
+impl TemplateMatcher {
+    pub fn new() -> Self {
+        Self {
+            templates: Vec::new(),
+            match_method: true,
+        }
+    }
+
+    pub fn with_templates(mut self, templates: impl IntoIterator<Item = UrlTemplate>) -> Self {
+        self.templates.extend(templates);
+        self
+    }
+
+    pub fn with_method(mut self, match_method: bool) -> Self {
+        self.match_method = match_method;
+        self
+    }
+
+    fn urls_match(&self, request_url: &str, recorded_url: &str) -> bool {
+        let template = template_for(recorded_url, &self.templates);
+        template.matches(request_url)
+            && Self::query_keys(request_url) == Self::query_keys(recorded_url)
+    }
+
+    fn query_keys(url: &str) -> Vec<String> {
+        let mut keys: Vec<String> = url::Url::parse(url)
+            .map(|url| url.query_pairs().map(|(k, _)| k.to_string()).collect())
+            .unwrap_or_default();
+        keys.sort();
+        keys
+    }
+}
+
+impl Default for TemplateMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestMatcher for TemplateMatcher {
+    fn matches(&self, request: &Request, recorded_request: &SerializableRequest) -> bool {
+        (!self.match_method || request.method().to_string() == recorded_request.method)
+            && self.urls_match(request.url().as_str(), &recorded_request.url)
+    }
+
+    fn matches_serializable(
+        &self,
+        request: &SerializableRequest,
+        recorded_request: &SerializableRequest,
+    ) -> bool {
+        (!self.match_method || request.method == recorded_request.method)
+            && self.urls_match(&request.url, &recorded_request.url)
+    }
 }
@@ -0,0 +1,135 @@
+//! Upgrading cassettes stored in older layouts.
+//!
+//! Every saved cassette carries a `format_version`. Cassettes from before the field
+//! existed are version 1. Loading runs the migrations between the stored version and
+//! [`CURRENT_FORMAT_VERSION`] on the raw YAML, so the rest of the crate only ever sees
+//! the current layout.
+
+use crate::serializable::split_url;
+use http_client::Error;
+use serde::Serializer;
+use serde_yaml::{Mapping, Value};
+
+/// Upgrades one interaction from the version at its index (plus one) to the next
+type Migration = fn(&mut Mapping);
+
+const MIGRATIONS: &[Migration] = &[split_query_from_url];
+
+/// The layout version written by this version of the crate
+pub const CURRENT_FORMAT_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// The version of cassettes saved before `format_version` was added
+const UNVERSIONED: u32 = 1;
+
+pub(crate) fn current_format_version() -> u32 {
+    CURRENT_FORMAT_VERSION
+}
+
+/// Cassettes are always saved in the current layout, whatever they were loaded from
+pub(crate) fn serialize_current_version<S: Serializer>(
+    _: &u32,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u32(CURRENT_FORMAT_VERSION)
+}
+
+/// The `format_version` of a cassette document, or of a directory cassette's
+/// `metadata.yaml`
+pub(crate) fn document_version(document: &Value) -> Result<u32, Error> {
+    match document.get("format_version") {
+        None | Some(Value::Null) => Ok(UNVERSIONED),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|&version| version >= UNVERSIONED)
+            .ok_or_else(|| {
+                Error::from_str(500, format!("Invalid cassette format_version: {version:?}"))
+            }),
+    }
+}
+
+/// Upgrade a list of interactions stored at `version` to the current layout
+pub(crate) fn migrate_interactions(version: u32, interactions: &mut Value) -> Result<(), Error> {
+    if version > CURRENT_FORMAT_VERSION {
+        return Err(Error::from_str(
+            500,
+            format!(
+                "Cassette format version {version} is newer than the latest this version of \
+                 http-client-vcr reads ({CURRENT_FORMAT_VERSION})"
+            ),
+        ));
+    }
+    let Some(interactions) = interactions.as_sequence_mut() else {
+        return Ok(());
+    };
+    for migration in &MIGRATIONS[(version - UNVERSIONED) as usize..] {
+        for interaction in interactions.iter_mut() {
+            if let Some(interaction) = interaction.as_mapping_mut() {
+                migration(interaction);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Upgrade a single-file cassette document in place, returning the version it was
+/// stored in
+pub(crate) fn migrate_cassette(document: &mut Value) -> Result<u32, Error> {
+    let version = document_version(document)?;
+    if let Some(interactions) = document.get_mut("interactions") {
+        migrate_interactions(version, interactions)?;
+    }
+    if let Some(document) = document.as_mapping_mut() {
+        document.remove("format_version");
+    }
+    Ok(version)
+}
+
+/// 1 -> 2: query strings move out of `url` into the `query` list
+fn split_query_from_url(interaction: &mut Mapping) {
+    let Some(request) = interaction
+        .get_mut("request")
+        .and_then(Value::as_mapping_mut)
+    else {
+        return;
+    };
+    if request.contains_key("query") {
+        return;
+    }
+    let Some(url) = request.get("url").and_then(Value::as_str) else {
+        return;
+    };
+    let (url, query) = split_url(url);
+    if query.is_empty() {
+        return;
+    }
+    let url = url.into_owned();
+    let Ok(query) = serde_yaml::to_value(query) else {
+        return;
+    };
+    request.insert("url".into(), url.into());
+    request.insert("query".into(), query);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unversioned_cassettes_are_upgraded() {
+        let mut document: Value = serde_yaml::from_str(
+            "interactions:\n  - request:\n      url: https://example.com/items?page=2\n",
+        )
+        .unwrap();
+        assert_eq!(migrate_cassette(&mut document).unwrap(), 1);
+
+        let request = &document["interactions"][0]["request"];
+        assert_eq!(request["url"], "https://example.com/items");
+        assert_eq!(request["query"][0]["name"], "page");
+        assert_eq!(request["query"][0]["value"], "2");
+
+        let mut newer: Value =
+            serde_yaml::from_str("format_version: 99\ninteractions: []").unwrap();
+        assert!(migrate_cassette(&mut newer).is_err());
+    }
+}
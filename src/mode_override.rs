@@ -0,0 +1,61 @@
+use crate::VcrMode;
+use std::sync::Mutex;
+
+/// Modes set temporarily with `VcrClient::with_mode`. The most recent block still
+/// running wins, and each block only removes its own mode, so blocks can nest or
+/// overlap on different tasks and the client always returns to its own mode.
+#[derive(Debug, Default)]
+pub(crate) struct ModeOverrides {
+    next_id: u64,
+    active: Vec<(u64, VcrMode)>,
+}
+
+impl ModeOverrides {
+    pub(crate) fn current(&self) -> Option<&VcrMode> {
+        self.active.last().map(|(_, mode)| mode)
+    }
+}
+
+/// Removes its mode from the overrides when dropped, including when the block
+/// panics or its future is cancelled
+pub(crate) struct ModeOverrideGuard<'a> {
+    overrides: &'a Mutex<ModeOverrides>,
+    id: u64,
+}
+
+impl<'a> ModeOverrideGuard<'a> {
+    pub(crate) fn push(overrides: &'a Mutex<ModeOverrides>, mode: VcrMode) -> Self {
+        let mut state = overrides.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.active.push((id, mode));
+        Self { overrides, id }
+    }
+}
+
+impl Drop for ModeOverrideGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.overrides.lock().unwrap_or_else(|e| e.into_inner());
+        state.active.retain(|(id, _)| *id != self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlapping_overrides_are_removed_independently() {
+        let overrides = Mutex::new(ModeOverrides::default());
+        let current = || overrides.lock().unwrap().current().cloned();
+
+        let first = ModeOverrideGuard::push(&overrides, VcrMode::Record);
+        let second = ModeOverrideGuard::push(&overrides, VcrMode::None);
+        assert!(matches!(current(), Some(VcrMode::None)));
+        // The first block ends while the second is still running
+        drop(first);
+        assert!(matches!(current(), Some(VcrMode::None)));
+        drop(second);
+        assert!(current().is_none());
+    }
+}
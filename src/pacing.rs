@@ -0,0 +1,263 @@
+use crate::cassette::Interaction;
+use futures_lite::io::{AsyncBufRead, AsyncRead};
+use http_types::Body;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Replay recorded bodies in chunks instead of as a single buffer.
+///
+/// Useful for exercising progress reporting and incremental parsing code,
+/// which behaves differently when the whole body is available at once.
+#[derive(Debug, Clone)]
+pub struct ChunkedReplay {
+    chunk_size: usize,
+    delay: Duration,
+}
+
+impl ChunkedReplay {
+    /// Emit the body in chunks of at most `chunk_size` bytes with no delay between them
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Wait `delay` before emitting each chunk after the first
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Wrap the body bytes in a paced reader
+    pub(crate) fn body(&self, bytes: Vec<u8>) -> Body {
+        let len = bytes.len() as u64;
+        Body::from_reader(PacedReader::new(bytes, self.clone()), Some(len))
+    }
+}
+
+/// How long replayed responses wait before being returned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LatencyMode {
+    /// The response time recorded with each interaction; interactions recorded
+    /// without timing return immediately
+    Recorded,
+    /// The recorded response time multiplied by a factor, e.g. `0.1` to keep tests fast
+    Scaled(f64),
+    /// The same delay for every response
+    Fixed(Duration),
+    /// A random delay between `min` and `max`, drawn independently for each response
+    Jitter { min: Duration, max: Duration },
+}
+
+impl LatencyMode {
+    /// Delay before returning the response for `interaction`
+    pub fn delay_for(&self, interaction: &Interaction) -> Option<Duration> {
+        match self {
+            LatencyMode::Recorded => interaction.elapsed(),
+            LatencyMode::Scaled(factor) => interaction
+                .elapsed()
+                .map(|elapsed| elapsed.mul_f64(factor.max(0.0))),
+            LatencyMode::Fixed(delay) => Some(*delay),
+            LatencyMode::Jitter { min, max } => {
+                let (min, max) = if min <= max { (min, max) } else { (max, min) };
+                Some(*min + (*max - *min).mul_f64(fastrand::f64()))
+            }
+        }
+    }
+}
+
+/// Latency applied to replayed responses, optionally overridden per host
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LatencyProfile {
+    default: Option<LatencyMode>,
+    hosts: HashMap<String, LatencyMode>,
+}
+
+impl LatencyProfile {
+    pub(crate) fn set_default(&mut self, latency: Option<LatencyMode>) {
+        self.default = latency;
+    }
+
+    pub(crate) fn set_host(&mut self, host: &str, latency: LatencyMode) {
+        self.hosts.insert(host.to_lowercase(), latency);
+    }
+
+    pub(crate) fn delay_for(&self, interaction: &Interaction) -> Option<Duration> {
+        let host = url::Url::parse(&interaction.request.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase));
+        host.and_then(|host| self.hosts.get(&host))
+            .or(self.default.as_ref())
+            .and_then(|latency| latency.delay_for(interaction))
+    }
+}
+
+/// An async reader that hands out its buffer one chunk at a time
+struct PacedReader {
+    data: Vec<u8>,
+    pos: usize,
+    chunk_end: usize,
+    config: ChunkedReplay,
+    sleep: Option<crate::runtime::Timer>,
+}
+
+impl PacedReader {
+    fn new(data: Vec<u8>, config: ChunkedReplay) -> Self {
+        Self {
+            data,
+            pos: 0,
+            chunk_end: 0,
+            config,
+            sleep: None,
+        }
+    }
+}
+
+impl AsyncBufRead for PacedReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        let this = self.get_mut();
+
+        if this.pos >= this.data.len() {
+            return Poll::Ready(Ok(&[]));
+        }
+
+        if this.pos >= this.chunk_end {
+            if let Some(sleep) = this.sleep.as_mut() {
+                if Pin::new(sleep).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.sleep = None;
+            }
+            this.chunk_end = (this.pos + this.config.chunk_size).min(this.data.len());
+        }
+
+        Poll::Ready(Ok(&this.data[this.pos..this.chunk_end]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.pos = (this.pos + amt).min(this.chunk_end);
+
+        // Schedule the delay for the next chunk once the current one is drained
+        if this.pos == this.chunk_end && this.pos < this.data.len() && !this.config.delay.is_zero()
+        {
+            this.sleep = Some(crate::runtime::Timer::after(this.config.delay));
+        }
+    }
+}
+
+impl AsyncRead for PacedReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let available = match self.as_mut().poll_fill_buf(cx) {
+            Poll::Ready(Ok(available)) => available,
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::io::AsyncBufReadExt;
+
+    #[tokio::test]
+    async fn test_reader_yields_configured_chunks() {
+        let config = ChunkedReplay::new(4).with_delay(Duration::from_millis(1));
+        let mut reader = PacedReader::new(b"hello world".to_vec(), config);
+
+        let mut chunks = Vec::new();
+        loop {
+            let chunk = reader.fill_buf().await.unwrap().to_vec();
+            if chunk.is_empty() {
+                break;
+            }
+            Pin::new(&mut reader).consume(chunk.len());
+            chunks.push(chunk);
+        }
+
+        assert_eq!(
+            chunks,
+            vec![b"hell".to_vec(), b"o wo".to_vec(), b"rld".to_vec()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_body_reads_back_in_full() {
+        let body = ChunkedReplay::new(3).body(b"{\"ok\":true}".to_vec());
+        assert_eq!(body.len(), Some(11));
+        assert_eq!(body.into_string().await.unwrap(), "{\"ok\":true}");
+    }
+
+    #[test]
+    fn test_host_latency_overrides_default() {
+        let interaction = |url: &str| Interaction {
+            request: crate::SerializableRequest {
+                method: "GET".to_string(),
+                url: url.to_string(),
+                headers: Default::default(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            response: crate::SerializableResponse {
+                status: 200,
+                headers: Default::default(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            recorded_at: None,
+            elapsed_ms: Some(40),
+            sequence: None,
+            error: None,
+            stream: None,
+            metadata: Default::default(),
+            tags: Vec::new(),
+            requires_state: None,
+            sets_state: None,
+        };
+
+        let mut profile = LatencyProfile::default();
+        profile.set_default(Some(LatencyMode::Recorded));
+        profile.set_host(
+            "Slow.example.com",
+            LatencyMode::Jitter {
+                min: Duration::from_millis(200),
+                max: Duration::from_millis(100),
+            },
+        );
+
+        assert_eq!(
+            profile.delay_for(&interaction("https://fast.example.com/")),
+            Some(Duration::from_millis(40))
+        );
+        for _ in 0..20 {
+            let delay = profile
+                .delay_for(&interaction("https://slow.example.com/"))
+                .unwrap();
+            assert!((Duration::from_millis(100)..=Duration::from_millis(200)).contains(&delay));
+        }
+    }
+}
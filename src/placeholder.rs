@@ -0,0 +1,301 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// The untyped redaction marker, used when nothing more is known about a value
+pub const FILTERED_PLACEHOLDER: &str = "[FILTERED]";
+
+/// What kind of value a redaction placeholder stands in for.
+///
+/// Built-in filters replace values with `[FILTERED:<kind>]` (or plain `[FILTERED]`
+/// for [`PlaceholderKind::Any`]), so tooling can tell what was removed and
+/// [`DefaultMatcher`](crate::DefaultMatcher) can treat the placeholder as a wildcard
+/// for values of that kind when matching requests during replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlaceholderKind {
+    /// Any value
+    Any,
+    /// A JSON Web Token
+    Jwt,
+    /// An email address
+    Email,
+    /// A UUID
+    Uuid,
+    /// An opaque token, key or signature
+    Token,
+    /// A password
+    Password,
+    /// Encoded credentials, e.g. the payload of a Basic `Authorization` header
+    Credentials,
+}
+
+impl PlaceholderKind {
+    const ALL: [PlaceholderKind; 7] = [
+        PlaceholderKind::Any,
+        PlaceholderKind::Jwt,
+        PlaceholderKind::Email,
+        PlaceholderKind::Uuid,
+        PlaceholderKind::Token,
+        PlaceholderKind::Password,
+        PlaceholderKind::Credentials,
+    ];
+
+    /// Type hint written after `FILTERED:`, or `None` for the untyped placeholder
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            PlaceholderKind::Any => None,
+            PlaceholderKind::Jwt => Some("jwt"),
+            PlaceholderKind::Email => Some("email"),
+            PlaceholderKind::Uuid => Some("uuid"),
+            PlaceholderKind::Token => Some("token"),
+            PlaceholderKind::Password => Some("password"),
+            PlaceholderKind::Credentials => Some("credentials"),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == Some(name))
+    }
+
+    /// The placeholder text, e.g. `[FILTERED:jwt]`
+    pub fn placeholder(&self) -> String {
+        match self.name() {
+            Some(name) => format!("[FILTERED:{name}]"),
+            None => FILTERED_PLACEHOLDER.to_string(),
+        }
+    }
+
+    /// Parse a whole value as a placeholder
+    pub fn parse(value: &str) -> Option<Self> {
+        let inner = value.strip_prefix("[FILTERED")?.strip_suffix(']')?;
+        match inner.strip_prefix(':') {
+            Some(name) => Self::from_name(name),
+            None if inner.is_empty() => Some(PlaceholderKind::Any),
+            None => None,
+        }
+    }
+
+    /// Infer the kind of a secret from its value
+    pub fn classify(value: &str) -> Self {
+        [
+            PlaceholderKind::Jwt,
+            PlaceholderKind::Uuid,
+            PlaceholderKind::Email,
+        ]
+        .into_iter()
+        .find(|kind| kind.accepts(value))
+        .unwrap_or(PlaceholderKind::Any)
+    }
+
+    /// Infer the kind of a secret from the name of the field holding it, falling
+    /// back to its value
+    pub fn for_field(name: &str, value: &str) -> Self {
+        let name = name.to_lowercase();
+        if name.contains("pass") || name.contains("pwd") {
+            PlaceholderKind::Password
+        } else if name.contains("email") {
+            PlaceholderKind::Email
+        } else {
+            match Self::classify(value) {
+                PlaceholderKind::Any
+                    if ["token", "key", "secret", "signature", "session"]
+                        .iter()
+                        .any(|hint| name.contains(hint)) =>
+                {
+                    PlaceholderKind::Token
+                }
+                kind => kind,
+            }
+        }
+    }
+
+    /// Whether `value` is a plausible value of this kind
+    pub fn accepts(&self, value: &str) -> bool {
+        static PATTERNS: OnceLock<Vec<Regex>> = OnceLock::new();
+        let patterns = PATTERNS.get_or_init(|| {
+            Self::ALL
+                .iter()
+                .map(|kind| {
+                    Regex::new(&format!("^(?:{})$", kind.pattern())).expect("placeholder pattern")
+                })
+                .collect()
+        });
+        // `ALL` lists the kinds in declaration order
+        patterns[*self as usize].is_match(value)
+    }
+
+    fn pattern(&self) -> &'static str {
+        match self {
+            PlaceholderKind::Any | PlaceholderKind::Password => r".*?",
+            PlaceholderKind::Jwt => r"eyJ[A-Za-z0-9_-]*\.[A-Za-z0-9_-]*\.[A-Za-z0-9_-]*",
+            PlaceholderKind::Email => r"[^\s@&=,;:/?]+@[^\s@&=,;:/?]+\.[^\s@&=,;:/?]+",
+            PlaceholderKind::Uuid => {
+                r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}"
+            }
+            PlaceholderKind::Token => r"[A-Za-z0-9._~+/=-]+",
+            PlaceholderKind::Credentials => r"[A-Za-z0-9+/=]+",
+        }
+    }
+}
+
+/// Replace a secret with the placeholder for its inferred kind
+pub fn redact(value: &str) -> String {
+    PlaceholderKind::classify(value).placeholder()
+}
+
+fn placeholder_regex() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER
+        .get_or_init(|| Regex::new(r"\[FILTERED(?::([a-z_]+))?\]").expect("placeholder pattern"))
+}
+
+/// Whether `text` contains any redaction placeholder
+pub fn contains_placeholder(text: &str) -> bool {
+    placeholder_regex().is_match(text)
+}
+
+/// Compare recorded text against live text, treating each placeholder in the
+/// recorded text as a wildcard for values of its kind.
+///
+/// Placeholders also match themselves, so text that went through the same filters
+/// still compares equal.
+pub fn matches_with_placeholders(recorded: &str, actual: &str) -> bool {
+    if recorded == actual {
+        return true;
+    }
+    if !contains_placeholder(recorded) {
+        // Placeholders in URLs and form bodies are percent-encoded
+        return recorded.contains("%5BFILTERED")
+            && match (urlencoding::decode(recorded), urlencoding::decode(actual)) {
+                (Ok(recorded), Ok(actual)) => matches_with_placeholders(&recorded, &actual),
+                _ => false,
+            };
+    }
+
+    let mut segments = Vec::new();
+    let mut last = 0;
+    for caps in placeholder_regex().captures_iter(recorded) {
+        let whole = caps.get(0).unwrap();
+        if whole.start() > last {
+            segments.push(Segment::Literal(&recorded[last..whole.start()]));
+        }
+        let kind = match caps.get(1) {
+            Some(name) => PlaceholderKind::from_name(name.as_str()),
+            None => Some(PlaceholderKind::Any),
+        };
+        segments.push(match kind {
+            Some(kind) => Segment::Placeholder(whole.as_str(), kind),
+            // Unknown type hints only match literally
+            None => Segment::Literal(whole.as_str()),
+        });
+        last = whole.end();
+    }
+    if last < recorded.len() {
+        segments.push(Segment::Literal(&recorded[last..]));
+    }
+
+    matches_segments(&segments, actual)
+}
+
+/// A piece of recorded text: literal text, or a placeholder and its kind
+enum Segment<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str, PlaceholderKind),
+}
+
+/// Whether `actual` is `segments` with each placeholder either kept as is or replaced
+/// by a value of its kind
+fn matches_segments(segments: &[Segment], actual: &str) -> bool {
+    let Some((segment, rest)) = segments.split_first() else {
+        return actual.is_empty();
+    };
+    match segment {
+        Segment::Literal(literal) => actual
+            .strip_prefix(literal)
+            .is_some_and(|actual| matches_segments(rest, actual)),
+        Segment::Placeholder(placeholder, kind) => {
+            if actual
+                .strip_prefix(placeholder)
+                .is_some_and(|actual| matches_segments(rest, actual))
+            {
+                return true;
+            }
+            // The value ends where the following literal text starts, so only those
+            // positions need checking
+            let ends: Vec<usize> = match rest.first() {
+                Some(Segment::Literal(literal)) => {
+                    let mut ends = Vec::new();
+                    let mut from = 0;
+                    while let Some(offset) = actual[from..].find(literal) {
+                        let end = from + offset;
+                        ends.push(end);
+                        // Occurrences can overlap, so continue from the next character
+                        from = end + actual[end..].chars().next().map_or(1, char::len_utf8);
+                    }
+                    ends
+                }
+                Some(Segment::Placeholder(..)) => (0..=actual.len())
+                    .filter(|&end| actual.is_char_boundary(end))
+                    .collect(),
+                None => vec![actual.len()],
+            };
+            ends.into_iter()
+                .any(|end| kind.accepts(&actual[..end]) && matches_segments(rest, &actual[end..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_and_parses_placeholders() {
+        assert_eq!(
+            redact("550e8400-e29b-41d4-a716-446655440000"),
+            "[FILTERED:uuid]"
+        );
+        assert_eq!(redact("someone@example.com"), "[FILTERED:email]");
+        assert_eq!(redact("hunter2"), "[FILTERED]");
+        assert_eq!(
+            PlaceholderKind::for_field("api_key", "abc123"),
+            PlaceholderKind::Token
+        );
+        assert_eq!(
+            PlaceholderKind::parse("[FILTERED:jwt]"),
+            Some(PlaceholderKind::Jwt)
+        );
+        assert_eq!(
+            PlaceholderKind::parse("[FILTERED]"),
+            Some(PlaceholderKind::Any)
+        );
+        assert_eq!(PlaceholderKind::parse("[FILTERED:nope]"), None);
+    }
+
+    #[test]
+    fn test_typed_placeholders_match_values_of_their_kind() {
+        let recorded = "user=[FILTERED:email]&id=[FILTERED:uuid]";
+        assert!(matches_with_placeholders(recorded, recorded));
+        assert!(matches_with_placeholders(
+            recorded,
+            "user=a@b.co&id=550e8400-e29b-41d4-a716-446655440000"
+        ));
+        assert!(!matches_with_placeholders(
+            recorded,
+            "user=not-an-email&id=550e8400-e29b-41d4-a716-446655440000"
+        ));
+        assert!(matches_with_placeholders(
+            "Bearer [FILTERED]",
+            "Bearer anything at all"
+        ));
+        assert!(!matches_with_placeholders("a=1", "a=2"));
+        assert!(matches_with_placeholders(
+            "[FILTERED:uuid][FILTERED:email]",
+            "550e8400-e29b-41d4-a716-446655440000a@b.co"
+        ));
+        assert!(matches_with_placeholders("id=[FILTERED];", "id=a;b;"));
+        assert!(!matches_with_placeholders(
+            "id=[FILTERED:token];",
+            "id=a b;"
+        ));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::filter::Filter;
+use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+use crate::upload::{PresignedUploadFilter, PRESIGNED_PLACEHOLDER};
+
+/// Ready-made filters for common providers
+pub struct Presets;
+
+impl Presets {
+    /// Redacts AWS credentials: SigV4 `Authorization` headers, `X-Amz-Date` and
+    /// session tokens, and presigned URL query parameters. The same filter runs on
+    /// live requests before matching, so fresh signatures still match the recording.
+    pub fn aws() -> Box<dyn Filter> {
+        Box::new(AwsFilter::new())
+    }
+}
+
+/// Headers whose values change on every signed request
+const AWS_VOLATILE_HEADERS: &[&str] = &["x-amz-date"];
+
+/// Headers that carry credentials and are dropped entirely
+const AWS_SECRET_HEADERS: &[&str] = &["x-amz-security-token"];
+
+#[derive(Debug)]
+struct AwsFilter {
+    presigned: PresignedUploadFilter,
+}
+
+impl AwsFilter {
+    fn new() -> Self {
+        Self {
+            presigned: PresignedUploadFilter::new(),
+        }
+    }
+
+    fn filter_headers(&self, headers: &mut Headers) {
+        headers.retain(|name, _| {
+            !AWS_SECRET_HEADERS
+                .iter()
+                .any(|secret| name.eq_ignore_ascii_case(secret))
+        });
+
+        for (name, values) in headers.iter_mut() {
+            if AWS_VOLATILE_HEADERS
+                .iter()
+                .any(|volatile| name.eq_ignore_ascii_case(volatile))
+            {
+                *values = vec![PRESIGNED_PLACEHOLDER.to_string()];
+            } else if name.eq_ignore_ascii_case("authorization") {
+                for value in values.iter_mut() {
+                    if let Some(normalized) = normalize_sigv4_authorization(value) {
+                        *value = normalized;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Keep the algorithm and signed header list of a SigV4 `Authorization` header but
+/// drop the credential scope and signature
+fn normalize_sigv4_authorization(value: &str) -> Option<String> {
+    let params = value.strip_prefix("AWS4-HMAC-SHA256")?;
+    let signed_headers = params
+        .split(',')
+        .find_map(|param| param.trim().strip_prefix("SignedHeaders="))
+        .unwrap_or_default();
+
+    Some(format!(
+        "AWS4-HMAC-SHA256 Credential={PRESIGNED_PLACEHOLDER}, SignedHeaders={signed_headers}, Signature={PRESIGNED_PLACEHOLDER}"
+    ))
+}
+
+impl Filter for AwsFilter {
+    fn filter_request(&self, request: &mut SerializableRequest) {
+        self.filter_headers(&mut request.headers);
+        self.presigned.redact_url(&mut request.url);
+    }
+
+    fn filter_response(&self, response: &mut SerializableResponse) {
+        // Presigned URLs handed out in bodies and Location headers
+        self.presigned.filter_response(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aws_preset_normalizes_signed_requests() {
+        let mut headers = Headers::new();
+        headers.insert(
+            "authorization".to_string(),
+            vec!["AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240101/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-date, Signature=abcdef".to_string()],
+        );
+        headers.insert(
+            "x-amz-date".to_string(),
+            vec!["20240101T000000Z".to_string()],
+        );
+        headers.insert(
+            "X-Amz-Security-Token".to_string(),
+            vec!["session".to_string()],
+        );
+        let mut request = SerializableRequest {
+            method: "GET".to_string(),
+            url: "https://bucket.s3.amazonaws.com/key?X-Amz-Credential=AKID&X-Amz-Signature=abc"
+                .to_string(),
+            headers,
+            body: None,
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+
+        Presets::aws().filter_request(&mut request);
+
+        assert_eq!(
+            request.headers["authorization"],
+            vec!["AWS4-HMAC-SHA256 Credential=FILTERED, SignedHeaders=host;x-amz-date, Signature=FILTERED"]
+        );
+        assert_eq!(request.headers["x-amz-date"], vec!["FILTERED"]);
+        assert!(!request.headers.contains_key("X-Amz-Security-Token"));
+        assert_eq!(
+            request.url,
+            "https://bucket.s3.amazonaws.com/key?X-Amz-Credential=FILTERED&X-Amz-Signature=FILTERED"
+        );
+    }
+}
@@ -0,0 +1,504 @@
+use crate::filter::Filter;
+use crate::matcher::{DefaultMatcher, RequestMatcher};
+use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+use http_client::{Error, Request};
+use prost_reflect::prost::bytes::Bytes;
+use prost_reflect::prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, Kind, MessageDescriptor, ReflectMessage, Value,
+};
+use std::collections::HashMap;
+
+/// Decodes the protobuf bodies of gRPC-web, Twirp and similar APIs with a compiled
+/// descriptor set, as written by `protoc --descriptor_set_out` or prost-build's
+/// `file_descriptor_set_path`.
+///
+/// The message types come from the request URL, whose last two path segments name
+/// the service and method, as in `/twirp/acme.Auth/Login` or `/acme.Auth/Login`.
+/// Routes added with `with_route` cover other endpoints. Only bodies with a
+/// protobuf or gRPC-web content type are decoded; everything else, and bodies that
+/// fail to decode, stay opaque bytes stored as base64.
+#[derive(Debug, Clone)]
+pub struct ProtobufCodec {
+    pool: DescriptorPool,
+    routes: Vec<(String, MessageDescriptor, MessageDescriptor)>,
+}
+
+impl ProtobufCodec {
+    pub fn from_descriptor_set(bytes: &[u8]) -> Result<Self, Error> {
+        let pool = DescriptorPool::decode(bytes)
+            .map_err(|e| Error::from_str(500, format!("Invalid protobuf descriptor set: {e}")))?;
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: DescriptorPool) -> Self {
+        Self {
+            pool,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Decode requests to `path` as `request_type` and their responses as
+    /// `response_type`, given as fully qualified message names
+    pub fn with_route(
+        mut self,
+        path: impl Into<String>,
+        request_type: &str,
+        response_type: &str,
+    ) -> Result<Self, Error> {
+        let message = |name: &str| {
+            self.pool.get_message_by_name(name).ok_or_else(|| {
+                Error::from_str(500, format!("Unknown protobuf message type {name}"))
+            })
+        };
+        let (request, response) = (message(request_type)?, message(response_type)?);
+        self.routes.push((path.into(), request, response));
+        Ok(self)
+    }
+
+    /// The request and response message types for `url`
+    fn messages_for(&self, url: &str) -> Option<(MessageDescriptor, MessageDescriptor)> {
+        let url = url::Url::parse(url).ok()?;
+        let path = url.path();
+        if let Some((_, request, response)) = self.routes.iter().find(|(route, ..)| route == path) {
+            return Some((request.clone(), response.clone()));
+        }
+
+        let mut segments = path.rsplit('/');
+        let (method, service) = (segments.next()?, segments.next()?);
+        let method = self
+            .pool
+            .get_service_by_name(service)?
+            .methods()
+            .find(|candidate| candidate.name() == method)?;
+        Some((method.input(), method.output()))
+    }
+
+    /// The messages of a request body, if it is protobuf this codec knows
+    pub fn decode_request(&self, request: &SerializableRequest) -> Option<Vec<DynamicMessage>> {
+        let (message, _) = self.messages_for(&request.url)?;
+        let body = ProtobufBody::decode(&request.headers, &request.bytes()?, message)?;
+        Some(body.into_messages())
+    }
+
+    /// The messages of the body of the response to `request`
+    pub fn decode_response(
+        &self,
+        request: &SerializableRequest,
+        response: &SerializableResponse,
+    ) -> Option<Vec<DynamicMessage>> {
+        let (_, message) = self.messages_for(&request.url)?;
+        let body = ProtobufBody::decode(&response.headers, &response.bytes()?, message)?;
+        Some(body.into_messages())
+    }
+}
+
+/// A decoded body: a single message, or gRPC-web frames holding messages and a
+/// trailer frame
+#[derive(Debug)]
+struct ProtobufBody {
+    framed: bool,
+    frames: Vec<(u8, Frame)>,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Message(DynamicMessage),
+    /// Trailers and compressed messages, kept as they are
+    Raw(Vec<u8>),
+}
+
+impl ProtobufBody {
+    fn decode(headers: &Headers, bytes: &[u8], message: MessageDescriptor) -> Option<Self> {
+        let content_type = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .and_then(|(_, values)| values.first())?
+            .split(';')
+            .next()?
+            .trim()
+            .to_ascii_lowercase();
+
+        if content_type.starts_with("application/grpc") {
+            // grpc-web-text bodies are base64 on the wire and not worth decoding
+            if content_type.contains("text") {
+                return None;
+            }
+            return Self::decode_frames(bytes, message);
+        }
+        if !content_type.contains("protobuf") {
+            return None;
+        }
+        let decoded = DynamicMessage::decode(message, bytes).ok()?;
+        Some(Self {
+            framed: false,
+            frames: vec![(0, Frame::Message(decoded))],
+        })
+    }
+
+    /// Split a gRPC-web body into its length-prefixed frames
+    fn decode_frames(mut bytes: &[u8], message: MessageDescriptor) -> Option<Self> {
+        let mut frames = Vec::new();
+        while !bytes.is_empty() {
+            let header = bytes.get(..5)?;
+            let flags = header[0];
+            let len = u32::from_be_bytes(header[1..5].try_into().ok()?) as usize;
+            let payload = bytes.get(5..5 + len)?;
+            bytes = &bytes[5 + len..];
+
+            // Bit 0 marks a compressed message and bit 7 the trailers
+            let frame = if flags == 0 {
+                Frame::Message(DynamicMessage::decode(message.clone(), payload).ok()?)
+            } else {
+                Frame::Raw(payload.to_vec())
+            };
+            frames.push((flags, frame));
+        }
+        Some(Self {
+            framed: true,
+            frames,
+        })
+    }
+
+    fn messages_mut(&mut self) -> impl Iterator<Item = &mut DynamicMessage> {
+        self.frames.iter_mut().filter_map(|(_, frame)| match frame {
+            Frame::Message(message) => Some(message),
+            Frame::Raw(_) => None,
+        })
+    }
+
+    fn into_messages(self) -> Vec<DynamicMessage> {
+        self.frames
+            .into_iter()
+            .filter_map(|(_, frame)| match frame {
+                Frame::Message(message) => Some(message),
+                Frame::Raw(_) => None,
+            })
+            .collect()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (flags, frame) in &self.frames {
+            let payload = match frame {
+                Frame::Message(message) => message.encode_to_vec(),
+                Frame::Raw(payload) => payload.clone(),
+            };
+            if self.framed {
+                bytes.push(*flags);
+                bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            }
+            bytes.extend_from_slice(&payload);
+        }
+        bytes
+    }
+}
+
+/// Removes or replaces fields, by name and at any depth, in protobuf request and
+/// response bodies that a [`ProtobufCodec`] can decode.
+///
+/// Replacements apply to string and bytes fields; other fields named for replacement
+/// are cleared instead. Responses are only filtered when their request is known.
+#[derive(Debug)]
+pub struct ProtobufFilter {
+    codec: ProtobufCodec,
+    fields_to_remove: Vec<String>,
+    fields_to_replace: HashMap<String, String>,
+}
+
+impl ProtobufFilter {
+    pub fn new(codec: ProtobufCodec) -> Self {
+        Self {
+            codec,
+            fields_to_remove: Vec::new(),
+            fields_to_replace: HashMap::new(),
+        }
+    }
+
+    pub fn remove_field(mut self, name: impl Into<String>) -> Self {
+        self.fields_to_remove.push(name.into());
+        self
+    }
+
+    pub fn replace_field(
+        mut self,
+        name: impl Into<String>,
+        replacement: impl Into<String>,
+    ) -> Self {
+        self.fields_to_replace
+            .insert(name.into(), replacement.into());
+        self
+    }
+
+    fn filter_message(&self, message: &mut DynamicMessage) {
+        let fields: Vec<_> = message.descriptor().fields().collect();
+        for field in fields {
+            if !message.has_field(&field) {
+                continue;
+            }
+            let name = field.name();
+            if self.fields_to_remove.iter().any(|removed| removed == name) {
+                message.clear_field(&field);
+            } else if let Some(replacement) = self.fields_to_replace.get(name) {
+                if field.is_map() || !matches!(field.kind(), Kind::String | Kind::Bytes) {
+                    message.clear_field(&field);
+                } else {
+                    replace_value(message.get_field_mut(&field), replacement);
+                }
+            } else {
+                self.filter_value(message.get_field_mut(&field));
+            }
+        }
+    }
+
+    fn filter_value(&self, value: &mut Value) {
+        match value {
+            Value::Message(message) => self.filter_message(message),
+            Value::List(items) => items.iter_mut().for_each(|item| self.filter_value(item)),
+            Value::Map(entries) => entries
+                .values_mut()
+                .for_each(|item| self.filter_value(item)),
+            _ => {}
+        }
+    }
+
+    fn filter_body(
+        &self,
+        headers: &Headers,
+        bytes: Option<Vec<u8>>,
+        message: MessageDescriptor,
+    ) -> Option<Vec<u8>> {
+        let mut body = ProtobufBody::decode(headers, &bytes?, message)?;
+        body.messages_mut()
+            .for_each(|message| self.filter_message(message));
+        Some(body.encode())
+    }
+}
+
+fn replace_value(value: &mut Value, replacement: &str) {
+    match value {
+        Value::String(text) => *text = replacement.to_string(),
+        Value::Bytes(bytes) => *bytes = Bytes::from(replacement.to_string()),
+        Value::List(items) => items
+            .iter_mut()
+            .for_each(|item| replace_value(item, replacement)),
+        _ => {}
+    }
+}
+
+impl Filter for ProtobufFilter {
+    fn filter_request(&self, request: &mut SerializableRequest) {
+        let Some((message, _)) = self.codec.messages_for(&request.url) else {
+            return;
+        };
+        if let Some(filtered) = self.filter_body(&request.headers, request.bytes(), message) {
+            request.set_bytes(filtered);
+        }
+    }
+
+    fn filter_response(&self, _response: &mut SerializableResponse) {
+        // The message type of a response depends on its request
+    }
+
+    fn filter_response_for(
+        &self,
+        request: &SerializableRequest,
+        response: &mut SerializableResponse,
+    ) {
+        let Some((_, message)) = self.codec.messages_for(&request.url) else {
+            return;
+        };
+        if let Some(filtered) = self.filter_body(&response.headers, response.bytes(), message) {
+            response.set_bytes(filtered);
+        }
+    }
+}
+
+/// Matches like an inner matcher (a [`DefaultMatcher`] unless set) and also
+/// requires protobuf request bodies to hold the same messages. Messages are
+/// compared after decoding, so differences in field order or encoding don't
+/// matter, and fields passed to `ignore_field` are left out. Bodies the codec
+/// can't decode are compared byte for byte.
+#[derive(Debug)]
+pub struct ProtobufMatcher {
+    inner: Box<dyn RequestMatcher>,
+    ignored: ProtobufFilter,
+}
+
+impl ProtobufMatcher {
+    pub fn new(codec: ProtobufCodec) -> Self {
+        Self {
+            inner: Box::new(DefaultMatcher::new()),
+            ignored: ProtobufFilter::new(codec),
+        }
+    }
+
+    pub fn with_inner(mut self, inner: Box<dyn RequestMatcher>) -> Self {
+        self.inner = inner;
+        self
+    }
+
+    pub fn ignore_field(mut self, name: impl Into<String>) -> Self {
+        self.ignored = self.ignored.remove_field(name);
+        self
+    }
+
+    fn bodies_match(&self, request: &SerializableRequest, recorded: &SerializableRequest) -> bool {
+        let decode = |request: &SerializableRequest| {
+            let mut messages = self.ignored.codec.decode_request(request)?;
+            messages
+                .iter_mut()
+                .for_each(|message| self.ignored.filter_message(message));
+            Some(messages)
+        };
+        match (decode(request), decode(recorded)) {
+            (Some(messages), Some(recorded_messages)) => messages == recorded_messages,
+            _ => request.bytes() == recorded.bytes(),
+        }
+    }
+}
+
+impl RequestMatcher for ProtobufMatcher {
+    /// Live requests whose body couldn't be read can only be matched by the inner
+    /// matcher
+    fn matches(&self, request: &Request, recorded_request: &SerializableRequest) -> bool {
+        self.inner.matches(request, recorded_request)
+    }
+
+    fn matches_serializable(
+        &self,
+        request: &SerializableRequest,
+        recorded_request: &SerializableRequest,
+    ) -> bool {
+        self.inner.matches_serializable(request, recorded_request)
+            && self.bodies_match(request, recorded_request)
+    }
+
+    fn index_key(&self, request: &SerializableRequest) -> Option<String> {
+        self.inner.index_key(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::prost_types::{
+        field_descriptor_proto::Type, DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        FileDescriptorSet, MethodDescriptorProto, ServiceDescriptorProto,
+    };
+
+    fn codec() -> ProtobufCodec {
+        let field = |name: &str, number, kind: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            r#type: Some(kind as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("auth.proto".to_string()),
+            package: Some("acme".to_string()),
+            syntax: Some("proto3".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Login".to_string()),
+                    field: vec![
+                        field("user", 1, Type::String),
+                        field("password", 2, Type::String),
+                        field("nonce", 3, Type::Int64),
+                    ],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("Session".to_string()),
+                    field: vec![field("token", 1, Type::String)],
+                    ..Default::default()
+                },
+            ],
+            service: vec![ServiceDescriptorProto {
+                name: Some("Auth".to_string()),
+                method: vec![MethodDescriptorProto {
+                    name: Some("Login".to_string()),
+                    input_type: Some(".acme.Login".to_string()),
+                    output_type: Some(".acme.Session".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let set = FileDescriptorSet { file: vec![file] };
+        ProtobufCodec::from_descriptor_set(&set.encode_to_vec()).unwrap()
+    }
+
+    fn login(codec: &ProtobufCodec, password: &str, nonce: i64) -> SerializableRequest {
+        let descriptor = codec.pool.get_message_by_name("acme.Login").unwrap();
+        let mut message = DynamicMessage::new(descriptor);
+        message.set_field_by_name("user", Value::String("alice".to_string()));
+        message.set_field_by_name("password", Value::String(password.to_string()));
+        message.set_field_by_name("nonce", Value::I64(nonce));
+        let mut request = SerializableRequest {
+            method: "POST".to_string(),
+            url: "https://api.example.com/twirp/acme.Auth/Login".to_string(),
+            headers: Headers::from([(
+                "content-type".to_string(),
+                vec!["application/protobuf".to_string()],
+            )]),
+            body: None,
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+        request.set_bytes(message.encode_to_vec());
+        request
+    }
+
+    #[test]
+    fn test_protobuf_fields_are_filtered_and_matched_structurally() {
+        let codec = codec();
+        let filter = ProtobufFilter::new(codec.clone()).replace_field("password", "[FILTERED]");
+        let mut request = login(&codec, "hunter2", 1);
+        filter.filter_request(&mut request);
+        let messages = codec.decode_request(&request).unwrap();
+        assert_eq!(
+            messages[0].get_field_by_name("password").unwrap().as_str(),
+            Some("[FILTERED]")
+        );
+
+        // gRPC-web responses are filtered frame by frame, keeping the trailers
+        let session = {
+            let descriptor = codec.pool.get_message_by_name("acme.Session").unwrap();
+            let mut message = DynamicMessage::new(descriptor);
+            message.set_field_by_name("token", Value::String("secret".to_string()));
+            message.encode_to_vec()
+        };
+        let trailers = b"grpc-status: 0\r\n".to_vec();
+        let mut body = vec![0];
+        body.extend_from_slice(&(session.len() as u32).to_be_bytes());
+        body.extend_from_slice(&session);
+        body.push(0x80);
+        body.extend_from_slice(&(trailers.len() as u32).to_be_bytes());
+        body.extend_from_slice(&trailers);
+        let mut response = SerializableResponse {
+            status: 200,
+            headers: Headers::from([(
+                "content-type".to_string(),
+                vec!["application/grpc-web+proto".to_string()],
+            )]),
+            body: None,
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+        response.set_bytes(body);
+        ProtobufFilter::new(codec.clone())
+            .remove_field("token")
+            .filter_response_for(&request, &mut response);
+        let filtered = response.bytes().unwrap();
+        assert_eq!(&filtered[..5], [0, 0, 0, 0, 0]);
+        assert!(filtered.ends_with(&trailers));
+
+        let matcher = ProtobufMatcher::new(codec.clone())
+            .with_inner(Box::new(DefaultMatcher::new().with_headers(vec![])))
+            .ignore_field("nonce");
+        assert!(matcher.matches_serializable(&login(&codec, "a", 1), &login(&codec, "a", 2)));
+        assert!(!matcher.matches_serializable(&login(&codec, "a", 1), &login(&codec, "b", 1)));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::cassette::Interaction;
+
+/// Runs on every interaction just before it is written to the cassette, after
+/// filters have run. Unlike a `Filter`, a hook can drop the interaction entirely,
+/// e.g. to keep health checks or telemetry out of the cassette, or attach notes to
+/// its `metadata`.
+pub trait RecordHook: std::fmt::Debug + Send + Sync {
+    /// Return the interaction to record, possibly modified, or `None` to drop it
+    fn before_record(&self, interaction: Interaction) -> Option<Interaction>;
+}
+
+/// Pass `interaction` through each hook in order, stopping at the first that drops it
+pub(crate) fn run_record_hooks(
+    hooks: &[Box<dyn RecordHook>],
+    interaction: Interaction,
+) -> Option<Interaction> {
+    hooks.iter().try_fold(interaction, |interaction, hook| {
+        hook.before_record(interaction)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializable::{SerializableRequest, SerializableResponse};
+
+    #[derive(Debug)]
+    struct DropPath(&'static str);
+
+    impl RecordHook for DropPath {
+        fn before_record(&self, interaction: Interaction) -> Option<Interaction> {
+            (!interaction.request.url.ends_with(self.0)).then_some(interaction)
+        }
+    }
+
+    #[derive(Debug)]
+    struct Tag;
+
+    impl RecordHook for Tag {
+        fn before_record(&self, mut interaction: Interaction) -> Option<Interaction> {
+            interaction
+                .metadata
+                .insert("tagged".to_string(), "yes".to_string());
+            Some(interaction)
+        }
+    }
+
+    fn interaction(url: &str) -> Interaction {
+        Interaction {
+            request: SerializableRequest {
+                method: "GET".to_string(),
+                url: url.to_string(),
+                headers: Default::default(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            response: SerializableResponse {
+                status: 200,
+                headers: Default::default(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            recorded_at: None,
+            elapsed_ms: None,
+            sequence: None,
+            error: None,
+            stream: None,
+            metadata: Default::default(),
+            tags: Vec::new(),
+            requires_state: None,
+            sets_state: None,
+        }
+    }
+
+    #[test]
+    fn test_hooks_run_in_order_until_one_drops() {
+        let hooks: Vec<Box<dyn RecordHook>> = vec![Box::new(DropPath("/healthz")), Box::new(Tag)];
+
+        assert!(run_record_hooks(&hooks, interaction("https://api.test/healthz")).is_none());
+        let kept = run_record_hooks(&hooks, interaction("https://api.test/users")).unwrap();
+        assert_eq!(kept.metadata["tagged"], "yes");
+    }
+}
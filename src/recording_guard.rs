@@ -0,0 +1,79 @@
+use http_client::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// What happens when a client is built to record a cassette that another client in
+/// this process is already recording
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SharedCassetteAction {
+    /// Fail to build the second client
+    #[default]
+    Error,
+    /// Log a warning and build it anyway; whichever client saves last wins
+    Warn,
+}
+
+/// Marks a cassette as being recorded by a client in this process while it is held,
+/// so a second client recording the same path is caught instead of silently
+/// overwriting the first one's saves
+#[derive(Debug)]
+pub(crate) struct RecordingGuard {
+    path: PathBuf,
+}
+
+impl RecordingGuard {
+    pub(crate) fn acquire(path: &Path, action: SharedCassetteAction) -> Result<Self, Error> {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut recording = recording_paths().lock().unwrap();
+        let holders = recording.entry(path.clone()).or_default();
+        if *holders > 0 {
+            let message = format!(
+                "Cassette {path:?} is already being recorded by another VcrClient in this \
+                 process; drop it first, or the two will overwrite each other's recordings"
+            );
+            match action {
+                SharedCassetteAction::Error => return Err(Error::from_str(409, message)),
+                SharedCassetteAction::Warn => log::warn!("{message}"),
+            }
+        }
+        *holders += 1;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for RecordingGuard {
+    fn drop(&mut self) {
+        let mut recording = recording_paths().lock().unwrap();
+        if let Some(holders) = recording.get_mut(&self.path) {
+            *holders -= 1;
+            if *holders == 0 {
+                recording.remove(&self.path);
+            }
+        }
+    }
+}
+
+/// Cassettes clients in this process are recording, with the number of clients
+fn recording_paths() -> &'static Mutex<HashMap<PathBuf, usize>> {
+    static RECORDING: OnceLock<Mutex<HashMap<PathBuf, usize>>> = OnceLock::new();
+    RECORDING.get_or_init(Default::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_guard_rejects_a_second_recorder() {
+        let path = std::env::temp_dir().join(format!("vcr-guard-{}.yaml", std::process::id()));
+        let first = RecordingGuard::acquire(&path, SharedCassetteAction::Error).unwrap();
+        assert!(RecordingGuard::acquire(&path, SharedCassetteAction::Error).is_err());
+
+        let second = RecordingGuard::acquire(&path, SharedCassetteAction::Warn).unwrap();
+        drop(first);
+        assert!(RecordingGuard::acquire(&path, SharedCassetteAction::Error).is_err());
+        drop(second);
+        drop(RecordingGuard::acquire(&path, SharedCassetteAction::Error).unwrap());
+    }
+}
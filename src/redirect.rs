@@ -0,0 +1,131 @@
+use http_client::{Error, Request, Response};
+use http_types::headers::{
+    HeaderName, HeaderValues, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION,
+};
+use http_types::mime::Mime;
+use http_types::{Body, Method, StatusCode, Url, Version};
+
+/// The request a redirect chain is currently on, kept so each hop can be sent as
+/// its own request. Follows the same rules as browsers and most clients: 303, and
+/// 301/302 after a POST, continue as a GET without a body; 307 and 308 resend the
+/// request unchanged. Credentials are dropped when the redirect leaves the host.
+#[derive(Debug)]
+pub(crate) struct RedirectHop {
+    method: Method,
+    url: Url,
+    version: Option<Version>,
+    headers: Vec<(HeaderName, HeaderValues)>,
+    body: Option<(Vec<u8>, Option<Mime>)>,
+}
+
+impl RedirectHop {
+    /// Buffer `req`'s body so it can be sent again after a 307 or 308
+    pub(crate) async fn from_request(mut req: Request) -> Result<Self, Error> {
+        let body = req.take_body();
+        let body = match body.len() {
+            Some(0) => None,
+            _ => {
+                let mime = body.mime().cloned();
+                Some((body.into_bytes().await?, mime))
+            }
+        };
+        Ok(Self {
+            method: req.method(),
+            url: req.url().clone(),
+            version: req.version(),
+            headers: req
+                .iter()
+                .map(|(name, values)| (name.clone(), values.clone()))
+                .collect(),
+            body,
+        })
+    }
+
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// A fresh copy of the current request
+    pub(crate) fn request(&self) -> Request {
+        let mut req = Request::new(self.method, self.url.clone());
+        req.set_version(self.version);
+        for (name, values) in &self.headers {
+            for value in values.iter() {
+                let _ = req.append_header(name, value.clone());
+            }
+        }
+        if let Some((bytes, mime)) = &self.body {
+            let mut body = Body::from_bytes(bytes.clone());
+            body.set_mime(mime.clone());
+            req.set_body(body);
+        }
+        req
+    }
+
+    /// Move on to where `response` redirects to. Returns `false`, leaving the hop
+    /// unchanged, if it isn't a redirect with a usable `Location`.
+    pub(crate) fn follow(&mut self, response: &Response) -> bool {
+        let status = response.status();
+        if !matches!(
+            status,
+            StatusCode::MovedPermanently
+                | StatusCode::Found
+                | StatusCode::SeeOther
+                | StatusCode::TemporaryRedirect
+                | StatusCode::PermanentRedirect
+        ) {
+            return false;
+        }
+        let Some(location) = response.header(LOCATION) else {
+            return false;
+        };
+        let Ok(next) = self.url.join(location.last().as_str()) else {
+            log::warn!("Not following redirect to invalid location {location}");
+            return false;
+        };
+
+        let becomes_get = status == StatusCode::SeeOther
+            || (matches!(status, StatusCode::MovedPermanently | StatusCode::Found)
+                && self.method == Method::Post);
+        if becomes_get {
+            if self.method != Method::Head {
+                self.method = Method::Get;
+            }
+            self.body = None;
+            self.headers
+                .retain(|(name, _)| *name != CONTENT_TYPE && *name != CONTENT_LENGTH);
+        }
+        if next.origin() != self.url.origin() {
+            self.headers
+                .retain(|(name, _)| *name != AUTHORIZATION && *name != COOKIE);
+        }
+        self.url = next;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_see_other_to_another_host_becomes_plain_get() {
+        let mut req = Request::new(Method::Post, Url::parse("https://a.example/login").unwrap());
+        req.insert_header(AUTHORIZATION, "Bearer secret").unwrap();
+        req.insert_header("x-trace", "1").unwrap();
+        let mut hop = futures_lite::future::block_on(RedirectHop::from_request(req)).unwrap();
+
+        let mut response = Response::new(StatusCode::SeeOther);
+        response
+            .insert_header(LOCATION, "https://b.example/home")
+            .unwrap();
+        assert!(hop.follow(&response));
+
+        let next = hop.request();
+        assert_eq!(next.method(), Method::Get);
+        assert_eq!(next.url().as_str(), "https://b.example/home");
+        assert!(next.header(AUTHORIZATION).is_none());
+        assert!(next.header("x-trace").is_some());
+        assert!(!hop.follow(&Response::new(StatusCode::Ok)));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::cassette::Interaction;
+use http_client::Response;
+
+/// Adjusts every replayed response right before it is returned, after filters,
+/// time shifting and templating have run. The cassette itself is never changed, so
+/// this suits per-test tweaks like fresh request IDs or dates relative to now.
+pub trait ReplayTransformer: std::fmt::Debug + Send + Sync {
+    /// Modify `response`, built from the matched `interaction`
+    fn before_playback(&self, response: &mut Response, interaction: &Interaction);
+}
+
+/// Run each transformer over `response` in the order they were added
+pub(crate) fn run_replay_transformers(
+    transformers: &[Box<dyn ReplayTransformer>],
+    response: &mut Response,
+    interaction: &Interaction,
+) {
+    for transformer in transformers {
+        transformer.before_playback(response, interaction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+
+    #[derive(Debug)]
+    struct AppendHeader(&'static str);
+
+    impl ReplayTransformer for AppendHeader {
+        fn before_playback(&self, response: &mut Response, interaction: &Interaction) {
+            response
+                .append_header("x-trace", format!("{}:{}", self.0, interaction.request.url))
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transformers_run_in_order() {
+        let interaction = Interaction {
+            request: SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://api.test/users".to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            response: SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            recorded_at: None,
+            elapsed_ms: None,
+            sequence: None,
+            error: None,
+            stream: None,
+            metadata: Default::default(),
+            tags: Vec::new(),
+            requires_state: None,
+            sets_state: None,
+        };
+        let transformers: Vec<Box<dyn ReplayTransformer>> = vec![
+            Box::new(AppendHeader("first")),
+            Box::new(AppendHeader("second")),
+        ];
+
+        let mut response = interaction.response.to_response().await;
+        run_replay_transformers(&transformers, &mut response, &interaction);
+        let values: Vec<_> = response["x-trace"].iter().map(|v| v.as_str()).collect();
+        assert_eq!(
+            values,
+            [
+                "first:https://api.test/users",
+                "second:https://api.test/users"
+            ]
+        );
+    }
+}
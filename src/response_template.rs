@@ -0,0 +1,136 @@
+use crate::serializable::SerializableResponse;
+use http_client::Request;
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Values available to `{{...}}` directives in a replayed response.
+///
+/// Supported directives:
+///
+/// - `{{now_iso8601}}`: the current time as RFC 3339, e.g. `2026-10-17T09:30:00Z`
+/// - `{{now_unix}}`: the current time in seconds since the Unix epoch
+/// - `{{uuid}}`: a random v4 UUID, the same for every occurrence within one response
+/// - `{{request.method}}`, `{{request.url}}` and `{{request.url.path}}`
+/// - `{{request.url.param.<name>}}`: a query parameter of the live request
+/// - `{{request.header.<name>}}`: a header of the live request
+///
+/// Unknown directives and parameters or headers missing from the live request are
+/// left as they are.
+pub(crate) struct TemplateContext<'a> {
+    request: &'a Request,
+    now: OffsetDateTime,
+    uuid: String,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub(crate) fn new(request: &'a Request) -> Self {
+        Self {
+            request,
+            now: OffsetDateTime::now_utc(),
+            uuid: random_uuid(),
+        }
+    }
+
+    /// Expand the directives in the headers and text body of `response`
+    pub(crate) fn expand_response(&self, response: &mut SerializableResponse) {
+        for values in response.headers.values_mut() {
+            for value in values.iter_mut() {
+                *value = self.expand(value);
+            }
+        }
+        if let Some(body) = &mut response.body {
+            *body = self.expand(body).into();
+        }
+    }
+
+    pub(crate) fn expand(&self, text: &str) -> String {
+        directive_regex()
+            .replace_all(text, |caps: &Captures| {
+                self.resolve(&caps[1])
+                    .unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned()
+    }
+
+    fn resolve(&self, directive: &str) -> Option<String> {
+        let url = self.request.url();
+        match directive {
+            "now_iso8601" => self.now.format(&Rfc3339).ok(),
+            "now_unix" => Some(self.now.unix_timestamp().to_string()),
+            "uuid" => Some(self.uuid.clone()),
+            "request.method" => Some(self.request.method().to_string()),
+            "request.url" => Some(url.to_string()),
+            "request.url.path" => Some(url.path().to_string()),
+            _ => {
+                if let Some(name) = directive.strip_prefix("request.url.param.") {
+                    url.query_pairs()
+                        .find(|(key, _)| key == name)
+                        .map(|(_, value)| value.into_owned())
+                } else if let Some(name) = directive.strip_prefix("request.header.") {
+                    self.request
+                        .header(name)
+                        .map(|values| values.as_str().to_string())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+fn directive_regex() -> &'static Regex {
+    static DIRECTIVE: OnceLock<Regex> = OnceLock::new();
+    DIRECTIVE
+        .get_or_init(|| Regex::new(r"\{\{\s*([A-Za-z0-9_.-]+)\s*\}\}").expect("directive pattern"))
+}
+
+fn random_uuid() -> String {
+    let mut bytes = fastrand::u128(..).to_be_bytes();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::placeholder::PlaceholderKind;
+    use http_types::{Method, Url};
+
+    #[test]
+    fn test_expands_request_and_generated_values() {
+        let mut request = Request::new(
+            Method::Get,
+            Url::parse("https://api.example.com/users?id=42").unwrap(),
+        );
+        request.insert_header("X-Trace", "abc").unwrap();
+        let context = TemplateContext::new(&request);
+
+        assert_eq!(
+            context.expand(
+                r#"{"id": "{{request.url.param.id}}", "trace": "{{ request.header.x-trace }}"}"#
+            ),
+            r#"{"id": "42", "trace": "abc"}"#
+        );
+        assert_eq!(context.expand("{{uuid}} {{uuid}}"), {
+            let uuid = context.expand("{{uuid}}");
+            format!("{uuid} {uuid}")
+        });
+        assert!(PlaceholderKind::Uuid.accepts(&context.expand("{{uuid}}")));
+        assert!(OffsetDateTime::parse(&context.expand("{{now_iso8601}}"), &Rfc3339).is_ok());
+        assert_eq!(
+            context.expand("{{request.url.param.missing}} {{unknown}}"),
+            "{{request.url.param.missing}} {{unknown}}"
+        );
+    }
+}
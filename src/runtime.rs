@@ -0,0 +1,92 @@
+//! The few things the client needs from its environment: timers and somewhere to run
+//! blocking saves. Natively these come from `async-io` and `blocking`, which work
+//! under any executor. On `wasm32` timers are the browser's `setTimeout`, and since
+//! there are no threads, saves run inline.
+
+pub(crate) use web_time::Instant;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use async_io::Timer;
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use blocking::{unblock, Task};
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) use wasm::{unblock, Task, Timer};
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use super::Instant;
+    use gloo_timers::future::TimeoutFuture;
+    use send_wrapper::SendWrapper;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    /// A `setTimeout` that fires at `deadline`. The browser runs everything on one
+    /// thread, so the wrapper only makes it `Send` for bodies that have to be.
+    pub(crate) struct Timer {
+        deadline: Instant,
+        timeout: SendWrapper<TimeoutFuture>,
+    }
+
+    impl Timer {
+        pub(crate) fn after(duration: Duration) -> Self {
+            Self::at(Instant::now() + duration)
+        }
+
+        pub(crate) fn at(deadline: Instant) -> Self {
+            let millis = deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis();
+            Timer {
+                deadline,
+                timeout: SendWrapper::new(TimeoutFuture::new(
+                    u32::try_from(millis).unwrap_or(u32::MAX),
+                )),
+            }
+        }
+    }
+
+    impl std::fmt::Debug for Timer {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Timer")
+                .field("deadline", &self.deadline)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl Future for Timer {
+        type Output = Instant;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Instant> {
+            Pin::new(&mut *self.timeout)
+                .poll(cx)
+                .map(|()| Instant::now())
+        }
+    }
+
+    /// The result of work that already ran on the calling thread
+    #[derive(Debug)]
+    pub(crate) struct Task<T>(Option<T>);
+
+    impl<T> Task<T> {
+        pub(crate) fn is_finished(&self) -> bool {
+            true
+        }
+
+        pub(crate) fn detach(self) {}
+    }
+
+    impl<T: Unpin> Future for Task<T> {
+        type Output = T;
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<T> {
+            Poll::Ready(self.0.take().expect("Task polled after completion"))
+        }
+    }
+
+    pub(crate) fn unblock<T, F: FnOnce() -> T>(work: F) -> Task<T> {
+        Task(Some(work()))
+    }
+}
@@ -1,14 +1,35 @@
+use crate::body_text::BodyText;
+use crate::cookie::{
+    redact_request_cookies, redact_response_cookies, request_cookies, response_cookies, Cookie,
+};
+use crate::form_data::{encode_form_data, parse_form_data};
 use base64::{engine::general_purpose, Engine as _};
 use http_client::{Error, Request, Response};
-use http_types::{Method, StatusCode, Url};
-use serde::{Deserialize, Serialize};
+use http_types::{Method, StatusCode, Url, Version};
+use indexmap::IndexMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use std::borrow::Cow;
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Header values by name. Repeated headers, like `Set-Cookie`, keep every value in
+/// order, and headers read from a cassette keep the order they were written in.
+/// Headers copied from an http-types message are sorted by name, since http-types
+/// doesn't keep their order.
+pub type Headers = IndexMap<String, Vec<String>>;
+
+/// A recorded request.
+///
+/// `url` is the full URL. Cassettes store its query string as a separate `query`
+/// list of name/value pairs, so parameters are readable and diff cleanly, unless
+/// re-encoding the pairs wouldn't give back the exact same URL. Cassettes that
+/// only have `url` still load.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "StoredRequest")]
 pub struct SerializableRequest {
     pub method: String,
     pub url: String,
-    pub headers: HashMap<String, Vec<String>>,
+    pub headers: Headers,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub body: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -19,39 +40,90 @@ pub struct SerializableRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SerializableResponse {
     pub status: u16,
-    pub headers: HashMap<String, Vec<String>>,
+    pub headers: Headers,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub body: Option<String>,
+    pub body: Option<BodyText>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub body_base64: Option<String>,
     pub version: String,
 }
 
+/// A query parameter as cassettes store it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct QueryParam {
+    pub(crate) name: String,
+    pub(crate) value: String,
+}
+
+#[derive(Serialize)]
+struct StoredRequestRef<'a> {
+    method: &'a str,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    query: Vec<QueryParam>,
+    headers: &'a Headers,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_base64: &'a Option<String>,
+    version: &'a str,
+}
+
+#[derive(Deserialize)]
+struct StoredRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    query: Vec<QueryParam>,
+    headers: Headers,
+    body: Option<String>,
+    body_base64: Option<String>,
+    version: String,
+}
+
+impl Serialize for SerializableRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (url, query) = split_url(&self.url);
+        StoredRequestRef {
+            method: &self.method,
+            url: &url,
+            query,
+            headers: &self.headers,
+            body: &self.body,
+            body_base64: &self.body_base64,
+            version: &self.version,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl From<StoredRequest> for SerializableRequest {
+    fn from(stored: StoredRequest) -> Self {
+        Self {
+            method: stored.method,
+            url: join_url(stored.url, stored.query),
+            headers: stored.headers,
+            body: stored.body,
+            body_base64: stored.body_base64,
+            version: stored.version,
+        }
+    }
+}
+
 impl SerializableRequest {
     pub async fn from_request(mut req: Request) -> Result<Self, Error> {
         let method = req.method().to_string();
         let url = req.url().to_string();
         let version = format!("{:?}", req.version());
 
-        let mut headers = HashMap::new();
-        for (name, values) in req.iter() {
-            let header_values: Vec<String> =
-                values.iter().map(|v| v.as_str().to_string()).collect();
-            headers.insert(name.as_str().to_string(), header_values);
-        }
+        let headers = headers_from(req.as_ref());
 
         let (body, body_base64) = if req.len().is_some() {
-            let body_string = req
-                .body_string()
+            let bytes = req
+                .body_bytes()
                 .await
                 .map_err(|e| Error::from_str(500, format!("Failed to read request body: {e}")))?;
-
-            // Check if body contains binary/HTML content that should be base64 encoded
-            if Self::should_base64_encode(&body_string) {
-                (None, Some(general_purpose::STANDARD.encode(&body_string)))
-            } else {
-                (Some(body_string), None)
-            }
+            body_fields(bytes)
         } else {
             (None, None)
         };
@@ -78,6 +150,7 @@ impl SerializableRequest {
             .map_err(|e| Error::from_str(400, format!("Invalid URL: {e}")))?;
 
         let mut req = Request::new(method, url);
+        req.set_version(parse_version(&self.version));
 
         for (name, values) in &self.headers {
             for value in values {
@@ -91,24 +164,128 @@ impl SerializableRequest {
             let decoded = general_purpose::STANDARD
                 .decode(body_base64)
                 .map_err(|e| Error::from_str(500, format!("Failed to decode base64 body: {e}")))?;
-            let body_string = String::from_utf8(decoded).map_err(|e| {
-                Error::from_str(
-                    500,
-                    format!("Failed to convert decoded body to string: {e}"),
-                )
-            })?;
-            req.set_body(body_string);
+            req.set_body(decoded_body(decoded));
         }
 
         Ok(req)
     }
 
-    /// Determine if content should be base64 encoded to avoid YAML serialization issues
-    fn should_base64_encode(content: &str) -> bool {
-        // Base64 encode if content contains HTML tags, special YAML characters, or high ratio of non-ASCII
-        content.contains('<') && content.contains('>') || // HTML content
-        content.contains('%') && content.len() > 100 || // URL-encoded content
-        content.chars().filter(|c| !c.is_ascii()).count() > content.len() / 10 // High non-ASCII ratio
+    /// The URL without its query string or fragment
+    pub fn base_url(&self) -> &str {
+        let end = self.url.find(['?', '#']).unwrap_or(self.url.len());
+        &self.url[..end]
+    }
+
+    /// The decoded query parameters in order, keeping every value of repeated names
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        query_string(&self.url)
+            .map(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Replace the query string with `pairs`, encoded in order. An empty list
+    /// removes the query string entirely.
+    pub fn set_query_pairs<K, V>(&mut self, pairs: impl IntoIterator<Item = (K, V)>)
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let fragment = self.url.find('#').map(|at| self.url[at..].to_string());
+        let mut url = self.base_url().to_string();
+        let query = encode_query(pairs);
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query);
+        }
+        url.extend(fragment);
+        self.url = url;
+    }
+
+    /// The body as text, decoding `body_base64` if that's where it is stored.
+    /// `None` if there is no body or it isn't valid UTF-8.
+    pub fn text(&self) -> Option<String> {
+        body_text(&self.body, &self.body_base64)
+    }
+
+    /// The raw body bytes, decoding `body_base64` if that's where it is stored
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        body_bytes(&self.body, &self.body_base64)
+    }
+
+    /// Deserialize the body as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.text().unwrap_or_default())
+    }
+
+    /// Parse the body as URL-encoded form data
+    pub fn form(&self) -> HashMap<String, String> {
+        self.text()
+            .map(|text| parse_form_data(&text))
+            .unwrap_or_default()
+    }
+
+    /// Replace the body with text, stored in whichever field suits its content
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        set_body_text(&mut self.body, &mut self.body_base64, text.into());
+    }
+
+    /// Replace the body with raw bytes; bytes that aren't UTF-8 are stored as base64
+    pub fn set_bytes(&mut self, bytes: Vec<u8>) {
+        set_body_bytes(&mut self.body, &mut self.body_base64, bytes);
+    }
+
+    /// Replace the body with a value serialized as JSON
+    pub fn set_json<T: Serialize>(&mut self, value: &T) -> serde_json::Result<()> {
+        self.set_text(serde_json::to_string(value)?);
+        Ok(())
+    }
+
+    /// Replace the body with URL-encoded form data
+    pub fn set_form(&mut self, params: &HashMap<String, String>) {
+        self.set_text(encode_form_data(params));
+    }
+
+    /// The cookies sent in `Cookie` headers, in order
+    pub fn cookies(&self) -> Vec<Cookie> {
+        request_cookies(&self.headers)
+    }
+
+    /// Replace every cookie value in `Cookie` headers with `placeholder`, keeping
+    /// the names so matchers can still tell sessions apart from anonymous requests
+    pub fn redact_cookie_values(&mut self, placeholder: &str) {
+        redact_request_cookies(&mut self.headers, placeholder);
+    }
+
+    /// Run `f` with a base64-stored body decoded into `body`, then store the result
+    /// back in whichever field `from_request` would have used for it.
+    ///
+    /// Lets filters and mutators work on `body` without caring how it was stored.
+    /// Bodies that aren't valid UTF-8 are left in `body_base64`. If `f` changes the
+    /// body's length, `Content-Length` is updated to match.
+    pub fn with_decoded_body<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        let len = body_len(&self.body, &self.body_base64);
+        let decoded = decode_body(&mut self.body, &mut self.body_base64);
+        f(self);
+        if decoded {
+            encode_body(&mut self.body, &mut self.body_base64);
+        }
+        let new_len = body_len(&self.body, &self.body_base64);
+        if new_len != len {
+            fix_length_headers(&mut self.headers, new_len);
+        }
+    }
+
+    /// Make `Content-Length` match the stored body and drop `Transfer-Encoding:
+    /// chunked`, which no longer describes it. Requests without a body are left alone.
+    pub fn fix_body_headers(&mut self) {
+        let len = body_len(&self.body, &self.body_base64);
+        if len > 0 {
+            fix_length_headers(&mut self.headers, len);
+        }
     }
 }
 
@@ -117,25 +294,14 @@ impl SerializableResponse {
         let status = res.status().into();
         let version = format!("{:?}", res.version());
 
-        let mut headers = HashMap::new();
-        for (name, values) in res.iter() {
-            let header_values: Vec<String> =
-                values.iter().map(|v| v.as_str().to_string()).collect();
-            headers.insert(name.as_str().to_string(), header_values);
-        }
+        let headers = headers_from(res.as_ref());
 
         let (body, body_base64) = if res.len().is_some() {
-            let body_string = res
-                .body_string()
+            let bytes = res
+                .body_bytes()
                 .await
                 .map_err(|e| Error::from_str(500, format!("Failed to read response body: {e}")))?;
-
-            // Check if body contains binary/HTML content that should be base64 encoded
-            if Self::should_base64_encode(&body_string) {
-                (None, Some(general_purpose::STANDARD.encode(&body_string)))
-            } else {
-                (Some(body_string), None)
-            }
+            body_fields(bytes)
         } else {
             (None, None)
         };
@@ -153,6 +319,7 @@ impl SerializableResponse {
         let status = StatusCode::try_from(self.status).unwrap_or(StatusCode::InternalServerError);
 
         let mut res = Response::new(status);
+        res.set_version(parse_version(&self.version));
 
         for (name, values) in &self.headers {
             for value in values {
@@ -161,23 +328,329 @@ impl SerializableResponse {
         }
 
         if let Some(body) = &self.body {
-            res.set_body(body.clone());
+            let mut body = shared_body(body.clone());
+            body.set_mime(Some(http_types::mime::PLAIN));
+            res.set_body(body);
         } else if let Some(body_base64) = &self.body_base64 {
             if let Ok(decoded) = general_purpose::STANDARD.decode(body_base64) {
-                if let Ok(body_string) = String::from_utf8(decoded) {
-                    res.set_body(body_string);
-                }
+                res.set_body(decoded_body(decoded));
             }
         }
 
         res
     }
 
-    /// Determine if content should be base64 encoded to avoid YAML serialization issues
-    fn should_base64_encode(content: &str) -> bool {
-        // Base64 encode if content contains HTML tags, special YAML characters, or high ratio of non-ASCII
-        content.contains('<') && content.contains('>') || // HTML content
-        content.contains('%') && content.len() > 100 || // URL-encoded content
-        content.chars().filter(|c| !c.is_ascii()).count() > content.len() / 10 // High non-ASCII ratio
+    /// The body as text, decoding `body_base64` if that's where it is stored.
+    /// `None` if there is no body or it isn't valid UTF-8.
+    pub fn text(&self) -> Option<String> {
+        body_text(&self.body, &self.body_base64)
+    }
+
+    /// The raw body bytes, decoding `body_base64` if that's where it is stored
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        body_bytes(&self.body, &self.body_base64)
+    }
+
+    /// Deserialize the body as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_str(&self.text().unwrap_or_default())
+    }
+
+    /// Parse the body as URL-encoded form data
+    pub fn form(&self) -> HashMap<String, String> {
+        self.text()
+            .map(|text| parse_form_data(&text))
+            .unwrap_or_default()
+    }
+
+    /// Replace the body with text, stored in whichever field suits its content
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        set_body_text(&mut self.body, &mut self.body_base64, text.into());
+    }
+
+    /// Replace the body with raw bytes; bytes that aren't UTF-8 are stored as base64
+    pub fn set_bytes(&mut self, bytes: Vec<u8>) {
+        set_body_bytes(&mut self.body, &mut self.body_base64, bytes);
+    }
+
+    /// Replace the body with a value serialized as JSON
+    pub fn set_json<T: Serialize>(&mut self, value: &T) -> serde_json::Result<()> {
+        self.set_text(serde_json::to_string(value)?);
+        Ok(())
+    }
+
+    /// Replace the body with URL-encoded form data
+    pub fn set_form(&mut self, params: &HashMap<String, String>) {
+        self.set_text(encode_form_data(params));
+    }
+
+    /// The cookies set by `Set-Cookie` headers, in order
+    pub fn cookies(&self) -> Vec<Cookie> {
+        response_cookies(&self.headers)
+    }
+
+    /// Replace every `Set-Cookie` value with `placeholder`, keeping cookie names
+    /// and attributes like `Path` and `Expires`
+    pub fn redact_cookie_values(&mut self, placeholder: &str) {
+        redact_response_cookies(&mut self.headers, placeholder);
+    }
+
+    /// Run `f` with a base64-stored body decoded into `body`, then store the result
+    /// back in whichever field `from_response` would have used for it.
+    ///
+    /// Lets filters and mutators work on `body` without caring how it was stored.
+    /// Bodies that aren't valid UTF-8 are left in `body_base64`. If `f` changes the
+    /// body's length, `Content-Length` is updated to match.
+    pub fn with_decoded_body<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        let len = body_len(&self.body, &self.body_base64);
+        let decoded = decode_body(&mut self.body, &mut self.body_base64);
+        f(self);
+        if decoded {
+            encode_body(&mut self.body, &mut self.body_base64);
+        }
+        let new_len = body_len(&self.body, &self.body_base64);
+        if new_len != len {
+            fix_length_headers(&mut self.headers, new_len);
+        }
+    }
+
+    /// Make `Content-Length` match the stored body and drop `Transfer-Encoding:
+    /// chunked`, which no longer describes it. Responses without a body
+    /// are left alone, since HEAD and 304 responses describe a body they don't carry.
+    pub fn fix_body_headers(&mut self) {
+        let len = body_len(&self.body, &self.body_base64);
+        if len > 0 {
+            fix_length_headers(&mut self.headers, len);
+        }
+    }
+}
+
+/// Determine if content should be base64 encoded to avoid YAML serialization issues
+fn should_base64_encode(content: &str) -> bool {
+    // Base64 encode if content contains HTML tags, special YAML characters, or high ratio of non-ASCII
+    content.contains('<') && content.contains('>') || // HTML content
+    content.contains('%') && content.len() > 100 || // URL-encoded content
+    content.chars().filter(|c| !c.is_ascii()).count() > content.len() / 10 // High non-ASCII ratio
+}
+
+/// The raw query string of `url`, if it has one
+fn query_string(url: &str) -> Option<&str> {
+    let without_fragment = url.split('#').next().unwrap_or(url);
+    without_fragment.split_once('?').map(|(_, query)| query)
+}
+
+fn encode_query<K: AsRef<str>, V: AsRef<str>>(pairs: impl IntoIterator<Item = (K, V)>) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+/// Split `url` into the URL without its query string and the query's parameters.
+/// URLs whose query wouldn't be encoded back exactly the same way are returned whole.
+pub(crate) fn split_url(url: &str) -> (Cow<'_, str>, Vec<QueryParam>) {
+    let Some(query) = query_string(url).filter(|query| !query.is_empty()) else {
+        return (Cow::Borrowed(url), Vec::new());
+    };
+    let pairs: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    if encode_query(pairs.iter().map(|(name, value)| (name, value))) != query {
+        return (Cow::Borrowed(url), Vec::new());
+    }
+
+    let query_start = url.find('?').unwrap_or(url.len());
+    let base = format!(
+        "{}{}",
+        &url[..query_start],
+        &url[query_start + 1 + query.len()..]
+    );
+    let query = pairs
+        .into_iter()
+        .map(|(name, value)| QueryParam { name, value })
+        .collect();
+    (Cow::Owned(base), query)
+}
+
+/// Add stored query parameters back onto `url`, after any query it already has
+pub(crate) fn join_url(url: String, query: Vec<QueryParam>) -> String {
+    if query.is_empty() {
+        return url;
+    }
+    let (base, fragment) = match url.find('#') {
+        Some(at) => (&url[..at], &url[at..]),
+        None => (url.as_str(), ""),
+    };
+    let separator = match base.find('?') {
+        Some(at) if at + 1 < base.len() => "&",
+        Some(_) => "",
+        None => "?",
+    };
+    let query = encode_query(query.iter().map(|param| (&param.name, &param.value)));
+    format!("{base}{separator}{query}{fragment}")
+}
+
+/// Parse a stored HTTP version. Recordings store the `Debug` form of the message's
+/// version, like `Some(Http2_0)` or `None`; hand-written cassettes may also use
+/// `Http1_1` or `HTTP/1.1`. Unrecognized versions are treated as unknown.
+pub(crate) fn parse_version(version: &str) -> Option<Version> {
+    let version = version.trim();
+    let version = version
+        .strip_prefix("Some(")
+        .and_then(|inner| inner.strip_suffix(')'))
+        .unwrap_or(version);
+    let version = version
+        .strip_prefix("HTTP/")
+        .or_else(|| version.strip_prefix("http/"))
+        .unwrap_or(version);
+    match version {
+        "Http0_9" | "0.9" => Some(Version::Http0_9),
+        "Http1_0" | "1.0" => Some(Version::Http1_0),
+        "Http1_1" | "1.1" => Some(Version::Http1_1),
+        "Http2_0" | "2" | "2.0" => Some(Version::Http2_0),
+        "Http3_0" | "3" | "3.0" => Some(Version::Http3_0),
+        _ => None,
+    }
+}
+
+/// Copy the headers of an http-types message. http-types doesn't keep their order,
+/// so they are sorted by name to keep recordings deterministic.
+pub(crate) fn headers_from(headers: &http_types::headers::Headers) -> Headers {
+    let mut copied: Headers = headers
+        .iter()
+        .map(|(name, values)| {
+            let values = values.iter().map(|value| value.as_str().to_string());
+            (name.as_str().to_string(), values.collect())
+        })
+        .collect();
+    copied.sort_keys();
+    copied
+}
+
+/// Length in bytes of the body, decoding `body_base64` if that's where it is stored
+fn body_len<B: AsRef<str>>(body: &Option<B>, body_base64: &Option<String>) -> usize {
+    match (body, body_base64) {
+        (Some(body), _) => body.as_ref().len(),
+        (None, Some(encoded)) => general_purpose::STANDARD
+            .decode(encoded)
+            .map_or(0, |bytes| bytes.len()),
+        (None, None) => 0,
+    }
+}
+
+/// Set any `Content-Length` header to `len` and drop chunked `Transfer-Encoding`,
+/// since stored bodies are whole
+fn fix_length_headers(headers: &mut Headers, len: usize) {
+    for (name, values) in headers.iter_mut() {
+        if name.eq_ignore_ascii_case("content-length") {
+            *values = vec![len.to_string()];
+        }
+    }
+    headers.retain(|name, values| {
+        !(name.eq_ignore_ascii_case("transfer-encoding")
+            && values
+                .iter()
+                .any(|value| value.to_ascii_lowercase().contains("chunked")))
+    });
+}
+
+/// Split raw body bytes into `(body, body_base64)`: text goes in `body` unless it
+/// needs encoding, and bytes that aren't UTF-8 are kept exactly as base64
+pub(crate) fn body_fields<B: AsRef<str> + From<String>>(
+    bytes: Vec<u8>,
+) -> (Option<B>, Option<String>) {
+    let (mut body, mut body_base64) = (None, None);
+    set_body_bytes(&mut body, &mut body_base64, bytes);
+    (body, body_base64)
+}
+
+/// An http-types body reading from `buffer` without copying it
+pub(crate) fn shared_body<T>(buffer: T) -> http_types::Body
+where
+    T: AsRef<[u8]> + Unpin + Send + Sync + 'static,
+{
+    let len = buffer.as_ref().len();
+    http_types::Body::from_reader(futures_lite::io::Cursor::new(buffer), Some(len as u64))
+}
+
+/// A decoded base64 body as an http-types body, sent as text when it is UTF-8 and
+/// byte for byte otherwise
+fn decoded_body(decoded: Vec<u8>) -> http_types::Body {
+    match String::from_utf8(decoded) {
+        Ok(text) => text.into(),
+        Err(e) => e.into_bytes().into(),
+    }
+}
+
+fn decode_base64_text(body_base64: &str) -> Option<String> {
+    let decoded = general_purpose::STANDARD.decode(body_base64).ok()?;
+    String::from_utf8(decoded).ok()
+}
+
+fn body_text<B: AsRef<str>>(body: &Option<B>, body_base64: &Option<String>) -> Option<String> {
+    body.as_ref()
+        .map(|body| body.as_ref().to_string())
+        .or_else(|| body_base64.as_deref().and_then(decode_base64_text))
+}
+
+pub(crate) fn body_bytes<B: AsRef<str>>(
+    body: &Option<B>,
+    body_base64: &Option<String>,
+) -> Option<Vec<u8>> {
+    match (body, body_base64) {
+        (Some(body), _) => Some(body.as_ref().as_bytes().to_vec()),
+        (None, Some(encoded)) => general_purpose::STANDARD.decode(encoded).ok(),
+        (None, None) => None,
+    }
+}
+
+fn set_body_text<B: AsRef<str> + From<String>>(
+    body: &mut Option<B>,
+    body_base64: &mut Option<String>,
+    text: String,
+) {
+    *body = Some(text.into());
+    *body_base64 = None;
+    encode_body(body, body_base64);
+}
+
+fn set_body_bytes<B: AsRef<str> + From<String>>(
+    body: &mut Option<B>,
+    body_base64: &mut Option<String>,
+    bytes: Vec<u8>,
+) {
+    match String::from_utf8(bytes) {
+        Ok(text) => set_body_text(body, body_base64, text),
+        Err(e) => {
+            *body = None;
+            *body_base64 = Some(general_purpose::STANDARD.encode(e.into_bytes()));
+        }
+    }
+}
+
+/// Move a base64-stored text body into `body`, returning whether anything moved
+fn decode_body<B: From<String>>(body: &mut Option<B>, body_base64: &mut Option<String>) -> bool {
+    if body.is_some() {
+        return false;
+    }
+
+    match body_base64.as_deref().and_then(decode_base64_text) {
+        Some(text) => {
+            *body = Some(text.into());
+            *body_base64 = None;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Move a text body back to `body_base64` if it still needs encoding
+fn encode_body<B: AsRef<str>>(body: &mut Option<B>, body_base64: &mut Option<String>) {
+    if body
+        .as_ref()
+        .is_some_and(|body| should_base64_encode(body.as_ref()))
+    {
+        *body_base64 = body
+            .take()
+            .map(|text| general_purpose::STANDARD.encode(text.as_ref()));
     }
 }
@@ -0,0 +1,545 @@
+use crate::cassette::{Cassette, CassetteMetadata, Interaction};
+use crate::serializable::{Headers, SerializableRequest, SerializableResponse};
+use crate::storage::{CassetteStorage, InteractionIter};
+use http_client::Error;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS cassettes (
+        name TEXT PRIMARY KEY,
+        metadata TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS interactions (
+        cassette TEXT NOT NULL REFERENCES cassettes(name) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        method TEXT NOT NULL,
+        url TEXT NOT NULL,
+        request_headers TEXT NOT NULL,
+        request_body TEXT,
+        request_body_base64 TEXT,
+        request_version TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        response_headers TEXT NOT NULL,
+        response_body TEXT,
+        response_body_base64 TEXT,
+        response_version TEXT NOT NULL,
+        recorded_at TEXT,
+        elapsed_ms INTEGER,
+        error TEXT,
+        stream TEXT,
+        metadata TEXT,
+        tags TEXT,
+        requires_state TEXT,
+        sets_state TEXT,
+        PRIMARY KEY (cassette, position)
+    );
+    CREATE INDEX IF NOT EXISTS interactions_by_request
+        ON interactions (cassette, method, url);
+";
+
+const INTERACTION_COLUMNS: &str =
+    "method, url, request_headers, request_body, request_body_base64, \
+     request_version, status, response_headers, response_body, response_body_base64, \
+     response_version, recorded_at, elapsed_ms, error, stream, metadata, tags, \
+     requires_state, sets_state";
+
+/// Stores any number of cassettes in a single SQLite database, one row per
+/// interaction, indexed by method and URL.
+///
+/// A client built with `lazy_load` replays from it without loading the cassette:
+/// on a miss it fetches the interactions recorded for the request's method and URL
+/// through [`CassetteStorage::find`]. Single rows can also be read with
+/// [`interaction`] and [`find_interactions`], or streamed one by one with
+/// [`CassetteStorage::interactions`].
+///
+/// [`interaction`]: SqliteStorage::interaction
+/// [`find_interactions`]: SqliteStorage::find_interactions
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Open (or create) the database at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(Connection::open(path).map_err(sqlite_error)?)
+    }
+
+    /// A database that only lives as long as this storage, mostly useful for tests
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory().map_err(sqlite_error)?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self, Error> {
+        connection
+            .execute_batch("PRAGMA foreign_keys = ON;")
+            .and_then(|_| connection.execute_batch(SCHEMA))
+            .and_then(|_| add_missing_columns(&connection))
+            .map_err(sqlite_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Number of interactions stored for the cassette at `path`
+    pub fn len(&self, path: &Path) -> Result<usize, Error> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT COUNT(*) FROM interactions WHERE cassette = ?1",
+                params![key(path)],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count as usize)
+            .map_err(sqlite_error)
+    }
+
+    /// Load a single interaction by its position in the cassette
+    pub fn interaction(&self, path: &Path, position: usize) -> Result<Option<Interaction>, Error> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                &format!(
+                    "SELECT {INTERACTION_COLUMNS} FROM interactions WHERE cassette = ?1 AND position = ?2"
+                ),
+                params![key(path), position as i64],
+                read_interaction,
+            )
+            .optional()
+            .map_err(sqlite_error)?
+            .transpose()
+    }
+
+    /// Load the interactions recorded for `method` and `url`, in recorded order, with
+    /// their positions in the cassette
+    pub fn find_interactions(
+        &self,
+        path: &Path,
+        method: &str,
+        url: &str,
+    ) -> Result<Vec<(usize, Interaction)>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT position, {INTERACTION_COLUMNS} FROM interactions \
+                 WHERE cassette = ?1 AND method = ?2 AND url = ?3 ORDER BY position"
+            ))
+            .map_err(sqlite_error)?;
+        let rows = statement
+            .query_map(params![key(path), method, url], |row| {
+                let position = row.get::<_, i64>(0)? as usize;
+                Ok(read_interaction_at(row, 1)?.map(|interaction| (position, interaction)))
+            })
+            .map_err(sqlite_error)?;
+
+        rows.map(|row| row.map_err(sqlite_error)?).collect()
+    }
+}
+
+impl CassetteStorage for SqliteStorage {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        let Some(mut cassette) = self.load_metadata(path)? else {
+            return Ok(None);
+        };
+
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {INTERACTION_COLUMNS} FROM interactions WHERE cassette = ?1 ORDER BY position"
+            ))
+            .map_err(sqlite_error)?;
+        let interactions = statement
+            .query_map(params![key(path)], read_interaction)
+            .map_err(sqlite_error)?
+            .map(|row| row.map_err(sqlite_error)?)
+            .collect::<Result<Vec<_>, Error>>()?;
+        cassette.interactions = interactions;
+        Ok(Some(cassette))
+    }
+
+    fn load_metadata(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let metadata: Option<String> = connection
+            .query_row(
+                "SELECT metadata FROM cassettes WHERE name = ?1",
+                params![key(path)],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)?;
+        let Some(metadata) = metadata else {
+            return Ok(None);
+        };
+
+        let mut cassette = Cassette::new().with_path(path.to_path_buf());
+        cassette.metadata = serde_json::from_str::<CassetteMetadata>(&metadata)
+            .map_err(|e| Error::from_str(500, format!("Failed to parse cassette metadata: {e}")))?;
+        Ok(Some(cassette))
+    }
+
+    /// Looks the interactions up through the `(cassette, method, url)` index
+    fn find(
+        &self,
+        path: &Path,
+        method: &str,
+        url: &str,
+    ) -> Result<Option<Vec<Interaction>>, Error> {
+        let found = self.find_interactions(path, method, url)?;
+        Ok(Some(
+            found
+                .into_iter()
+                .map(|(_, interaction)| interaction)
+                .collect(),
+        ))
+    }
+
+    /// Reads one row per interaction as it is yielded
+    fn interactions<'a>(&'a self, path: &Path) -> Result<Option<InteractionIter<'a>>, Error> {
+        let exists = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT 1 FROM cassettes WHERE name = ?1",
+                params![key(path)],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(sqlite_error)?
+            .is_some();
+        if !exists {
+            return Ok(None);
+        }
+        let path = path.to_path_buf();
+        Ok(Some(Box::new((0..).map_while(move |position| {
+            self.interaction(&path, position).transpose()
+        }))))
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(sqlite_error)?;
+        let name = key(path);
+        let metadata = to_json(&cassette.metadata)?;
+
+        transaction
+            .execute("DELETE FROM cassettes WHERE name = ?1", params![name])
+            .and_then(|_| {
+                transaction.execute(
+                    "INSERT INTO cassettes (name, metadata) VALUES (?1, ?2)",
+                    params![name, metadata],
+                )
+            })
+            .map_err(sqlite_error)?;
+
+        {
+            let mut insert = transaction
+                .prepare(&format!(
+                    "INSERT INTO interactions (cassette, position, {INTERACTION_COLUMNS}) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)"
+                ))
+                .map_err(sqlite_error)?;
+            for (position, interaction) in cassette.interactions.iter().enumerate() {
+                let request = &interaction.request;
+                let response = &interaction.response;
+                let recorded_at = interaction
+                    .recorded_at
+                    .map(|recorded_at| recorded_at.format(&Rfc3339))
+                    .transpose()
+                    .map_err(|e| {
+                        Error::from_str(500, format!("Failed to format timestamp: {e}"))
+                    })?;
+                insert
+                    .execute(params![
+                        name,
+                        position as i64,
+                        request.method,
+                        request.url,
+                        to_json(&request.headers)?,
+                        request.body,
+                        request.body_base64,
+                        request.version,
+                        response.status,
+                        to_json(&response.headers)?,
+                        response.body.as_deref(),
+                        response.body_base64,
+                        response.version,
+                        recorded_at,
+                        interaction.elapsed_ms.map(|ms| ms as i64),
+                        interaction.error.as_ref().map(to_json).transpose()?,
+                        interaction.stream.as_ref().map(to_json).transpose()?,
+                        (!interaction.metadata.is_empty())
+                            .then(|| to_json(&interaction.metadata))
+                            .transpose()?,
+                        (!interaction.tags.is_empty())
+                            .then(|| to_json(&interaction.tags))
+                            .transpose()?,
+                        interaction.requires_state,
+                        interaction.sets_state,
+                    ])
+                    .map_err(sqlite_error)?;
+            }
+        }
+
+        transaction.commit().map_err(sqlite_error)
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT name FROM cassettes ORDER BY name")
+            .map_err(sqlite_error)?;
+        let names = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(sqlite_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_error)?;
+        Ok(names
+            .into_iter()
+            .map(PathBuf::from)
+            .filter(|path| path.starts_with(root))
+            .collect())
+    }
+}
+
+/// Bring databases created by older versions up to the current schema
+fn add_missing_columns(connection: &Connection) -> rusqlite::Result<()> {
+    for column in [
+        "error",
+        "stream",
+        "metadata",
+        "tags",
+        "requires_state",
+        "sets_state",
+    ] {
+        let exists = connection
+            .prepare("SELECT 1 FROM pragma_table_info('interactions') WHERE name = ?1")?
+            .exists([column])?;
+        if !exists {
+            connection.execute_batch(&format!(
+                "ALTER TABLE interactions ADD COLUMN {column} TEXT;"
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn sqlite_error(e: rusqlite::Error) -> Error {
+    Error::from_str(500, format!("SQLite cassette storage error: {e}"))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, Error> {
+    serde_json::to_string(value)
+        .map_err(|e| Error::from_str(500, format!("Failed to serialize cassette data: {e}")))
+}
+
+fn read_interaction(row: &Row<'_>) -> rusqlite::Result<Result<Interaction, Error>> {
+    read_interaction_at(row, 0)
+}
+
+/// Build an interaction from the `INTERACTION_COLUMNS` starting at column `first`.
+/// Decoding errors are returned separately from SQLite errors.
+fn read_interaction_at(
+    row: &Row<'_>,
+    first: usize,
+) -> rusqlite::Result<Result<Interaction, Error>> {
+    let column = |offset: usize| first + offset;
+    let request_headers: String = row.get(column(2))?;
+    let response_headers: String = row.get(column(7))?;
+    let recorded_at: Option<String> = row.get(column(11))?;
+    let elapsed_ms: Option<i64> = row.get(column(12))?;
+    let error: Option<String> = row.get(column(13))?;
+    let stream: Option<String> = row.get(column(14))?;
+    let metadata: Option<String> = row.get(column(15))?;
+    let tags: Option<String> = row.get(column(16))?;
+    let requires_state: Option<String> = row.get(column(17))?;
+    let sets_state: Option<String> = row.get(column(18))?;
+
+    let request = SerializableRequest {
+        method: row.get(column(0))?,
+        url: row.get(column(1))?,
+        headers: Headers::new(),
+        body: row.get(column(3))?,
+        body_base64: row.get(column(4))?,
+        version: row.get(column(5))?,
+    };
+    let response = SerializableResponse {
+        status: row.get(column(6))?,
+        headers: Headers::new(),
+        body: row.get::<_, Option<String>>(column(8))?.map(Into::into),
+        body_base64: row.get(column(9))?,
+        version: row.get(column(10))?,
+    };
+
+    Ok((|| {
+        let decode = |e: serde_json::Error| {
+            Error::from_str(500, format!("Failed to parse stored headers: {e}"))
+        };
+        Ok(Interaction {
+            request: SerializableRequest {
+                headers: serde_json::from_str(&request_headers).map_err(decode)?,
+                ..request
+            },
+            response: SerializableResponse {
+                headers: serde_json::from_str(&response_headers).map_err(decode)?,
+                ..response
+            },
+            recorded_at: recorded_at
+                .map(|recorded_at| OffsetDateTime::parse(&recorded_at, &Rfc3339))
+                .transpose()
+                .map_err(|e| Error::from_str(500, format!("Failed to parse timestamp: {e}")))?,
+            elapsed_ms: elapsed_ms.map(|ms| ms as u64),
+            sequence: None,
+            error: error
+                .map(|error| serde_json::from_str(&error))
+                .transpose()
+                .map_err(|e| Error::from_str(500, format!("Failed to parse stored error: {e}")))?,
+            stream: stream
+                .map(|stream| serde_json::from_str(&stream))
+                .transpose()
+                .map_err(|e| Error::from_str(500, format!("Failed to parse stored stream: {e}")))?,
+            metadata: metadata
+                .map(|metadata| serde_json::from_str(&metadata))
+                .transpose()
+                .map_err(|e| Error::from_str(500, format!("Failed to parse stored metadata: {e}")))?
+                .unwrap_or_default(),
+            tags: tags
+                .map(|tags| serde_json::from_str(&tags))
+                .transpose()
+                .map_err(|e| Error::from_str(500, format!("Failed to parse stored tags: {e}")))?
+                .unwrap_or_default(),
+            requires_state,
+            sets_state,
+        })
+    })())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::StreamExt;
+
+    fn request(url: &str) -> SerializableRequest {
+        SerializableRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: Headers::from([("Accept".to_string(), vec!["*/*".to_string()])]),
+            body: None,
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        }
+    }
+
+    fn response(body: &str) -> SerializableResponse {
+        SerializableResponse {
+            status: 200,
+            headers: Headers::new(),
+            body: Some(body.to_string().into()),
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trips_and_queries_single_interactions() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let path = Path::new("suite/big.yaml");
+
+        let mut cassette = Cassette::new();
+        for (url, body) in [
+            ("https://example.com/a", "first"),
+            ("https://example.com/b", "second"),
+            ("https://example.com/a", "third"),
+        ] {
+            cassette
+                .record_interaction(request(url), response(body))
+                .await
+                .unwrap();
+        }
+        cassette.interactions[2].requires_state = Some("started".to_string());
+        cassette.interactions[2].sets_state = Some("done".to_string());
+        storage.save(path, &cassette).unwrap();
+
+        let loaded = storage.load(path).unwrap().unwrap();
+        assert_eq!(loaded.interactions.len(), 3);
+        assert_eq!(loaded.interactions[2].sets_state.as_deref(), Some("done"));
+        assert_eq!(
+            loaded.interactions[1].response.body.as_deref(),
+            Some("second")
+        );
+        assert_eq!(
+            loaded.interactions[0].request.headers["Accept"],
+            vec!["*/*"]
+        );
+        assert_eq!(loaded.recorded_at(), cassette.recorded_at());
+
+        assert_eq!(storage.len(path).unwrap(), 3);
+        let plan: String = storage
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT position FROM interactions \
+                 WHERE cassette = ?1 AND method = ?2 AND url = ?3",
+                params![key(path), "GET", "https://example.com/a"],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(plan.contains("interactions_by_request"), "{plan}");
+        assert!(storage.load_metadata(path).unwrap().unwrap().is_empty());
+        assert_eq!(
+            storage
+                .find(path, "GET", "https://example.com/b")
+                .unwrap()
+                .unwrap()[0]
+                .response
+                .body,
+            Some("second".into())
+        );
+        let found = storage
+            .find_interactions(path, "GET", "https://example.com/a")
+            .unwrap();
+        assert_eq!(
+            found
+                .iter()
+                .map(|(position, _)| *position)
+                .collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert_eq!(
+            storage
+                .interaction(path, 2)
+                .unwrap()
+                .and_then(|interaction| interaction.response.body),
+            Some("third".into())
+        );
+
+        let streamed: Vec<Option<String>> = Cassette::stream(&storage, path)
+            .unwrap()
+            .map(|interaction| interaction.unwrap().response.body.map(String::from))
+            .collect()
+            .await;
+        assert_eq!(
+            streamed,
+            ["first", "second", "third"].map(|body| Some(body.to_string()))
+        );
+        assert!(storage
+            .interactions(Path::new("missing.yaml"))
+            .unwrap()
+            .is_none());
+
+        // Saving again replaces the previous interactions
+        storage.save(path, &Cassette::new()).unwrap();
+        assert_eq!(storage.len(path).unwrap(), 0);
+        assert_eq!(storage.list(Path::new("suite")).unwrap(), vec![path]);
+        assert!(storage.load(Path::new("missing.yaml")).unwrap().is_none());
+    }
+}
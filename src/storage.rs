@@ -0,0 +1,120 @@
+use crate::cassette::{Cassette, Interaction};
+use http_client::Error;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Interactions read one at a time by [`CassetteStorage::interactions`]
+pub type InteractionIter<'a> = Box<dyn Iterator<Item = Result<Interaction, Error>> + Send + 'a>;
+
+/// Where cassettes are persisted.
+///
+/// Cassettes are addressed by path, which custom backends are free to treat as a plain
+/// key (a table row, an object name, ...). Methods are blocking so cassettes can still
+/// be saved when a `VcrClient` is dropped; otherwise the client calls them on a blocking
+/// thread, never on the executor or with the cassette locked.
+pub trait CassetteStorage: Debug + Send + Sync {
+    /// Load the cassette stored at `path`, or `None` if there isn't one
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error>;
+
+    /// Store `cassette` at `path`, replacing whatever was there
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error>;
+
+    /// Paths of the cassettes stored under `root`
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error>;
+
+    /// The interactions of the cassette at `path` in order, or `None` if there isn't
+    /// one. The default loads the whole cassette; backends that can read interactions
+    /// separately yield them as they are read, so huge cassettes can be processed in
+    /// bounded memory.
+    fn interactions<'a>(&'a self, path: &Path) -> Result<Option<InteractionIter<'a>>, Error> {
+        Ok(self.load(path)?.map(|cassette| {
+            Box::new(cassette.interactions.into_iter().map(Ok)) as InteractionIter<'a>
+        }))
+    }
+
+    /// The cassette at `path` without its interactions, for clients that look them up
+    /// with [`find`](CassetteStorage::find) instead. The default loads the whole
+    /// cassette and drops them.
+    fn load_metadata(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        Ok(self.load(path)?.map(|mut cassette| {
+            cassette.interactions.clear();
+            cassette
+        }))
+    }
+
+    /// The interactions of the cassette at `path` recorded for `method` and `url`, in
+    /// recorded order, or `None` if this backend can't look them up without loading
+    /// the whole cassette, which is the default
+    fn find(
+        &self,
+        _path: &Path,
+        _method: &str,
+        _url: &str,
+    ) -> Result<Option<Vec<Interaction>>, Error> {
+        Ok(None)
+    }
+}
+
+/// Cassettes kept in memory, for targets without a filesystem such as the browser.
+///
+/// Clones share the same cassettes. Seed it with cassettes compiled into the test,
+/// e.g. from `include_str!`, and read back what was recorded with `load`.
+///
+/// ```rust
+/// # use http_client_vcr::{CassetteStorage, MemoryStorage};
+/// # use std::path::Path;
+/// let storage = MemoryStorage::new();
+/// storage
+///     .insert_yaml("api.yaml", "interactions: []")
+///     .unwrap();
+/// assert!(storage.load(Path::new("api.yaml")).unwrap().is_some());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    cassettes: Arc<Mutex<HashMap<PathBuf, Cassette>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `cassette` at `path`
+    pub fn insert<P: Into<PathBuf>>(&self, path: P, cassette: Cassette) {
+        let path = path.into();
+        let cassette = cassette.with_path(path.clone());
+        self.cassettes.lock().unwrap().insert(path, cassette);
+    }
+
+    /// Parse a single-file cassette and store it at `path`
+    pub fn insert_yaml<P: Into<PathBuf>>(&self, path: P, yaml: &str) -> Result<(), Error> {
+        self.insert(path, Cassette::from_yaml(yaml)?);
+        Ok(())
+    }
+}
+
+impl CassetteStorage for MemoryStorage {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        Ok(self.cassettes.lock().unwrap().get(path).cloned())
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        self.insert(path, cassette.clone());
+        Ok(())
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut found: Vec<PathBuf> = self
+            .cassettes
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| path.starts_with(root))
+            .cloned()
+            .collect();
+        found.sort();
+        Ok(found)
+    }
+}
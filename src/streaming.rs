@@ -0,0 +1,297 @@
+use crate::runtime::{Instant, Timer};
+use crate::serializable::Headers;
+use base64::{engine::general_purpose, Engine as _};
+use futures_lite::io::{AsyncRead, BufReader};
+use http_types::Body;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A piece of a streamed response body and when it arrived. Server-Sent Events
+/// responses are recorded one event per chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamChunk {
+    /// Milliseconds after the response headers arrived
+    pub at_ms: u64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub data: String,
+    /// The chunk's bytes, base64-encoded, when they aren't UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_base64: Option<String>,
+}
+
+impl StreamChunk {
+    pub fn new(at_ms: u64, bytes: Vec<u8>) -> Self {
+        let (data, data_base64) = match String::from_utf8(bytes) {
+            Ok(text) => (text, None),
+            Err(e) => (
+                String::new(),
+                Some(general_purpose::STANDARD.encode(e.into_bytes())),
+            ),
+        };
+        Self {
+            at_ms,
+            data,
+            data_base64,
+        }
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        match &self.data_base64 {
+            Some(encoded) => general_purpose::STANDARD
+                .decode(encoded)
+                .unwrap_or_else(|_| encoded.clone().into_bytes()),
+            None => self.data.clone().into_bytes(),
+        }
+    }
+}
+
+/// Split a body into `chunks` parts of about the same size, spread evenly over
+/// `elapsed_ms` so the first arrives right away. Text is only split between
+/// characters, so each part stays readable in the cassette.
+pub(crate) fn split_body(
+    bytes: Vec<u8>,
+    chunks: usize,
+    elapsed_ms: Option<u64>,
+) -> Vec<StreamChunk> {
+    let chunks = chunks.clamp(1, bytes.len().max(1));
+    let text = std::str::from_utf8(&bytes).ok();
+    let mut parts = Vec::with_capacity(chunks);
+    let mut start = 0;
+    for i in 1..=chunks {
+        let mut end = bytes.len() * i / chunks;
+        if let Some(text) = text {
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+        }
+        if end > start || i == chunks {
+            parts.push(bytes[start..end].to_vec());
+        }
+        start = end;
+    }
+
+    let count = parts.len() as u64;
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let at_ms = elapsed_ms.unwrap_or(0) * i as u64 / count;
+            StreamChunk::new(at_ms, part)
+        })
+        .collect()
+}
+
+/// Chunks of a response still being streamed to the caller while it is recorded
+pub(crate) type StreamCapture = Arc<Mutex<Vec<StreamChunk>>>;
+
+/// Whether the response is a Server-Sent Events stream
+pub(crate) fn is_event_stream(headers: &Headers) -> bool {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .flat_map(|(_, values)| values)
+        .any(|value| {
+            value
+                .split(';')
+                .next()
+                .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/event-stream"))
+        })
+}
+
+/// Pass `body` through unchanged, adding each complete event to `capture` as it
+/// goes by. Nothing is buffered beyond the event being read, so endpoints that
+/// never close are recorded for as long as the caller keeps reading.
+pub(crate) fn record_body(body: Body, capture: StreamCapture) -> Body {
+    let mime = body.mime().cloned();
+    let reader = RecordingReader {
+        inner: body,
+        started: Instant::now(),
+        pending: Vec::new(),
+        capture,
+    };
+    let mut body = Body::from_reader(BufReader::new(reader), None);
+    body.set_mime(mime);
+    body
+}
+
+/// A body that yields `chunks` at their recorded times, sped up by `speed`
+pub(crate) fn replay_body(chunks: &[StreamChunk], speed: f64) -> Body {
+    let reader = ReplayReader {
+        chunks: chunks.iter().cloned().collect(),
+        speed,
+        started: Instant::now(),
+        current: Vec::new(),
+        pos: 0,
+        timer: None,
+    };
+    Body::from_reader(BufReader::new(reader), None)
+}
+
+struct RecordingReader {
+    inner: Body,
+    started: Instant,
+    // Bytes of the event currently being read
+    pending: Vec<u8>,
+    capture: StreamCapture,
+}
+
+impl RecordingReader {
+    fn push(&mut self, data: Vec<u8>) {
+        let chunk = StreamChunk::new(self.started.elapsed().as_millis() as u64, data);
+        self.capture.lock().unwrap().push(chunk);
+    }
+
+    /// Move every complete event out of `pending`
+    fn split_events(&mut self) {
+        while let Some(end) = event_end(&self.pending) {
+            let rest = self.pending.split_off(end);
+            let event = std::mem::replace(&mut self.pending, rest);
+            self.push(event);
+        }
+    }
+}
+
+/// The end of the first event in `data`, after the blank line closing it
+fn event_end(data: &[u8]) -> Option<usize> {
+    let mut previous_line_empty = true;
+    let mut line_start = 0;
+    for (i, byte) in data.iter().enumerate() {
+        if *byte == b'\n' {
+            let line = &data[line_start..i];
+            let empty = line.is_empty() || line == b"\r";
+            if empty && !previous_line_empty {
+                return Some(i + 1);
+            }
+            previous_line_empty = empty;
+            line_start = i + 1;
+        }
+    }
+    None
+}
+
+impl AsyncRead for RecordingReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let read = match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(read)) => read,
+            other => return other,
+        };
+        if read == 0 {
+            // An unterminated last event is still worth keeping
+            if !this.pending.iter().all(u8::is_ascii_whitespace) {
+                let event = std::mem::take(&mut this.pending);
+                this.push(event);
+            }
+        } else {
+            this.pending.extend_from_slice(&buf[..read]);
+            this.split_events();
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+struct ReplayReader {
+    chunks: VecDeque<StreamChunk>,
+    speed: f64,
+    started: Instant,
+    current: Vec<u8>,
+    pos: usize,
+    timer: Option<Timer>,
+}
+
+impl ReplayReader {
+    fn due(&self, chunk: &StreamChunk) -> Duration {
+        if self.speed > 0.0 && self.speed.is_finite() {
+            Duration::from_millis(chunk.at_ms).div_f64(self.speed)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+impl AsyncRead for ReplayReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        while this.pos >= this.current.len() {
+            let Some(next) = this.chunks.front() else {
+                return Poll::Ready(Ok(0));
+            };
+            let deadline = this.started + this.due(next);
+            if Instant::now() < deadline {
+                let timer = this.timer.get_or_insert_with(|| Timer::at(deadline));
+                if Pin::new(timer).poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+            this.timer = None;
+            let chunk = this.chunks.pop_front().expect("checked above");
+            this.current = chunk.bytes();
+            this.pos = 0;
+        }
+
+        let n = (this.current.len() - this.pos).min(buf.len());
+        buf[..n].copy_from_slice(&this.current[this.pos..this.pos + n]);
+        this.pos += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events_are_captured_as_they_stream_and_replayed() {
+        let capture = StreamCapture::default();
+        let upstream =
+            Body::from_string("data: one\n\nevent: tick\ndata: two\r\n\r\ndata: three".into());
+        let recorded = record_body(upstream, capture.clone());
+        let text = recorded.into_string().await.unwrap();
+        assert_eq!(
+            text,
+            "data: one\n\nevent: tick\ndata: two\r\n\r\ndata: three"
+        );
+
+        let chunks = capture.lock().unwrap().clone();
+        let events: Vec<_> = chunks.iter().map(|chunk| chunk.data.as_str()).collect();
+        assert_eq!(
+            events,
+            [
+                "data: one\n\n",
+                "event: tick\ndata: two\r\n\r\n",
+                "data: three"
+            ]
+        );
+
+        let replayed = replay_body(&chunks, f64::INFINITY);
+        assert_eq!(replayed.into_string().await.unwrap(), text);
+    }
+
+    #[test]
+    fn test_split_body_keeps_characters_whole_and_spreads_timing() {
+        let chunks = split_body("aé€b".as_bytes().to_vec(), 3, Some(90));
+        let parts: Vec<_> = chunks.iter().map(|chunk| chunk.data.as_str()).collect();
+        assert_eq!(parts, ["aé", "€", "b"]);
+        let times: Vec<_> = chunks.iter().map(|chunk| chunk.at_ms).collect();
+        assert_eq!(times, [0, 30, 60]);
+
+        let binary = split_body(vec![0xff, 0x00, 0xfe], 2, None);
+        assert_eq!(binary.len(), 2);
+        assert!(binary[0].data_base64.is_some());
+        let joined: Vec<u8> = binary.iter().flat_map(StreamChunk::bytes).collect();
+        assert_eq!(joined, [0xff, 0x00, 0xfe]);
+    }
+}
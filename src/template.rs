@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+
+/// Placeholder used for path segments that look like identifiers
+pub const ID_PLACEHOLDER: &str = "{id}";
+
+/// A URL with variable path segments, e.g. `https://api.example.com/users/{id}/orders/{id}`.
+///
+/// Any segment written as `{name}` matches exactly one path segment. The query
+/// string and fragment are not part of a template.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UrlTemplate {
+    origin: String,
+    segments: Vec<String>,
+}
+
+impl UrlTemplate {
+    /// Parse a template string
+    pub fn parse(template: &str) -> Self {
+        let (origin, path) = split_origin(template);
+        Self {
+            origin: origin.to_string(),
+            segments: path_segments(path),
+        }
+    }
+
+    /// Infer a template from a concrete URL by replacing ID-like path segments with `{id}`
+    pub fn infer(url: &str) -> Self {
+        let (origin, path) = split_origin(url);
+        let segments = path_segments(path)
+            .into_iter()
+            .map(|segment| {
+                if is_identifier_segment(&segment) {
+                    ID_PLACEHOLDER.to_string()
+                } else {
+                    segment
+                }
+            })
+            .collect();
+
+        Self {
+            origin: origin.to_string(),
+            segments,
+        }
+    }
+
+    /// Check whether a concrete URL fits this template
+    pub fn matches(&self, url: &str) -> bool {
+        let (origin, path) = split_origin(url);
+        if !origin.eq_ignore_ascii_case(&self.origin) {
+            return false;
+        }
+
+        let segments = path_segments(path);
+        segments.len() == self.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(&segments)
+                .all(|(template, actual)| is_variable(template) || template == actual)
+    }
+
+    /// Number of variable segments, used to prefer more specific templates
+    fn variable_count(&self) -> usize {
+        self.segments.iter().filter(|s| is_variable(s)).count()
+    }
+}
+
+impl std::fmt::Display for UrlTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.origin, self.segments.join("/"))
+    }
+}
+
+/// Pick the template for a URL: the most specific of `known` that matches, or an inferred one
+pub fn template_for(url: &str, known: &[UrlTemplate]) -> UrlTemplate {
+    known
+        .iter()
+        .filter(|template| template.matches(url))
+        .min_by_key(|template| template.variable_count())
+        .cloned()
+        .unwrap_or_else(|| UrlTemplate::infer(url))
+}
+
+/// Group URLs by template, returning each template with the indices of the URLs it covers
+pub fn group_by_template<'a, I>(urls: I, known: &[UrlTemplate]) -> BTreeMap<String, Vec<usize>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, url) in urls.into_iter().enumerate() {
+        groups
+            .entry(template_for(url, known).to_string())
+            .or_default()
+            .push(index);
+    }
+    groups
+}
+
+fn split_origin(url: &str) -> (&str, &str) {
+    // Drop query and fragment
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+
+    let after_scheme = url.find("://").map(|i| i + 3).unwrap_or(0);
+    match url[after_scheme..].find('/') {
+        Some(i) => url.split_at(after_scheme + i),
+        None => (url, ""),
+    }
+}
+
+fn path_segments(path: &str) -> Vec<String> {
+    path.trim_start_matches('/')
+        .split('/')
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_variable(segment: &str) -> bool {
+    segment.len() > 2 && segment.starts_with('{') && segment.ends_with('}')
+}
+
+/// Heuristic for path segments that identify a resource rather than name an endpoint
+fn is_identifier_segment(segment: &str) -> bool {
+    if segment.is_empty() {
+        return false;
+    }
+
+    // Numeric IDs
+    if segment.chars().all(|c| c.is_ascii_digit()) {
+        return true;
+    }
+
+    // UUIDs
+    let parts: Vec<&str> = segment.split('-').collect();
+    if parts.iter().map(|p| p.len()).collect::<Vec<_>>() == [8, 4, 4, 4, 12]
+        && parts
+            .iter()
+            .all(|p| p.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return true;
+    }
+
+    // Long opaque tokens and hashes
+    segment.len() >= 16
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && segment.chars().any(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_replaces_identifiers() {
+        let template = UrlTemplate::infer(
+            "https://api.example.com/users/42/orders/3f2b8c1e-9d4a-4b7e-8f00-123456789abc?page=2",
+        );
+        assert_eq!(
+            template.to_string(),
+            "https://api.example.com/users/{id}/orders/{id}"
+        );
+
+        let template = UrlTemplate::infer("https://api.example.com/v1/search");
+        assert_eq!(template.to_string(), "https://api.example.com/v1/search");
+    }
+
+    #[test]
+    fn test_template_matching() {
+        let template = UrlTemplate::parse("https://api.example.com/users/{name}");
+        assert!(template.matches("https://api.example.com/users/alice?x=1"));
+        assert!(!template.matches("https://api.example.com/users/alice/orders"));
+        assert!(!template.matches("https://other.example.com/users/alice"));
+    }
+
+    #[test]
+    fn test_group_by_template_prefers_known_templates() {
+        let known = vec![UrlTemplate::parse("https://api.example.com/users/{name}")];
+        let groups = group_by_template(
+            [
+                "https://api.example.com/users/alice",
+                "https://api.example.com/users/bob",
+                "https://api.example.com/items/1",
+                "https://api.example.com/items/2",
+            ],
+            &known,
+        );
+
+        assert_eq!(groups["https://api.example.com/users/{name}"], vec![0, 1]);
+        assert_eq!(groups["https://api.example.com/items/{id}"], vec![2, 3]);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{CassetteFormat, FilterChain, RequestMatcher, VcrClient, VcrConfig, VcrMode};
+use http_client::{Error, HttpClient};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+type InnerClientFn = dyn Fn() -> Box<dyn HttpClient> + Send + Sync;
+type MatcherFn = dyn Fn() -> Box<dyn RequestMatcher> + Send + Sync;
+type FilterChainFn = dyn Fn() -> FilterChain + Send + Sync;
+
+/// Whether `VCR_RECORD` asks for cassettes to be recorded (`1`, `true` or `on`)
+pub fn record_requested() -> bool {
+    matches!(
+        std::env::var("VCR_RECORD").as_deref(),
+        Ok("1" | "true" | "on")
+    )
+}
+
+/// Everything a test needs to get a [`VcrClient`] for its own cassette.
+///
+/// The cassette lives at `<cassette_dir>/<name>`, with `cassette_dir` defaulting to
+/// `tests/fixtures`. It is recorded when `VCR_RECORD` is set (see
+/// [`record_requested`]) and replayed otherwise. Replaying a cassette that doesn't
+/// exist fails with a hint to record it.
+///
+/// ```no_run
+/// # use http_client_vcr::{testing::VcrTestContext, CassetteFormat, DefaultMatcher};
+/// # async fn run() -> Result<(), http_client::Error> {
+/// let context = VcrTestContext::new("checkout_flow")
+///     .with_format(CassetteFormat::Directory)
+///     .with_matcher(|| Box::new(DefaultMatcher::new().with_headers(vec![])));
+/// let vcr = context.client().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct VcrTestContext {
+    cassette_dir: PathBuf,
+    name: PathBuf,
+    mode: VcrMode,
+    format: Option<CassetteFormat>,
+    inner_client: Option<Arc<InnerClientFn>>,
+    matcher: Option<Box<MatcherFn>>,
+    filter_chain: Option<Box<FilterChainFn>>,
+    config: Option<VcrConfig>,
+}
+
+impl VcrTestContext {
+    /// A context for the cassette `name` under `tests/fixtures`
+    pub fn new<P: Into<PathBuf>>(name: P) -> Self {
+        Self {
+            cassette_dir: PathBuf::from("tests/fixtures"),
+            name: name.into(),
+            mode: if record_requested() {
+                VcrMode::Record
+            } else {
+                VcrMode::Replay
+            },
+            format: None,
+            inner_client: None,
+            matcher: None,
+            filter_chain: None,
+            config: None,
+        }
+    }
+
+    /// A context for the test function `test_name` in `module_path`, usually
+    /// `module_path!()`, using the cassette `<module path>/<test_name>.yaml`
+    pub fn for_test(module_path: &str, test_name: &str) -> Self {
+        let mut name: PathBuf = module_path.split("::").collect();
+        name.push(format!("{test_name}.yaml"));
+        Self::new(name)
+    }
+
+    /// Keep cassettes under `dir` instead of `tests/fixtures`
+    pub fn with_cassette_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.cassette_dir = dir.into();
+        self
+    }
+
+    /// Override the mode chosen from `VCR_RECORD`
+    pub fn with_mode(mut self, mode: VcrMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_format(mut self, format: CassetteFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Create the real client with `inner_client`. It's only called when recording;
+    /// replaying clients get a `NoOpClient`.
+    pub fn with_inner_client<F>(mut self, inner_client: F) -> Self
+    where
+        F: Fn() -> Box<dyn HttpClient> + Send + Sync + 'static,
+    {
+        self.inner_client = Some(Arc::new(inner_client));
+        self
+    }
+
+    /// Give each client the matcher returned by `matcher`
+    pub fn with_matcher<F>(mut self, matcher: F) -> Self
+    where
+        F: Fn() -> Box<dyn RequestMatcher> + Send + Sync + 'static,
+    {
+        self.matcher = Some(Box::new(matcher));
+        self
+    }
+
+    /// Give each client the filters returned by `filter_chain`
+    pub fn with_filter_chain<F>(mut self, filter_chain: F) -> Self
+    where
+        F: Fn() -> FilterChain + Send + Sync + 'static,
+    {
+        self.filter_chain = Some(Box::new(filter_chain));
+        self
+    }
+
+    /// Build clients with the defaults of a `vcr.toml`, e.g. `VcrConfig::discover()?`
+    pub fn with_config(mut self, config: VcrConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// The cassette's path, resolved against the `cassette_root` of the config given
+    /// to `with_config`
+    pub fn cassette_path(&self) -> PathBuf {
+        let path = self.cassette_dir.join(&self.name);
+        match &self.config {
+            Some(config) => config.resolve_cassette_path(path),
+            None => path,
+        }
+    }
+
+    pub fn mode(&self) -> &VcrMode {
+        &self.mode
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.mode, VcrMode::Record)
+    }
+
+    /// Build a client for the cassette, creating its fixture directory if needed.
+    ///
+    /// Each call builds a fresh client, so a test can record with one and replay
+    /// with another.
+    pub async fn client(&self) -> Result<VcrClient, Error> {
+        let cassette_path = self.cassette_path();
+        if !self.is_recording() && !cassette_path.exists() {
+            return Err(missing_cassette(&cassette_path));
+        }
+        if let Some(parent) = cassette_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                Error::from_str(500, format!("Failed to create fixture directory: {e}"))
+            })?;
+        }
+
+        let mut builder = VcrClient::builder(cassette_path).mode(self.mode.clone());
+        if let Some(inner_client) = &self.inner_client {
+            let inner_client = inner_client.clone();
+            builder = builder.inner_client_factory(move || inner_client());
+        }
+        if let Some(format) = &self.format {
+            builder = builder.format(format.clone());
+        }
+        if let Some(matcher) = &self.matcher {
+            builder = builder.matcher(matcher());
+        }
+        if let Some(filter_chain) = &self.filter_chain {
+            builder = builder.filter_chain(filter_chain());
+        }
+        if let Some(config) = &self.config {
+            builder = builder.with_config(config.clone());
+        }
+        builder.build().await
+    }
+}
+
+impl std::fmt::Debug for VcrTestContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VcrTestContext")
+            .field("cassette_path", &self.cassette_path())
+            .field("mode", &self.mode)
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+fn missing_cassette(path: &Path) -> Error {
+    Error::from_str(
+        404,
+        format!(
+            "No cassette found at {} and VCR_RECORD is not set. Set VCR_RECORD=1 to record it.",
+            path.display()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replaying_missing_cassette_fails_with_hint() {
+        let context = VcrTestContext::for_test("suite::checkout", "test_pays")
+            .with_cassette_dir(std::env::temp_dir().join("vcr-test-context"))
+            .with_mode(VcrMode::Replay);
+        assert!(context
+            .cassette_path()
+            .ends_with("suite/checkout/test_pays.yaml"));
+
+        let error = context.client().await.unwrap_err();
+        assert!(error.to_string().contains("VCR_RECORD=1"));
+    }
+}
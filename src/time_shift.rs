@@ -0,0 +1,167 @@
+use crate::serializable::SerializableResponse;
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::format_description::FormatItem;
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+/// Shift timestamps in replayed responses by the time elapsed since recording.
+///
+/// Recorded responses carry recording-time dates, so clients that check freshness
+/// reject them on replay, e.g. OAuth tokens that already expired. `TimeShift` moves
+/// HTTP-date and ISO-8601 timestamps in the configured headers, and ISO-8601
+/// timestamps in JSON bodies, forward by the delta between the interaction's
+/// recording time and now. Interactions with no known recording time, neither their
+/// own `recorded_at` nor the cassette's, are replayed unchanged.
+#[derive(Debug, Clone)]
+pub struct TimeShift {
+    headers: Vec<String>,
+    json_bodies: bool,
+}
+
+impl Default for TimeShift {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeShift {
+    /// Shift the `Date`, `Expires` and `Last-Modified` headers and JSON bodies
+    pub fn new() -> Self {
+        Self {
+            headers: vec![
+                "date".to_string(),
+                "expires".to_string(),
+                "last-modified".to_string(),
+            ],
+            json_bodies: true,
+        }
+    }
+
+    /// Also shift timestamps in this header
+    pub fn with_header(mut self, name: impl Into<String>) -> Self {
+        self.headers.push(name.into().to_lowercase());
+        self
+    }
+
+    /// Leave response bodies alone and only shift headers
+    pub fn headers_only(mut self) -> Self {
+        self.json_bodies = false;
+        self
+    }
+
+    /// Shift the timestamps in `response` by `delta`
+    pub fn shift_response(&self, response: &mut SerializableResponse, delta: Duration) {
+        if delta.is_zero() {
+            return;
+        }
+
+        for (name, values) in response.headers.iter_mut() {
+            if self.headers.contains(&name.to_lowercase()) {
+                for value in values.iter_mut() {
+                    if let Some(shifted) = shift_timestamp(value, delta) {
+                        *value = shifted;
+                    }
+                }
+            }
+        }
+
+        if self.json_bodies {
+            if let Some(body) = &mut response.body {
+                if serde_json::from_str::<serde_json::Value>(body).is_ok() {
+                    *body = shift_json_timestamps(body, delta).into();
+                }
+            }
+        }
+    }
+
+    /// Shift `response` by the time elapsed since `recorded_at`
+    pub(crate) fn shift_since(
+        &self,
+        response: &mut SerializableResponse,
+        recorded_at: OffsetDateTime,
+    ) {
+        self.shift_response(response, OffsetDateTime::now_utc() - recorded_at);
+    }
+}
+
+/// Shift a whole value that is an HTTP-date or an ISO-8601 timestamp, keeping its format
+fn shift_timestamp(value: &str, delta: Duration) -> Option<String> {
+    let value = value.trim();
+    if let Ok(timestamp) = OffsetDateTime::parse(value, &Rfc3339) {
+        return (timestamp + delta).format(&Rfc3339).ok();
+    }
+    let timestamp = OffsetDateTime::parse(value, &Rfc2822).ok()?;
+    (timestamp + delta)
+        .to_offset(UtcOffset::UTC)
+        .format(http_date_format())
+        .ok()
+}
+
+/// Shift every ISO-8601 timestamp that makes up a whole JSON string
+fn shift_json_timestamps(body: &str, delta: Duration) -> String {
+    static TIMESTAMP: OnceLock<Regex> = OnceLock::new();
+    let timestamp = TIMESTAMP.get_or_init(|| {
+        Regex::new(
+            r#""(\d{4}-\d{2}-\d{2}[Tt]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:[Zz]|[+-]\d{2}:\d{2}))""#,
+        )
+        .expect("timestamp pattern")
+    });
+    timestamp
+        .replace_all(body, |caps: &Captures| {
+            match shift_timestamp(&caps[1], delta) {
+                Some(shifted) => format!("\"{shifted}\""),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// IMF-fixdate, the preferred HTTP-date format
+fn http_date_format() -> &'static [FormatItem<'static>] {
+    static FORMAT: OnceLock<Vec<FormatItem<'static>>> = OnceLock::new();
+    FORMAT.get_or_init(|| {
+        time::format_description::parse(
+            "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT",
+        )
+        .expect("HTTP-date format")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializable::Headers;
+
+    #[test]
+    fn test_shifts_headers_and_json_timestamps() {
+        let mut response = SerializableResponse {
+            status: 200,
+            headers: Headers::from([
+                (
+                    "Date".to_string(),
+                    vec!["Sun, 06 Nov 1994 08:49:37 GMT".to_string()],
+                ),
+                (
+                    "X-Other".to_string(),
+                    vec!["2020-01-01T00:00:00Z".to_string()],
+                ),
+            ]),
+            body: Some(r#"{"expires_at": "2020-01-01T00:00:00Z", "note": "2020-01-01"}"#.into()),
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+
+        TimeShift::new().shift_response(&mut response, Duration::days(1));
+
+        assert_eq!(
+            response.headers["Date"],
+            vec!["Mon, 07 Nov 1994 08:49:37 GMT"]
+        );
+        assert_eq!(response.headers["X-Other"], vec!["2020-01-01T00:00:00Z"]);
+        assert_eq!(
+            response.body.as_deref(),
+            Some(r#"{"expires_at": "2020-01-02T00:00:00Z", "note": "2020-01-01"}"#)
+        );
+    }
+}
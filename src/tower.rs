@@ -0,0 +1,338 @@
+use crate::{VcrClient, VcrClientBuilder};
+use async_lock::OnceCell;
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body::Body;
+use http_body_util::{BodyExt, Full};
+use http_client::{Config, Error, HttpClient};
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type BuilderFn = dyn Fn() -> VcrClientBuilder + Send + Sync;
+
+/// Puts a [`VcrClient`] in front of a tower HTTP service.
+///
+/// `make_builder` configures each wrapped service's client as usual, except that
+/// its inner client is replaced by the wrapped service. Requests go through the
+/// same recording, replay, matching and filters as with an `HttpClient`.
+///
+/// ```rust,no_run
+/// # use http_client_vcr::{VcrClient, VcrLayer, VcrMode};
+/// let layer = VcrLayer::new(|| {
+///     VcrClient::builder("tests/fixtures/api.yaml").mode(VcrMode::Once)
+/// });
+/// ```
+#[derive(Clone)]
+pub struct VcrLayer {
+    make_builder: Arc<BuilderFn>,
+}
+
+impl VcrLayer {
+    pub fn new<F>(make_builder: F) -> Self
+    where
+        F: Fn() -> VcrClientBuilder + Send + Sync + 'static,
+    {
+        Self {
+            make_builder: Arc::new(make_builder),
+        }
+    }
+}
+
+impl std::fmt::Debug for VcrLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("VcrLayer")
+    }
+}
+
+impl<S> Layer<S> for VcrLayer {
+    type Service = VcrService<S>;
+
+    fn layer(&self, inner: S) -> VcrService<S> {
+        VcrService {
+            inner,
+            make_builder: self.make_builder.clone(),
+            client: Arc::new(OnceCell::new()),
+        }
+    }
+}
+
+/// A tower service recording and replaying the requests of the service it wraps.
+///
+/// Its client is built on the first request, or by [`VcrService::vcr_client`], and
+/// shared by all clones. Responses have fully buffered bodies.
+pub struct VcrService<S> {
+    inner: S,
+    make_builder: Arc<BuilderFn>,
+    client: Arc<OnceCell<VcrClient>>,
+}
+
+impl<S: Clone> Clone for VcrService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            make_builder: self.make_builder.clone(),
+            client: self.client.clone(),
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for VcrService<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VcrService")
+            .field("client", &self.client.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, ResBody> VcrService<S>
+where
+    S: Service<http::Request<Full<Bytes>>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ResBody: Body + Send,
+    ResBody::Data: Send,
+    ResBody::Error: Into<BoxError>,
+{
+    /// The client requests go through, e.g. to `finalize` it or read its cassette
+    pub async fn vcr_client(&self) -> Result<&VcrClient, Error> {
+        self.client
+            .get_or_try_init(|| async {
+                (self.make_builder)()
+                    .inner_client(Box::new(TowerClient {
+                        service: Mutex::new(Box::new(self.inner.clone())),
+                        config: Config::new(),
+                    }))
+                    .build()
+                    .await
+            })
+            .await
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for VcrService<S>
+where
+    S: Service<http::Request<Full<Bytes>>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ReqBody: Body + Send + 'static,
+    ReqBody::Data: Send,
+    ReqBody::Error: Into<BoxError>,
+    ResBody: Body + Send,
+    ResBody::Data: Send,
+    ResBody::Error: Into<BoxError>,
+{
+    type Response = http::Response<Full<Bytes>>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        // The wrapped service is made ready when a request actually reaches it
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move {
+            let request = to_http_types_request(request).await?;
+            let mut response = service.vcr_client().await?.send(request).await?;
+            from_http_types_response(&mut response).await
+        })
+    }
+}
+
+/// Sends a `VcrClient`'s real requests through a tower service
+struct TowerClient<S> {
+    // Boxed so the client is Unpin whatever the service is
+    service: Mutex<Box<S>>,
+    // Not applied: the wrapped service is configured when it is built
+    config: Config,
+}
+
+impl<S> std::fmt::Debug for TowerClient<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TowerClient")
+    }
+}
+
+#[async_trait]
+impl<S, ResBody> HttpClient for TowerClient<S>
+where
+    S: Service<http::Request<Full<Bytes>>, Response = http::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ResBody: Body + Send,
+    ResBody::Data: Send,
+    ResBody::Error: Into<BoxError>,
+{
+    async fn send(&self, mut req: http_client::Request) -> Result<http_client::Response, Error> {
+        let mut builder = http::Request::builder()
+            .method(req.method().to_string().as_str())
+            .uri(req.url().as_str());
+        for (name, values) in req.iter() {
+            for value in values.iter() {
+                builder = builder.header(name.as_str(), value.as_str());
+            }
+        }
+        let request = builder
+            .body(Full::new(Bytes::from(req.body_bytes().await?)))
+            .map_err(|e| Error::from_str(400, format!("Invalid request: {e}")))?;
+
+        let mut service = self.service.lock().unwrap().as_ref().clone();
+        std::future::poll_fn(|cx| service.poll_ready(cx))
+            .await
+            .map_err(|e| upstream_error(e.into()))?;
+        let response = service
+            .call(request)
+            .await
+            .map_err(|e| upstream_error(e.into()))?;
+
+        let (parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| upstream_error(e.into()))?
+            .to_bytes();
+        let mut converted =
+            http_client::Response::new(http_types::StatusCode::try_from(parts.status.as_u16())?);
+        for (name, value) in &parts.headers {
+            converted.append_header(
+                name.as_str(),
+                String::from_utf8_lossy(value.as_bytes()).as_ref(),
+            )?;
+        }
+        converted.set_body(body.to_vec());
+        Ok(converted)
+    }
+
+    fn set_config(&mut self, config: Config) -> Result<(), Error> {
+        self.config = config;
+        Ok(())
+    }
+
+    fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+fn upstream_error(e: BoxError) -> Error {
+    Error::from_str(502, format!("Tower service failed: {e}"))
+}
+
+async fn to_http_types_request<B>(
+    request: http::Request<B>,
+) -> Result<http_client::Request, BoxError>
+where
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    let (parts, body) = request.into_parts();
+    let method = http_types::Method::from_str(parts.method.as_str())?;
+    let url = http_types::Url::parse(&parts.uri.to_string()).map_err(|e| {
+        format!(
+            "VcrService needs absolute request URIs, got {}: {e}",
+            parts.uri
+        )
+    })?;
+    let mut converted = http_client::Request::new(method, url);
+    for (name, value) in &parts.headers {
+        converted.append_header(
+            name.as_str(),
+            String::from_utf8_lossy(value.as_bytes()).as_ref(),
+        )?;
+    }
+    let body = body.collect().await.map_err(Into::into)?.to_bytes();
+    converted.set_body(body.to_vec());
+    Ok(converted)
+}
+
+async fn from_http_types_response(
+    response: &mut http_client::Response,
+) -> Result<http::Response<Full<Bytes>>, BoxError> {
+    let mut builder = http::Response::builder().status(u16::from(response.status()));
+    for (name, values) in response.iter() {
+        for value in values.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+    }
+    let body = response.body_bytes().await?;
+    Ok(builder.body(Full::new(Bytes::from(body)))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cassette, Persistence, VcrMode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Clone, Default)]
+    struct Upstream {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Service<http::Request<Full<Bytes>>> for Upstream {
+        type Response = http::Response<Full<Bytes>>;
+        type Error = std::convert::Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<Full<Bytes>>) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let body = format!("{} {}", request.method(), request.uri().path());
+            std::future::ready(Ok(http::Response::new(Full::new(Bytes::from(body)))))
+        }
+    }
+
+    async fn send(service: &mut VcrService<Upstream>) -> String {
+        let request = http::Request::post("https://example.com/items")
+            .body(Full::new(Bytes::from_static(b"{}")))
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_vcr_service_records_then_replays_tower_service() {
+        let upstream = Upstream::default();
+        let mut recording = VcrLayer::new(|| {
+            VcrClient::builder("unused.yaml")
+                .mode(VcrMode::Record)
+                .persistence(Persistence::InMemory)
+        })
+        .layer(upstream.clone());
+        assert_eq!(send(&mut recording).await, "POST /items");
+
+        let cassette = Cassette {
+            interactions: recording.vcr_client().await.unwrap().interactions().await,
+            ..Cassette::new()
+        };
+        let cassette = Arc::new(Mutex::new(Some(cassette)));
+        let mut replaying = VcrLayer::new(move || {
+            VcrClientBuilder::from_cassette(cassette.lock().unwrap().take().unwrap())
+                .mode(VcrMode::Replay)
+                .persistence(Persistence::InMemory)
+        })
+        .layer(upstream.clone());
+        assert_eq!(send(&mut replaying).await, "POST /items");
+        assert_eq!(upstream.calls.load(Ordering::SeqCst), 1);
+    }
+}
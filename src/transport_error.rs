@@ -0,0 +1,116 @@
+use http_client::Error;
+use http_types::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// What kind of failure kept a recorded request from getting a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransportErrorKind {
+    Timeout,
+    ConnectionRefused,
+    ConnectionReset,
+    /// The host name couldn't be resolved
+    Dns,
+    Other,
+}
+
+impl TransportErrorKind {
+    fn io_kind(self) -> io::ErrorKind {
+        match self {
+            Self::Timeout => io::ErrorKind::TimedOut,
+            Self::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            Self::ConnectionReset => io::ErrorKind::ConnectionReset,
+            Self::Dns | Self::Other => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// An error the inner client returned instead of a response, recorded with
+/// `record_errors` and returned again on replay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedError {
+    pub kind: TransportErrorKind,
+    /// Status of the `http_client::Error`, usually a 5xx chosen by the client adapter
+    pub status: u16,
+    pub message: String,
+}
+
+impl RecordedError {
+    /// Classify an inner client error. The kind comes from a wrapped
+    /// `std::io::Error` when there is one, and from the message otherwise, since
+    /// most adapters only keep the text of the underlying error.
+    pub fn from_error(error: &Error) -> Self {
+        let message = error.to_string();
+        let kind = match error.downcast_ref::<io::Error>().map(io::Error::kind) {
+            Some(io::ErrorKind::TimedOut) => TransportErrorKind::Timeout,
+            Some(io::ErrorKind::ConnectionRefused) => TransportErrorKind::ConnectionRefused,
+            Some(io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted) => {
+                TransportErrorKind::ConnectionReset
+            }
+            _ => Self::kind_from_message(&message),
+        };
+        Self {
+            kind,
+            status: error.status().into(),
+            message,
+        }
+    }
+
+    fn kind_from_message(message: &str) -> TransportErrorKind {
+        let message = message.to_ascii_lowercase();
+        if message.contains("timed out") || message.contains("timeout") {
+            TransportErrorKind::Timeout
+        } else if message.contains("connection refused") {
+            TransportErrorKind::ConnectionRefused
+        } else if message.contains("connection reset") || message.contains("broken pipe") {
+            TransportErrorKind::ConnectionReset
+        } else if message.contains("dns")
+            || message.contains("resolve")
+            || message.contains("name or service not known")
+            || message.contains("no such host")
+        {
+            TransportErrorKind::Dns
+        } else {
+            TransportErrorKind::Other
+        }
+    }
+
+    /// The error to hand back on replay: same status and message, wrapping a
+    /// `std::io::Error` of the matching kind so callers can inspect it as before
+    pub fn to_error(&self) -> Error {
+        let status = StatusCode::try_from(self.status).unwrap_or(StatusCode::InternalServerError);
+        Error::new(
+            status,
+            io::Error::new(self.kind.io_kind(), self.message.clone()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_error_replays_with_same_kind() {
+        let error = Error::new(
+            502,
+            io::Error::new(io::ErrorKind::ConnectionRefused, "connect failed"),
+        );
+        let recorded = RecordedError::from_error(&error);
+        assert_eq!(recorded.kind, TransportErrorKind::ConnectionRefused);
+
+        let replayed = recorded.to_error();
+        assert_eq!(u16::from(replayed.status()), 502);
+        assert_eq!(
+            replayed.downcast_ref::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::ConnectionRefused)
+        );
+
+        let message_only = Error::from_str(502, "dns error: failed to lookup address");
+        assert_eq!(
+            RecordedError::from_error(&message_only).kind,
+            TransportErrorKind::Dns
+        );
+    }
+}
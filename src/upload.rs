@@ -0,0 +1,294 @@
+use crate::filter::Filter;
+use crate::serializable::{SerializableRequest, SerializableResponse};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+/// Query parameters that carry the signature or short-lived credentials of a presigned URL
+/// (S3, GCS and Azure SAS). Some of them (`st`, `se`, `Expires`) are common names, so
+/// they are only redacted or ignored on URLs `is_presigned_url` accepts.
+pub const PRESIGNED_QUERY_PARAMS: &[&str] = &[
+    "X-Amz-Signature",
+    "X-Amz-Credential",
+    "X-Amz-Security-Token",
+    "X-Amz-Date",
+    "X-Amz-Expires",
+    "X-Goog-Signature",
+    "X-Goog-Credential",
+    "X-Goog-Date",
+    "X-Goog-Expires",
+    "Signature",
+    "Expires",
+    "AWSAccessKeyId",
+    "sig",
+    "se",
+    "st",
+];
+
+/// Value presigned query parameters are replaced with
+pub const PRESIGNED_PLACEHOLDER: &str = "FILTERED";
+
+/// Returns true if the URL carries a presigned-URL signature: `X-Amz-Signature`,
+/// `X-Goog-Signature`, a SigV2 `Signature` next to `AWSAccessKeyId`, or an Azure SAS
+/// `sig` next to `sv`. Parameter names are compared case-insensitively.
+pub fn is_presigned_url(url: &str) -> bool {
+    let Ok(url) = url::Url::parse(url) else {
+        return false;
+    };
+    let keys: Vec<String> = url
+        .query_pairs()
+        .map(|(key, _)| key.to_ascii_lowercase())
+        .collect();
+    let has = |name: &str| keys.iter().any(|key| key == name);
+
+    has("x-amz-signature")
+        || has("x-goog-signature")
+        || (has("signature") && has("awsaccesskeyid"))
+        || (has("sig") && has("sv"))
+}
+
+/// Handles presigned upload flows end to end:
+///
+/// - redacts signature parameters in presigned request URLs, and in presigned URLs
+///   handed out by the metadata response (bodies and `Location` headers); other URLs
+///   are left alone, so a plain `?st=` or `?sig=` survives
+/// - replaces upload bodies sent to presigned URLs with their SHA-256 and size, so
+///   huge uploads don't end up in the cassette
+///
+/// Pair it with `DefaultMatcher::presigned_uploads()` (or `ignore_query_params`) so
+/// requests carrying a fresh signature still match the recording.
+#[derive(Debug)]
+pub struct PresignedUploadFilter {
+    param_pattern: Regex,
+    url_pattern: Regex,
+    hash_threshold: usize,
+}
+
+impl PresignedUploadFilter {
+    pub fn new() -> Self {
+        let params = PRESIGNED_QUERY_PARAMS
+            .iter()
+            .map(|p| regex::escape(p))
+            .collect::<Vec<_>>()
+            .join("|");
+        Self {
+            param_pattern: Regex::new(&format!(r#"(?i)([?&](?:{params})=)[^&#"'\s<>\\]*"#))
+                .expect("presigned parameter pattern is valid"),
+            url_pattern: Regex::new(r#"https?://[^\s"'<>\\]+"#).expect("URL pattern is valid"),
+            hash_threshold: 0,
+        }
+    }
+
+    /// Only hash upload bodies larger than `bytes`; smaller bodies are stored as-is
+    pub fn hash_bodies_larger_than(mut self, bytes: usize) -> Self {
+        self.hash_threshold = bytes;
+        self
+    }
+
+    /// Redact the signature parameters of `url` if it is presigned
+    pub(crate) fn redact_url(&self, url: &mut String) {
+        if is_presigned_url(url) {
+            *url = self
+                .param_pattern
+                .replace_all(url, format!("${{1}}{PRESIGNED_PLACEHOLDER}"))
+                .into_owned();
+        }
+    }
+
+    /// Redact every presigned URL embedded in `text`
+    fn redact_urls_in<T: AsRef<str> + From<String>>(&self, text: &mut T) {
+        if self.url_pattern.is_match(text.as_ref()) {
+            *text = self
+                .url_pattern
+                .replace_all(text.as_ref(), |caps: &regex::Captures| {
+                    let mut url = caps[0].to_string();
+                    self.redact_url(&mut url);
+                    url
+                })
+                .into_owned()
+                .into();
+        }
+    }
+
+    /// Summary stored in place of an upload body
+    pub fn body_digest(bytes: &[u8]) -> String {
+        let digest = Sha256::digest(bytes);
+        let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+        format!("[UPLOAD sha256={hex} bytes={}]", bytes.len())
+    }
+
+    fn hash_upload_body(&self, request: &mut SerializableRequest) {
+        let Some(bytes) = request.bytes() else {
+            return;
+        };
+
+        if bytes.len() > self.hash_threshold {
+            request.body = Some(Self::body_digest(&bytes));
+            request.body_base64 = None;
+        }
+    }
+}
+
+impl Default for PresignedUploadFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Filter for PresignedUploadFilter {
+    fn filter_request(&self, request: &mut SerializableRequest) {
+        let is_upload = matches!(request.method.as_str(), "PUT" | "POST" | "PATCH")
+            && is_presigned_url(&request.url);
+        if is_upload {
+            self.hash_upload_body(request);
+        }
+
+        self.redact_url(&mut request.url);
+    }
+
+    fn filter_response(&self, response: &mut SerializableResponse) {
+        for (name, values) in response.headers.iter_mut() {
+            if name.eq_ignore_ascii_case("location") {
+                for value in values.iter_mut() {
+                    self.redact_url(value);
+                }
+            }
+        }
+
+        if let Some(body) = &mut response.body {
+            self.redact_urls_in(body);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializable::Headers;
+
+    #[test]
+    fn test_redacts_signature_and_hashes_upload_body() {
+        let filter = PresignedUploadFilter::new();
+        let mut request = SerializableRequest {
+            method: "PUT".to_string(),
+            url: "https://bucket.s3.amazonaws.com/file.bin?X-Amz-Date=20240101T000000Z&X-Amz-Signature=abc123&partNumber=1".to_string(),
+            headers: Headers::new(),
+            body: Some("hello".to_string()),
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+
+        filter.filter_request(&mut request);
+
+        assert_eq!(
+            request.url,
+            "https://bucket.s3.amazonaws.com/file.bin?X-Amz-Date=FILTERED&X-Amz-Signature=FILTERED&partNumber=1"
+        );
+        assert_eq!(
+            request.body.as_deref(),
+            Some("[UPLOAD sha256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824 bytes=5]")
+        );
+    }
+
+    #[test]
+    fn test_redacts_presigned_url_in_metadata_response() {
+        let filter = PresignedUploadFilter::new();
+        let mut response = SerializableResponse {
+            status: 200,
+            headers: Headers::new(),
+            body: Some(
+                r#"{"upload_url":"https://bucket.s3.amazonaws.com/f?X-Amz-Signature=deadbeef"}"#
+                    .into(),
+            ),
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+
+        filter.filter_response(&mut response);
+
+        assert_eq!(
+            response.body.as_deref(),
+            Some(r#"{"upload_url":"https://bucket.s3.amazonaws.com/f?X-Amz-Signature=FILTERED"}"#)
+        );
+    }
+
+    #[test]
+    fn test_leaves_unsigned_urls_alone() {
+        let filter = PresignedUploadFilter::new();
+        let mut request = SerializableRequest {
+            method: "GET".to_string(),
+            url: "https://api.example.com/search?st=active&sig=1&Expires=never".to_string(),
+            headers: Headers::new(),
+            body: None,
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+        let mut headers = Headers::new();
+        headers.insert(
+            "Location".to_string(),
+            vec!["https://example.com/next?st=2".to_string()],
+        );
+        let mut response = SerializableResponse {
+            status: 302,
+            headers,
+            body: Some(
+                r#"{"next":"https://example.com/?se=1","put":"https://acct.blob.core.windows.net/c/b?sv=2022-11-02&SIG=abc&se=2024"}"#
+                    .into(),
+            ),
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+
+        filter.filter_request(&mut request);
+        filter.filter_response(&mut response);
+
+        assert_eq!(
+            request.url,
+            "https://api.example.com/search?st=active&sig=1&Expires=never"
+        );
+        assert_eq!(
+            response.headers["Location"],
+            vec!["https://example.com/next?st=2"]
+        );
+        assert_eq!(
+            response.body.as_deref(),
+            Some(
+                r#"{"next":"https://example.com/?se=1","put":"https://acct.blob.core.windows.net/c/b?sv=2022-11-02&SIG=FILTERED&se=FILTERED"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_presigned_detection_ignores_case() {
+        assert!(is_presigned_url(
+            "https://b.s3.amazonaws.com/k?x-amz-signature=abc"
+        ));
+        assert!(is_presigned_url(
+            "https://b.s3.amazonaws.com/k?awsaccesskeyid=AKID&signature=abc"
+        ));
+        assert!(!is_presigned_url("https://example.com/?Signature=abc"));
+    }
+
+    #[test]
+    fn test_presigned_matcher_only_ignores_params_on_presigned_urls() {
+        use crate::matcher::{DefaultMatcher, RequestMatcher};
+
+        let get = |url: &str| SerializableRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: Headers::new(),
+            body: None,
+            body_base64: None,
+            version: "Http1_1".to_string(),
+        };
+        let matcher = DefaultMatcher::presigned_uploads();
+
+        assert!(matcher.matches_serializable(
+            &get("https://b.s3.amazonaws.com/k?X-Amz-Signature=new"),
+            &get("https://b.s3.amazonaws.com/k?X-Amz-Signature=FILTERED"),
+        ));
+        assert!(!matcher.matches_serializable(
+            &get("https://example.com/list?st=open"),
+            &get("https://example.com/list?st=closed"),
+        ));
+    }
+}
@@ -1,11 +1,20 @@
 use crate::cassette::Cassette;
+#[cfg(feature = "fs")]
+use crate::cassette::Interaction;
+#[cfg(feature = "fs")]
 use crate::filter::FilterChain;
+#[cfg(feature = "fs")]
+use crate::placeholder::PlaceholderKind;
+#[cfg(feature = "fs")]
 use crate::serializable::{SerializableRequest, SerializableResponse};
+#[cfg(feature = "fs")]
 use http_client::Error;
+use std::collections::{BTreeSet, HashMap};
 use std::path::PathBuf;
 
 /// Utility function to apply filters to a cassette file and save the filtered version
 /// This is useful for batch processing cassette files without creating a VcrClient
+#[cfg(feature = "fs")]
 pub async fn filter_cassette_file<P: Into<PathBuf>>(
     cassette_path: P,
     filter_chain: FilterChain,
@@ -17,8 +26,7 @@ pub async fn filter_cassette_file<P: Into<PathBuf>>(
 
     // Apply filters to all interactions
     for interaction in &mut cassette.interactions {
-        filter_chain.filter_request(&mut interaction.request);
-        filter_chain.filter_response(&mut interaction.response);
+        filter_chain.filter_interaction(&mut interaction.request, &mut interaction.response);
     }
 
     // Save the filtered cassette
@@ -32,7 +40,9 @@ pub async fn filter_cassette_file<P: Into<PathBuf>>(
 }
 
 /// Apply a filter function to all requests in a cassette file
-/// This allows for custom mutation logic beyond the standard filter chains
+/// This allows for custom mutation logic beyond the standard filter chains.
+/// Base64-stored bodies are decoded into `body` while the mutator runs.
+#[cfg(feature = "fs")]
 pub async fn mutate_all_requests<P, F>(cassette_path: P, mut mutator: F) -> Result<(), Error>
 where
     P: Into<PathBuf>,
@@ -42,7 +52,7 @@ where
     let mut cassette = Cassette::load_from_file(path.clone()).await?;
 
     for interaction in &mut cassette.interactions {
-        mutator(&mut interaction.request);
+        interaction.request.with_decoded_body(&mut mutator);
     }
 
     cassette.save_to_file().await?;
@@ -54,6 +64,7 @@ where
 }
 
 /// Apply a filter function to all responses in a cassette file
+#[cfg(feature = "fs")]
 pub async fn mutate_all_responses<P, F>(cassette_path: P, mut mutator: F) -> Result<(), Error>
 where
     P: Into<PathBuf>,
@@ -63,7 +74,7 @@ where
     let mut cassette = Cassette::load_from_file(path.clone()).await?;
 
     for interaction in &mut cassette.interactions {
-        mutator(&mut interaction.response);
+        interaction.response.with_decoded_body(&mut mutator);
     }
 
     cassette.save_to_file().await?;
@@ -75,6 +86,7 @@ where
 }
 
 /// Apply mutation functions to both requests and responses in a cassette file
+#[cfg(feature = "fs")]
 pub async fn mutate_all_interactions<P, RF, ResF>(
     cassette_path: P,
     mut request_mutator: RF,
@@ -89,8 +101,10 @@ where
     let mut cassette = Cassette::load_from_file(path.clone()).await?;
 
     for interaction in &mut cassette.interactions {
-        request_mutator(&mut interaction.request);
-        response_mutator(&mut interaction.response);
+        interaction.request.with_decoded_body(&mut request_mutator);
+        interaction
+            .response
+            .with_decoded_body(&mut response_mutator);
     }
 
     cassette.save_to_file().await?;
@@ -102,6 +116,7 @@ where
 }
 
 /// Helper to remove all sensitive form data from requests using smart detection
+#[cfg(feature = "fs")]
 pub async fn strip_all_credentials_from_requests<P: Into<PathBuf>>(
     cassette_path: P,
 ) -> Result<(), Error> {
@@ -109,7 +124,7 @@ pub async fn strip_all_credentials_from_requests<P: Into<PathBuf>>(
         if let Some(body) = &mut request.body {
             // Check if this looks like form data
             if body.contains('=') && (body.contains('&') || !body.contains(' ')) {
-                let filtered = crate::form_data::filter_form_data(body, "[REMOVED]");
+                let filtered = crate::form_data::redact_form_data(body);
                 *body = filtered;
             }
         }
@@ -118,22 +133,66 @@ pub async fn strip_all_credentials_from_requests<P: Into<PathBuf>>(
 }
 
 /// Helper to remove all cookie headers from requests and set-cookie from responses
+#[cfg(feature = "fs")]
 pub async fn strip_all_cookies<P: Into<PathBuf>>(cassette_path: P) -> Result<(), Error> {
     mutate_all_interactions(
         cassette_path,
         |request| {
-            request.headers.remove("cookie");
-            request.headers.remove("Cookie");
+            request
+                .headers
+                .retain(|name, _| !name.eq_ignore_ascii_case("cookie"));
         },
         |response| {
-            response.headers.remove("set-cookie");
-            response.headers.remove("Set-Cookie");
+            response
+                .headers
+                .retain(|name, _| !name.eq_ignore_ascii_case("set-cookie"));
         },
     )
     .await
 }
 
+/// Helper to replace every cookie value with `[FILTERED]`, keeping cookie names
+/// and `Set-Cookie` attributes
+#[cfg(feature = "fs")]
+pub async fn redact_all_cookie_values<P: Into<PathBuf>>(cassette_path: P) -> Result<(), Error> {
+    mutate_all_interactions(
+        cassette_path,
+        |request| request.redact_cookie_values(crate::placeholder::FILTERED_PLACEHOLDER),
+        |response| response.redact_cookie_values(crate::placeholder::FILTERED_PLACEHOLDER),
+    )
+    .await
+}
+
+/// Mark the responses of the interactions `select` picks as streamed in `chunks`
+/// parts, so they are replayed incrementally. See [`Interaction::stream_in_chunks`].
+#[cfg(feature = "fs")]
+pub async fn stream_responses_in_chunks<P, F>(
+    cassette_path: P,
+    chunks: usize,
+    mut select: F,
+) -> Result<(), Error>
+where
+    P: Into<PathBuf>,
+    F: FnMut(&Interaction) -> bool,
+{
+    let path = cassette_path.into();
+    let mut cassette = Cassette::load_from_file(path.clone()).await?;
+
+    let mut marked = 0;
+    for interaction in &mut cassette.interactions {
+        if interaction.error.is_none() && select(interaction) {
+            interaction.stream_in_chunks(chunks);
+            marked += 1;
+        }
+    }
+
+    cassette.save_to_file().await?;
+    log::debug!("Marked {marked} responses in {path:?} as streamed in {chunks} chunks");
+    Ok(())
+}
+
 /// Replace specific field values in all form data requests
+#[cfg(feature = "fs")]
 pub async fn replace_form_field_in_all_requests<P: Into<PathBuf>>(
     cassette_path: P,
     field_name: &str,
@@ -157,6 +216,7 @@ pub async fn replace_form_field_in_all_requests<P: Into<PathBuf>>(
 }
 
 /// Remove specific header from all requests
+#[cfg(feature = "fs")]
 pub async fn remove_header_from_all_requests<P: Into<PathBuf>>(
     cassette_path: P,
     header_name: &str,
@@ -164,14 +224,15 @@ pub async fn remove_header_from_all_requests<P: Into<PathBuf>>(
     let header = header_name.to_string();
 
     mutate_all_requests(cassette_path, move |request| {
-        request.headers.remove(&header);
+        request.headers.shift_remove(&header);
         // Also try lowercase version
-        request.headers.remove(&header.to_lowercase());
+        request.headers.shift_remove(&header.to_lowercase());
     })
     .await
 }
 
 /// Replace specific header value in all requests
+#[cfg(feature = "fs")]
 pub async fn replace_header_in_all_requests<P: Into<PathBuf>>(
     cassette_path: P,
     header_name: &str,
@@ -198,6 +259,7 @@ pub async fn replace_header_in_all_requests<P: Into<PathBuf>>(
 }
 
 /// Scrub URLs by removing or replacing query parameters
+#[cfg(feature = "fs")]
 pub async fn scrub_urls_in_all_requests<P: Into<PathBuf>, F>(
     cassette_path: P,
     mut url_mutator: F,
@@ -212,6 +274,7 @@ where
 }
 
 /// Helper to replace all instances of a specific username across all requests
+#[cfg(feature = "fs")]
 pub async fn replace_username_in_all_requests<P: Into<PathBuf>>(
     cassette_path: P,
     new_username: &str,
@@ -242,7 +305,7 @@ pub async fn replace_username_in_all_requests<P: Into<PathBuf>>(
                 if auth_header.starts_with("Basic ") {
                     // For basic auth, we'd need to decode, replace username, re-encode
                     // For now, just replace the whole thing
-                    *auth_header = "[FILTERED_BASIC_AUTH]".to_string();
+                    *auth_header = format!("Basic {}", PlaceholderKind::Credentials.placeholder());
                 }
             }
         }
@@ -251,6 +314,7 @@ pub async fn replace_username_in_all_requests<P: Into<PathBuf>>(
 }
 
 /// One-stop function to sanitize an entire cassette for sharing/testing
+#[cfg(feature = "fs")]
 pub async fn sanitize_cassette_for_sharing<P: Into<PathBuf>>(
     cassette_path: P,
 ) -> Result<(), Error> {
@@ -269,31 +333,23 @@ pub async fn sanitize_cassette_for_sharing<P: Into<PathBuf>>(
         &path,
         |request| {
             // Clean headers
-            request.headers.remove("authorization");
-            request.headers.remove("Authorization");
+            request.headers.shift_remove("authorization");
+            request.headers.shift_remove("Authorization");
 
             // Clean form data
             if let Some(body) = &mut request.body {
                 if body.contains('=') && (body.contains('&') || !body.contains(' ')) {
-                    *body = crate::form_data::filter_form_data(body, "[SANITIZED]");
+                    *body = crate::form_data::redact_form_data(body);
                 }
             }
 
             // Clean URLs of sensitive query params
-            if let Ok(mut url) = url::Url::parse(&request.url) {
-                let sensitive_params = ["api_key", "access_token", "key"];
-                let query_pairs: Vec<(String, String)> = url
-                    .query_pairs()
-                    .filter(|(key, _)| !sensitive_params.contains(&key.as_ref()))
-                    .map(|(k, v)| (k.to_string(), v.to_string()))
-                    .collect();
-
-                url.query_pairs_mut().clear();
-                for (key, value) in query_pairs {
-                    url.query_pairs_mut().append_pair(&key, &value);
-                }
-
-                request.url = url.to_string();
+            let sensitive_params = ["api_key", "access_token", "key"];
+            let mut query_pairs = request.query_pairs();
+            let len = query_pairs.len();
+            query_pairs.retain(|(key, _)| !sensitive_params.contains(&key.as_str()));
+            if query_pairs.len() != len {
+                request.set_query_pairs(query_pairs);
             }
         },
         |response| {
@@ -302,7 +358,12 @@ pub async fn sanitize_cassette_for_sharing<P: Into<PathBuf>>(
             // Clean sensitive data from response bodies
             if let Some(body) = &mut response.body {
                 // Simple replacements for common sensitive patterns
-                *body = body.replace(r#""sessionid":"[^"]*""#, r#""sessionid":"[SANITIZED]""#);
+                *body = body
+                    .replace(
+                        r#""sessionid":"[^"]*""#,
+                        r#""sessionid":"[FILTERED:token]""#,
+                    )
+                    .into();
             }
         },
     )
@@ -316,25 +377,34 @@ pub async fn sanitize_cassette_for_sharing<P: Into<PathBuf>>(
 
 /// Analyze a cassette file for sensitive data without modifying it
 /// This helps identify what needs to be filtered
+#[cfg(feature = "fs")]
 pub async fn analyze_cassette_file<P: Into<PathBuf>>(
     cassette_path: P,
 ) -> Result<CassetteAnalysis, Error> {
     let path = cassette_path.into();
     let cassette = Cassette::load_from_file(path.clone()).await?;
 
+    let mut analysis = analyze_cassette(&cassette);
+    analysis.file_path = path;
+    Ok(analysis)
+}
+
+/// Analyze an in-memory cassette for sensitive data
+pub fn analyze_cassette(cassette: &Cassette) -> CassetteAnalysis {
     let mut analysis = CassetteAnalysis {
-        file_path: path,
+        file_path: cassette.path.clone().unwrap_or_default(),
         total_interactions: cassette.interactions.len(),
         requests_with_form_data: Vec::new(),
         requests_with_credentials: Vec::new(),
         sensitive_headers: Vec::new(),
+        high_entropy_params: Vec::new(),
     };
 
     for (i, interaction) in cassette.interactions.iter().enumerate() {
         // Analyze request body for form data
-        if let Some(body) = &interaction.request.body {
+        if let Some(body) = interaction.request.text() {
             if body.contains('=') && (body.contains('&') || !body.contains(' ')) {
-                let form_analysis = crate::form_data::analyze_form_data(body);
+                let form_analysis = crate::form_data::analyze_form_data(&body);
                 if !form_analysis.credential_fields.is_empty() {
                     analysis.requests_with_form_data.push(i);
                     analysis
@@ -371,13 +441,58 @@ pub async fn analyze_cassette_file<P: Into<PathBuf>>(
                 ));
             }
         }
+
+        // Random-looking query parameters are usually keys or signatures
+        if let Ok(url) = url::Url::parse(&interaction.request.url) {
+            for (key, value) in url.query_pairs() {
+                if looks_high_entropy(&value) {
+                    analysis
+                        .high_entropy_params
+                        .push((i, key.into_owned(), value.into_owned()));
+                }
+            }
+        }
     }
 
-    Ok(analysis)
+    analysis
+}
+
+/// Whether a value has already been replaced by a filter
+pub fn is_redacted(value: &str) -> bool {
+    const MARKERS: [&str; 4] = ["FILTERED", "SANITIZED", "REMOVED", "REDACTED"];
+    value.is_empty()
+        || MARKERS.iter().any(|marker| value.contains(marker))
+        || (value.starts_with('<') && value.ends_with('>'))
+}
+
+/// Long strings mixing letters and digits with high Shannon entropy, like API keys
+fn looks_high_entropy(value: &str) -> bool {
+    if value.len() < 20
+        || !value.chars().any(|c| c.is_ascii_digit())
+        || !value.chars().any(|c| c.is_ascii_alphabetic())
+        || value.chars().any(char::is_whitespace)
+    {
+        return false;
+    }
+
+    let mut counts = HashMap::new();
+    for c in value.chars() {
+        *counts.entry(c).or_insert(0usize) += 1;
+    }
+    let len = value.chars().count() as f64;
+    let entropy: f64 = counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    entropy > 3.5
 }
 
 /// Replace the password in all requests with a test password
 /// This is useful when you want to use a known test password for replay
+#[cfg(feature = "fs")]
 pub async fn set_test_password_in_cassette<P: Into<PathBuf>>(
     cassette_path: P,
     test_password: &str,
@@ -407,6 +522,7 @@ pub async fn set_test_password_in_cassette<P: Into<PathBuf>>(
 
 /// Get the username from a cassette (useful for test setup)
 /// Returns the first username found in form data
+#[cfg(feature = "fs")]
 pub async fn extract_username_from_cassette<P: Into<PathBuf>>(
     cassette_path: P,
 ) -> Result<Option<String>, Error> {
@@ -436,6 +552,15 @@ pub async fn extract_username_from_cassette<P: Into<PathBuf>>(
     Ok(None)
 }
 
+/// What strict sanitization does with interactions that still contain secrets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsanitizedAction {
+    /// Refuse to save the cassette
+    Error,
+    /// Save the cassette without the offending interactions
+    Drop,
+}
+
 #[derive(Debug)]
 pub struct CassetteAnalysis {
     pub file_path: PathBuf,
@@ -443,9 +568,32 @@ pub struct CassetteAnalysis {
     pub requests_with_form_data: Vec<usize>,
     pub requests_with_credentials: Vec<(usize, Vec<(String, String)>)>,
     pub sensitive_headers: Vec<(usize, String, Vec<String>)>,
+    /// Query parameters whose values look like keys or signatures
+    pub high_entropy_params: Vec<(usize, String, String)>,
 }
 
 impl CassetteAnalysis {
+    /// Indices of interactions with findings whose values haven't been redacted
+    pub fn unsanitized_interactions(&self) -> BTreeSet<usize> {
+        let credentials = self
+            .requests_with_credentials
+            .iter()
+            .filter(|(_, fields)| fields.iter().any(|(_, value)| !is_redacted(value)))
+            .map(|(i, _)| *i);
+        let headers = self
+            .sensitive_headers
+            .iter()
+            .filter(|(_, _, values)| values.iter().any(|value| !is_redacted(value)))
+            .map(|(i, _, _)| *i);
+        let params = self
+            .high_entropy_params
+            .iter()
+            .filter(|(_, _, value)| !is_redacted(value))
+            .map(|(i, _, _)| *i);
+
+        credentials.chain(headers).chain(params).collect()
+    }
+
     /// Print a detailed analysis report
     pub fn print_report(&self) {
         log::debug!("📊 Cassette Analysis Report");
@@ -507,6 +655,17 @@ impl CassetteAnalysis {
             log::debug!("");
         }
 
+        if !self.high_entropy_params.is_empty() {
+            log::debug!(
+                "🎲 Random-looking query parameters: {}",
+                self.high_entropy_params.len()
+            );
+            for (idx, key, _) in &self.high_entropy_params {
+                log::debug!("  - Interaction #{idx}: {key}");
+            }
+            log::debug!("");
+        }
+
         log::debug!("💡 Recommendations:");
         if !self.requests_with_credentials.is_empty() {
             log::debug!(
@@ -516,7 +675,13 @@ impl CassetteAnalysis {
         if !self.sensitive_headers.is_empty() {
             log::debug!("  - Use HeaderFilter to filter sensitive headers like cookies and tokens");
         }
-        if self.requests_with_form_data.is_empty() && self.sensitive_headers.is_empty() {
+        if !self.high_entropy_params.is_empty() {
+            log::debug!("  - Use UrlFilter to filter query parameters carrying keys or signatures");
+        }
+        if self.requests_with_form_data.is_empty()
+            && self.sensitive_headers.is_empty()
+            && self.high_entropy_params.is_empty()
+        {
             log::debug!("  - No obvious sensitive data detected, but consider reviewing manually");
         }
     }
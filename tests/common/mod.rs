@@ -0,0 +1,40 @@
+//! Fixtures shared by the integration tests
+#![allow(dead_code)]
+
+use http_client_vcr::{Cassette, Headers, SerializableRequest, SerializableResponse};
+use http_types::Error;
+
+/// A GET request for `url` with no headers or body
+pub fn get_request(url: &str) -> SerializableRequest {
+    SerializableRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: Headers::new(),
+        body: None,
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    }
+}
+
+/// A 200 response with `body`
+pub fn ok_response(body: &str) -> SerializableResponse {
+    SerializableResponse {
+        status: 200,
+        headers: Headers::new(),
+        body: Some(body.into()),
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    }
+}
+
+/// A cassette holding one GET of `https://example.com/{path}` answered with `path`
+pub async fn single_interaction_cassette(path: &str) -> Result<Cassette, Error> {
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(
+            get_request(&format!("https://example.com/{path}")),
+            ok_response(path),
+        )
+        .await?;
+    Ok(cassette)
+}
@@ -0,0 +1,80 @@
+use base64::{engine::general_purpose, Engine as _};
+use http_client_vcr::{
+    BodyFilter, FilterChain, Headers, SerializableRequest, SerializableResponse,
+};
+use std::collections::HashMap;
+
+#[test]
+fn test_filter_chain_filters_base64_bodies() {
+    let chain = FilterChain::new().add_filter(Box::new(
+        BodyFilter::new()
+            .replace_regex(r"csrf=\w+", "csrf=[FILTERED]")
+            .unwrap(),
+    ));
+
+    let html = "<html><form>csrf=abc123</form></html>";
+    let mut response = SerializableResponse {
+        status: 200,
+        headers: Headers::new(),
+        body: None,
+        body_base64: Some(general_purpose::STANDARD.encode(html)),
+        version: "Http1_1".to_string(),
+    };
+
+    chain.filter_response(&mut response);
+
+    // Still HTML, so it goes back to base64 storage
+    assert!(response.body.is_none());
+    assert_eq!(
+        response.text().as_deref(),
+        Some("<html><form>csrf=[FILTERED]</form></html>")
+    );
+
+    let form = format!("password=hunter2&padding={}", "%20".repeat(40));
+    let mut request = SerializableRequest {
+        method: "POST".to_string(),
+        url: "https://example.com/login".to_string(),
+        headers: Headers::new(),
+        body: None,
+        body_base64: Some(general_purpose::STANDARD.encode(&form)),
+        version: "Http1_1".to_string(),
+    };
+
+    FilterChain::new()
+        .add_filter(Box::new(BodyFilter::new()))
+        .filter_request(&mut request);
+
+    assert!(!request.text().unwrap().contains("hunter2"));
+}
+
+#[test]
+fn test_typed_body_accessors() {
+    let mut response = SerializableResponse {
+        status: 200,
+        headers: Headers::new(),
+        body: None,
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    };
+
+    response
+        .set_json(&serde_json::json!({"id": 7, "name": "widget"}))
+        .unwrap();
+    let value: serde_json::Value = response.json().unwrap();
+    assert_eq!(value["id"], 7);
+
+    // HTML goes to base64 storage but reads back transparently
+    response.set_text("<p>hi</p>");
+    assert!(response.body.is_none());
+    assert_eq!(response.text().as_deref(), Some("<p>hi</p>"));
+    assert_eq!(response.bytes().unwrap(), b"<p>hi</p>");
+
+    response.set_bytes(vec![0xff, 0x00]);
+    assert_eq!(response.text(), None);
+    assert_eq!(response.bytes().unwrap(), vec![0xff, 0x00]);
+
+    let mut params = HashMap::new();
+    params.insert("q".to_string(), "a b".to_string());
+    response.set_form(&params);
+    assert_eq!(response.form(), params);
+}
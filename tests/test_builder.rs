@@ -0,0 +1,367 @@
+#![cfg(feature = "fs")]
+
+mod common;
+
+use common::{get_request, ok_response, single_interaction_cassette};
+use http_client::HttpClient;
+use http_client_vcr::{
+    Cassette, DefaultMatcher, Filter, FilterChain, LatencyMode, LockstepClient, MissBehavior,
+    NoOpClient, Persistence, SerializableRequest, SerializableResponse, VcrClientBuilder, VcrMode,
+};
+use http_types::{Method, Url};
+
+#[tokio::test]
+async fn test_builder_from_preloaded_cassette() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(
+            get_request("https://example.com/stub"),
+            ok_response("stubbed"),
+        )
+        .await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/stub")?);
+    let mut response = vcr_client.send(request).await?;
+    assert_eq!(response.body_string().await?, "stubbed");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cassette_paths_fill_in_placeholders() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("vcr-path-template-{}", std::process::id()));
+    let record = |builder: VcrClientBuilder| async {
+        let vcr_client = builder
+            .inner_client(Box::new(
+                LockstepClient::new(single_interaction_cassette("data").await?)
+                    .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+            ))
+            .mode(VcrMode::Record)
+            .build()
+            .await?;
+        vcr_client
+            .send(http_types::Request::new(
+                Method::Get,
+                Url::parse("https://example.com/data")?,
+            ))
+            .await?;
+        vcr_client.save_cassette().await
+    };
+
+    record(
+        VcrClientBuilder::cassette_dir(&dir)
+            .cassette_name("{test}/{mode}.yaml")
+            .test_name("checkout::test_pays"),
+    )
+    .await?;
+    assert!(dir.join("checkout/test_pays/record.yaml").is_file());
+
+    // Named after the test's thread by default
+    record(VcrClientBuilder::cassette_dir(&dir)).await?;
+    assert!(dir
+        .join("test_cassette_paths_fill_in_placeholders.yaml")
+        .is_file());
+
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cassettes_can_be_swapped_at_runtime() -> Result<(), Box<dyn std::error::Error>> {
+    let mut vcr_client =
+        VcrClientBuilder::from_cassette(single_interaction_cassette("first").await?)
+            .inner_client(Box::new(
+                LockstepClient::new(single_interaction_cassette("recorded").await?)
+                    .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+            ))
+            .mode(VcrMode::Replay)
+            .build()
+            .await?;
+
+    let send = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+
+    let first = vcr_client
+        .insert_cassette(single_interaction_cassette("second").await?)
+        .await;
+    assert_eq!(first.interactions.len(), 1);
+    assert!(vcr_client.send(send("first")).await.is_err());
+    let mut response = vcr_client.send(send("second")).await?;
+    assert_eq!(response.body_string().await?, "second");
+
+    let second = vcr_client.eject_cassette().await;
+    assert_eq!(
+        second.interactions[0].request.url,
+        "https://example.com/second"
+    );
+    vcr_client.insert_cassette(first).await;
+
+    let path = std::env::temp_dir().join(format!("vcr-use-cassette-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let body = vcr_client
+        .use_cassette(&path, VcrMode::Once, async |client| {
+            client.send(send("recorded")).await?.body_string().await
+        })
+        .await??;
+    assert_eq!(body, "recorded");
+
+    // The recorded cassette was saved and the original one is back in place
+    let saved = Cassette::load_from_file(path.clone()).await?;
+    assert_eq!(saved.interactions.len(), 1);
+    let mut response = vcr_client.send(send("first")).await?;
+    assert_eq!(response.body_string().await?, "first");
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_additional_cassettes_are_searched_but_not_recorded_into(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let shared_path =
+        std::env::temp_dir().join(format!("vcr-shared-login-{}.yaml", std::process::id()));
+    let shared = single_interaction_cassette("login")
+        .await?
+        .with_path(shared_path.clone());
+    shared.save_to_file().await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("data").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Once)
+        .additional_cassette(&shared_path)
+        .build()
+        .await?;
+
+    let send = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+
+    // Replayed from the shared cassette; the inner client only knows about /data
+    let mut response = vcr_client.send(send("login")).await?;
+    assert_eq!(response.body_string().await?, "login");
+    let mut response = vcr_client.send(send("data")).await?;
+    assert_eq!(response.body_string().await?, "data");
+
+    let recorded = vcr_client.eject_cassette().await;
+    assert_eq!(recorded.interactions.len(), 1);
+    assert_eq!(
+        recorded.interactions[0].request.url,
+        "https://example.com/data"
+    );
+    let shared = Cassette::load_from_file(shared_path.clone()).await?;
+    assert_eq!(shared.interactions.len(), 1);
+
+    let _ = std::fs::remove_file(&shared_path);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_in_memory_client_records_without_touching_disk(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-in-memory-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .persistence(Persistence::InMemory)
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("data").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .build()
+        .await?;
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/data")?);
+    vcr_client.send(request).await?;
+    vcr_client.save_cassette().await?;
+
+    let interactions = vcr_client.interactions().await;
+    assert_eq!(interactions.len(), 1);
+    assert_eq!(interactions[0].request.url, "https://example.com/data");
+
+    drop(vcr_client);
+    assert!(!path.exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_inner_client_only_created_when_needed() -> Result<(), Box<dyn std::error::Error>> {
+    let created = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let factory = || {
+        let created = created.clone();
+        move || -> Box<dyn HttpClient> {
+            created.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Box::new(NoOpClient::new())
+        }
+    };
+
+    // Replay needs no real client at all
+    let vcr = VcrClientBuilder::from_cassette(single_interaction_cassette("data").await?)
+        .mode(VcrMode::Replay)
+        .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        .build()
+        .await?;
+    let mut response = vcr
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/data")?,
+        ))
+        .await?;
+    assert_eq!(response.body_string().await?, "data");
+
+    VcrClientBuilder::from_cassette(single_interaction_cassette("data").await?)
+        .mode(VcrMode::Replay)
+        .inner_client_factory(factory())
+        .build()
+        .await?;
+    assert_eq!(created.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    // Forwarding misses does
+    VcrClientBuilder::from_cassette(single_interaction_cassette("data").await?)
+        .mode(VcrMode::Replay)
+        .on_miss(MissBehavior::Forward)
+        .inner_client_factory(factory())
+        .build()
+        .await?;
+    assert_eq!(created.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    let missing = VcrClientBuilder::from_cassette(single_interaction_cassette("data").await?)
+        .mode(VcrMode::Record)
+        .build()
+        .await;
+    assert!(missing.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_client_runs_without_tokio_runtime() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-no-tokio-{}.yaml", std::process::id()));
+    futures_lite::future::block_on(async {
+        let vcr = VcrClientBuilder::from_cassette(single_interaction_cassette("data").await?)
+            .with_save_path(&path)
+            .mode(VcrMode::Replay)
+            .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+            .simulate_latency(LatencyMode::Fixed(std::time::Duration::from_millis(5)))
+            .build()
+            .await?;
+        let mut response = vcr
+            .send(http_types::Request::new(
+                Method::Get,
+                Url::parse("https://example.com/data")?,
+            ))
+            .await?;
+        assert_eq!(response.body_string().await?, "data");
+        vcr.save_in_background().await?.flush().await?;
+        Ok::<_, Box<dyn std::error::Error>>(())
+    })?;
+    assert!(path.exists());
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+/// Counts the requests it filters
+#[derive(Debug, Clone, Default)]
+struct CountingFilter(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+impl Filter for CountingFilter {
+    fn filter_request(&self, _request: &mut SerializableRequest) {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    fn filter_response(&self, _response: &mut SerializableResponse) {}
+}
+
+#[tokio::test]
+async fn test_request_is_filtered_once_across_cassette_layers(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let counter = CountingFilter::default();
+    let mut client = VcrClientBuilder::from_cassette(single_interaction_cassette("top").await?)
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .filter_chain(FilterChain::new().add_filter(Box::new(counter.clone())))
+        .build()
+        .await?;
+    client.set_additional_cassettes(vec![single_interaction_cassette("shared").await?]);
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/shared")?);
+    let mut response = client.send(request).await?;
+    assert_eq!(response.body_string().await?, "shared");
+    // Missing in the topmost cassette and found in the shared one, filtered once
+    assert_eq!(counter.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cloned_handles_share_one_client() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = single_interaction_cassette("users").await?;
+    cassette
+        .record_interaction(
+            get_request("https://example.com/orders"),
+            ok_response("orders"),
+        )
+        .await?;
+    let handle = VcrClientBuilder::from_cassette(cassette)
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .build()
+        .await?
+        .into_handle();
+    let users_api: Box<dyn HttpClient> = Box::new(handle.clone());
+    let orders_api: Box<dyn HttpClient> = Box::new(handle.clone());
+    let request = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+
+    let mut response = users_api.send(request("users")).await?;
+    assert_eq!(response.body_string().await?, "users");
+    // Already replayed through the other handle
+    assert!(orders_api.send(request("users")).await.is_err());
+    let mut response = orders_api.send(request("orders")).await?;
+    assert_eq!(response.body_string().await?, "orders");
+
+    assert_eq!(handle.playback_stats().await.replayed, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_build_with_keeps_the_concrete_inner_client() -> Result<(), Box<dyn std::error::Error>>
+{
+    let vcr_client: http_client_vcr::VcrClient<LockstepClient> = VcrClientBuilder::in_memory()
+        .mode(VcrMode::Record)
+        .build_with(
+            LockstepClient::new(single_interaction_cassette("users").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        )
+        .await?;
+
+    let url = Url::parse("https://example.com/users")?;
+    let mut response = vcr_client
+        .send(http_types::Request::new(Method::Get, url))
+        .await?;
+    assert_eq!(response.body_string().await?, "users");
+    assert_eq!(vcr_client.interactions().await.len(), 1);
+
+    let _inner: &LockstepClient = vcr_client.inner();
+    let _inner: LockstepClient = vcr_client.into_inner();
+    Ok(())
+}
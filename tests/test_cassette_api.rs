@@ -0,0 +1,113 @@
+#![cfg(feature = "fs")]
+
+mod common;
+
+use common::{get_request, ok_response};
+use http_client_vcr::Cassette;
+
+#[tokio::test]
+async fn test_cassette_query_helpers_return_indices() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    for url in [
+        "https://api.example.com/users/1",
+        "https://cdn.example.com/logo.png",
+        "https://API.example.com:8443/users/2/orders",
+    ] {
+        cassette
+            .record_interaction(get_request(url), ok_response("ok"))
+            .await?;
+    }
+    let mut post = get_request("https://api.example.com/users");
+    post.method = "POST".to_string();
+    cassette
+        .record_interaction(post, ok_response("created"))
+        .await?;
+
+    let indices = |found: Vec<(usize, &http_client_vcr::Interaction)>| {
+        found
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        indices(cassette.interactions_for_host("api.example.com").collect()),
+        [0, 2, 3]
+    );
+    assert_eq!(
+        indices(cassette.requests_matching("get", r"/users/\d+$")?.collect()),
+        [0]
+    );
+    assert!(cassette.requests_matching("GET", "(").is_err());
+
+    let (index, created) = cassette
+        .find(|interaction| interaction.response.body.as_deref() == Some("created"))
+        .next()
+        .unwrap();
+    assert_eq!(index, 3);
+    assert_eq!(created.request.method, "POST");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cassette_mutations_mark_it_modified() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    for body in ["a", "b", "c", "d"] {
+        cassette
+            .record_interaction(
+                get_request(&format!("https://example.com/{body}")),
+                ok_response(body),
+            )
+            .await?;
+    }
+    let bodies = |cassette: &Cassette| {
+        cassette
+            .interactions
+            .iter()
+            .map(|i| i.response.body.clone().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    cassette.modified_since_load = false;
+    assert!(cassette.remove_interaction(10).is_none());
+    assert_eq!(cassette.retain(|_| true), 0);
+    cassette.swap(1, 1);
+    assert!(!cassette.modified_since_load);
+
+    assert_eq!(
+        cassette
+            .remove_interaction(0)
+            .unwrap()
+            .response
+            .body
+            .as_deref(),
+        Some("a")
+    );
+    assert!(cassette.modified_since_load);
+
+    cassette.modified_since_load = false;
+    assert_eq!(
+        cassette.retain(|i| i.response.body.as_deref() != Some("c")),
+        1
+    );
+    assert!(cassette.modified_since_load);
+
+    cassette.modified_since_load = false;
+    cassette.swap(0, 1);
+    assert!(cassette.modified_since_load);
+    assert_eq!(bodies(&cassette), ["d", "b"]);
+
+    cassette.infer_url_templates();
+    cassette.modified_since_load = false;
+    let mut replacement = cassette.interactions[0].clone();
+    replacement.request = get_request("https://example.com/users/42");
+    replacement.response = ok_response("e");
+    let replaced = cassette.replace_interaction(0, replacement).unwrap();
+    assert_eq!(replaced.response.body.as_deref(), Some("d"));
+    assert!(cassette.modified_since_load);
+    assert_eq!(bodies(&cassette), ["e", "b"]);
+    assert!(cassette
+        .metadata
+        .url_templates
+        .contains(&"https://example.com/users/{id}".to_string()));
+    Ok(())
+}
@@ -1,145 +1,37 @@
-use async_trait::async_trait;
-use http_client::{Error, HttpClient, Request, Response};
-use http_client_vcr::{CassetteFormat, DefaultMatcher, NoOpClient, VcrClient, VcrMode};
+#![cfg(feature = "fs")]
+
+use http_client::HttpClient;
+#[cfg(feature = "reqwest")]
+use http_client_vcr::adapters::ReqwestClient;
+use http_client_vcr::testing::VcrTestContext;
+use http_client_vcr::{
+    Cassette, CassetteFormat, DefaultMatcher, Headers, Persistence, SerializableRequest,
+    SerializableResponse, VcrClientBuilder, VcrMode,
+};
 use http_types::{Method, Url};
-use std::env;
-use std::path::PathBuf;
-
-// Simple adapter to make reqwest work with http-client trait
-#[derive(Debug, Clone)]
-struct ReqwestAdapter {
-    client: reqwest::Client,
-}
-
-impl ReqwestAdapter {
-    fn new() -> Self {
-        Self {
-            client: reqwest::Client::new(),
-        }
-    }
-}
-
-#[async_trait]
-impl HttpClient for ReqwestAdapter {
-    async fn send(&self, mut req: Request) -> Result<Response, Error> {
-        let method = match req.method() {
-            Method::Get => reqwest::Method::GET,
-            Method::Post => reqwest::Method::POST,
-            Method::Put => reqwest::Method::PUT,
-            Method::Delete => reqwest::Method::DELETE,
-            Method::Head => reqwest::Method::HEAD,
-            Method::Options => reqwest::Method::OPTIONS,
-            Method::Patch => reqwest::Method::PATCH,
-            _ => reqwest::Method::GET,
-        };
-
-        let mut reqwest_req = self.client.request(method, req.url().as_str());
-
-        // Add headers
-        for (name, values) in req.iter() {
-            for value in values.iter() {
-                reqwest_req = reqwest_req.header(name.as_str(), value.as_str());
-            }
-        }
-
-        // Add body if present
-        let body = req
-            .body_string()
-            .await
-            .map_err(|e| Error::from_str(500, e))?;
-        if !body.is_empty() {
-            reqwest_req = reqwest_req.body(body);
-        }
-
-        let reqwest_resp = reqwest_req
-            .send()
-            .await
-            .map_err(|e| Error::from_str(500, e))?;
-
-        let mut response = Response::new(reqwest_resp.status().as_u16());
-
-        // Copy headers
-        for (name, value) in reqwest_resp.headers() {
-            let _ = response.insert_header(name.as_str(), value.to_str().unwrap_or(""));
-        }
-
-        // Set body
-        let body_bytes = reqwest_resp
-            .bytes()
-            .await
-            .map_err(|e| Error::from_str(500, e))?;
-        response.set_body(body_bytes.to_vec());
-
-        Ok(response)
-    }
-}
-
-/// Simple VCR test setup for directory-based cassettes
-struct VcrTestSetup {
-    cassette_path: PathBuf,
-    mode: VcrMode,
-}
-
-impl VcrTestSetup {
-    fn new(test_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let cassette_path = PathBuf::from(format!("tests/fixtures/{test_name}"));
-
-        // Check environment variable for recording mode
-        let vcr_record = env::var("VCR_RECORD").unwrap_or_default();
-        let cassette_exists = cassette_path.exists();
-
-        let mode = match vcr_record.as_str() {
-            "1" | "true" | "on" => VcrMode::Record,
-            _ => {
-                if !cassette_exists {
-                    return Err(format!(
-                        "No cassette found at '{}' and VCR_RECORD is not set. Either set VCR_RECORD=1 to record new interactions or ensure the cassette directory exists.",
-                        cassette_path.display()
-                    ).into());
-                }
-                VcrMode::Replay
-            }
-        };
-
-        Ok(Self {
-            cassette_path,
-            mode,
-        })
-    }
-
-    async fn create_vcr_client(&self) -> Result<VcrClient, Box<dyn std::error::Error>> {
-        // Ensure test fixtures directory exists
-        if let Some(parent_dir) = self.cassette_path.parent() {
-            std::fs::create_dir_all(parent_dir)?;
-        }
-
-        let inner_client: Box<dyn HttpClient + Send + Sync> = match self.mode {
-            VcrMode::Record => Box::new(ReqwestAdapter::new()),
-            _ => Box::new(NoOpClient::new()),
-        };
-
-        // Create a custom matcher that only matches method and URL, ignoring headers
-        let matcher = DefaultMatcher::new().with_headers(vec![]); // Don't match any headers
-
-        let vcr_client = VcrClient::builder(&self.cassette_path)
-            .inner_client(inner_client)
-            .mode(self.mode.clone())
-            .format(CassetteFormat::Directory) // Use directory format
-            .matcher(Box::new(matcher))
-            .build()
-            .await?;
-
-        Ok(vcr_client)
-    }
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Test context for the directory-format cassette `test_name`
+fn vcr_test_context(test_name: &str) -> VcrTestContext {
+    let context = VcrTestContext::new(test_name)
+        .with_format(CassetteFormat::Directory)
+        // Only match method and URL, ignoring headers
+        .with_matcher(|| Box::new(DefaultMatcher::new().with_headers(vec![])));
+    // Recording makes real requests: VCR_RECORD=1 cargo test --features reqwest
+    #[cfg(feature = "reqwest")]
+    let context = context.with_inner_client(|| Box::new(ReqwestClient::new()));
+    context
 }
 
 #[tokio::test]
 async fn test_concurrent_requests_edge_case() -> Result<(), Box<dyn std::error::Error>> {
-    let setup = VcrTestSetup::new("concurrent_requests_test")?;
+    let setup = vcr_test_context("concurrent_requests_test");
 
     // First, record multiple different UUID requests
-    if matches!(setup.mode, VcrMode::Record) {
-        let vcr_client = setup.create_vcr_client().await?;
+    if setup.is_recording() {
+        let vcr_client = setup.client().await?;
         let url = "https://httpbin.org/uuid";
 
         for i in 1..=3 {
@@ -151,7 +43,7 @@ async fn test_concurrent_requests_edge_case() -> Result<(), Box<dyn std::error::
     }
 
     // In replay mode, test concurrent access to the same VCR client
-    let vcr_client = std::sync::Arc::new(setup.create_vcr_client().await?);
+    let vcr_client = Arc::new(setup.client().await?);
     let url = "https://httpbin.org/uuid";
 
     // Create multiple concurrent tasks that make identical requests
@@ -178,7 +70,6 @@ async fn test_concurrent_requests_edge_case() -> Result<(), Box<dyn std::error::
     }
 
     // All responses should be different (since we recorded different UUIDs)
-    // But with concurrent access, we might see race conditions in the used_interactions tracking
     assert_ne!(
         responses[0], responses[1],
         "Concurrent requests should get different responses"
@@ -195,3 +86,119 @@ async fn test_concurrent_requests_edge_case() -> Result<(), Box<dyn std::error::
     println!("Concurrent requests handled correctly: {responses:?}");
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_replay_never_consumes_an_interaction_twice(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    for i in 0..64 {
+        cassette
+            .record_interaction(
+                SerializableRequest {
+                    method: "GET".to_string(),
+                    url: "https://example.com/uuid".to_string(),
+                    headers: Headers::new(),
+                    body: None,
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+                SerializableResponse {
+                    status: 200,
+                    headers: Headers::new(),
+                    body: Some(i.to_string().into()),
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+            )
+            .await?;
+    }
+    let vcr_client = Arc::new(
+        VcrClientBuilder::from_cassette(cassette)
+            .mode(VcrMode::Replay)
+            .persistence(Persistence::InMemory)
+            .build()
+            .await?,
+    );
+
+    let handles: Vec<_> = (0..64)
+        .map(|_| {
+            let client = vcr_client.clone();
+            tokio::spawn(async move {
+                let request =
+                    http_types::Request::new(Method::Get, Url::parse("https://example.com/uuid")?);
+                client.send(request).await?.body_string().await
+            })
+        })
+        .collect();
+    let mut bodies = BTreeSet::new();
+    for handle in handles {
+        bodies.insert(handle.await.unwrap()?);
+    }
+
+    assert_eq!(bodies.len(), 64);
+    Ok(())
+}
+
+/// Answers `/slow` only once `/fast` has been answered
+#[derive(Debug, Clone, Default)]
+struct OutOfOrderClient {
+    slow_sent: Arc<AtomicBool>,
+    fast_answered: Arc<AtomicBool>,
+}
+
+#[async_trait::async_trait]
+impl HttpClient for OutOfOrderClient {
+    async fn send(
+        &self,
+        req: http_client::Request,
+    ) -> Result<http_client::Response, http_client::Error> {
+        if req.url().path() == "/slow" {
+            self.slow_sent.store(true, Ordering::SeqCst);
+            while !self.fast_answered.load(Ordering::SeqCst) {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        } else {
+            self.fast_answered.store(true, Ordering::SeqCst);
+        }
+        Ok(http_client::Response::new(200))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_concurrent_recording_keeps_the_order_requests_were_sent(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let inner = OutOfOrderClient::default();
+    let vcr_client = Arc::new(
+        VcrClientBuilder::from_cassette(Cassette::new())
+            .mode(VcrMode::Record)
+            .persistence(Persistence::InMemory)
+            .inner_client(Box::new(inner.clone()))
+            .build()
+            .await?,
+    );
+
+    let client = vcr_client.clone();
+    let slow = tokio::spawn(async move {
+        let request =
+            http_types::Request::new(Method::Get, Url::parse("https://example.com/slow")?);
+        client.send(request).await.map(|_| ())
+    });
+    while !inner.slow_sent.load(Ordering::SeqCst) {
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/fast")?);
+    vcr_client.send(request).await?;
+    slow.await.unwrap()?;
+
+    let urls: Vec<_> = vcr_client
+        .interactions()
+        .await
+        .into_iter()
+        .map(|interaction| interaction.request.url)
+        .collect();
+    assert_eq!(
+        urls,
+        ["https://example.com/slow", "https://example.com/fast"]
+    );
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use http_client::HttpClient;
+use http_client_vcr::{MemoryStorage, VcrClientBuilder, VcrConfig, VcrMode};
+use http_types::{Method, Url};
+
+const GREETING: &str = "interactions:
+- request:
+    method: GET
+    url: https://example.com/greeting
+    headers: {}
+    version: Http1_1
+  response:
+    status: 200
+    headers: {}
+    body: hello
+    version: Http1_1
+";
+
+#[tokio::test]
+async fn test_config_is_only_used_when_passed_in() -> Result<(), Box<dyn std::error::Error>> {
+    let storage = MemoryStorage::new();
+    storage.insert_yaml("/suite/greeting.yaml", GREETING)?;
+    let config = VcrConfig::parse(
+        r#"
+        cassette_root = "/suite"
+        matcher = "method_and_url"
+        "#,
+    )?;
+    let request = || {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/greeting").unwrap(),
+        )
+    };
+
+    let configured = VcrClientBuilder::new("greeting.yaml")
+        .storage(Box::new(storage.clone()))
+        .mode(VcrMode::Replay)
+        .with_config(config)
+        .build()
+        .await?;
+    let mut response = configured.send(request()).await?;
+    assert_eq!(response.body_string().await?, "hello");
+
+    // Without a config the path isn't rooted, so there is nothing to replay
+    let plain = VcrClientBuilder::new("greeting.yaml")
+        .storage(Box::new(storage))
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+    assert!(plain.send(request()).await.is_err());
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use http_client_vcr::{
+    BodyFilter, ErrorBodyFilter, Filter, FilterChain, FilterEntry, FilterScope, FilterStage,
+    HeaderFilter, Headers, SerializableRequest, SerializableResponse, SmartFormFilter, UrlFilter,
+    UrlRewriteFilter,
+};
+
+fn post_request(url: &str, body: &str) -> SerializableRequest {
+    SerializableRequest {
+        method: "POST".to_string(),
+        url: url.to_string(),
+        headers: Headers::new(),
+        body: Some(body.to_string()),
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    }
+}
+
+#[test]
+fn test_scoped_filter_only_applies_to_matching_requests() {
+    let chain = FilterChain::new().add_scoped_filter(
+        FilterScope::new()
+            .host("auth.example.com")
+            .path_prefix("/login")
+            .method("POST"),
+        Box::new(SmartFormFilter::new()),
+    );
+
+    let mut login = post_request(
+        "https://auth.example.com/login?next=/",
+        "username=alice&password=hunter2",
+    );
+    chain.filter_request(&mut login);
+    assert!(!login.body.unwrap().contains("hunter2"));
+
+    let mut analytics = post_request(
+        "https://analytics.example.com/login",
+        "user=alice&event=login",
+    );
+    chain.filter_request(&mut analytics);
+    assert_eq!(analytics.body.as_deref(), Some("user=alice&event=login"));
+
+    // Responses are scoped by the request they answer
+    let mut response = SerializableResponse {
+        status: 200,
+        headers: Headers::new(),
+        body: Some("ok".into()),
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    };
+    chain.filter_response_for(&analytics, &mut response);
+    assert_eq!(response.body.as_deref(), Some("ok"));
+
+    let wildcard = FilterScope::new().host("*.example.com");
+    assert!(wildcard.matches(&post_request("https://api.example.com/x", "")));
+    assert!(!wildcard.matches(&post_request("https://example.org/x", "")));
+}
+
+#[test]
+fn test_named_filters_can_be_adjusted() {
+    let chain = FilterChain::new()
+        .add_named_filter("auth", Box::new(HeaderFilter::new().remove_auth_headers()))
+        .add_named_filter("forms", Box::new(SmartFormFilter::new()))
+        .add_filter(Box::new(BodyFilter::new()));
+
+    let chain = chain
+        .insert_before("forms", "urls", Box::new(UrlFilter::new()))
+        .remove_filter("auth");
+
+    let names: Vec<Option<&str>> = chain.iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec![Some("urls"), Some("forms"), None]);
+    assert!(!chain.contains("auth"));
+    assert_eq!(chain.len(), 3);
+
+    // Re-adding under an existing name replaces the filter in place
+    let chain = chain.add_named_filter("urls", Box::new(UrlFilter::new()));
+    assert_eq!(chain.len(), 3);
+    assert_eq!(chain.iter().next().unwrap().0, Some("urls"));
+
+    // Inserting under an existing name replaces the filter at the new position
+    let chain = chain
+        .add_named_filter("bodies", Box::new(BodyFilter::new()))
+        .insert_before("bodies", "urls", Box::new(UrlFilter::new()));
+    let names: Vec<Option<&str>> = chain.iter().map(|(name, _)| name).collect();
+    assert_eq!(
+        names,
+        vec![Some("forms"), None, Some("urls"), Some("bodies")]
+    );
+}
+
+#[test]
+fn test_named_filters_keep_their_scope_and_stage() -> Result<(), regex::Error> {
+    let chain = FilterChain::new()
+        .add_named_entry(
+            "forms",
+            FilterEntry::new(Box::new(SmartFormFilter::new()))
+                .scoped(FilterScope::new().host("auth.example.com")),
+        )
+        .add_named_entry(
+            "fresh",
+            FilterEntry::new(Box::new(
+                BodyFilter::new().replace_regex("event", "[EVENT]")?,
+            ))
+            .at_stage(FilterStage::BeforePlayback),
+        )
+        // Replaced, and inserted next to the scoped and playback filters
+        .add_named_filter("forms", Box::new(SmartFormFilter::new()))
+        .insert_before(
+            "forms",
+            "users",
+            Box::new(BodyFilter::new().replace_regex("alice", "[USER]")?),
+        )
+        .insert_before(
+            "fresh",
+            "logins",
+            Box::new(BodyFilter::new().replace_regex("login", "[LOGIN]")?),
+        );
+
+    let mut login = post_request(
+        "https://auth.example.com/login",
+        "username=alice&password=hunter2",
+    );
+    chain.filter_request(&mut login);
+    let body = login.body.unwrap();
+    assert!(
+        !body.contains("hunter2") && !body.contains("alice"),
+        "{body}"
+    );
+
+    let mut analytics = post_request(
+        "https://analytics.example.com/track",
+        "user=alice&password=hunter2&event=login",
+    );
+    chain.filter_request(&mut analytics);
+    assert_eq!(
+        analytics.body.as_deref(),
+        Some("user=alice&password=hunter2&event=login")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_error_body_filter_drops_and_truncates() {
+    let response = |status: u16, body: &str| SerializableResponse {
+        status,
+        headers: Headers::new(),
+        body: Some(body.into()),
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    };
+
+    let mut ok = response(200, "fine");
+    let mut not_found = response(404, "trace at internal.host:8080");
+    let drop = ErrorBodyFilter::new();
+    drop.filter_response(&mut ok);
+    drop.filter_response(&mut not_found);
+    assert_eq!(ok.body.as_deref(), Some("fine"));
+    assert_eq!(not_found.body, None);
+    assert_eq!(not_found.status, 404);
+
+    let mut server_error = response(500, "Traceback (most recent call last)");
+    ErrorBodyFilter::new()
+        .truncate_to(9)
+        .filter_response(&mut server_error);
+    assert_eq!(
+        server_error.body.as_deref(),
+        Some("Traceback...[truncated]")
+    );
+}
+
+#[test]
+fn test_filtered_bodies_get_matching_length_headers() {
+    let chain = FilterChain::new().add_filter(Box::new(
+        BodyFilter::new().replace_json_key("key", "[FILTERED]"),
+    ));
+
+    let mut response = SerializableResponse {
+        status: 200,
+        headers: Headers::from([
+            ("content-length".to_string(), vec!["29".to_string()]),
+            ("transfer-encoding".to_string(), vec!["chunked".to_string()]),
+        ]),
+        body: Some(r#"{"key":"sk_live_abcdef12345"}"#.into()),
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    };
+    chain.filter_response(&mut response);
+
+    assert_eq!(response.body.as_deref(), Some(r#"{"key":"[FILTERED]"}"#));
+    assert_eq!(response.headers["content-length"], vec!["20".to_string()]);
+    assert!(!response.headers.contains_key("transfer-encoding"));
+
+    // Bodiless responses, like answers to HEAD, keep the length they describe
+    let mut head = SerializableResponse {
+        body: None,
+        ..response
+    };
+    head.headers
+        .insert("content-length".to_string(), vec!["512".to_string()]);
+    head.fix_body_headers();
+    assert_eq!(head.headers["content-length"], vec!["512".to_string()]);
+}
+
+#[test]
+fn test_ndjson_bodies_are_filtered_line_by_line() {
+    let filter = BodyFilter::new().remove_common_sensitive_keys();
+    let mut response = SerializableResponse {
+        status: 200,
+        headers: Headers::from([(
+            "Content-Type".to_string(),
+            vec!["application/x-ndjson; charset=utf-8".to_string()],
+        )]),
+        body: Some("{\"id\":1,\"token\":\"abc\"}\n\n{\"id\":2,\"secret\":\"xyz\"}\r\n".into()),
+        body_base64: None,
+        version: "Http1_1".to_string(),
+    };
+    filter.filter_response(&mut response);
+    assert_eq!(
+        response.body.as_deref(),
+        Some("{\"id\":1}\n\n{\"id\":2}\r\n")
+    );
+
+    // Without the content type, the flag turns line-by-line filtering on
+    let mut request = post_request(
+        "https://example.com/bulk",
+        "{\"password\":\"p\"}\n{\"api_key\":\"k\",\"n\":1}",
+    );
+    BodyFilter::new()
+        .remove_common_sensitive_keys()
+        .ndjson(true)
+        .filter_request(&mut request);
+    assert_eq!(request.body.as_deref(), Some("{}\n{\"n\":1}"));
+}
+
+#[test]
+fn test_url_rewrite_filter_canonicalizes_urls() {
+    let filter = UrlRewriteFilter::canonical()
+        .replace_regex(r"/v\d+/", "/v1/")
+        .unwrap();
+
+    let mut request = post_request("HTTPS://API.Example.com:443/v2/items?q=Abc#top", "");
+    filter.filter_request(&mut request);
+    assert_eq!(request.url, "https://api.example.com/v1/items?q=Abc");
+
+    // Credentials, non-default ports and IPv6 hosts are left alone
+    assert_eq!(
+        filter.rewrite("http://User:Pw@Example.com:8080/x"),
+        "http://User:Pw@example.com:8080/x"
+    );
+    assert_eq!(filter.rewrite("http://[::1]:80/x"), "http://[::1]/x");
+    assert_eq!(filter.rewrite("http://[::1]/x"), "http://[::1]/x");
+    assert_eq!(
+        UrlRewriteFilter::new().rewrite("https://Example.com:443/#a"),
+        "https://Example.com:443/#a"
+    );
+}
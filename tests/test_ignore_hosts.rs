@@ -0,0 +1,58 @@
+use http_client::HttpClient;
+use http_client_vcr::{Cassette, VcrClientBuilder, VcrMode};
+use http_types::{Method, Request, Url};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Answers every request with `live`, counting them
+#[derive(Debug, Clone, Default)]
+struct LiveClient {
+    sent: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl HttpClient for LiveClient {
+    async fn send(
+        &self,
+        _req: http_client::Request,
+    ) -> Result<http_client::Response, http_client::Error> {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        let mut response = http_client::Response::new(200);
+        response.set_body("live");
+        Ok(response)
+    }
+}
+
+fn get(url: &str) -> Request {
+    Request::new(Method::Get, Url::parse(url).unwrap())
+}
+
+#[tokio::test]
+async fn test_ignored_hosts_bypass_the_cassette() -> Result<(), Box<dyn std::error::Error>> {
+    let inner = LiveClient::default();
+    let replaying = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(inner.clone()))
+        .mode(VcrMode::Replay)
+        .ignore_hosts(["LOCALHOST", "*.internal"])
+        .build()
+        .await?;
+    for url in ["http://localhost:5432/health", "https://db.internal/status"] {
+        let mut response = replaying.send(get(url)).await?;
+        assert_eq!(response.body_string().await?, "live");
+    }
+    assert!(replaying.send(get("https://example.com/")).await.is_err());
+    assert_eq!(inner.sent.load(Ordering::SeqCst), 2);
+
+    // Record mode would replay the second request if the first had been recorded
+    let recording = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(inner.clone()))
+        .mode(VcrMode::Record)
+        .ignore_hosts(["localhost"])
+        .build()
+        .await?;
+    for _ in 0..2 {
+        recording.send(get("http://localhost:5432/health")).await?;
+    }
+    assert_eq!(inner.sent.load(Ordering::SeqCst), 4);
+    Ok(())
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{get_request, ok_response};
+use http_client::HttpClient;
+use http_client_vcr::{Cassette, LockstepClient, NoOpClient, VcrClient, VcrMode};
+use http_types::{Method, Url};
+
+async fn session(second_body: &str) -> Result<Cassette, Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(get_request("https://api.example.com/a"), ok_response("a"))
+        .await?;
+    cassette
+        .record_interaction(
+            get_request("https://api.example.com/b"),
+            ok_response(second_body),
+        )
+        .await?;
+    Ok(cassette)
+}
+
+#[tokio::test]
+async fn test_lockstep_consumer_and_provider() -> Result<(), Box<dyn std::error::Error>> {
+    // Stand-in for the provider service
+    let provider = VcrClient::new(
+        Box::new(NoOpClient::new()),
+        VcrMode::Replay,
+        session("b").await?,
+    );
+    let lockstep = LockstepClient::new(session("b").await?).with_provider(Box::new(provider));
+
+    // Out of order requests are rejected
+    let request = http_types::Request::new(Method::Get, Url::parse("https://api.example.com/b")?);
+    assert!(lockstep.send(request).await.is_err());
+
+    for (path, body) in [("a", "a"), ("b", "b")] {
+        let url = format!("https://api.example.com/{path}");
+        let request = http_types::Request::new(Method::Get, Url::parse(&url)?);
+        let mut response = lockstep.send(request).await?;
+        assert_eq!(response.body_string().await?, body);
+    }
+    assert!(lockstep.is_complete().await);
+
+    // A provider whose responses drifted from the recording fails verification
+    let drifted = VcrClient::new(
+        Box::new(NoOpClient::new()),
+        VcrMode::Replay,
+        session("changed").await?,
+    );
+    let lockstep = LockstepClient::new(session("b").await?).with_provider(Box::new(drifted));
+    let error = lockstep.verify_provider().await.unwrap_err();
+    assert!(error.to_string().contains("interaction 1"));
+
+    Ok(())
+}
@@ -0,0 +1,376 @@
+#![cfg(feature = "fs")]
+
+mod common;
+
+use common::{get_request, ok_response, single_interaction_cassette};
+use http_client::HttpClient;
+use http_client_vcr::{
+    Cassette, DefaultMatcher, LockstepClient, MissAction, MissBehavior, NoOpClient, Persistence,
+    RecordBehavior, VcrClientBuilder, VcrMode,
+};
+use http_types::{Error, Method, Url};
+
+#[tokio::test]
+async fn test_stale_cassette_is_re_recorded() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-stale-{}.yaml", std::process::id()));
+
+    let mut stale = Cassette::new().with_path(path.clone());
+    stale
+        .record_interaction(get_request("https://example.com/api"), ok_response("old"))
+        .await?;
+    stale.metadata.recorded_at =
+        Some(time::OffsetDateTime::now_utc() - std::time::Duration::from_secs(30 * 86400));
+    stale.save_to_file().await?;
+
+    let mut upstream = Cassette::new();
+    upstream
+        .record_interaction(get_request("https://example.com/api"), ok_response("new"))
+        .await?;
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Once)
+        .re_record_interval(std::time::Duration::from_secs(86400))
+        .build()
+        .await?;
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/api")?);
+    let mut response = vcr_client.send(request).await?;
+    assert_eq!(response.body_string().await?, "new");
+
+    vcr_client.save_cassette().await?;
+    let stored = Cassette::load_from_file(path.clone()).await?;
+    assert_eq!(stored.interactions.len(), 1);
+    assert!(!stored.is_older_than(std::time::Duration::from_secs(86400)));
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_on_miss_handler_mocks_unrecorded_endpoints() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(
+            get_request("https://example.com/recorded"),
+            ok_response("from cassette"),
+        )
+        .await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .on_miss(MissBehavior::handler(|req| match req.url().path() {
+            "/mocked" => {
+                let mut response = http_types::Response::new(201);
+                response.set_body("mocked");
+                MissAction::Respond(Box::new(response))
+            }
+            "/forwarded" => MissAction::Forward,
+            _ => MissAction::Fail(Error::from_str(418, "unexpected request")),
+        }))
+        .build()
+        .await?;
+
+    let send = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+
+    let mut response = vcr_client.send(send("recorded")).await?;
+    assert_eq!(response.body_string().await?, "from cassette");
+
+    let mut response = vcr_client.send(send("mocked")).await?;
+    assert_eq!(response.status(), 201);
+    assert_eq!(response.body_string().await?, "mocked");
+
+    // Forwarded to the NoOpClient, which refuses to make real requests
+    assert!(vcr_client.send(send("forwarded")).await.is_err());
+
+    let error = vcr_client.send(send("other")).await.unwrap_err();
+    assert_eq!(error.status(), 418);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_passthrough_with_warning_reports_misses() -> Result<(), Box<dyn std::error::Error>> {
+    let report = std::env::temp_dir().join(format!("vcr-miss-report-{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&report);
+
+    let vcr_client = VcrClientBuilder::new("tests/fixtures/legacy.yaml")
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("live").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .on_miss(MissBehavior::passthrough_with_warning(&report))
+        .build()
+        .await?;
+    vcr_client
+        .insert_cassette(single_interaction_cassette("recorded").await?)
+        .await;
+
+    let send = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+    let mut response = vcr_client.send(send("recorded")).await?;
+    assert_eq!(response.body_string().await?, "recorded");
+    let mut response = vcr_client.send(send("live")).await?;
+    assert_eq!(response.body_string().await?, "live");
+
+    // The miss was reported, but not recorded
+    let reported = std::fs::read_to_string(&report)?;
+    assert_eq!(reported.lines().count(), 1);
+    assert!(reported.ends_with("\tGET https://example.com/live\n"));
+    assert_eq!(vcr_client.eject_cassette().await.interactions.len(), 1);
+
+    let _ = std::fs::remove_file(&report);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_record_append_keeps_existing_interactions() -> Result<(), Box<dyn std::error::Error>>
+{
+    let path = std::env::temp_dir().join(format!("vcr-append-{}.yaml", std::process::id()));
+    let existing = single_interaction_cassette("first")
+        .await?
+        .with_path(path.clone());
+    existing.save_to_file().await?;
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("second").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .record_behavior(RecordBehavior::Append)
+        .build()
+        .await?;
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/second")?);
+    vcr_client.send(request).await?;
+    vcr_client.save_cassette().await?;
+
+    let stored = Cassette::load_from_file(path.clone()).await?;
+    let urls: Vec<_> = stored
+        .interactions
+        .iter()
+        .map(|interaction| interaction.request.url.as_str())
+        .collect();
+    assert_eq!(
+        urls,
+        vec!["https://example.com/first", "https://example.com/second"]
+    );
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mode_from_env() -> Result<(), Box<dyn std::error::Error>> {
+    let var = format!("VCR_MODE_TEST_{}", std::process::id());
+    let missing = std::env::temp_dir().join(format!("vcr-mode-env-{}.yaml", std::process::id()));
+    let build = |builder: VcrClientBuilder| {
+        builder
+            .inner_client(Box::new(NoOpClient::new()))
+            .mode_from_env(&var)
+            .build()
+    };
+
+    // Unset: replay existing cassettes, record new ones
+    let existing = build(VcrClientBuilder::from_cassette(
+        single_interaction_cassette("data").await?,
+    ))
+    .await?;
+    assert!(matches!(existing.mode(), VcrMode::Replay));
+    let new = build(VcrClientBuilder::new(&missing)).await?;
+    assert!(matches!(new.mode(), VcrMode::Record));
+    drop(new);
+
+    std::env::set_var(&var, "All");
+    let existing = build(VcrClientBuilder::from_cassette(
+        single_interaction_cassette("data").await?,
+    ))
+    .await?;
+    assert!(matches!(existing.mode(), VcrMode::Record));
+
+    std::env::set_var(&var, "sometimes");
+    assert!(build(VcrClientBuilder::new(&missing)).await.is_err());
+
+    std::env::remove_var(&var);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mode_header_overrides_the_mode_for_one_request(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::MODE_HEADER;
+
+    let mut upstream = Cassette::new();
+    for path in ["live", "new", "extension"] {
+        upstream
+            .record_interaction(
+                get_request(&format!("https://example.com/{path}")),
+                ok_response(path),
+            )
+            .await?;
+    }
+    let vcr_client = VcrClientBuilder::from_cassette(single_interaction_cassette("users").await?)
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .build()
+        .await?;
+    let request = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+
+    let mut live = request("live");
+    live.insert_header(MODE_HEADER, "bypass")?;
+    assert_eq!(vcr_client.send(live).await?.body_string().await?, "live");
+
+    let mut new = request("new");
+    new.insert_header(MODE_HEADER, "record")?;
+    assert_eq!(vcr_client.send(new).await?.body_string().await?, "new");
+
+    let mut extension = request("extension");
+    extension.ext_mut().insert(VcrMode::None);
+    assert_eq!(
+        vcr_client.send(extension).await?.body_string().await?,
+        "extension"
+    );
+
+    let mut invalid = request("users");
+    invalid.insert_header(MODE_HEADER, "sometimes")?;
+    assert_eq!(vcr_client.send(invalid).await.unwrap_err().status(), 400);
+
+    // Only the overridden recording was added, without the directive header
+    let interactions = vcr_client.interactions().await;
+    let urls: Vec<&str> = interactions
+        .iter()
+        .map(|i| i.request.url.as_str())
+        .collect();
+    assert_eq!(
+        urls,
+        ["https://example.com/users", "https://example.com/new"]
+    );
+    assert!(!interactions[1].request.headers.contains_key(MODE_HEADER));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mode_header_needs_an_inner_client() -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::MODE_HEADER;
+
+    let vcr_client = VcrClientBuilder::from_cassette(single_interaction_cassette("users").await?)
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .build()
+        .await?;
+
+    for mode in ["record", "bypass"] {
+        let url = Url::parse("https://example.com/users")?;
+        let mut request = http_types::Request::new(Method::Get, url);
+        request.insert_header(MODE_HEADER, mode)?;
+        let error = vcr_client.send(request).await.unwrap_err();
+        assert_eq!(error.status(), 400);
+        assert!(
+            error.to_string().contains("built without an inner_client"),
+            "{error}"
+        );
+    }
+    // Nothing was recorded over the cassette, which still replays
+    assert_eq!(vcr_client.interactions().await.len(), 1);
+    let url = Url::parse("https://example.com/users")?;
+    let mut response = vcr_client
+        .send(http_types::Request::new(Method::Get, url))
+        .await?;
+    assert_eq!(response.body_string().await?, "users");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_with_mode_switches_mode_temporarily_on_a_shared_client(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let vcr_client = std::sync::Arc::new(
+        VcrClientBuilder::from_cassette(single_interaction_cassette("users").await?)
+            .inner_client(Box::new(
+                LockstepClient::new(single_interaction_cassette("new").await?)
+                    .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+            ))
+            .mode(VcrMode::Replay)
+            .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+            .persistence(Persistence::InMemory)
+            .build()
+            .await?,
+    );
+    let request = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+
+    let body = vcr_client
+        .with_mode(VcrMode::Record, async |client| {
+            assert!(matches!(client.current_mode(), VcrMode::Record));
+            client.send(request("new")).await?.body_string().await
+        })
+        .await?;
+    assert_eq!(body, "new");
+    assert!(matches!(vcr_client.current_mode(), VcrMode::Replay));
+
+    // Recorded alongside the existing interaction, and replayed afterwards
+    for path in ["users", "new"] {
+        let mut response = vcr_client.send(request(path)).await?;
+        assert_eq!(response.body_string().await?, path);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_with_mode_needs_an_inner_client() -> Result<(), Box<dyn std::error::Error>> {
+    let vcr_client = VcrClientBuilder::from_cassette(single_interaction_cassette("users").await?)
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .build()
+        .await?;
+    let request = || {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/users").unwrap(),
+        )
+    };
+
+    for mode in [VcrMode::Record, VcrMode::None] {
+        let error = vcr_client
+            .with_mode(mode, async |client| client.send(request()).await)
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), 400);
+        assert!(
+            error.to_string().contains("built without an inner_client"),
+            "{error}"
+        );
+    }
+    // The cassette was left alone and replays once the block is over
+    let mut response = vcr_client.send(request()).await?;
+    assert_eq!(response.body_string().await?, "users");
+    Ok(())
+}
@@ -0,0 +1,213 @@
+mod common;
+
+use common::{get_request, ok_response};
+use http_client::HttpClient;
+use http_client_vcr::{
+    Cassette, CassetteStorage, DefaultMatcher, Filter, NoOpClient, PlaceholderFilter,
+    RequestMatcher, SensitiveDataFilter, SerializableRequest, VcrClient, VcrMode,
+};
+use http_types::{Method, Url};
+
+fn request(url: &str, token: &str) -> SerializableRequest {
+    let mut request = get_request(url);
+    request
+        .headers
+        .insert("authorization".to_string(), vec![format!("Bearer {token}")]);
+    request
+}
+
+#[test]
+fn test_defined_and_captured_placeholders_are_stable() {
+    let filter = PlaceholderFilter::new()
+        .define("API_KEY", "real-secret")
+        .capture_regex("TOKEN", r"tok_([a-z0-9]+)")
+        .unwrap();
+
+    let mut first = request("https://api.example.com/items?key=real-secret", "tok_abc");
+    let mut second = request("https://api.example.com/other", "tok_abc");
+    let mut third = request("https://api.example.com/other", "tok_xyz");
+    filter.filter_request(&mut first);
+    filter.filter_request(&mut second);
+    filter.filter_request(&mut third);
+
+    assert_eq!(first.url, "https://api.example.com/items?key=<<API_KEY>>");
+    let token = filter.placeholder_for("abc").unwrap();
+    assert_eq!(token, "<<TOKEN_1>>");
+    assert_eq!(
+        first.headers["authorization"],
+        vec!["Bearer tok_<<TOKEN_1>>"]
+    );
+    assert_eq!(
+        second.headers["authorization"],
+        first.headers["authorization"]
+    );
+    assert_eq!(
+        third.headers["authorization"],
+        vec!["Bearer tok_<<TOKEN_2>>"]
+    );
+
+    // Names are only numbers, so they can't be used to check guesses at the value
+    let later = PlaceholderFilter::new()
+        .capture_regex("TOKEN", r"tok_([a-z0-9]+)")
+        .unwrap();
+    let mut appended = request("https://api.example.com/other", "tok_xyz");
+    later.filter_request(&mut appended);
+    assert_eq!(
+        appended.headers["authorization"],
+        vec!["Bearer tok_<<TOKEN_1>>"]
+    );
+    assert_eq!(later.placeholder_for("abc"), None);
+
+    // Captured values seen in this session are put back into replayed responses
+    let mut replayed = ok_response(&format!("token: {token}"));
+    filter.restore_response(&mut replayed);
+    assert_eq!(replayed.body.as_deref(), Some("token: abc"));
+}
+
+#[tokio::test]
+async fn test_replay_substitutes_test_value_back() -> Result<(), Box<dyn std::error::Error>> {
+    let filter = PlaceholderFilter::new().define_with_replay_value(
+        "SESSION",
+        "recorded-session",
+        "test-session",
+    );
+
+    let mut recorded_request = request("https://api.example.com/session", "none");
+    let mut recorded_response = ok_response(r#"{"session":"recorded-session"}"#);
+    filter.filter_request(&mut recorded_request);
+    filter.filter_response(&mut recorded_response);
+    assert_eq!(
+        recorded_response.body.as_deref(),
+        Some(r#"{"session":"<<SESSION>>"}"#)
+    );
+
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(recorded_request, recorded_response)
+        .await?;
+
+    let mut vcr_client = VcrClient::new(Box::new(NoOpClient::new()), VcrMode::Replay, cassette);
+    vcr_client.set_matcher(Box::new(DefaultMatcher::new().with_headers(vec![])));
+    vcr_client.add_filter(Box::new(filter));
+
+    let request =
+        http_types::Request::new(Method::Get, Url::parse("https://api.example.com/session")?);
+    let mut response = vcr_client.send(request).await?;
+    assert_eq!(
+        response.body_string().await?,
+        r#"{"session":"test-session"}"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sensitive_data_filter_replaces_everywhere() {
+    let filter = SensitiveDataFilter::new(|| "s3cr3t value".to_string(), "<API_KEY>");
+
+    let mut recorded_request = request(
+        "https://api.example.com/?key=s3cr3t%20value",
+        "s3cr3t value",
+    );
+    recorded_request.body = Some(r#"{"key":"s3cr3t value"}"#.to_string());
+    filter.filter_request(&mut recorded_request);
+
+    assert_eq!(
+        recorded_request.url,
+        "https://api.example.com/?key=<API_KEY>"
+    );
+    assert_eq!(
+        recorded_request.headers["authorization"],
+        vec!["Bearer <API_KEY>"]
+    );
+    assert_eq!(
+        recorded_request.body.as_deref(),
+        Some(r#"{"key":"<API_KEY>"}"#)
+    );
+
+    let mut recorded_response = ok_response("echo: s3cr3t value");
+    filter.filter_response(&mut recorded_response);
+    assert_eq!(recorded_response.body.as_deref(), Some("echo: <API_KEY>"));
+    filter.restore_response(&mut recorded_response);
+    assert_eq!(
+        recorded_response.body.as_deref(),
+        Some("echo: s3cr3t value")
+    );
+}
+
+#[test]
+fn test_typed_placeholders_match_as_wildcards() {
+    let recorded = request(
+        "https://example.com/users?email=%5BFILTERED%3Aemail%5D",
+        "[FILTERED:jwt]",
+    );
+    let matcher = DefaultMatcher::new();
+
+    let live = request(
+        "https://example.com/users?email=someone%40example.com",
+        "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.c2ln",
+    );
+    assert!(matcher.matches_serializable(&live, &recorded));
+
+    let not_a_jwt = request(
+        "https://example.com/users?email=someone%40example.com",
+        "opaque-token",
+    );
+    assert!(!matcher.matches_serializable(&not_a_jwt, &recorded));
+}
+
+#[tokio::test]
+async fn test_appended_recordings_continue_placeholder_numbers(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::{LockstepClient, MemoryStorage, RecordBehavior};
+
+    let storage = MemoryStorage::new();
+    let record = |token: &'static str, behavior: RecordBehavior| {
+        let storage = storage.clone();
+        async move {
+            let mut upstream = Cassette::new();
+            upstream
+                .record_interaction(
+                    request("https://api.example.com/me", token),
+                    ok_response("me"),
+                )
+                .await?;
+            let vcr_client = VcrClient::builder("api.yaml")
+                .storage(Box::new(storage))
+                .inner_client(Box::new(
+                    LockstepClient::new(upstream)
+                        .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+                ))
+                .mode(VcrMode::Record)
+                .record_behavior(behavior)
+                .add_filter(Box::new(
+                    PlaceholderFilter::new().capture_regex("TOKEN", r"tok_([a-z0-9]+)")?,
+                ))
+                .build()
+                .await?;
+            let mut live =
+                http_types::Request::new(Method::Get, Url::parse("https://api.example.com/me")?);
+            live.insert_header("authorization", format!("Bearer {token}"))?;
+            vcr_client.send(live).await?;
+            vcr_client.save_cassette().await?;
+            Ok::<_, Box<dyn std::error::Error>>(())
+        }
+    };
+
+    record("tok_abc", RecordBehavior::Replace).await?;
+    // A later session, with a filter that hasn't seen the first token
+    record("tok_xyz", RecordBehavior::Append).await?;
+
+    let stored = storage.load(std::path::Path::new("api.yaml"))?.unwrap();
+    let authorizations: Vec<&str> = stored
+        .interactions
+        .iter()
+        .map(|interaction| interaction.request.headers["authorization"][0].as_str())
+        .collect();
+    assert_eq!(
+        authorizations,
+        ["Bearer tok_<<TOKEN_1>>", "Bearer tok_<<TOKEN_2>>"]
+    );
+    assert_eq!(stored.metadata.placeholders["TOKEN"], 2);
+    Ok(())
+}
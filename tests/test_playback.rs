@@ -0,0 +1,499 @@
+#![cfg(feature = "fs")]
+
+mod common;
+
+use common::{get_request, ok_response, single_interaction_cassette};
+use http_client::HttpClient;
+use http_client_vcr::{
+    Cassette, ConnectionReplay, DefaultMatcher, Fault, FaultInjector, Filter, FilterChain,
+    LatencyMode, LockstepClient, NoOpClient, Persistence, SerializableRequest,
+    SerializableResponse, TimeShift, VcrClientBuilder, VcrMode,
+};
+use http_types::{Error, Method, Url};
+
+#[tokio::test]
+async fn test_replay_validator_rejects_responses() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(get_request("https://example.com/json"), ok_response("{}"))
+        .await?;
+    cassette
+        .record_interaction(
+            get_request("https://example.com/broken"),
+            ok_response("<html>"),
+        )
+        .await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .on_replay_validate(|interaction| {
+            let body = interaction.response.body.as_deref().unwrap_or_default();
+            serde_json::from_str::<serde_json::Value>(body)
+                .map(|_| ())
+                .map_err(|e| Error::from_str(500, format!("Replayed body is not JSON: {e}")))
+        })
+        .build()
+        .await?;
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/json")?);
+    assert!(vcr_client.send(request).await.is_ok());
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/broken")?);
+    let error = vcr_client.send(request).await.unwrap_err();
+    assert!(error.to_string().contains("not JSON"));
+
+    Ok(())
+}
+
+/// Rewrites the `Date` header of replayed responses
+#[derive(Debug)]
+struct FreshDate;
+
+impl Filter for FreshDate {
+    fn filter_request(&self, _request: &mut SerializableRequest) {}
+
+    fn filter_response(&self, response: &mut SerializableResponse) {
+        response.headers.insert(
+            "date".to_string(),
+            vec!["Fri, 16 Oct 2026 00:00:00 GMT".to_string()],
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_playback_filters_do_not_touch_cassette() -> Result<(), Box<dyn std::error::Error>> {
+    let mut recorded = ok_response("dated");
+    recorded.headers.insert(
+        "date".to_string(),
+        vec!["Mon, 01 Jan 2024 00:00:00 GMT".to_string()],
+    );
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(get_request("https://example.com/dated"), recorded)
+        .await?;
+
+    let path = std::env::temp_dir().join(format!("vcr-playback-{}.yaml", std::process::id()));
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .with_save_path(&path)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .filter_chain(FilterChain::new().before_playback(Box::new(FreshDate)))
+        .build()
+        .await?;
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/dated")?);
+    let response = vcr_client.send(request).await?;
+    assert_eq!(
+        response.header("date").unwrap().as_str(),
+        "Fri, 16 Oct 2026 00:00:00 GMT"
+    );
+
+    vcr_client.save_cassette().await?;
+    let stored = Cassette::load_from_file(path.clone()).await?;
+    assert_eq!(
+        stored.interactions[0].response.headers["date"],
+        vec!["Mon, 01 Jan 2024 00:00:00 GMT".to_string()]
+    );
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connection_replay_releases_in_recorded_order(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    for path in ["first", "second"] {
+        cassette
+            .record_interaction(
+                get_request(&format!("https://example.com/{path}")),
+                ok_response(path),
+            )
+            .await?;
+    }
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .connection_replay(ConnectionReplay::per_host().recorded_order())
+        .build()
+        .await?;
+
+    let completed = std::sync::Mutex::new(Vec::new());
+    let fetch = |path: &'static str, delay: u64| {
+        let (vcr_client, completed) = (&vcr_client, &completed);
+        async move {
+            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+            let url = Url::parse(&format!("https://example.com/{path}")).unwrap();
+            let mut response = vcr_client
+                .send(http_types::Request::new(Method::Get, url))
+                .await
+                .unwrap();
+            let body = response.body_string().await.unwrap();
+            completed.lock().unwrap().push(body);
+        }
+    };
+
+    // The second request arrives first but is held until the first one is served
+    tokio::join!(fetch("second", 0), fetch("first", 50));
+    assert_eq!(*completed.lock().unwrap(), vec!["first", "second"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_simulated_latency_uses_recorded_duration() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    cassette
+        .record_timed_interaction(
+            get_request("https://example.com/slow"),
+            ok_response("eventually"),
+            time::OffsetDateTime::now_utc(),
+            std::time::Duration::from_millis(200),
+        )
+        .await?;
+    assert_eq!(cassette.interactions[0].elapsed_ms, Some(200));
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .simulate_latency(LatencyMode::Recorded)
+        .build()
+        .await?;
+
+    let started = std::time::Instant::now();
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/slow")?);
+    vcr_client.send(request).await?;
+    assert!(started.elapsed() >= std::time::Duration::from_millis(200));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fault_injector_replaces_selected_interactions(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    for path in ["a", "b", "c"] {
+        cassette
+            .record_interaction(
+                get_request(&format!("https://example.com/{path}")),
+                ok_response("recorded body"),
+            )
+            .await?;
+    }
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .fault_injector(
+            FaultInjector::new()
+                .at_interactions([0], Fault::Status(http_types::StatusCode::TooManyRequests))
+                .at_interactions([1], Fault::TruncateBody)
+                .at_interactions([2], Fault::ConnectionError),
+        )
+        .build()
+        .await?;
+
+    let send = |path: &str| {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        )
+    };
+
+    let response = vcr_client.send(send("a")).await?;
+    assert_eq!(response.status(), 429);
+
+    let mut response = vcr_client.send(send("b")).await?;
+    assert_eq!(response.status(), 200);
+    assert!(response.body_string().await.is_err());
+
+    let error = vcr_client.send(send("c")).await.unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+        Some(std::io::ErrorKind::ConnectionReset)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_response_templating_expands_directives() -> Result<(), Box<dyn std::error::Error>> {
+    let mut response =
+        ok_response(r#"{"id": "{{request.url.param.id}}", "at": "{{now_iso8601}}"}"#);
+    response
+        .headers
+        .insert("X-Request-Id".to_string(), vec!["{{uuid}}".to_string()]);
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(get_request("https://example.com/items?id=7"), response)
+        .await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .response_templating(true)
+        .build()
+        .await?;
+
+    let request =
+        http_types::Request::new(Method::Get, Url::parse("https://example.com/items?id=7")?);
+    let mut response = vcr_client.send(request).await?;
+    assert_ne!(response["X-Request-Id"].as_str(), "{{uuid}}");
+    let body: serde_json::Value = serde_json::from_str(&response.body_string().await?)?;
+    assert_eq!(body["id"], "7");
+    assert!(!body["at"].as_str().unwrap_or_default().contains("{{"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_time_shift_moves_recorded_dates_forward() -> Result<(), Box<dyn std::error::Error>> {
+    let recorded_at = time::OffsetDateTime::now_utc() - time::Duration::days(30);
+    let mut cassette = Cassette::new();
+    cassette
+        .record_timed_interaction(
+            get_request("https://example.com/token"),
+            ok_response(&format!(
+                r#"{{"expires_at": "{}"}}"#,
+                (recorded_at + time::Duration::hours(1))
+                    .format(&time::format_description::well_known::Rfc3339)?
+            )),
+            recorded_at,
+            std::time::Duration::ZERO,
+        )
+        .await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .time_shift(TimeShift::new())
+        .build()
+        .await?;
+
+    let request = http_types::Request::new(Method::Get, Url::parse("https://example.com/token")?);
+    let mut response = vcr_client.send(request).await?;
+    let body: serde_json::Value = serde_json::from_str(&response.body_string().await?)?;
+    let expires_at = time::OffsetDateTime::parse(
+        body["expires_at"].as_str().unwrap_or_default(),
+        &time::format_description::well_known::Rfc3339,
+    )?;
+    assert!(expires_at > time::OffsetDateTime::now_utc());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_scenario_states_pick_between_responses() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = Cassette::new();
+    for (path, body, requires_state, sets_state) in [
+        ("job", "pending", Some("started"), None),
+        ("job/cancel", "cancelling", None, Some("cancelled")),
+        ("job", "done", Some("finished"), None),
+        ("job", "cancelled", Some("cancelled"), None),
+    ] {
+        cassette
+            .record_interaction(
+                get_request(&format!("https://example.com/{path}")),
+                ok_response(body),
+            )
+            .await?;
+        let interaction = cassette.interactions.last_mut().unwrap();
+        interaction.requires_state = requires_state.map(str::to_string);
+        interaction.sets_state = sets_state.map(str::to_string);
+    }
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+    let body = |path: &'static str| {
+        let request = http_types::Request::new(
+            Method::Get,
+            Url::parse(&format!("https://example.com/{path}")).unwrap(),
+        );
+        let vcr_client = &vcr_client;
+        async move { vcr_client.send(request).await?.body_string().await }
+    };
+
+    assert_eq!(body("job").await?, "pending");
+    let before_cancel = vcr_client.snapshot_replay_state().await;
+    assert_eq!(before_cancel.scenario_state(), "started");
+    assert_eq!(body("job/cancel").await?, "cancelling");
+    assert_eq!(vcr_client.scenario_state(), "cancelled");
+    // The "done" interaction comes first, but needs another state
+    assert_eq!(body("job").await?, "cancelled");
+
+    vcr_client.reset_replay_state().await;
+    vcr_client.set_scenario_state("finished");
+    assert_eq!(body("job").await?, "done");
+    assert!(body("job").await.is_err());
+
+    // Recording while a state is set stores it as the state the interaction requires
+    let recorder = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("job").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .persistence(Persistence::InMemory)
+        .build()
+        .await?;
+    recorder.set_scenario_state("finished");
+    recorder
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/job")?,
+        ))
+        .await?;
+    assert_eq!(
+        recorder.interactions().await[0].requires_state.as_deref(),
+        Some("finished")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_state_can_be_reset_and_restored() -> Result<(), Box<dyn std::error::Error>> {
+    let vcr_client = VcrClientBuilder::from_cassette(single_interaction_cassette("data").await?)
+        .inner_client(Box::new(NoOpClient::new()))
+        .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+    let request =
+        || http_types::Request::new(Method::Get, Url::parse("https://example.com/data").unwrap());
+
+    let fresh = vcr_client.snapshot_replay_state().await;
+    vcr_client.send(request()).await?;
+    let consumed = vcr_client.snapshot_replay_state().await;
+    assert!(consumed.used_interactions().contains(&0));
+    assert!(vcr_client.send(request()).await.is_err());
+
+    vcr_client.reset_replay_state().await;
+    vcr_client.send(request()).await?;
+
+    vcr_client.restore_replay_state(fresh).await;
+    vcr_client.send(request()).await?;
+    vcr_client.restore_replay_state(consumed).await;
+    assert!(vcr_client.send(request()).await.is_err());
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct FreshRequestIds(std::sync::atomic::AtomicUsize);
+
+impl http_client_vcr::ReplayTransformer for FreshRequestIds {
+    fn before_playback(
+        &self,
+        response: &mut http_types::Response,
+        _interaction: &http_client_vcr::Interaction,
+    ) {
+        let id = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        response
+            .insert_header("x-request-id", format!("req-{id}"))
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_replay_transformer_adjusts_responses_without_touching_cassette(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = single_interaction_cassette("users").await?;
+    cassette
+        .record_interaction(
+            get_request("https://example.com/users"),
+            ok_response("users"),
+        )
+        .await?;
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .replay_transformer(Box::new(FreshRequestIds::default()))
+        .build()
+        .await?;
+
+    let url = Url::parse("https://example.com/users")?;
+    for expected in ["req-0", "req-1"] {
+        let mut response = vcr_client
+            .send(http_types::Request::new(Method::Get, url.clone()))
+            .await?;
+        assert_eq!(response["x-request-id"], expected);
+        assert_eq!(response.body_string().await?, "users");
+    }
+
+    let interactions = vcr_client.interactions().await;
+    assert!(interactions
+        .iter()
+        .all(|interaction| !interaction.response.headers.contains_key("x-request-id")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_interactions_are_tagged_and_replay_can_be_scoped_to_a_tag(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut upstream = Cassette::new();
+    for body in ["login", "admin"] {
+        upstream
+            .record_interaction(get_request("https://example.com/users"), ok_response(body))
+            .await?;
+    }
+    let recorder = VcrClientBuilder::in_memory()
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .build()
+        .await?;
+    let url = Url::parse("https://example.com/users")?;
+    let mut request = http_types::Request::new(Method::Get, url.clone());
+    request.insert_header(http_client_vcr::TAGS_HEADER, "login_flow, smoke")?;
+    recorder.send(request).await?;
+    recorder.scope_to_tag("admin_flow");
+    recorder
+        .send(http_types::Request::new(Method::Get, url.clone()))
+        .await?;
+
+    let cassette = recorder.eject_cassette().await;
+    assert_eq!(cassette.interactions[0].tags, ["login_flow", "smoke"]);
+    assert!(!cassette.interactions[0]
+        .request
+        .headers
+        .contains_key(http_client_vcr::TAGS_HEADER));
+    assert_eq!(cassette.interactions[1].tags, ["admin_flow"]);
+    let tagged: Vec<usize> = cassette
+        .interactions_tagged("admin_flow")
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(tagged, [1]);
+
+    let replayer = VcrClientBuilder::from_cassette(cassette)
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        .scope_to_tag("admin_flow")
+        .build()
+        .await?;
+    let mut response = replayer
+        .send(http_types::Request::new(Method::Get, url.clone()))
+        .await?;
+    assert_eq!(response.body_string().await?, "admin");
+    // The login interaction is out of scope
+    assert!(replayer
+        .send(http_types::Request::new(Method::Get, url.clone()))
+        .await
+        .is_err());
+
+    replayer.clear_tag_scope();
+    let mut response = replayer
+        .send(http_types::Request::new(Method::Get, url))
+        .await?;
+    assert_eq!(response.body_string().await?, "login");
+    Ok(())
+}
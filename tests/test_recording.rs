@@ -0,0 +1,632 @@
+#![cfg(feature = "fs")]
+
+mod common;
+
+use common::{get_request, ok_response};
+use http_client::HttpClient;
+use http_client_vcr::{
+    Cassette, CassetteLimits, ContentEncoding, DefaultMatcher, FilterChain, LockstepClient,
+    NoOpClient, Persistence, VcrClientBuilder, VcrMode,
+};
+use http_types::{Error, Method, Url};
+
+#[tokio::test]
+async fn test_environment_snapshot_round_trips() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-env-{}.yaml", std::process::id()));
+    std::env::set_var("VCR_BUILDER_TEST_BASE_URL", "https://staging.example.com");
+
+    let mut cassette = Cassette::new().with_path(path.clone());
+    cassette.record_environment(http_client_vcr::capture_environment([
+        "VCR_BUILDER_TEST_BASE_URL",
+        "VCR_BUILDER_TEST_SECRET",
+    ]));
+    cassette.save_to_file().await?;
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+
+    let environment = vcr_client.recorded_environment().await;
+    assert_eq!(
+        environment
+            .get("VCR_BUILDER_TEST_BASE_URL")
+            .map(String::as_str),
+        Some("https://staging.example.com")
+    );
+    assert_eq!(environment.len(), 1);
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_strict_sanitization_blocks_leaked_secrets() -> Result<(), Box<dyn std::error::Error>>
+{
+    let path = std::env::temp_dir().join(format!("vcr-strict-{}.yaml", std::process::id()));
+
+    async fn leaky_cassette() -> Result<Cassette, Error> {
+        let mut leaked = get_request("https://example.com/me");
+        leaked.headers.insert(
+            "Authorization".to_string(),
+            vec!["Bearer live-token".to_string()],
+        );
+        let mut cassette = Cassette::new();
+        cassette
+            .record_interaction(get_request("https://example.com/public"), ok_response("ok"))
+            .await?;
+        cassette
+            .record_interaction(leaked, ok_response("me"))
+            .await?;
+        Ok(cassette)
+    }
+
+    let strict_client = VcrClientBuilder::from_cassette(leaky_cassette().await?)
+        .with_save_path(&path)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .strict_sanitization(true)
+        .build()
+        .await?;
+    assert!(strict_client.save_cassette().await.is_err());
+    assert!(!path.exists());
+
+    let dropping_client = VcrClientBuilder::from_cassette(leaky_cassette().await?)
+        .with_save_path(&path)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .drop_unsanitized_interactions()
+        .build()
+        .await?;
+    let get = |url: &str| http_types::Request::new(Method::Get, Url::parse(url).unwrap());
+    let mut response = dropping_client
+        .send(get("https://example.com/public"))
+        .await?;
+    assert_eq!(response.body_string().await?, "ok");
+    dropping_client.save_cassette().await?;
+    let stored = Cassette::load_from_file(path.clone()).await?;
+    assert_eq!(stored.interactions.len(), 1);
+    assert_eq!(
+        stored.interactions[0].request.url,
+        "https://example.com/public"
+    );
+
+    // Only the saved copy loses the interaction, so replay and stats are unaffected
+    let mut response = dropping_client.send(get("https://example.com/me")).await?;
+    assert_eq!(response.body_string().await?, "me");
+    let stats = dropping_client.playback_stats().await;
+    assert_eq!(stats.play_counts, vec![1, 1]);
+    assert!(stats.unused.is_empty());
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_record_if_skips_excluded_requests() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-record-if-{}.yaml", std::process::id()));
+
+    // Stands in for the real API
+    let mut upstream = Cassette::new();
+    upstream
+        .record_interaction(
+            get_request("https://example.com/healthz"),
+            ok_response("ok"),
+        )
+        .await?;
+    upstream
+        .record_interaction(get_request("https://example.com/api"), ok_response("data"))
+        .await?;
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .record_if(|req| !req.url.ends_with("/healthz"))
+        .build()
+        .await?;
+
+    for path in ["healthz", "api"] {
+        let url = Url::parse(&format!("https://example.com/{path}"))?;
+        vcr_client
+            .send(http_types::Request::new(Method::Get, url))
+            .await?;
+    }
+    vcr_client.save_cassette().await?;
+
+    let stored = Cassette::load_from_file(path.clone()).await?;
+    assert_eq!(stored.interactions.len(), 1);
+    assert_eq!(
+        stored.interactions[0].request.url,
+        "https://example.com/api"
+    );
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recorded_response_is_returned_unchanged() -> Result<(), Box<dyn std::error::Error>> {
+    let body = "x".repeat(1 << 20);
+    let mut response = ok_response(&body);
+    response.headers.insert(
+        "content-type".to_string(),
+        vec!["application/octet-stream".to_string()],
+    );
+    let mut upstream = Cassette::new();
+    upstream
+        .record_interaction(get_request("https://example.com/large"), response)
+        .await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .persistence(Persistence::InMemory)
+        .build()
+        .await?;
+    let mut returned = vcr_client
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/large")?,
+        ))
+        .await?;
+
+    assert_eq!(
+        returned["content-type"].as_str(),
+        "application/octet-stream"
+    );
+    assert_eq!(returned.len(), Some(body.len() as u64));
+
+    // The recording holds the same buffer as the returned response until it is read
+    let recorded = vcr_client.eject_cassette().await;
+    let recorded_body = recorded.interactions[0].response.body.as_ref().unwrap();
+    assert!(recorded_body.is_shared());
+    assert_eq!(returned.body_string().await?, body);
+    assert!(!recorded_body.is_shared());
+    assert_eq!(recorded_body.as_str(), body);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_gzip_responses_are_stored_decoded() -> Result<(), Box<dyn std::error::Error>> {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"compressed data")?;
+    let compressed = encoder.finish()?;
+    let mut response = ok_response("");
+    response.set_bytes(compressed.clone());
+    response
+        .headers
+        .insert("content-encoding".to_string(), vec!["gzip".to_string()]);
+    let mut upstream = Cassette::new();
+    upstream
+        .record_interaction(get_request("https://example.com/data"), response)
+        .await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .persistence(Persistence::InMemory)
+        .content_encoding(ContentEncoding::Decode)
+        .build()
+        .await?;
+    let mut returned = vcr_client
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/data")?,
+        ))
+        .await?;
+
+    // The caller gets the response as sent, and the cassette a readable one
+    assert_eq!(returned["content-encoding"].as_str(), "gzip");
+    assert_eq!(returned.body_bytes().await?, compressed);
+    let recorded = &vcr_client.interactions().await[0].response;
+    assert_eq!(recorded.body.as_deref(), Some("compressed data"));
+    assert!(!recorded.headers.contains_key("content-encoding"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_version_round_trips_and_can_be_matched() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut cassette = Cassette::new();
+    let mut h2_request = get_request("https://example.com/h2");
+    h2_request.version = "Some(Http2_0)".to_string();
+    let mut h2_response = ok_response("over h2");
+    h2_response.version = "Some(Http2_0)".to_string();
+    cassette.record_interaction(h2_request, h2_response).await?;
+
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .matcher(Box::new(DefaultMatcher::new().with_version(true)))
+        .build()
+        .await?;
+
+    let mut request = http_types::Request::new(Method::Get, Url::parse("https://example.com/h2")?);
+    request.set_version(Some(http_types::Version::Http2_0));
+    let response = vcr_client.send(request).await?;
+    assert_eq!(response.version(), Some(http_types::Version::Http2_0));
+
+    let mut request = http_types::Request::new(Method::Get, Url::parse("https://example.com/h2")?);
+    request.set_version(Some(http_types::Version::Http1_1));
+    assert!(vcr_client.send(request).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_redacted_cookies_match_on_names() -> Result<(), Box<dyn std::error::Error>> {
+    let mut recorded = get_request("https://example.com/account");
+    recorded.headers.insert(
+        "cookie".to_string(),
+        vec!["session=abc123; theme=dark".to_string()],
+    );
+    recorded.redact_cookie_values("[FILTERED]");
+    assert_eq!(
+        recorded.headers["cookie"],
+        ["session=[FILTERED]; theme=[FILTERED]"]
+    );
+    let names: Vec<_> = recorded.cookies().into_iter().map(|c| c.name).collect();
+    assert_eq!(names, ["session", "theme"]);
+
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(recorded, ok_response("account"))
+        .await?;
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .mode(VcrMode::Replay)
+        .matcher(Box::new(DefaultMatcher::new().with_cookie_names_only(true)))
+        .build()
+        .await?;
+
+    let url = Url::parse("https://example.com/account")?;
+    let mut request = http_types::Request::new(Method::Get, url.clone());
+    request.insert_header("cookie", "theme=light; session=xyz789")?;
+    assert_eq!(
+        vcr_client.send(request).await?.body_string().await?,
+        "account"
+    );
+
+    let mut request = http_types::Request::new(Method::Get, url);
+    request.insert_header("cookie", "theme=light")?;
+    assert!(vcr_client.send(request).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_redirect_hops_are_recorded_and_replayed() -> Result<(), Box<dyn std::error::Error>> {
+    let mut redirect = ok_response("");
+    redirect.status = 302;
+    redirect
+        .headers
+        .insert("location".to_string(), vec!["/new".to_string()]);
+    let mut upstream = Cassette::new();
+    upstream
+        .record_interaction(get_request("https://example.com/old"), redirect)
+        .await?;
+    upstream
+        .record_interaction(get_request("https://example.com/new"), ok_response("moved"))
+        .await?;
+
+    let recording = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .persistence(Persistence::InMemory)
+        .follow_redirects(5)
+        .build()
+        .await?;
+    let old = Url::parse("https://example.com/old")?;
+    let mut response = recording
+        .send(http_types::Request::new(Method::Get, old.clone()))
+        .await?;
+    assert_eq!(response.body_string().await?, "moved");
+
+    let interactions = recording.interactions().await;
+    let hops: Vec<_> = interactions
+        .iter()
+        .map(|i| (i.request.url.as_str(), i.response.status))
+        .collect();
+    assert_eq!(
+        hops,
+        [
+            ("https://example.com/old", 302),
+            ("https://example.com/new", 200)
+        ]
+    );
+
+    // Without following, replay hands back the recorded redirect itself
+    let replaying = VcrClientBuilder::from_cassette(Cassette {
+        interactions,
+        ..Cassette::new()
+    })
+    .inner_client(Box::new(NoOpClient::new()))
+    .mode(VcrMode::Replay)
+    .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+    .build()
+    .await?;
+    let response = replaying
+        .send(http_types::Request::new(Method::Get, old))
+        .await?;
+    assert_eq!(response.status(), 302);
+    assert_eq!(response["location"].as_str(), "/new");
+    Ok(())
+}
+
+/// Fails every request like a client whose connection timed out
+#[derive(Debug)]
+struct TimeoutClient;
+
+#[async_trait::async_trait]
+impl HttpClient for TimeoutClient {
+    async fn send(
+        &self,
+        _req: http_client::Request,
+    ) -> Result<http_client::Response, http_client::Error> {
+        Err(http_client::Error::new(
+            504,
+            std::io::Error::new(std::io::ErrorKind::TimedOut, "operation timed out"),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn test_transport_errors_are_recorded_and_replayed() -> Result<(), Box<dyn std::error::Error>>
+{
+    let url = Url::parse("https://example.com/slow")?;
+    let recording = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(TimeoutClient))
+        .mode(VcrMode::Record)
+        .persistence(Persistence::InMemory)
+        .record_errors(true)
+        .build()
+        .await?;
+    assert!(recording
+        .send(http_types::Request::new(Method::Get, url.clone()))
+        .await
+        .is_err());
+
+    // The cassette stores the error in place of a response
+    let cassette = Cassette {
+        interactions: recording.interactions().await,
+        ..Cassette::new()
+    };
+    let yaml = serde_yaml::to_string(&cassette.interactions)?;
+    assert!(yaml.contains("kind: timeout"));
+    assert!(!yaml.contains("response:"));
+    let interactions: Vec<http_client_vcr::Interaction> = serde_yaml::from_str(&yaml)?;
+
+    let replaying = VcrClientBuilder::from_cassette(Cassette {
+        interactions,
+        ..Cassette::new()
+    })
+    .inner_client(Box::new(NoOpClient::new()))
+    .mode(VcrMode::Replay)
+    .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+    .build()
+    .await?;
+    let error = replaying
+        .send(http_types::Request::new(Method::Get, url))
+        .await
+        .unwrap_err();
+    assert_eq!(error.status(), 504);
+    assert_eq!(
+        error.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+        Some(std::io::ErrorKind::TimedOut)
+    );
+    Ok(())
+}
+
+/// Serves a short Server-Sent Events stream
+#[derive(Debug)]
+struct EventStreamClient;
+
+#[async_trait::async_trait]
+impl HttpClient for EventStreamClient {
+    async fn send(
+        &self,
+        _req: http_client::Request,
+    ) -> Result<http_client::Response, http_client::Error> {
+        let mut response = http_client::Response::new(200);
+        response.insert_header("content-type", "text/event-stream")?;
+        response.set_body("data: token=secret\n\ndata: done\n\n");
+        Ok(response)
+    }
+}
+
+#[tokio::test]
+async fn test_event_streams_are_recorded_as_chunks_and_replayed(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = Url::parse("https://example.com/events")?;
+    let filters = FilterChain::new().add_filter(Box::new(
+        http_client_vcr::BodyFilter::new().replace_regex("token=\\w+", "token=[FILTERED]")?,
+    ));
+    let recording = VcrClientBuilder::from_cassette(Cassette::new())
+        .inner_client(Box::new(EventStreamClient))
+        .mode(VcrMode::Record)
+        .persistence(Persistence::InMemory)
+        .filter_chain(filters)
+        .build()
+        .await?;
+    let mut response = recording
+        .send(http_types::Request::new(Method::Get, url.clone()))
+        .await?;
+    // The caller gets the stream unfiltered, as it arrives
+    assert_eq!(
+        response.body_string().await?,
+        "data: token=secret\n\ndata: done\n\n"
+    );
+
+    let interactions = recording.interactions().await;
+    let chunks: Vec<_> = interactions[0]
+        .stream
+        .iter()
+        .flatten()
+        .map(|chunk| chunk.data.as_str())
+        .collect();
+    assert_eq!(chunks, ["data: token=[FILTERED]\n\n", "data: done\n\n"]);
+    assert!(interactions[0].response.body.is_none());
+
+    let replaying = VcrClientBuilder::from_cassette(Cassette {
+        interactions,
+        ..Cassette::new()
+    })
+    .inner_client(Box::new(NoOpClient::new()))
+    .mode(VcrMode::Replay)
+    .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+    .stream_replay_speed(f64::INFINITY)
+    .build()
+    .await?;
+    let mut response = replaying
+        .send(http_types::Request::new(Method::Get, url))
+        .await?;
+    assert_eq!(
+        response
+            .content_type()
+            .map(|mime| mime.essence().to_string()),
+        Some("text/event-stream".to_string())
+    );
+    assert_eq!(
+        response.body_string().await?,
+        "data: token=[FILTERED]\n\ndata: done\n\n"
+    );
+    Ok(())
+}
+
+#[derive(Debug)]
+struct SkipHealthChecks;
+
+impl http_client_vcr::RecordHook for SkipHealthChecks {
+    fn before_record(
+        &self,
+        mut interaction: http_client_vcr::Interaction,
+    ) -> Option<http_client_vcr::Interaction> {
+        if interaction.request.url.ends_with("/healthz") {
+            return None;
+        }
+        interaction
+            .metadata
+            .insert("suite".to_string(), "billing".to_string());
+        Some(interaction)
+    }
+}
+
+#[tokio::test]
+async fn test_record_hook_drops_and_annotates_interactions(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut upstream = Cassette::new();
+    for path in ["healthz", "users"] {
+        upstream
+            .record_interaction(
+                get_request(&format!("https://example.com/{path}")),
+                ok_response(path),
+            )
+            .await?;
+    }
+
+    let vcr_client = VcrClientBuilder::in_memory()
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .record_hook(Box::new(SkipHealthChecks))
+        .build()
+        .await?;
+    for path in ["healthz", "users"] {
+        let url = Url::parse(&format!("https://example.com/{path}"))?;
+        let mut response = vcr_client
+            .send(http_types::Request::new(Method::Get, url))
+            .await?;
+        // A dropped interaction's response is still returned
+        assert_eq!(response.body_string().await?, path);
+    }
+
+    let interactions = vcr_client.interactions().await;
+    assert_eq!(interactions.len(), 1);
+    assert_eq!(interactions[0].request.url, "https://example.com/users");
+    assert_eq!(interactions[0].metadata["suite"], "billing");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_limits_truncate_bodies_and_cap_interactions() -> Result<(), Box<dyn std::error::Error>>
+{
+    let path = std::env::temp_dir().join(format!("vcr-limits-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut upstream = Cassette::new();
+    for (url, body) in [
+        ("https://example.com/download", "x".repeat(100)),
+        ("https://example.com/a", "a".to_string()),
+        ("https://example.com/b", "b".to_string()),
+    ] {
+        upstream
+            .record_interaction(get_request(url), ok_response(&body))
+            .await?;
+    }
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .limits(
+            CassetteLimits::new()
+                .max_body_size(10)
+                .max_interactions(2)
+                .fail_when_exceeded(),
+        )
+        .save_on_drop(false)
+        .build()
+        .await?;
+
+    let mut bodies = Vec::new();
+    for path in ["download", "a", "b"] {
+        let url = Url::parse(&format!("https://example.com/{path}"))?;
+        let mut response = vcr_client
+            .send(http_types::Request::new(Method::Get, url))
+            .await?;
+        bodies.push(response.body_string().await?);
+    }
+    // The caller still gets the full responses
+    assert_eq!(bodies[0].len(), 100);
+
+    let interactions = vcr_client.interactions().await;
+    assert_eq!(interactions.len(), 2);
+    assert_eq!(
+        interactions[0].response.body.as_deref(),
+        Some("xxxxxxxxxx[TRUNCATED 90 bytes]")
+    );
+
+    let error = vcr_client.save_cassette().await.unwrap_err().to_string();
+    assert!(error.contains("over the 10-byte limit"), "{error}");
+    assert!(error.contains("maximum of 2 interactions"), "{error}");
+    assert!(!path.exists());
+
+    // A fresh cassette starts with nothing over the limits
+    vcr_client
+        .insert_cassette(Cassette::new().with_path(path.clone()))
+        .await;
+    vcr_client.save_cassette().await?;
+    assert!(path.exists());
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use http_client_vcr::{NoOpClient, SharedCassetteAction, VcrClientBuilder, VcrMode};
+
+#[tokio::test]
+async fn test_second_recorder_of_a_cassette_is_rejected() -> Result<(), Box<dyn std::error::Error>>
+{
+    let path = std::env::temp_dir().join(format!("vcr-shared-{}.yaml", std::process::id()));
+    let recorder = || {
+        VcrClientBuilder::new(&path)
+            .inner_client(Box::new(NoOpClient::new()))
+            .mode(VcrMode::Record)
+    };
+
+    let first = recorder().build().await?;
+    let error = recorder().build().await.unwrap_err().to_string();
+    assert!(error.contains("already being recorded"), "{error}");
+
+    // Replaying the cassette doesn't write it
+    let replayer = recorder().mode(VcrMode::Replay).build().await?;
+    let second = recorder()
+        .on_shared_cassette(SharedCassetteAction::Warn)
+        .build()
+        .await?;
+
+    drop((first, second, replayer));
+    recorder().build().await?;
+    Ok(())
+}
@@ -0,0 +1,212 @@
+#![cfg(feature = "s3")]
+
+use http_client::HttpClient;
+use http_client_vcr::{
+    Cassette, CassetteStorage, DefaultMatcher, Headers, LockstepClient, S3Storage,
+    SerializableRequest, SerializableResponse, VcrClientBuilder, VcrMode,
+};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+type Objects = Arc<Mutex<HashMap<String, String>>>;
+
+/// Serve GET, PUT and ListObjectsV2 for one bucket, counting full downloads
+fn fake_s3(objects: Objects, downloads: Arc<Mutex<usize>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            handle(stream, &objects, &downloads);
+        }
+    });
+    format!("http://{address}")
+}
+
+fn handle(mut stream: TcpStream, objects: &Objects, downloads: &Mutex<usize>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').unwrap();
+        headers.insert(name.to_lowercase(), value.trim().to_string());
+    }
+    let mut body = vec![
+        0;
+        headers
+            .get("content-length")
+            .map_or(0, |l| l.parse().unwrap())
+    ];
+    reader.read_exact(&mut body).unwrap();
+    assert!(headers["authorization"].starts_with("AWS4-HMAC-SHA256 Credential=test-key/"));
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap();
+    let target = parts.next().unwrap();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let key = path.trim_start_matches("/fixtures").trim_start_matches('/');
+    let mut objects = objects.lock().unwrap();
+
+    let (status, etag, body) = match method {
+        "PUT" => {
+            objects.insert(key.to_string(), String::from_utf8(body).unwrap());
+            (
+                "200 OK",
+                Some(format!("\"{}\"", objects[key].len())),
+                String::new(),
+            )
+        }
+        "GET" if key.is_empty() => {
+            let prefix = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("prefix="))
+                .unwrap_or_default()
+                .replace("%2F", "/");
+            let mut keys: Vec<_> = objects.keys().filter(|k| k.starts_with(&prefix)).collect();
+            keys.sort();
+            let contents: String = keys
+                .iter()
+                .map(|k| format!("<Contents><Key>{k}</Key></Contents>"))
+                .collect();
+            (
+                "200 OK",
+                None,
+                format!("<ListBucketResult>{contents}</ListBucketResult>"),
+            )
+        }
+        _ => match objects.get(key) {
+            None => ("404 Not Found", None, String::new()),
+            Some(object) => {
+                let etag = format!("\"{}\"", object.len());
+                if headers.get("if-none-match") == Some(&etag) {
+                    ("304 Not Modified", Some(etag), String::new())
+                } else {
+                    *downloads.lock().unwrap() += 1;
+                    ("200 OK", Some(etag), object.clone())
+                }
+            }
+        },
+    };
+
+    let etag = etag
+        .map(|etag| format!("ETag: {etag}\r\n"))
+        .unwrap_or_default();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n{etag}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+}
+
+async fn cassette() -> Result<Cassette, http_client::Error> {
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(
+            SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://example.com/data".to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: Some("stored".into()),
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+        )
+        .await?;
+    Ok(cassette)
+}
+
+#[tokio::test]
+async fn test_s3_storage_round_trips_and_caches() -> Result<(), Box<dyn std::error::Error>> {
+    let objects = Objects::default();
+    let downloads = Arc::new(Mutex::new(0));
+    let endpoint = fake_s3(objects.clone(), downloads.clone());
+    let cache_dir = std::env::temp_dir().join(format!("vcr-s3-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let storage = |endpoint: &str| {
+        S3Storage::new("fixtures", "team/cassettes")
+            .with_endpoint(endpoint)
+            .with_credentials("test-key", "test-secret")
+            .with_cache_dir(&cache_dir)
+    };
+
+    let s3 = storage(&endpoint);
+    let path = Path::new("suite/data.yaml");
+    s3.save(path, &cassette().await?)?;
+    assert!(objects
+        .lock()
+        .unwrap()
+        .contains_key("team/cassettes/suite/data.yaml"));
+    assert_eq!(s3.list(Path::new("suite"))?, vec![PathBuf::from(path)]);
+    assert!(s3.load(Path::new("suite/missing.yaml"))?.is_none());
+
+    // The copy cached on save is still current, so nothing is downloaded
+    let loaded = s3.load(path)?.unwrap();
+    assert_eq!(
+        loaded.interactions[0].response.body.as_deref(),
+        Some("stored")
+    );
+    assert_eq!(*downloads.lock().unwrap(), 0);
+
+    // Without the bucket, the cached copy is used
+    let unreachable = {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        format!("http://{}", listener.local_addr()?)
+    };
+    let offline = storage(&unreachable).load(path)?.unwrap();
+    assert_eq!(offline.interactions.len(), 1);
+
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_client_records_into_s3() -> Result<(), Box<dyn std::error::Error>> {
+    let objects = Objects::default();
+    let endpoint = fake_s3(objects.clone(), Arc::default());
+    let cache_dir = std::env::temp_dir().join(format!("vcr-s3-client-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let storage = S3Storage::new("fixtures", "team/cassettes")
+        .with_endpoint(&endpoint)
+        .with_credentials("test-key", "test-secret")
+        .with_cache_dir(&cache_dir);
+
+    let upstream = LockstepClient::new(cassette().await?)
+        .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![])));
+    let recorder = VcrClientBuilder::new("suite/client.yaml")
+        .storage(Box::new(storage))
+        .inner_client(Box::new(upstream))
+        .mode(VcrMode::Once)
+        .build()
+        .await?;
+    recorder
+        .send(http_types::Request::new(
+            http_types::Method::Get,
+            http_types::Url::parse("https://example.com/data")?,
+        ))
+        .await?;
+    assert!(recorder.finalize().await?.saved);
+    assert!(objects
+        .lock()
+        .unwrap()
+        .contains_key("team/cassettes/suite/client.yaml"));
+
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    Ok(())
+}
@@ -0,0 +1,352 @@
+#![cfg(feature = "fs")]
+
+mod common;
+
+use common::{get_request, ok_response, single_interaction_cassette};
+use http_client::HttpClient;
+use http_client_vcr::{
+    Cassette, DefaultMatcher, LockstepClient, MissBehavior, NoOpClient, Persistence,
+    VcrClientBuilder, VcrMode,
+};
+use http_types::{Method, Url};
+
+#[tokio::test]
+async fn test_auto_save_flushes_while_recording() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-auto-save-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut upstream = Cassette::new();
+    for page in ["one", "two", "three"] {
+        upstream
+            .record_interaction(
+                get_request(&format!("https://example.com/{page}")),
+                ok_response(page),
+            )
+            .await?;
+    }
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .auto_save_every(2)
+        .build()
+        .await?;
+
+    let mut saved = Vec::new();
+    for page in ["one", "two", "three"] {
+        let url = Url::parse(&format!("https://example.com/{page}"))?;
+        vcr_client
+            .send(http_types::Request::new(Method::Get, url))
+            .await?;
+        saved.push(
+            Cassette::load_from_file(path.clone())
+                .await
+                .map_or(0, |cassette| cassette.interactions.len()),
+        );
+    }
+    assert_eq!(saved, vec![0, 2, 2]);
+
+    drop(vcr_client);
+    assert_eq!(
+        Cassette::load_from_file(path.clone())
+            .await?
+            .interactions
+            .len(),
+        3
+    );
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_finalize_saves_and_reports_stats() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-finalize-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let recorder = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("data").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Once)
+        .save_on_drop(false)
+        .build()
+        .await?;
+    let request =
+        || http_types::Request::new(Method::Get, Url::parse("https://example.com/data").unwrap());
+    recorder.send(request()).await?;
+    let stats = recorder.finalize().await?;
+    assert!(stats.saved);
+    assert_eq!((stats.interactions, stats.recorded), (1, 1));
+    assert!(stats.unused.is_empty());
+    assert!(path.exists());
+    drop(recorder);
+
+    let mut cassette = Cassette::load_from_file(path.clone()).await?;
+    cassette
+        .record_interaction(
+            get_request("https://example.com/other"),
+            ok_response("other"),
+        )
+        .await?;
+    let replayer = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(NoOpClient::new()))
+        .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        .mode(VcrMode::Replay)
+        .save_on_drop(false)
+        .build()
+        .await?;
+    replayer.send(request()).await?;
+    let stats = replayer.finalize().await?;
+    assert!(!stats.saved);
+    assert_eq!((stats.replayed, stats.recorded), (1, 0));
+    assert_eq!(stats.unused, vec![1]);
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_playback_stats_count_plays_and_find_unused_interactions(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = single_interaction_cassette("users").await?;
+    cassette
+        .record_interaction(
+            get_request("https://example.com/stale"),
+            ok_response("stale"),
+        )
+        .await?;
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .build()
+        .await?;
+
+    let request = || {
+        http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/users").unwrap(),
+        )
+    };
+    vcr_client.send(request()).await?;
+    // Interactions replayed again after a reset count each time
+    vcr_client.reset_replay_state().await;
+    vcr_client.send(request()).await?;
+
+    let stats = vcr_client.playback_stats().await;
+    assert_eq!(stats.play_counts, vec![2, 0]);
+    assert_eq!(stats.unused, vec![1]);
+    let unused = vcr_client.unused_interactions().await;
+    assert_eq!(unused.len(), 1);
+    assert_eq!(unused[0].request.url, "https://example.com/stale");
+
+    let panic = tokio::spawn(async move { vcr_client.assert_all_played().await })
+        .await
+        .unwrap_err()
+        .into_panic();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(
+        message.contains("#1: GET https://example.com/stale"),
+        "{message}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_playback_stats_start_over_with_each_cassette(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let vcr_client = VcrClientBuilder::from_cassette(single_interaction_cassette("users").await?)
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("new").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .on_miss(MissBehavior::Forward)
+        .build()
+        .await?;
+
+    for path in ["users", "new"] {
+        let url = Url::parse(&format!("https://example.com/{path}"))?;
+        vcr_client
+            .send(http_types::Request::new(Method::Get, url))
+            .await?;
+    }
+    let stats = vcr_client.playback_stats().await;
+    assert_eq!(stats.play_counts, vec![1]);
+    assert_eq!(stats.passed_through.len(), 1);
+
+    vcr_client
+        .insert_cassette(single_interaction_cassette("orders").await?)
+        .await;
+    let stats = vcr_client.playback_stats().await;
+    assert_eq!(stats.play_counts, vec![0]);
+    assert!(stats.passed_through.is_empty());
+
+    let url = Url::parse("https://example.com/orders")?;
+    vcr_client
+        .send(http_types::Request::new(Method::Get, url))
+        .await?;
+    let stats = vcr_client.playback_stats().await;
+    assert_eq!(stats.play_counts, vec![1]);
+    assert!(stats.passed_through.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_strict_playback_fails_finalize_on_unplayed_and_forwarded(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cassette = single_interaction_cassette("users").await?;
+    cassette
+        .record_interaction(
+            get_request("https://example.com/stale"),
+            ok_response("stale"),
+        )
+        .await?;
+    let vcr_client = VcrClientBuilder::from_cassette(cassette)
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("new").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Replay)
+        .persistence(Persistence::InMemory)
+        .on_miss(MissBehavior::Forward)
+        .strict_playback(true)
+        .build()
+        .await?;
+
+    for path in ["users", "new"] {
+        let url = Url::parse(&format!("https://example.com/{path}"))?;
+        let mut response = vcr_client
+            .send(http_types::Request::new(Method::Get, url))
+            .await?;
+        assert_eq!(response.body_string().await?, path);
+    }
+    assert_eq!(
+        vcr_client.playback_stats().await.passed_through,
+        vec!["GET https://example.com/new".to_string()]
+    );
+
+    let error = vcr_client.finalize().await.unwrap_err().to_string();
+    assert!(
+        error.contains("#1: GET https://example.com/stale"),
+        "{error}"
+    );
+    assert!(error.contains("  GET https://example.com/new"), "{error}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_background_saves_are_joined_by_flush() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vcr-background-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut upstream = Cassette::new();
+    for page in ["one", "two"] {
+        upstream
+            .record_interaction(
+                get_request(&format!("https://example.com/{page}")),
+                ok_response(page),
+            )
+            .await?;
+    }
+
+    let vcr_client = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .auto_save_every(1)
+        .background_save(true)
+        .save_on_drop(false)
+        .build()
+        .await?;
+
+    for page in ["one", "two"] {
+        let url = Url::parse(&format!("https://example.com/{page}"))?;
+        vcr_client
+            .send(http_types::Request::new(Method::Get, url))
+            .await?;
+    }
+    vcr_client.flush().await?;
+    let stored = Cassette::load_from_file(path.clone()).await?;
+    assert_eq!(stored.interactions.len(), 2);
+
+    std::fs::remove_file(&path)?;
+    vcr_client.save_in_background().await?.flush().await?;
+    assert!(path.exists());
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_event_handlers_see_records_replays_misses_and_saves(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::VcrEvent;
+    use std::sync::{Arc, Mutex};
+
+    let path = std::env::temp_dir().join(format!("vcr-events-{}.yaml", std::process::id()));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let collect = |events: &Arc<Mutex<Vec<VcrEvent>>>| {
+        let events = Arc::clone(events);
+        move |event| events.lock().unwrap().push(event)
+    };
+
+    let recording = VcrClientBuilder::new(&path)
+        .inner_client(Box::new(
+            LockstepClient::new(single_interaction_cassette("users").await?)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .on_event(collect(&events))
+        .build()
+        .await?;
+    let url = Url::parse("https://example.com/users")?;
+    recording
+        .send(http_types::Request::new(Method::Get, url.clone()))
+        .await?;
+    recording.save_cassette().await?;
+    assert_eq!(
+        std::mem::take(&mut *events.lock().unwrap()),
+        [
+            VcrEvent::Recorded {
+                index: 0,
+                summary: "GET https://example.com/users -> 200".to_string()
+            },
+            VcrEvent::Saved { path: path.clone() }
+        ]
+    );
+
+    let replaying = VcrClientBuilder::new(&path)
+        .mode(VcrMode::Replay)
+        .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        .on_event(collect(&events))
+        .build()
+        .await?;
+    replaying
+        .send(http_types::Request::new(Method::Get, url))
+        .await?;
+    let missed = Url::parse("https://example.com/orders")?;
+    assert!(replaying
+        .send(http_types::Request::new(Method::Get, missed))
+        .await
+        .is_err());
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            VcrEvent::Replayed { index: 0 },
+            VcrEvent::Miss {
+                request_summary: "GET https://example.com/orders".to_string()
+            }
+        ]
+    );
+
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "sqlite")]
+
+use http_client::{Error, HttpClient};
+use http_client_vcr::{
+    Cassette, CassetteStorage, Headers, Interaction, MemoryStorage, SerializableRequest,
+    SerializableResponse, SqliteStorage, VcrClient, VcrMode,
+};
+use http_types::{Method, Request, Url};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts the calls a client makes into the storage it wraps
+#[derive(Debug)]
+struct Counting<S> {
+    storage: S,
+    loads: Arc<AtomicUsize>,
+    finds: Arc<AtomicUsize>,
+}
+
+impl<S: CassetteStorage> CassetteStorage for Counting<S> {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        self.loads.fetch_add(1, Ordering::SeqCst);
+        self.storage.load(path)
+    }
+
+    fn load_metadata(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        self.storage.load_metadata(path)
+    }
+
+    fn find(
+        &self,
+        path: &Path,
+        method: &str,
+        url: &str,
+    ) -> Result<Option<Vec<Interaction>>, Error> {
+        self.finds.fetch_add(1, Ordering::SeqCst);
+        self.storage.find(path, method, url)
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        self.storage.save(path, cassette)
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        self.storage.list(root)
+    }
+}
+
+async fn cassette() -> Result<Cassette, Error> {
+    let mut cassette = Cassette::new();
+    for (path, body) in [("a", "first"), ("b", "second"), ("a", "third")] {
+        cassette
+            .record_interaction(
+                SerializableRequest {
+                    method: "GET".to_string(),
+                    url: format!("https://example.com/{path}"),
+                    headers: Headers::new(),
+                    body: None,
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+                SerializableResponse {
+                    status: 200,
+                    headers: Headers::new(),
+                    body: Some(body.into()),
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+            )
+            .await?;
+    }
+    Ok(cassette)
+}
+
+async fn get(client: &VcrClient, path: &str) -> Result<String, Error> {
+    let url = Url::parse(&format!("https://example.com/{path}"))?;
+    client
+        .send(Request::new(Method::Get, url))
+        .await?
+        .body_string()
+        .await
+}
+
+#[tokio::test]
+async fn test_lazy_load_replays_from_sqlite_lookups() -> Result<(), Box<dyn std::error::Error>> {
+    let sqlite = SqliteStorage::open_in_memory()?;
+    sqlite.save(Path::new("suite/big.yaml"), &cassette().await?)?;
+    let loads = Arc::new(AtomicUsize::new(0));
+    let finds = Arc::new(AtomicUsize::new(0));
+
+    let vcr_client = VcrClient::builder("suite/big.yaml")
+        .storage(Box::new(Counting {
+            storage: sqlite,
+            loads: loads.clone(),
+            finds: finds.clone(),
+        }))
+        .mode(VcrMode::Replay)
+        .lazy_load(true)
+        .build()
+        .await?;
+    assert!(vcr_client.interactions().await.is_empty());
+
+    assert_eq!(get(&vcr_client, "a").await?, "first");
+    assert_eq!(get(&vcr_client, "b").await?, "second");
+    // Already fetched with the first request for it
+    assert_eq!(get(&vcr_client, "a").await?, "third");
+    assert!(get(&vcr_client, "missing").await.is_err());
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+    assert_eq!(finds.load(Ordering::SeqCst), 3);
+    assert_eq!(vcr_client.interactions().await.len(), 3);
+
+    // Saving the fetched interactions would throw the rest of the cassette away
+    assert!(vcr_client.save_cassette().await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lazy_load_falls_back_to_loading_storages_without_lookups(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let memory = MemoryStorage::new();
+    memory.insert("api.yaml", cassette().await?);
+    let loads = Arc::new(AtomicUsize::new(0));
+
+    let vcr_client = VcrClient::builder("api.yaml")
+        .storage(Box::new(Counting {
+            storage: memory,
+            loads: loads.clone(),
+            finds: Arc::new(AtomicUsize::new(0)),
+        }))
+        .mode(VcrMode::Replay)
+        .lazy_load(true)
+        .build()
+        .await?;
+
+    assert_eq!(get(&vcr_client, "b").await?, "second");
+    assert_eq!(get(&vcr_client, "a").await?, "first");
+    assert_eq!(get(&vcr_client, "a").await?, "third");
+    // In full on the first miss, and not again
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lazy_load_requires_replay_mode() {
+    let result = VcrClient::builder("api.yaml")
+        .storage(Box::new(MemoryStorage::new()))
+        .mode(VcrMode::Record)
+        .lazy_load(true)
+        .build()
+        .await;
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("lazy_load only works in Replay mode"));
+}
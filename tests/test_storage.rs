@@ -0,0 +1,832 @@
+use http_client::{Error, HttpClient};
+use http_client_vcr::{
+    Cassette, CassetteStorage, DefaultMatcher, Headers, LockstepClient, MemoryStorage, NoOpClient,
+    SerializableRequest, SerializableResponse, VcrClientBuilder, VcrMode,
+};
+#[cfg(feature = "fs")]
+use http_client_vcr::{CassetteFormat, FilesystemStorage, Rotation, CURRENT_FORMAT_VERSION};
+use http_types::{Method, Url};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Keeps cassettes as YAML strings in a shared map, standing in for a database or
+/// object store
+#[derive(Debug, Clone, Default)]
+struct MapStorage {
+    cassettes: Arc<Mutex<HashMap<PathBuf, String>>>,
+}
+
+impl CassetteStorage for MapStorage {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        let cassettes = self.cassettes.lock().unwrap();
+        let Some(yaml) = cassettes.get(path) else {
+            return Ok(None);
+        };
+        let mut cassette = Cassette::from_yaml(yaml)?;
+        cassette.path = Some(path.to_path_buf());
+        Ok(Some(cassette))
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        let yaml = serde_yaml::to_string(cassette)
+            .map_err(|e| Error::from_str(500, format!("Failed to serialize cassette: {e}")))?;
+        self.cassettes
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), yaml);
+        Ok(())
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut paths: Vec<PathBuf> = self
+            .cassettes
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|path| path.starts_with(root))
+            .cloned()
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// Takes a while to save, like a remote object store
+#[derive(Debug, Clone, Default)]
+struct SlowStorage {
+    inner: MapStorage,
+}
+
+impl CassetteStorage for SlowStorage {
+    fn load(&self, path: &Path) -> Result<Option<Cassette>, Error> {
+        self.inner.load(path)
+    }
+
+    fn save(&self, path: &Path, cassette: &Cassette) -> Result<(), Error> {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        self.inner.save(path, cassette)
+    }
+
+    fn list(&self, root: &Path) -> Result<Vec<PathBuf>, Error> {
+        self.inner.list(root)
+    }
+}
+
+async fn upstream() -> Result<LockstepClient, Error> {
+    let mut cassette = Cassette::new();
+    cassette
+        .record_interaction(
+            SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://example.com/data".to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: Some("stored".into()),
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+        )
+        .await?;
+    Ok(LockstepClient::new(cassette)
+        .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))))
+}
+
+#[tokio::test]
+async fn test_custom_storage_records_and_replays() -> Result<(), Box<dyn std::error::Error>> {
+    let storage = MapStorage::default();
+    let request =
+        || http_types::Request::new(Method::Get, Url::parse("https://example.com/data").unwrap());
+
+    let recorder = VcrClientBuilder::new("suite/data.yaml")
+        .storage(Box::new(storage.clone()))
+        .inner_client(Box::new(upstream().await?))
+        .mode(VcrMode::Once)
+        .build()
+        .await?;
+    recorder.send(request()).await?;
+    recorder.save_cassette().await?;
+    drop(recorder);
+
+    assert!(!Path::new("suite/data.yaml").exists());
+    assert_eq!(
+        storage.list(Path::new("suite"))?,
+        vec![PathBuf::from("suite/data.yaml")]
+    );
+
+    let replayer = VcrClientBuilder::new("suite/data.yaml")
+        .storage(Box::new(storage))
+        .inner_client(Box::new(NoOpClient::new()))
+        .matcher(Box::new(DefaultMatcher::new().with_headers(vec![])))
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+    let mut response = replayer.send(request()).await?;
+    assert_eq!(response.body_string().await?, "stored");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_slow_saves_leave_the_executor_and_cassette_free(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = SlowStorage::default();
+    let recorder = VcrClientBuilder::new("slow/data.yaml")
+        .storage(Box::new(storage.clone()))
+        .inner_client(Box::new(upstream().await?))
+        .mode(VcrMode::Once)
+        .build()
+        .await?;
+    recorder
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/data")?,
+        ))
+        .await?;
+
+    // The save runs off the executor and without the cassette locked, so the cassette
+    // can be read while it is written
+    let started = std::time::Instant::now();
+    let ((read, read_after), saved) = tokio::join!(
+        async {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            let interactions = recorder.interactions().await;
+            (interactions.len(), started.elapsed())
+        },
+        recorder.save_cassette()
+    );
+    saved?;
+    assert_eq!(read, 1);
+    assert!(
+        read_after < std::time::Duration::from_millis(250),
+        "{read_after:?}"
+    );
+    assert!(storage.load(Path::new("slow/data.yaml"))?.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_memory_storage_replays_seeded_cassette() -> Result<(), Box<dyn std::error::Error>> {
+    let storage = MemoryStorage::new();
+    storage.insert_yaml(
+        "api/greeting.yaml",
+        r#"
+interactions:
+- request:
+    method: GET
+    url: https://example.com/greeting
+    headers: {}
+    version: Http1_1
+  response:
+    status: 200
+    headers: {}
+    body: hello
+    version: Http1_1
+"#,
+    )?;
+
+    let replayer = VcrClientBuilder::new("api/greeting.yaml")
+        .storage(Box::new(storage.clone()))
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+    let request = http_types::Request::new(
+        Method::Get,
+        Url::parse("https://example.com/greeting").unwrap(),
+    );
+    let mut response = replayer.send(request).await?;
+    assert_eq!(response.body_string().await?, "hello");
+
+    let recorder = VcrClientBuilder::new("api/data.yaml")
+        .storage(Box::new(storage.clone()))
+        .inner_client(Box::new(upstream().await?))
+        .mode(VcrMode::Record)
+        .build()
+        .await?;
+    recorder
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/data").unwrap(),
+        ))
+        .await?;
+    recorder.save_cassette().await?;
+
+    assert!(!Path::new("api/data.yaml").exists());
+    assert_eq!(
+        storage.list(Path::new("api"))?,
+        vec![
+            PathBuf::from("api/data.yaml"),
+            PathBuf::from("api/greeting.yaml")
+        ]
+    );
+    let stored = storage.load(Path::new("api/data.yaml"))?.unwrap();
+    assert_eq!(stored.interactions.len(), 1);
+
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn test_filesystem_storage_lists_both_formats() -> Result<(), Box<dyn std::error::Error>> {
+    let root = std::env::temp_dir().join(format!("vcr-storage-list-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+
+    let file = root.join("single.yaml");
+    let directory = root.join("nested").join("split");
+    FilesystemStorage.save(&file, &Cassette::new())?;
+    FilesystemStorage.save(
+        &directory,
+        &Cassette::new().with_format(CassetteFormat::Directory),
+    )?;
+
+    assert_eq!(FilesystemStorage.list(&root)?, vec![directory, file]);
+
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn test_directory_storage_dedupes_bodies_and_removes_orphans(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::DirectoryStorage;
+
+    let path = std::env::temp_dir().join(format!("vcr-storage-dedupe-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let bodies = || -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(path.join("bodies"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        files.sort();
+        files
+    };
+
+    let mut polling = Cassette::new().with_format(CassetteFormat::Directory);
+    for body in ["pending", "pending", "pending", "done"] {
+        polling
+            .record_interaction(
+                SerializableRequest {
+                    method: "GET".to_string(),
+                    url: "https://example.com/job".to_string(),
+                    headers: Headers::new(),
+                    body: None,
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+                SerializableResponse {
+                    status: 200,
+                    headers: Headers::new(),
+                    body: Some(body.into()),
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+            )
+            .await?;
+    }
+    DirectoryStorage.save(&path, &polling)?;
+    assert_eq!(bodies().len(), 2);
+    let loaded = DirectoryStorage.load(&path)?.unwrap();
+    assert_eq!(
+        loaded.interactions[2].response.body.as_deref(),
+        Some("pending")
+    );
+
+    polling.interactions.truncate(3);
+    DirectoryStorage.save(&path, &polling)?;
+    assert_eq!(bodies().len(), 2);
+    let removed = DirectoryStorage.remove_orphaned_bodies(&path)?;
+    assert_eq!(removed.len(), 1);
+    assert!(!removed[0].exists());
+    assert_eq!(bodies().len(), 1);
+
+    let _ = std::fs::remove_dir_all(&path);
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn test_directory_interactions_hold_the_cassette_until_dropped(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::DirectoryStorage;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let path = std::env::temp_dir().join(format!("vcr-storage-iter-lock-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let mut cassette = Cassette::new().with_format(CassetteFormat::Directory);
+    cassette
+        .record_interaction(
+            SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://example.com/a".to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: Some("a".into()),
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+        )
+        .await?;
+    DirectoryStorage.save(&path, &cassette)?;
+
+    let mut interactions = DirectoryStorage.interactions(&path)?.unwrap();
+    let saved = Arc::new(AtomicBool::new(false));
+    let writer = std::thread::spawn({
+        let (path, saved) = (path.clone(), saved.clone());
+        move || {
+            DirectoryStorage.save(&path, &cassette).unwrap();
+            saved.store(true, Ordering::SeqCst);
+        }
+    });
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert!(!saved.load(Ordering::SeqCst));
+    assert_eq!(
+        interactions.next().unwrap()?.response.body.as_deref(),
+        Some("a")
+    );
+    drop(interactions);
+    writer.join().unwrap();
+    assert!(saved.load(Ordering::SeqCst));
+
+    let _ = std::fs::remove_dir_all(&path);
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn test_binary_bodies_round_trip_byte_for_byte() -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::DirectoryStorage;
+
+    let path = std::env::temp_dir().join(format!("vcr-storage-binary-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let png = vec![0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe, 0x80];
+
+    let mut upstream = http_types::Response::new(200);
+    upstream.set_body(png.clone());
+    let response = SerializableResponse::from_response(upstream).await?;
+    assert_eq!(response.body, None);
+    assert_eq!(response.bytes(), Some(png.clone()));
+
+    let mut cassette = Cassette::new().with_format(CassetteFormat::Directory);
+    cassette
+        .record_interaction(
+            SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://example.com/logo.png".to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            response,
+        )
+        .await?;
+    DirectoryStorage.save(&path, &cassette)?;
+    let files: Vec<PathBuf> = std::fs::read_dir(path.join("bodies"))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].extension().unwrap(), "bin");
+    assert_eq!(std::fs::read(&files[0])?, png);
+
+    let vcr_client = VcrClientBuilder::from_cassette(DirectoryStorage.load(&path)?.unwrap())
+        .mode(VcrMode::Replay)
+        .build()
+        .await?;
+    let mut replayed = vcr_client
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/logo.png")?,
+        ))
+        .await?;
+    assert_eq!(replayed.body_bytes().await?, png);
+
+    let _ = std::fs::remove_dir_all(&path);
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn test_streamed_responses_store_chunks_and_replay_incrementally(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use futures_lite::io::AsyncBufReadExt;
+    use http_client_vcr::DirectoryStorage;
+
+    let path = std::env::temp_dir().join(format!("vcr-storage-stream-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+
+    let mut cassette = Cassette::new().with_format(CassetteFormat::Directory);
+    cassette
+        .record_interaction(
+            SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://example.com/export".to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: Some("first,second,third".into()),
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+        )
+        .await?;
+    cassette.interactions[0].elapsed_ms = Some(30);
+    cassette.interactions[0].stream_in_chunks(3);
+    DirectoryStorage.save(&path, &cassette)?;
+
+    // One body file per chunk, and none for the response itself
+    assert_eq!(std::fs::read_dir(path.join("bodies"))?.count(), 3);
+    let loaded = DirectoryStorage.load(&path)?.unwrap();
+    let stream = loaded.interactions[0].stream.clone().unwrap();
+    let times: Vec<u64> = stream.iter().map(|chunk| chunk.at_ms).collect();
+    assert_eq!(times, [0, 10, 20]);
+    assert_eq!(loaded.interactions[0].response.body, None);
+
+    let vcr_client = VcrClientBuilder::from_cassette(loaded)
+        .mode(VcrMode::Replay)
+        .stream_replay_speed(f64::INFINITY)
+        .build()
+        .await?;
+    let mut response = vcr_client
+        .send(http_types::Request::new(
+            Method::Get,
+            Url::parse("https://example.com/export")?,
+        ))
+        .await?;
+    let mut body = response.take_body();
+    let mut chunks = Vec::new();
+    loop {
+        let chunk = body.fill_buf().await?.to_vec();
+        if chunk.is_empty() {
+            break;
+        }
+        std::pin::Pin::new(&mut body).consume(chunk.len());
+        chunks.push(String::from_utf8(chunk)?);
+    }
+    assert_eq!(chunks, ["first,", "second", ",third"]);
+
+    let _ = std::fs::remove_dir_all(&path);
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+#[tokio::test]
+async fn test_directory_storage_compresses_large_bodies() -> Result<(), Box<dyn std::error::Error>>
+{
+    use http_client_vcr::{BodyCompression, DirectoryStorage};
+
+    let path = std::env::temp_dir().join(format!("vcr-storage-zstd-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let page = "<html>".repeat(1000);
+
+    let mut cassette = Cassette::new()
+        .with_format(CassetteFormat::Directory)
+        .with_body_compression(BodyCompression::zstd(1024));
+    for body in [page.as_str(), "small"] {
+        cassette
+            .record_interaction(
+                SerializableRequest {
+                    method: "GET".to_string(),
+                    url: "https://example.com/page".to_string(),
+                    headers: Headers::new(),
+                    body: None,
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+                SerializableResponse {
+                    status: 200,
+                    headers: Headers::new(),
+                    body: Some(body.into()),
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+            )
+            .await?;
+    }
+    DirectoryStorage.save(&path, &cassette)?;
+
+    let mut names: Vec<String> = std::fs::read_dir(path.join("bodies"))?
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort_by_key(|name| name.ends_with(".zst"));
+    assert!(names[0].ends_with(".txt"));
+    assert!(names[1].ends_with(".txt.zst"));
+    assert!(std::fs::metadata(path.join("bodies").join(&names[1]))?.len() < 1000);
+
+    let loaded = DirectoryStorage.load(&path)?.unwrap();
+    assert_eq!(
+        loaded.interactions[0].response.body.as_deref(),
+        Some(page.as_str())
+    );
+    assert_eq!(
+        loaded.interactions[1].response.body.as_deref(),
+        Some("small")
+    );
+
+    let _ = std::fs::remove_dir_all(&path);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_headers_keep_their_order_and_repeated_values(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let yaml = "status: 200
+headers:
+  x-request-id:
+  - abc
+  set-cookie:
+  - a=1
+  - b=2
+  content-type:
+  - text/plain
+version: Http1_1
+";
+    let response: SerializableResponse = serde_yaml::from_str(yaml)?;
+    assert_eq!(serde_yaml::to_string(&response)?, yaml);
+
+    // http-types doesn't keep header order, so recorded headers are sorted by name
+    let mut upstream = http_types::Response::new(200);
+    upstream.append_header("x-request-id", "abc")?;
+    upstream.append_header("set-cookie", "a=1")?;
+    upstream.append_header("set-cookie", "b=2")?;
+    let recorded = SerializableResponse::from_response(upstream).await?;
+    let names: Vec<_> = recorded.headers.keys().map(String::as_str).collect();
+    assert_eq!(names, ["set-cookie", "x-request-id"]);
+    assert_eq!(recorded.headers["set-cookie"], ["a=1", "b=2"]);
+    Ok(())
+}
+
+#[test]
+fn test_query_strings_are_stored_as_ordered_pairs() -> Result<(), Box<dyn std::error::Error>> {
+    let yaml = "method: GET
+url: https://example.com/search
+query:
+- name: tag
+  value: a
+- name: q
+  value: rust vcr
+- name: tag
+  value: b
+headers: {}
+version: Http1_1
+";
+    let mut request: SerializableRequest = serde_yaml::from_str(yaml)?;
+    assert_eq!(
+        request.url,
+        "https://example.com/search?tag=a&q=rust+vcr&tag=b"
+    );
+    assert_eq!(request.base_url(), "https://example.com/search");
+    assert_eq!(request.query_pairs()[1], ("q".into(), "rust vcr".into()));
+    assert_eq!(serde_yaml::to_string(&request)?, yaml);
+
+    // Older cassettes keep the query in the URL, and so do queries that wouldn't
+    // be encoded back exactly the same way
+    for url in [
+        "https://example.com/search?tag=a&tag=b",
+        "https://example.com/search?q=rust%20vcr",
+    ] {
+        let old = format!("method: GET\nurl: {url}\nheaders: {{}}\nversion: Http1_1\n");
+        let request: SerializableRequest = serde_yaml::from_str(&old)?;
+        assert_eq!(request.url, url);
+        let reloaded: SerializableRequest =
+            serde_yaml::from_str(&serde_yaml::to_string(&request)?)?;
+        assert_eq!(reloaded.url, url);
+    }
+
+    request.set_query_pairs(Vec::<(String, String)>::new());
+    assert_eq!(request.url, "https://example.com/search");
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn test_older_cassettes_are_migrated_on_load() -> Result<(), Box<dyn std::error::Error>> {
+    let path =
+        std::env::temp_dir().join(format!("vcr-storage-migrate-{}.yaml", std::process::id()));
+    std::fs::write(
+        &path,
+        "interactions:
+- request:
+    method: GET
+    url: https://example.com/items?page=2
+    headers: {}
+    version: Http1_1
+  response:
+    status: 200
+    headers: {}
+    body: ok
+    version: Http1_1
+",
+    )?;
+
+    let cassette = FilesystemStorage.load(&path)?.unwrap();
+    assert_eq!(cassette.format_version, 1);
+    assert!(cassette.needs_migration());
+    assert_eq!(
+        cassette.interactions[0].request.url,
+        "https://example.com/items?page=2"
+    );
+
+    FilesystemStorage.save(&path, &cassette)?;
+    let saved = std::fs::read_to_string(&path)?;
+    assert!(saved.starts_with(&format!("format_version: {CURRENT_FORMAT_VERSION}\n")));
+    assert!(saved.contains("query:"));
+    let reloaded = FilesystemStorage.load(&path)?.unwrap();
+    assert!(!reloaded.needs_migration());
+
+    // Cassettes from a newer version of the crate are refused rather than misread
+    std::fs::write(&path, "format_version: 999\ninteractions: []\n")?;
+    assert!(FilesystemStorage.load(&path).is_err());
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn test_directory_storage_detects_tampered_body_files(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use http_client_vcr::DirectoryStorage;
+
+    let path = std::env::temp_dir().join(format!("vcr-storage-checksum-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+
+    let mut cassette = Cassette::new().with_format(CassetteFormat::Directory);
+    cassette
+        .record_interaction(
+            SerializableRequest {
+                method: "GET".to_string(),
+                url: "https://example.com/report".to_string(),
+                headers: Headers::new(),
+                body: None,
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+            SerializableResponse {
+                status: 200,
+                headers: Headers::new(),
+                body: Some("the complete report".into()),
+                body_base64: None,
+                version: "Http1_1".to_string(),
+            },
+        )
+        .await?;
+    DirectoryStorage.save(&path, &cassette)?;
+    assert!(std::fs::read_to_string(path.join("interactions.yaml"))?.contains("body_sha256:"));
+
+    let body_file = std::fs::read_dir(path.join("bodies"))?
+        .next()
+        .unwrap()?
+        .path();
+    let name = body_file
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .into_owned();
+    std::fs::write(&body_file, "the comp")?;
+    let error = DirectoryStorage.load(&path).unwrap_err().to_string();
+    assert!(error.contains(&name), "{error}");
+    assert!(error.contains("doesn't match the sha256"), "{error}");
+
+    let unverified = DirectoryStorage.load_unverified(&path)?.unwrap();
+    assert_eq!(
+        unverified.interactions[0].response.body.as_deref(),
+        Some("the comp")
+    );
+
+    std::fs::remove_file(&body_file)?;
+    let error = FilesystemStorage.load(&path).unwrap_err().to_string();
+    assert!(
+        error.contains(&name) && error.contains("is missing"),
+        "{error}"
+    );
+
+    // Re-recording the same body replaces the truncated file
+    std::fs::write(&body_file, "the comp")?;
+    DirectoryStorage.save(&path, &cassette)?;
+    let reloaded = DirectoryStorage.load(&path)?.unwrap();
+    assert_eq!(
+        reloaded.interactions[0].response.body.as_deref(),
+        Some("the complete report")
+    );
+
+    let _ = std::fs::remove_dir_all(&path);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_interactions_stream_from_any_storage() -> Result<(), Box<dyn std::error::Error>> {
+    use futures_lite::StreamExt;
+
+    let storage = MemoryStorage::new();
+    storage.insert_yaml(
+        "api.yaml",
+        "interactions:
+- request:
+    method: GET
+    url: https://example.com/a
+    headers: {}
+    version: Http1_1
+  response:
+    status: 200
+    headers: {}
+    body: first
+    version: Http1_1
+- request:
+    method: GET
+    url: https://example.com/b
+    headers: {}
+    version: Http1_1
+  response:
+    status: 404
+    headers: {}
+    version: Http1_1
+",
+    )?;
+
+    let mut interactions = Cassette::stream(&storage, Path::new("api.yaml"))?;
+    let first = interactions.next().await.unwrap()?;
+    assert_eq!(first.response.body.as_deref(), Some("first"));
+    let second = interactions.next().await.unwrap()?;
+    assert_eq!(second.response.status, 404);
+    assert!(interactions.next().await.is_none());
+
+    assert!(Cassette::stream(&storage, Path::new("missing.yaml")).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
+#[tokio::test]
+async fn test_rotated_cassettes_are_reassembled_on_load() -> Result<(), Box<dyn std::error::Error>>
+{
+    let root = std::env::temp_dir().join(format!("vcr-storage-rotate-{}", std::process::id()));
+    let path = root.join("api.yaml");
+    let part = |number: usize| root.join(format!("api.{number:04}.yaml"));
+
+    let mut cassette = Cassette::new();
+    for page in 1..=5 {
+        cassette
+            .record_interaction(
+                SerializableRequest {
+                    method: "GET".to_string(),
+                    url: format!("https://example.com/page/{page}"),
+                    headers: Headers::new(),
+                    body: None,
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+                SerializableResponse {
+                    status: 200,
+                    headers: Headers::new(),
+                    body: Some(format!("page {page}").into()),
+                    body_base64: None,
+                    version: "Http1_1".to_string(),
+                },
+            )
+            .await?;
+    }
+    cassette.set_description("paged listing");
+    cassette.set_rotation(Some(Rotation::interactions(2)));
+    FilesystemStorage.save(&path, &cassette)?;
+
+    assert!(!path.exists());
+    assert!(part(1).is_file() && part(2).is_file() && part(3).is_file());
+    assert!(!part(4).exists());
+    assert!(!std::fs::read_to_string(part(2))?.contains("paged listing"));
+
+    let loaded = FilesystemStorage.load(&path)?.unwrap();
+    assert_eq!(loaded.interactions.len(), 5);
+    assert_eq!(
+        loaded.interactions[4].request.url,
+        "https://example.com/page/5"
+    );
+    assert_eq!(loaded.description(), Some("paged listing"));
+    assert_eq!(FilesystemStorage.list(&root)?, vec![path.clone()]);
+
+    // Without rotation the cassette goes back to a single file
+    let mut loaded = loaded;
+    loaded.set_rotation(None);
+    FilesystemStorage.save(&path, &loaded)?;
+    assert!(path.is_file());
+    assert!(!part(1).exists() && !part(3).exists());
+
+    let _ = std::fs::remove_dir_all(&root);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{get_request, ok_response};
+use http_client::HttpClient;
+use http_client_vcr::{Cassette, DefaultMatcher, LockstepClient, VcrClient, VcrMode};
+use http_types::{Method, Url};
+
+/// Record GET requests for `urls` and return the recorded cassette
+async fn record(urls: &[&str], infer: bool) -> Result<Cassette, Box<dyn std::error::Error>> {
+    let mut upstream = Cassette::new();
+    for url in urls {
+        upstream
+            .record_interaction(get_request(url), ok_response("ok"))
+            .await?;
+    }
+    let vcr_client = VcrClient::in_memory()
+        .inner_client(Box::new(
+            LockstepClient::new(upstream)
+                .with_matcher(Box::new(DefaultMatcher::new().with_headers(vec![]))),
+        ))
+        .mode(VcrMode::Record)
+        .infer_url_templates(infer)
+        .build()
+        .await?;
+    for url in urls {
+        vcr_client
+            .send(http_types::Request::new(Method::Get, Url::parse(url)?))
+            .await?;
+    }
+    Ok(vcr_client.eject_cassette().await)
+}
+
+#[tokio::test]
+async fn test_url_templates_are_only_inferred_when_enabled(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let urls = [
+        "https://example.com/users/1",
+        "https://example.com/users/2",
+        "https://example.com/health",
+    ];
+
+    let plain = record(&urls, false).await?;
+    assert!(plain.metadata.url_templates.is_empty());
+
+    let inferred = record(&urls, true).await?;
+    assert_eq!(
+        inferred.metadata.url_templates,
+        [
+            "https://example.com/users/{id}",
+            "https://example.com/health"
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,19 @@
+#![cfg(feature = "macros")]
+
+use http_client::HttpClient;
+use http_client_vcr::{vcr_test, VcrClient};
+use http_types::{Method, Request, Url};
+
+fn unreachable_client() -> Box<dyn HttpClient> {
+    panic!("the inner client is only created when recording")
+}
+
+#[vcr_test(cassette_dir = "tests/fixtures", inner_client = unreachable_client)]
+async fn test_replays_cassette_named_after_test(
+    vcr: VcrClient,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request = Request::new(Method::Get, Url::parse("https://example.com/greeting")?);
+    let mut response = vcr.send(request).await?;
+    assert_eq!(response.body_string().await?, "hello");
+    Ok(())
+}